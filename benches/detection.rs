@@ -0,0 +1,71 @@
+//! Detection latency: local-only detection, detection through a mock metadata server, and the worst case of every
+//! provider timing out.
+//!
+//! Run with `cargo bench --all-features --bench detection`.
+
+use std::time::Duration;
+
+use cloud_detect::simulation::Environment;
+use cloud_detect::test_support::FakeProvider;
+use cloud_detect::{supported_providers, Detector, ProviderId};
+use criterion::{criterion_group, criterion_main, Criterion};
+use tokio::runtime::Runtime;
+
+fn bench_local_only(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let environment = runtime
+        .block_on(Environment::start(ProviderId::Azure))
+        .unwrap();
+
+    c.bench_function("local_only", |b| {
+        b.iter(|| {
+            let provider = runtime.block_on(environment.detector().local_only().detect());
+            assert_eq!(provider, ProviderId::Azure);
+        })
+    });
+}
+
+fn bench_metadata_server(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let environment = runtime
+        .block_on(Environment::start(ProviderId::Azure))
+        .unwrap();
+
+    c.bench_function("metadata_server", |b| {
+        b.iter(|| {
+            let provider = runtime.block_on(environment.detector().timeout(1).detect());
+            assert_eq!(provider, ProviderId::Azure);
+        })
+    });
+}
+
+fn bench_all_timeouts(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut detector = Detector::new().timeout(1);
+
+    for &provider in supported_providers() {
+        let fake = FakeProvider::matching(provider).after(Duration::from_secs(10));
+        detector = detector.fake_provider(fake);
+    }
+
+    // Every iteration takes the whole timeout
+    let mut group = c.benchmark_group("worst_case");
+    group.sample_size(10);
+
+    group.bench_function("all_timeouts", |b| {
+        b.iter(|| {
+            let provider = runtime.block_on(detector.detect());
+            assert_eq!(provider, ProviderId::Unknown);
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(
+    benches,
+    bench_local_only,
+    bench_metadata_server,
+    bench_all_timeouts
+);
+criterion_main!(benches);
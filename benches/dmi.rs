@@ -0,0 +1,89 @@
+//! Reading the DMI attributes matched by every provider, one read per check versus one read per attribute.
+//!
+//! Run with `cargo bench --bench dmi`.
+
+use std::fs;
+use std::hint::black_box;
+use std::path::PathBuf;
+
+use criterion::{criterion_group, criterion_main, Criterion};
+use tempfile::TempDir;
+
+#[allow(dead_code, unused_imports)]
+#[path = "../src/smbios.rs"]
+mod smbios;
+
+use smbios::SmbiosData;
+
+/// DMI attributes matched by the checks of every supported provider, in the order the checks are declared. Several
+/// providers match the same attributes.
+const CHECKED_ATTRIBUTES: &[&str] = &[
+    "sys_vendor",        // Akamai
+    "product_name",      // Alibaba Cloud
+    "product_version",   // AWS
+    "bios_vendor",       // AWS
+    "sys_vendor",        // Azure
+    "sys_vendor",        // Civo
+    "sys_vendor",        // DigitalOcean
+    "product_name",      // GCP
+    "sys_vendor",        // KVM (Harvester)
+    "sys_vendor",        // KVM (KubeVirt)
+    "sys_vendor",        // KVM (QEMU)
+    "sys_vendor",        // Naver Cloud Platform
+    "sys_vendor",        // Nutanix
+    "product_name",      // Nutanix
+    "chassis_asset_tag", // OCI
+    "product_name",      // OpenStack
+    "chassis_asset_tag", // OpenStack
+    "bios_vendor",       // Proxmox
+    "sys_vendor",        // Proxmox
+    "sys_vendor",        // VMware
+    "product_name",      // VMware
+    "sys_vendor",        // Vultr
+    "chassis_asset_tag", // Vultr
+    "sys_vendor",        // Yandex Cloud
+];
+
+fn bench_dmi(c: &mut Criterion) {
+    let dmi_dir = TempDir::new().unwrap();
+    let paths: Vec<PathBuf> = CHECKED_ATTRIBUTES
+        .iter()
+        .map(|attribute| dmi_dir.path().join(attribute))
+        .collect();
+
+    for path in &paths {
+        fs::write(path, "Unrelated Vendor").unwrap();
+    }
+
+    let smbios = SmbiosData::read(&paths);
+    println!(
+        "DMI attribute files read: {} per check, {} batched",
+        paths.len(),
+        smbios.len()
+    );
+
+    let mut group = c.benchmark_group("dmi");
+
+    group.bench_function("per_check", |b| {
+        b.iter(|| {
+            for path in &paths {
+                black_box(smbios::read_field(path));
+            }
+        })
+    });
+
+    group.bench_function("batched", |b| {
+        b.iter(|| {
+            let smbios = SmbiosData::read(&paths);
+
+            for path in &paths {
+                black_box(smbios.field(path));
+            }
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_dmi);
+criterion_main!(benches);
@@ -0,0 +1,9 @@
+#![no_main]
+
+use cloud_detect::fuzzing::parse_response;
+use cloud_detect::ProviderId;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|body: &[u8]| {
+    parse_response(ProviderId::DigitalOcean, 200, body);
+});
@@ -0,0 +1,121 @@
+//! Background work of a detector.
+//!
+//! Detection can return before all of its work is done, e.g. once a provider is identified while the metadata servers
+//! of others are still being queried. Tasks of the asynchronous API are aborted as soon as detection returns, but
+//! threads of the blocking API can't be, so they are cancelled instead: they stop at their next check. A detector keeps
+//! track of the threads it spawned, so that they can be reaped (see [Detector::shutdown](crate::Detector::shutdown)).
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+
+use tracing::{debug, error};
+
+/// Represents the threads spawned by a detector, shared by its clones.
+///
+/// The threads are reaped when the last clone of the detector is dropped.
+#[derive(Debug, Default)]
+pub(crate) struct Background {
+    threads: Mutex<Vec<JoinHandle<()>>>,
+}
+
+impl Background {
+    /// Spawns a thread, keeping track of it until it's reaped.
+    #[cfg_attr(
+        not(any(feature = "blocking", feature = "blocking-ureq")),
+        allow(dead_code)
+    )]
+    pub(crate) fn spawn<F: FnOnce() + Send + 'static>(&self, f: F) {
+        let thread = std::thread::spawn(f);
+
+        if let Ok(mut threads) = self.threads.lock() {
+            // Finished threads have nothing left to reap
+            threads.retain(|thread| !thread.is_finished());
+            threads.push(thread);
+        }
+    }
+
+    /// Number of threads not reaped yet.
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.threads.lock().map_or(0, |threads| threads.len())
+    }
+
+    /// Waits for every thread to finish.
+    pub(crate) fn shutdown(&self) {
+        let threads = match self.threads.lock() {
+            Ok(mut threads) => std::mem::take(&mut *threads),
+            Err(_) => return,
+        };
+
+        debug!("Reaping {} background threads", threads.len());
+
+        for thread in threads {
+            if thread.join().is_err() {
+                error!("Background thread panicked");
+            }
+        }
+    }
+}
+
+impl Drop for Background {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Represents the cancellation of the remaining work of a detection, shared by its threads.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(
+    not(any(feature = "blocking", feature = "blocking-ureq")),
+    allow(dead_code)
+)]
+pub(crate) struct Cancellation(Arc<AtomicBool>);
+
+#[cfg_attr(
+    not(any(feature = "blocking", feature = "blocking-ureq")),
+    allow(dead_code)
+)]
+impl Cancellation {
+    /// Cancels the detection, e.g. once it has returned.
+    pub(crate) fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Whether the detection was cancelled.
+    pub(crate) fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn test_shutdown() {
+        let background = Background::default();
+        let start = Instant::now();
+
+        background.spawn(|| std::thread::sleep(Duration::from_millis(100)));
+        background.spawn(|| {});
+        assert_eq!(background.len(), 2);
+
+        background.shutdown();
+
+        assert_eq!(background.len(), 0);
+        assert!(start.elapsed() >= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn test_cancellation() {
+        let cancellation = Cancellation::default();
+        let shared = cancellation.clone();
+
+        assert!(!shared.is_cancelled());
+        cancellation.cancel();
+        assert!(shared.is_cancelled());
+    }
+}
@@ -0,0 +1,248 @@
+//! Binding of metadata server requests to a network interface.
+//!
+//! Instances with several network interfaces (e.g. a secondary ENI with a route table of its own) sometimes route
+//! link-local traffic out of an interface the metadata server can't be reached from, so that its checks silently time
+//! out. A detector bound to an interface or a source address (see [Detector::interface](crate::Detector::interface)
+//! and [Detector::local_address](crate::Detector::local_address)) sends every request of its checks, and their
+//! reachability pre-check (see [crate::precheck]), from it instead.
+//!
+//! Interfaces are bound with the `SO_BINDTODEVICE` socket option, so binding to one is only supported on Linux (and
+//! Android), and not by the ureq client of the `blocking-ureq` feature, which can't bind its sockets at all.
+
+#[cfg(feature = "async")]
+use std::io;
+use std::net::IpAddr;
+#[cfg(feature = "async")]
+use std::net::SocketAddr;
+
+#[cfg(any(
+    feature = "blocking-ureq",
+    all(
+        any(feature = "async", feature = "blocking"),
+        not(any(target_os = "android", target_os = "linux"))
+    )
+))]
+use anyhow::bail;
+use anyhow::Result;
+#[cfg(feature = "async")]
+use tokio::net::{TcpSocket, TcpStream};
+
+use crate::checks::{Check, Probe};
+
+/// Represents the interface and source address requests to metadata servers are sent from.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub(crate) struct Binding {
+    /// Name of the network interface (e.g. `eth1`).
+    pub(crate) interface: Option<String>,
+    /// Source address of the requests.
+    pub(crate) local_address: Option<IpAddr>,
+}
+
+impl Binding {
+    /// Whether requests are bound to an interface or a source address.
+    pub(crate) fn is_bound(&self) -> bool {
+        self.interface.is_some() || self.local_address.is_some()
+    }
+
+    /// Binds the sockets of an HTTP client.
+    #[cfg(feature = "async")]
+    pub(crate) fn client(&self, builder: reqwest::ClientBuilder) -> Result<reqwest::ClientBuilder> {
+        let builder = builder.local_address(self.local_address);
+
+        let Some(interface) = &self.interface else {
+            return Ok(builder);
+        };
+
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        return Ok(builder.interface(interface));
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        bail!("Binding to interface {interface} is not supported on this platform");
+    }
+
+    /// Binds the sockets of a blocking HTTP client.
+    #[cfg(all(feature = "blocking", not(feature = "blocking-ureq")))]
+    pub(crate) fn blocking_client(
+        &self,
+        builder: reqwest::blocking::ClientBuilder,
+    ) -> Result<reqwest::blocking::ClientBuilder> {
+        let builder = builder.local_address(self.local_address);
+
+        let Some(interface) = &self.interface else {
+            return Ok(builder);
+        };
+
+        #[cfg(any(target_os = "android", target_os = "linux"))]
+        return Ok(builder.interface(interface));
+        #[cfg(not(any(target_os = "android", target_os = "linux")))]
+        bail!("Binding to interface {interface} is not supported on this platform");
+    }
+
+    /// Fails if requests are bound, as the ureq client can't bind its sockets.
+    #[cfg(feature = "blocking-ureq")]
+    pub(crate) fn ureq_client(&self) -> Result<()> {
+        if self.is_bound() {
+            bail!("Binding requests is not supported with the `blocking-ureq` feature");
+        }
+
+        Ok(())
+    }
+
+    /// Connects to an address (`host:port`) over TCP from the interface and source address, trying each address the
+    /// host resolves to in turn.
+    #[cfg(feature = "async")]
+    pub(crate) async fn connect(&self, address: &str) -> io::Result<TcpStream> {
+        let mut last = io::Error::new(io::ErrorKind::NotFound, "Host has no addresses");
+
+        for addr in tokio::net::lookup_host(address).await? {
+            match self.connect_to(addr).await {
+                Ok(stream) => return Ok(stream),
+                Err(err) => last = err,
+            }
+        }
+
+        Err(last)
+    }
+
+    #[cfg(feature = "async")]
+    async fn connect_to(&self, addr: SocketAddr) -> io::Result<TcpStream> {
+        let socket = match addr {
+            SocketAddr::V4(_) => TcpSocket::new_v4()?,
+            SocketAddr::V6(_) => TcpSocket::new_v6()?,
+        };
+
+        if let Some(local_address) = self.local_address {
+            socket.bind(SocketAddr::new(local_address, 0))?;
+        }
+
+        if let Some(interface) = &self.interface {
+            #[cfg(any(target_os = "android", target_os = "linux"))]
+            socket.bind_device(Some(interface.as_bytes()))?;
+            #[cfg(not(any(target_os = "android", target_os = "linux")))]
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                format!("Binding to interface {interface} is not supported on this platform"),
+            ));
+        }
+
+        socket.connect(addr).await
+    }
+}
+
+/// Binds the requests of checks querying a metadata server. Checks are unchanged if requests aren't bound.
+pub(crate) fn bind(checks: Vec<Check>, binding: &Binding) -> Vec<Check> {
+    if !binding.is_bound() {
+        return checks;
+    }
+
+    let binding = std::sync::Arc::new(binding.clone());
+
+    checks
+        .into_iter()
+        .map(|check| match check.probe {
+            Probe::Http {
+                mut request,
+                matches,
+            } => {
+                request.binding = Some(binding.clone());
+                Check {
+                    probe: Probe::Http { request, matches },
+                    ..check
+                }
+            }
+            _ => check,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, TcpListener};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::checks::Request;
+    use crate::Confidence;
+
+    #[test]
+    fn test_bind() {
+        let checks = || {
+            vec![
+                Check::http(
+                    "metadata_server",
+                    Confidence::Verified,
+                    Request::get("http://169.254.169.254", "/"),
+                    |_| true,
+                ),
+                Check::env("env", Confidence::Unverified, "HOME", |_| true),
+            ]
+        };
+        let binding = Binding {
+            interface: Some("eth1".to_string()),
+            local_address: None,
+        };
+
+        let bound = bind(checks(), &binding);
+        let Probe::Http { request, .. } = &bound[0].probe else {
+            panic!("Expected a metadata server check");
+        };
+        assert_eq!(request.binding.as_deref(), Some(&binding));
+        assert!(matches!(bound[1].probe, Probe::Env { .. }));
+
+        let unbound = bind(checks(), &Binding::default());
+        let Probe::Http { request, .. } = &unbound[0].probe else {
+            panic!("Expected a metadata server check");
+        };
+        assert_eq!(request.binding, None);
+    }
+
+    #[tokio::test]
+    async fn test_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let binding = |local_address: IpAddr| Binding {
+            interface: None,
+            local_address: Some(local_address),
+        };
+
+        let stream = binding(Ipv4Addr::LOCALHOST.into())
+            .connect(&address)
+            .await
+            .unwrap();
+        assert_eq!(stream.local_addr().unwrap().ip(), Ipv4Addr::LOCALHOST);
+
+        // An IPv4 host can't be reached from an IPv6 source address
+        let unreachable = binding("::1".parse().unwrap()).connect(&address).await;
+        assert!(unreachable.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bound_request() {
+        let server = MockServer::start().await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        let request = |local_address: IpAddr| Request {
+            binding: Some(Arc::new(Binding {
+                interface: None,
+                local_address: Some(local_address),
+            })),
+            ..Request::get(&server.uri(), "/metadata")
+        };
+        let timeout = Duration::from_secs(1);
+
+        let resp = request(Ipv4Addr::LOCALHOST.into())
+            .send(timeout)
+            .await
+            .unwrap();
+        assert_eq!(resp.status, 200);
+
+        // Addresses not assigned to the host can't be bound
+        let unassigned = request(Ipv4Addr::new(192, 0, 2, 1).into());
+        assert!(unassigned.send(timeout).await.is_err());
+    }
+}
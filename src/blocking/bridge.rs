@@ -0,0 +1,79 @@
+//! Blocking from within a tokio runtime.
+//!
+//! Calling the blocking API from an async task would stall the worker thread the task runs on and, with the blocking
+//! client of reqwest, panic (it runs a tokio runtime of its own, which can't be started or dropped within another).
+//! Blocking work is instead moved off the runtime: with [tokio::task::block_in_place] on a multi-threaded runtime, and
+//! onto a thread of its own on a current-thread runtime (which can't hand its tasks over to other workers, so it still
+//! waits for the work to finish).
+
+use tokio::runtime::{Handle, RuntimeFlavor};
+
+/// Runs blocking work, moving it off the tokio runtime of the current thread, if any.
+pub(crate) fn block<T, F>(f: F) -> T
+where
+    T: Send,
+    F: FnOnce() -> T + Send,
+{
+    let Ok(handle) = Handle::try_current() else {
+        return f();
+    };
+
+    match handle.runtime_flavor() {
+        RuntimeFlavor::MultiThread => tokio::task::block_in_place(f),
+        _ => std::thread::scope(|scope| match scope.spawn(f).join() {
+            Ok(value) => value,
+            Err(panic) => std::panic::resume_unwind(panic),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use std::time::Duration;
+
+    use mockito::Server;
+
+    use super::*;
+    use crate::blocking::checks;
+    use crate::checks::Request;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_block_without_runtime() {
+        let caller = thread::current().id();
+
+        assert_eq!(block(|| thread::current().id()), caller);
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_block_multi_thread() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/metadata")
+            .with_body("abc")
+            .create_async()
+            .await;
+
+        let resp = checks::send(&Request::get(&server.url(), "/metadata"), TIMEOUT).unwrap();
+
+        assert_eq!(resp.body, "abc");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_block_current_thread() {
+        let mut server = Server::new_async().await;
+        let mock = server
+            .mock("GET", "/metadata")
+            .with_body("abc")
+            .create_async()
+            .await;
+
+        let resp = checks::send(&Request::get(&server.url(), "/metadata"), TIMEOUT).unwrap();
+
+        assert_eq!(resp.body, "abc");
+        mock.assert_async().await;
+    }
+}
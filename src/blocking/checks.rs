@@ -0,0 +1,519 @@
+//! Blocking execution of provider checks.
+//!
+//! Mirrors [Check::run], [Request::send] and [Fetch::run], using a blocking HTTP client.
+
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use tracing::{debug, error, instrument};
+
+use super::bridge;
+use super::http::Client;
+use crate::checks::{
+    glob_files,
+    Check,
+    Fetch,
+    Inspection,
+    Probe,
+    Request,
+    Response,
+    Step,
+    TokenRequest,
+};
+use crate::clock::{self, Instant};
+use crate::probe_log::probe_log;
+use crate::redact::Redacted;
+use crate::{cpuid, retry, smbios, tokens};
+
+/// Runs a check.
+///
+/// # Arguments
+///
+/// * `check` - The check to run.
+/// * `timeout` - Maximum time allowed for requests to a metadata server.
+pub(crate) fn run(check: &Check, timeout: Duration) -> bool {
+    inspect(check, timeout).matched()
+}
+
+/// Runs a check, keeping the details of what was inspected.
+///
+/// # Arguments
+///
+/// * `check` - The check to run.
+/// * `timeout` - Maximum time allowed for requests to a metadata server.
+#[instrument(skip_all, fields(check = check.name))]
+pub(crate) fn inspect(check: &Check, timeout: Duration) -> Inspection {
+    match &check.probe {
+        Probe::Dmi { path, matches } => {
+            debug!("Checking DMI file: {}", path.display());
+            Inspection::content(smbios::read_field(path), matches)
+        }
+        Probe::File { path, matches } => {
+            debug!("Checking file: {}", path.display());
+
+            if !path.is_file() {
+                return Inspection::default();
+            }
+
+            match std::fs::read_to_string(path) {
+                Ok(content) => Inspection::content(Some(content), matches),
+                Err(err) => {
+                    error!("Error reading file: {:?}", err);
+                    Inspection::error(err.into())
+                }
+            }
+        }
+        Probe::Glob { pattern, matches } => {
+            debug!("Checking files matching: {}", pattern);
+
+            let contents = glob_files(pattern)
+                .into_iter()
+                .filter_map(|path| match std::fs::read_to_string(path) {
+                    Ok(content) => Some(content),
+                    Err(err) => {
+                        error!("Error reading file: {:?}", err);
+                        None
+                    }
+                })
+                .collect();
+
+            Inspection::contents(contents, matches)
+        }
+        Probe::Exists { path } => {
+            debug!("Checking path: {}", path.display());
+            Inspection::presence(path.exists())
+        }
+        Probe::Env { name, matches } => {
+            debug!("Checking environment variable: {}", name);
+            Inspection::content(std::env::var(name).ok(), matches)
+        }
+        Probe::Cpuid { matches } => {
+            debug!("Checking hypervisor vendor signature");
+            Inspection::content(cpuid::hypervisor_vendor(), matches)
+        }
+        Probe::Http { request, matches } => match send(request, timeout) {
+            Ok(resp) => Inspection::response(resp, matches).or_unauthorized(check.unauthorized),
+            Err(err) => {
+                probe_log!(
+                    check.log_level,
+                    "Error making request: {:?}",
+                    Redacted(&err)
+                );
+                Inspection::unreachable(err)
+            }
+        },
+    }
+}
+
+/// Sends a request, retrieving a session token first if required.
+///
+/// A cached token is reused if available. If the metadata server rejects it, a new token is retrieved and the request is
+/// sent again. Transient failures (e.g. throttling) are retried with backoff (see [crate::retry]).
+///
+/// # Arguments
+///
+/// * `request` - The request to send.
+/// * `timeout` - Maximum time allowed for the request, shared by the requests chained to it (retrieving a session token,
+///   and retries), so that each only gets the time left by the previous ones.
+pub(crate) fn send(request: &Request, timeout: Duration) -> Result<Response> {
+    bridge::block(|| send_blocking(request, timeout))
+}
+
+/// Sends a request on the current thread (see [send]).
+fn send_blocking(request: &Request, timeout: Duration) -> Result<Response> {
+    #[cfg(feature = "test_support")]
+    if let Some(transport) = &request.transport {
+        return transport.send_blocking(request, timeout);
+    }
+
+    let deadline = clock::deadline(timeout);
+    let client = Client::new(timeout, request.binding.as_deref())?;
+
+    let (Some(token), Some(token_url)) = (&request.token, request.token_url()) else {
+        return send_with_token(&client, request, None, deadline);
+    };
+
+    let value = match tokens::get(&token_url) {
+        Some(value) => value,
+        None => retrieve_token(&client, token, &token_url, deadline)?,
+    };
+    let resp = send_with_token(&client, request, Some(&value), deadline)?;
+
+    if resp.status != 401 {
+        return Ok(resp);
+    }
+
+    debug!("Token rejected, retrieving a new one");
+    tokens::invalidate(&token_url);
+
+    let value = retrieve_token(&client, token, &token_url, deadline)?;
+    send_with_token(&client, request, Some(&value), deadline)
+}
+
+/// Sends a request, passing the given session token, and retries transient failures until the deadline.
+fn send_with_token(
+    client: &Client,
+    request: &Request,
+    token: Option<&str>,
+    deadline: Instant,
+) -> Result<Response> {
+    let start = Instant::now();
+    let timeout = deadline.saturating_duration_since(start);
+    let mut attempt = 1;
+
+    loop {
+        let resp = send_once(client, request, token, deadline)?;
+
+        let Some(delay) = retry::delay(&resp, attempt, start.elapsed(), timeout) else {
+            return Ok(resp);
+        };
+
+        debug!(
+            "Transient status code {}, retrying in {:?}",
+            resp.status, delay
+        );
+        std::thread::sleep(delay);
+        attempt += 1;
+    }
+}
+
+/// Sends a request once, passing the given session token, within the time left until the deadline.
+fn send_once(
+    client: &Client,
+    request: &Request,
+    token: Option<&str>,
+    deadline: Instant,
+) -> Result<Response> {
+    let mut headers: Vec<(&str, &str)> = request
+        .headers
+        .iter()
+        .map(|(name, value)| (*name, value.as_str()))
+        .collect();
+
+    if let (Some(token), Some(value)) = (&request.token, token) {
+        headers.push((token.header, value));
+    }
+
+    debug!("Requesting metadata using url: {}", Redacted(request.url()));
+
+    client.get(&request.url(), &headers, clock::remaining(deadline)?)
+}
+
+/// Retrieves a session token within the time left until the deadline, and caches it for its lifetime.
+fn retrieve_token(
+    client: &Client,
+    token: &TokenRequest,
+    token_url: &str,
+    deadline: Instant,
+) -> Result<String> {
+    debug!("Retrieving token from: {}", Redacted(token_url));
+
+    let ttl = token.ttl.as_secs().to_string();
+    let resp = client.put(
+        token_url,
+        &[(token.ttl_header, &ttl)],
+        clock::remaining(deadline)?,
+    )?;
+
+    if !(200..300).contains(&resp.status) {
+        bail!("Unexpected status code for token: {}", resp.status);
+    }
+
+    let value = resp.body;
+
+    if value.is_empty() {
+        bail!("Token is empty");
+    }
+
+    tokens::insert(token_url, &value, token.ttl);
+    Ok(value)
+}
+
+/// Fetches metadata, trying the requests of each step in order until one succeeds.
+///
+/// # Arguments
+///
+/// * `fetch` - The metadata to fetch.
+/// * `timeout` - Maximum time allowed for each request.
+pub(crate) fn fetch<T>(fetch: &Fetch<T>, timeout: Duration) -> Result<T> {
+    let mut step = self::step(fetch, timeout)?;
+
+    loop {
+        match step {
+            Step::Done(value) => return Ok(value),
+            Step::Next(next) => step = self::step(&next, timeout)?,
+        }
+    }
+}
+
+/// Runs a single step of a fetch.
+fn step<T>(fetch: &Fetch<T>, timeout: Duration) -> Result<Step<T>> {
+    let mut last_err = None;
+
+    for request in &fetch.requests {
+        match send(request, timeout).and_then(|resp| (fetch.parse)(&resp)) {
+            Ok(step) => return Ok(step),
+            Err(err) => {
+                debug!(
+                    "Error fetching {}: {:?}",
+                    Redacted(request.url()),
+                    Redacted(&err)
+                );
+                last_err = Some(err);
+            }
+        }
+    }
+
+    (fetch.recover)(last_err.unwrap_or_else(|| anyhow!("No requests to send")))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::net::TcpListener;
+    use std::time::Instant;
+
+    use anyhow::Result;
+    use mockito::Server;
+    use tempfile::{NamedTempFile, TempDir};
+
+    use super::*;
+    use crate::Confidence;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[test]
+    fn test_run_dmi() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all(b"abc\n")?;
+
+        let check = Check::dmi("dmi", Confidence::Unverified, file.path(), |c| c == "abc\n");
+        assert!(run(&check, TIMEOUT));
+
+        let check = Check::dmi("dmi", Confidence::Unverified, file.path(), |c| c == "def");
+        assert!(!run(&check, TIMEOUT));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_exists() -> Result<()> {
+        let dir = TempDir::new()?;
+
+        let check = Check::exists("exists", Confidence::Unverified, dir.path());
+        assert!(run(&check, TIMEOUT));
+
+        let check = Check::exists("exists", Confidence::Unverified, dir.path().join("abc"));
+        assert!(!run(&check, TIMEOUT));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_http_with_headers() {
+        let mut server = Server::new();
+        let url = server.url();
+
+        let mock = server
+            .mock("GET", "/metadata")
+            .match_header("Metadata", "true")
+            .with_status(200)
+            .with_body("abc")
+            .create();
+
+        let request = Request::get(&url, "/metadata").header("Metadata", "true");
+        let check = Check::http("http", Confidence::Verified, request, |resp| {
+            resp.is_success() && resp.body == "abc"
+        });
+
+        assert!(run(&check, TIMEOUT));
+        mock.assert();
+    }
+
+    #[test]
+    fn test_run_http_with_token() {
+        let mut server = Server::new();
+        let url = server.url();
+        tokens::invalidate(&format!("{url}/token"));
+
+        let token_mock = server
+            .mock("PUT", "/token")
+            .match_header("X-Token-TTL", "60")
+            .with_status(200)
+            .with_body("123abc")
+            .create();
+        let metadata_mock = server
+            .mock("GET", "/metadata")
+            .match_header("X-Token", "123abc")
+            .with_status(200)
+            .with_body("abc")
+            .create();
+
+        let request = Request::get(&url, "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+        let check = Check::http("http", Confidence::Verified, request, |resp| {
+            resp.body == "abc"
+        });
+
+        assert!(run(&check, TIMEOUT));
+        token_mock.assert();
+        metadata_mock.assert();
+    }
+
+    #[test]
+    fn test_send_refreshes_rejected_token() -> Result<()> {
+        let mut server = Server::new();
+        let url = server.url();
+
+        let token_url = format!("{url}/token");
+        tokens::insert(&token_url, "expired", Duration::from_secs(60));
+
+        let token_mock = server
+            .mock("PUT", "/token")
+            .with_status(200)
+            .with_body("123abc")
+            .expect(1)
+            .create();
+        let expired_mock = server
+            .mock("GET", "/metadata")
+            .match_header("X-Token", "expired")
+            .with_status(401)
+            .expect(1)
+            .create();
+        let metadata_mock = server
+            .mock("GET", "/metadata")
+            .match_header("X-Token", "123abc")
+            .with_status(200)
+            .with_body("abc")
+            .expect(1)
+            .create();
+
+        let request = Request::get(&url, "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+
+        assert_eq!(send(&request, TIMEOUT)?.body, "abc");
+        assert_eq!(tokens::get(&token_url).as_deref(), Some("123abc"));
+        token_mock.assert();
+        expired_mock.assert();
+        metadata_mock.assert();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_retries_transient_failures() -> Result<()> {
+        let mut server = Server::new();
+        let mock = server
+            .mock("GET", "/metadata")
+            .with_status(503)
+            .expect(retry::MAX_ATTEMPTS as usize)
+            .create();
+
+        let resp = send(&Request::get(&server.url(), "/metadata"), TIMEOUT)?;
+
+        assert_eq!(resp.status, 503);
+        mock.assert();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_run_http_timeout() -> Result<()> {
+        // Accepts connections but never responds
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let url = format!("http://{}", listener.local_addr()?);
+
+        let request = Request::get(&url, "/metadata");
+        let check = Check::http("http", Confidence::Verified, request, |_| true);
+
+        let start = Instant::now();
+        let result = run(&check, Duration::from_millis(200));
+
+        assert!(!result);
+        assert!(start.elapsed() < Duration::from_secs(2));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inspect_http_error() {
+        let request = Request::get("http://127.0.0.1:1", "/metadata");
+        let check = Check::http("http", Confidence::Verified, request, |_| true);
+        let inspection = inspect(&check, TIMEOUT);
+
+        assert!(!inspection.matched());
+        assert_eq!(inspection.status, None);
+        assert!(inspection.error.is_some());
+        assert_eq!(inspection.content, None);
+    }
+
+    #[test]
+    fn test_fetch_falls_back() -> Result<()> {
+        let mut server = Server::new();
+        let url = server.url();
+
+        let v2_mock = server.mock("GET", "/v2").with_status(404).create();
+        let v1_mock = server
+            .mock("GET", "/v1")
+            .with_status(200)
+            .with_body(r#"{"id": "abc"}"#)
+            .create();
+
+        let value = fetch(
+            &Fetch::<serde_json::Value>::json(vec![
+                Request::get(&url, "/v2"),
+                Request::get(&url, "/v1"),
+            ]),
+            TIMEOUT,
+        )?;
+
+        assert_eq!(value["id"], "abc");
+        v2_mock.assert();
+        v1_mock.assert();
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_fetch_and_then() -> Result<()> {
+        let mut server = Server::new();
+        let url = server.url();
+
+        let mac_mock = server
+            .mock("GET", "/mac")
+            .with_status(200)
+            .with_body("abc")
+            .create();
+        let network_mock = server
+            .mock("GET", "/macs/abc")
+            .with_status(200)
+            .with_body("def")
+            .create();
+
+        let value = fetch(
+            &Fetch::new(vec![Request::get(&url, "/mac")], |resp| {
+                Ok(resp.body.clone())
+            })
+            .and_then(move |mac| {
+                Fetch::new(vec![Request::get(&url, &format!("/macs/{mac}"))], |resp| {
+                    Ok(resp.body.clone())
+                })
+            }),
+            TIMEOUT,
+        )?;
+
+        assert_eq!(value, "def");
+        mac_mock.assert();
+        network_mock.assert();
+
+        Ok(())
+    }
+}
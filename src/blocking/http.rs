@@ -0,0 +1,161 @@
+//! Blocking HTTP client.
+//!
+//! Backed by the blocking client of reqwest, which runs a tokio runtime in a thread of its own, or by ureq with the
+//! `blocking-ureq` feature, which doesn't (it's preferred if both are enabled).
+
+use std::time::Duration;
+
+#[cfg(not(feature = "blocking-ureq"))]
+use anyhow::Context;
+use anyhow::Result;
+
+use crate::bind::Binding;
+use crate::checks::Response;
+use crate::retry;
+
+/// Represents a blocking HTTP client, with a timeout for each request, which requests can shorten.
+pub(crate) struct Client {
+    #[cfg(not(feature = "blocking-ureq"))]
+    inner: reqwest::blocking::Client,
+    #[cfg(feature = "blocking-ureq")]
+    inner: ureq::Agent,
+}
+
+impl Client {
+    /// Creates a client.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time allowed for each request.
+    /// * `binding` - Interface and source address the requests are sent from, if bound.
+    #[cfg(not(feature = "blocking-ureq"))]
+    pub(crate) fn new(timeout: Duration, binding: Option<&Binding>) -> Result<Self> {
+        let builder = reqwest::blocking::Client::builder().timeout(timeout);
+        // rustls is preferred if both TLS backends are enabled
+        #[cfg(feature = "rustls-tls")]
+        let builder = builder.use_rustls_tls();
+        let builder = match binding {
+            Some(binding) => binding.blocking_client(builder)?,
+            None => builder,
+        };
+        let inner = builder.build().context("Error creating client")?;
+
+        Ok(Self { inner })
+    }
+
+    /// Creates a client.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time allowed for each request.
+    /// * `binding` - Interface and source address the requests are sent from, if bound, which ureq doesn't support.
+    #[cfg(feature = "blocking-ureq")]
+    pub(crate) fn new(timeout: Duration, binding: Option<&Binding>) -> Result<Self> {
+        if let Some(binding) = binding {
+            binding.ureq_client()?;
+        }
+
+        let config = ureq::Agent::config_builder()
+            .timeout_global(Some(timeout))
+            // Error statuses are responses like any other (e.g. to retry them)
+            .http_status_as_error(false);
+        // rustls is preferred if both TLS backends are enabled, as with reqwest
+        #[cfg(all(feature = "native-tls", not(feature = "rustls-tls")))]
+        let config = config.tls_config(
+            ureq::tls::TlsConfig::builder()
+                .provider(ureq::tls::TlsProvider::NativeTls)
+                .root_certs(ureq::tls::RootCerts::PlatformVerifier)
+                .build(),
+        );
+
+        Ok(Self {
+            inner: config.build().into(),
+        })
+    }
+
+    /// Sends a GET request, within the given timeout.
+    pub(crate) fn get(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<Response> {
+        self.send("GET", url, headers, timeout)
+    }
+
+    /// Sends a PUT request, without a body, within the given timeout.
+    pub(crate) fn put(
+        &self,
+        url: &str,
+        headers: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<Response> {
+        self.send("PUT", url, headers, timeout)
+    }
+
+    #[cfg(not(feature = "blocking-ureq"))]
+    fn send(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<Response> {
+        let method = reqwest::Method::from_bytes(method.as_bytes())?;
+        let mut req = self.inner.request(method, url).timeout(timeout);
+
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+
+        let resp = req.send()?;
+        let status = resp.status().as_u16();
+        let headers = resp.headers().clone();
+        let retry_after = retry::retry_after(&headers);
+        let body = resp.text()?;
+
+        Ok(Response {
+            status,
+            body,
+            headers,
+            retry_after,
+        })
+    }
+
+    #[cfg(feature = "blocking-ureq")]
+    fn send(
+        &self,
+        method: &str,
+        url: &str,
+        headers: &[(&str, &str)],
+        timeout: Duration,
+    ) -> Result<Response> {
+        // Parsed like reqwest does, percent-encoding what isn't valid in a URI (e.g. spaces in a path segment)
+        let url = url::Url::parse(url)?;
+        let mut req = ureq::http::Request::builder()
+            .method(method)
+            .uri(url.as_str());
+
+        for (name, value) in headers {
+            req = req.header(*name, *value);
+        }
+
+        let req = self
+            .inner
+            .configure_request(req.body(())?)
+            .timeout_global(Some(timeout))
+            .build();
+        let mut resp = self.inner.run(req)?;
+        let status = resp.status().as_u16();
+        let headers = resp.headers().clone();
+        let retry_after = retry::retry_after(&headers);
+        let body = resp.body_mut().read_to_string()?;
+
+        Ok(Response {
+            status,
+            body,
+            headers,
+            retry_after,
+        })
+    }
+}
@@ -1,14 +1,24 @@
 //! Blocking API for cloud provider detection.
 //!
-//! This module provides a blocking API for detecting the host's cloud provider. It is built on top of the asynchronous API
-//! and executes the blocking provider identification within threads.
+//! This module provides a blocking API for detecting the host's cloud provider. It runs the same provider checks as the
+//! asynchronous API, each provider within its own thread.
 //!
 //! This module is intended for use in synchronous applications or in situations where the asynchronous API is not suitable.
+//! It can still be called from within a tokio runtime, its blocking work being moved off the worker threads of the
+//! runtime.
 //! While not guaranteed, the performance of this module should be comparable to the asynchronous API.
 //!
+//! Every result of the asynchronous API is available here too, with the same types: detection along with its evidence
+//! ([Detector::detect_detailed_blocking](crate::Detector::detect_detailed_blocking)), metadata, health checks,
+//! diagnostics and watching for changes (with a callback instead of a stream).
+//!
 //! ## Optional
 //!
-//! This requires the `blocking` feature to be enabled.
+//! This requires the `blocking` feature to be enabled, or the `blocking-ureq` feature to make requests with ureq rather
+//! than the blocking client of reqwest (which runs a tokio runtime in the background).
+//! Unlike the asynchronous API, the blocking API with the `blocking-ureq` feature runs without a tokio runtime, e.g. on
+//! the blocking thread pool of another executor (such as `smol::unblock`). With the default features disabled, so that
+//! the `async` feature is too, tokio and reqwest aren't even built.
 //!
 //! ## Usage
 //!
@@ -44,42 +54,187 @@
 //! println!("Detected provider: {:?}", provider);
 //! ```
 
-pub(crate) mod providers;
+#[cfg(feature = "async")]
+mod bridge;
+/// Without the async engine there is no tokio runtime to move blocking work off, so it runs on the current thread.
+#[cfg(not(feature = "async"))]
+mod bridge {
+    pub(crate) fn block<T>(f: impl FnOnce() -> T) -> T {
+        f()
+    }
+}
+pub(crate) mod checks;
+mod http;
+#[cfg(test)]
+mod parity;
+pub mod providers;
+mod watch;
 
+use std::path::Path;
+use std::sync::mpsc;
 use std::sync::mpsc::RecvTimeoutError;
 use std::sync::mpsc::SyncSender;
-use std::sync::{mpsc, Arc, LazyLock, Mutex};
-use std::time::Duration;
+use std::sync::{Arc, Mutex};
 
 use anyhow::Result;
+use serde_json::Value;
+use tracing::{debug, error, info, instrument};
 
-use crate::blocking::providers::*;
-use crate::{ProviderId, DEFAULT_DETECTION_TIMEOUT};
+pub use self::watch::{watch, Watcher};
+use crate::background::Cancellation;
+use crate::budget::Budget;
+use crate::checks::{Check, Fetch};
+use crate::clock::{self, Instant};
+use crate::precheck::Precheck;
+use crate::provider_check;
+use crate::redact::Redacted;
+use crate::smbios::SmbiosData;
+use crate::{
+    health,
+    hints,
+    host_root,
+    provider,
+    timeout_or_default,
+    AccountInfo,
+    Confidence,
+    CredentialsInfo,
+    Detection,
+    Detector,
+    Diagnostics,
+    Match,
+    MetadataHealth,
+    NetworkInfo,
+    ProbeOutcome,
+    ProviderGroup,
+    ProviderId,
+    ProviderVerdict,
+    Region,
+    SkipReason,
+    P,
+    PROVIDERS,
+    RESOLUTION_WINDOW,
+    SHARED_PRECHECK_TIMEOUT,
+};
 
-/// Represents a cloud service provider.
-#[allow(dead_code)]
-pub(crate) trait Provider: Send + Sync {
-    fn identifier(&self) -> ProviderId;
-    fn identify(&self, tx: SyncSender<ProviderId>, timeout: Duration);
-}
+/// Tries to identify a provider by running the given checks in order, sending a match for the first one that succeeds.
+///
+/// Local checks run first, within the local budget, and the metadata server checks after them (see [Budget]).
+#[instrument(skip_all, fields(provider = %provider.identifier()))]
+fn identify(
+    provider: P,
+    checks: Vec<Check>,
+    tx: SyncSender<Match>,
+    budget: &Budget,
+    smbios: &SmbiosData,
+    cancellation: &Cancellation,
+) {
+    info!("Checking {}", provider.identifier());
+
+    let id = provider.identifier();
+    let progress = &budget.progress;
+    let start = Instant::now();
+    let (local, network): (Vec<Check>, Vec<Check>) = checks.into_iter().partition(Check::is_local);
+    #[cfg(feature = "metrics")]
+    let probes = (!local.is_empty(), !network.is_empty());
+    let mut identified = None;
+    let mut local = local.into_iter();
+
+    // Local checks can't be interrupted, so the budget is only enforced between them
+    while let Some(check) = local.next() {
+        if cancellation.is_cancelled() {
+            return;
+        }
+
+        if budget.local_remaining(start.elapsed()).is_none() {
+            debug!("Local budget exhausted for {}", id);
+            let rest = [&check].into_iter().chain(local.as_slice());
+            progress.skip(id, rest, SkipReason::LocalTimeout);
+            break;
+        }
+
+        let inspection = check
+            .inspect_dmi(smbios)
+            .unwrap_or_else(|| checks::inspect(&check, budget.network));
+        progress.done(id, check.name);
+
+        if inspection.matched() {
+            identified = Some((check, inspection));
+            break;
+        }
+    }
+
+    budget.spend_local(start.elapsed());
+
+    #[cfg(feature = "metrics")]
+    if probes.0 {
+        let method = crate::metrics::Method::Local;
+        crate::metrics::record_probe(provider.identifier(), method, start.elapsed());
+    }
 
-type P = Arc<dyn Provider>;
+    if identified.is_none() {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let phase_end = clock::deadline(budget.network);
 
-static PROVIDERS: LazyLock<Mutex<Vec<P>>> = LazyLock::new(|| {
-    Mutex::new(vec![
-        Arc::new(alibaba::Alibaba) as P,
-        Arc::new(aws::Aws) as P,
-        Arc::new(azure::Azure) as P,
-        Arc::new(digitalocean::DigitalOcean) as P,
-        Arc::new(gcp::Gcp) as P,
-        Arc::new(oci::Oci) as P,
-        Arc::new(openstack::OpenStack) as P,
-        Arc::new(vultr::Vultr) as P,
-    ])
-});
+        for check in network {
+            if cancellation.is_cancelled() {
+                return;
+            }
+
+            if let Some(precheck) = &budget.precheck {
+                if !precheck.reachable_blocking(&check) {
+                    debug!("Skipping {}: metadata server unreachable", check.name);
+                    progress.done(id, check.name);
+                    continue;
+                }
+            }
+
+            // Checks after the first get the time left by those before them, rather than a timeout of their own
+            let timeout = budget.request_timeout(phase_end);
+            if timeout.is_zero() {
+                debug!("Network budget exhausted before {}", check.name);
+                progress.skip_pending(id, SkipReason::Timeout);
+                break;
+            }
+
+            let inspection = checks::inspect(&check, timeout);
+            progress.done(id, check.name);
+
+            if inspection.matched() {
+                identified = Some((check, inspection));
+                break;
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        if probes.1 {
+            let method = crate::metrics::Method::Network;
+            crate::metrics::record_probe(provider.identifier(), method, start.elapsed());
+        }
+    }
+
+    // Checks after the one that matched are no longer needed
+    progress.finish(id);
+
+    let Some((check, inspection)) = identified else {
+        return;
+    };
+
+    info!("Identified {} using {}", provider.identifier(), check.name);
+    let confidence = check.match_confidence(&inspection);
+    let m = Match::new(provider.identifier(), confidence)
+        .source(check.name)
+        .outcome(inspection.outcome);
+
+    if let Err(err) = tx.send(m) {
+        error!("Error sending message: {:?}", err);
+    }
+}
 
 /// Returns a list of currently supported providers.
 ///
+/// The list is static, so this neither locks nor allocates.
+///
 /// # Examples
 ///
 /// Print the list of supported providers.
@@ -87,18 +242,11 @@ static PROVIDERS: LazyLock<Mutex<Vec<P>>> = LazyLock::new(|| {
 /// ```
 /// use cloud_detect::blocking::supported_providers;
 ///
-/// let providers = supported_providers().unwrap();
+/// let providers = supported_providers();
 /// println!("Supported providers: {:?}", providers);
 /// ```
-pub fn supported_providers() -> Result<Vec<String>> {
-    let guard = PROVIDERS
-        .lock()
-        .map_err(|_| anyhow::anyhow!("Error locking providers"))?;
-    let providers: Vec<String> = guard.iter().map(|p| p.identifier().to_string()).collect();
-
-    drop(guard);
-
-    Ok(providers)
+pub fn supported_providers() -> &'static [ProviderId] {
+    crate::supported_providers()
 }
 
 /// Detects the host's cloud provider.
@@ -130,46 +278,751 @@ pub fn supported_providers() -> Result<Vec<String>> {
 /// println!("Detected provider: {:?}", provider);
 /// ```
 pub fn detect(timeout: Option<u64>) -> Result<ProviderId> {
-    let timeout = Duration::from_secs(timeout.unwrap_or(DEFAULT_DETECTION_TIMEOUT));
-    let (tx, rx) = mpsc::sync_channel::<ProviderId>(1);
-    let guard = PROVIDERS
-        .lock()
-        .map_err(|_| anyhow::anyhow!("Error locking providers"))?;
-    let provider_entries: Vec<P> = guard.iter().cloned().collect();
+    Detector::new().with_timeout(timeout).detect_blocking()
+}
+
+/// Checks whether the host runs on any cloud, as fast as possible, for when the provider itself doesn't matter (see
+/// [Detector::is_cloud_blocking]).
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, if it has to run. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::is_cloud;
+///
+/// if is_cloud(Some(1)) {
+///     println!("Running on a cloud");
+/// }
+/// ```
+pub fn is_cloud(timeout: Option<u64>) -> bool {
+    Detector::new().with_timeout(timeout).is_cloud_blocking()
+}
+
+/// Checks whether the host runs on the given provider, probing that provider only (see
+/// [Detector::is_provider_blocking]).
+///
+/// # Arguments
+///
+/// * `provider` - Provider to check for.
+/// * `timeout` - Maximum time (seconds) allowed for detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::is_provider;
+/// use cloud_detect::ProviderId;
+///
+/// let verdict = is_provider(ProviderId::AWS, Some(1));
+/// println!("Running on AWS: {}", verdict.matched);
+/// ```
+pub fn is_provider(provider: ProviderId, timeout: Option<u64>) -> ProviderVerdict {
+    Detector::new()
+        .with_timeout(timeout)
+        .is_provider_blocking(provider)
+}
+
+/// Detects the host's cloud provider among the given providers only, e.g. when the host is known to run on one of a
+/// few clouds. Other providers are neither probed locally nor queried over the network.
+///
+/// Returns [ProviderId::Unknown] if none of the given providers was detected, or if the detection timed out.
+/// Unsupported providers are ignored (see [Detector::providers]).
+///
+/// # Arguments
+///
+/// * `providers` - Providers to probe.
+/// * `timeout` - Maximum time (seconds) allowed for detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::detect_with_providers;
+/// use cloud_detect::ProviderId;
+///
+/// let provider = detect_with_providers(&[ProviderId::AWS, ProviderId::GCP], Some(1)).unwrap();
+/// println!("Detected provider: {:?}", provider);
+/// ```
+pub fn detect_with_providers(providers: &[ProviderId], timeout: Option<u64>) -> Result<ProviderId> {
+    Detector::new()
+        .providers(providers)
+        .with_timeout(timeout)
+        .detect_blocking()
+}
+
+impl Detector {
+    /// Detects the host's cloud provider, blocking the current thread.
+    ///
+    /// Returns [ProviderId::Unknown] if the detection failed or timed out.
+    ///
+    /// # Examples
+    ///
+    /// Detect the cloud provider one provider at a time.
+    ///
+    /// ```
+    /// use cloud_detect::Detector;
+    ///
+    /// let provider = Detector::new()
+    ///     .timeout(1)
+    ///     .sequential()
+    ///     .detect_blocking()
+    ///     .unwrap();
+    /// println!("Detected provider: {:?}", provider);
+    /// ```
+    pub fn detect_blocking(&self) -> Result<ProviderId> {
+        Ok(self.detect_detailed_blocking().provider)
+    }
+
+    /// Checks whether the host runs on any cloud, as fast as possible, blocking the current thread.
+    ///
+    /// This is the blocking counterpart of [Detector::is_cloud].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::Detector;
+    ///
+    /// let on_cloud = Detector::new().timeout(1).is_cloud_blocking();
+    /// println!("Running on a cloud: {}", on_cloud);
+    /// ```
+    pub fn is_cloud_blocking(&self) -> bool {
+        if let Some(detection) = self.forced() {
+            return detection.provider.group() == Some(ProviderGroup::Cloud);
+        }
+
+        if self.strong_cloud_match(&self.smbios()) {
+            return true;
+        }
+
+        let detector = self.shared_precheck();
+        if let Some(check) = self.shared_metadata_check() {
+            let timeout = detector.precheck_timeout.unwrap_or(SHARED_PRECHECK_TIMEOUT);
+            if !Precheck::new(timeout).reachable_blocking(&check) {
+                info!("Shared metadata server unreachable, not on a cloud");
+                return false;
+            }
+        }
+
+        detector.detect_detailed_blocking().provider.group() == Some(ProviderGroup::Cloud)
+    }
 
-    for provider in provider_entries {
-        let tx = tx.clone();
-        std::thread::spawn(move || provider.identify(tx, timeout));
+    /// Checks whether the host runs on the given provider, probing only that provider, blocking the current thread.
+    ///
+    /// This is the blocking counterpart of [Detector::is_provider].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{Detector, ProviderId};
+    ///
+    /// let verdict = Detector::new()
+    ///     .timeout(1)
+    ///     .is_provider_blocking(ProviderId::AWS);
+    /// println!("Running on AWS: {}", verdict.matched);
+    /// ```
+    pub fn is_provider_blocking(&self, provider: ProviderId) -> ProviderVerdict {
+        ProviderVerdict::new(
+            provider,
+            self.targeting(provider).detect_detailed_blocking(),
+        )
     }
 
-    match rx.recv_timeout(timeout) {
-        Ok(provider_id) => Ok(provider_id),
-        Err(err) => match err {
-            RecvTimeoutError::Timeout => Ok(ProviderId::Unknown),
-            RecvTimeoutError::Disconnected => Err(anyhow::anyhow!("Error receiving message")),
-        },
+    /// Detects the host's cloud provider, along with the evidence it was resolved from, blocking the current thread.
+    ///
+    /// This is the blocking counterpart of [Detector::detect_detailed].
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::Detector;
+    ///
+    /// let detection = Detector::new().timeout(1).detect_detailed_blocking();
+    /// println!("Detected provider: {}", detection.provider);
+    ///
+    /// for evidence in &detection.evidence {
+    ///     println!(
+    ///         "{} matched {} ({:?})",
+    ///         evidence.source, evidence.provider, evidence.confidence
+    ///     );
+    /// }
+    /// ```
+    pub fn detect_detailed_blocking(&self) -> Detection {
+        bridge::block(|| {
+            let cancellation = Cancellation::default();
+            let detection = self.detect_until_cancelled(&cancellation);
+
+            // Threads still probing providers stop at their next check
+            cancellation.cancel();
+
+            detection
+        })
+    }
+
+    /// Detects the host's cloud provider, with threads that stop once the detection is cancelled.
+    fn detect_until_cancelled(&self, cancellation: &Cancellation) -> Detection {
+        if let Some(detection) = self.forced() {
+            return detection;
+        }
+
+        let smbios = self.smbios();
+
+        if let Some(detection) = self.fast_exit(&smbios) {
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_detection(&detection);
+            return detection;
+        }
+
+        let budget = self.budget();
+        let (tx, rx) = mpsc::sync_channel::<Match>(PROVIDERS.len() + 1);
+
+        #[cfg(feature = "systemd")]
+        let hypervisor = {
+            let (tx, rx) = mpsc::sync_channel(1);
+            self.background.spawn(move || {
+                // Detection may have returned already
+                let _ = tx.send(hints::virt::identify());
+            });
+            rx
+        };
+        #[cfg(feature = "network")]
+        let gateway = {
+            let (tx, rx) = mpsc::sync_channel(1);
+            self.background.spawn(move || {
+                // Detection may have returned already
+                let _ = tx.send(hints::network::identify());
+            });
+            rx
+        };
+
+        // Threads don't hold clones of the detector, as dropping the last one reaps them
+        let included = self.supported_providers();
+        let root = self.root().to_path_buf();
+
+        if self.host_signals() {
+            let tx = tx.clone();
+            let included = included.clone();
+            let root = root.clone();
+            self.background.spawn(move || {
+                // The hint may name a provider the detector doesn't probe
+                if let Some(provider) =
+                    hints::cloudinit::identify(&root).filter(|p| included.contains(p))
+                {
+                    let m = Match::new(provider, Confidence::Authoritative).source("cloud-init");
+
+                    if let Err(err) = tx.send(m) {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        #[cfg(feature = "kubernetes")]
+        if self.host_signals() {
+            let tx = tx.clone();
+            let included = included.clone();
+            let root = root.clone();
+            self.background.spawn(move || {
+                if let Some(provider) =
+                    hints::kubelet::identify(&root).filter(|p| included.contains(p))
+                {
+                    let m = Match::new(provider, Confidence::Verified).source("kubelet");
+
+                    if let Err(err) = tx.send(m) {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        for check in self.composed_checks() {
+            let tx = tx.clone();
+            let context = provider_check::context(check.as_ref(), &budget);
+            self.background.spawn(move || {
+                if let Some(m) = provider_check::identify_blocking(check.as_ref(), &context) {
+                    if let Err(err) = tx.send(m) {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        if self.local_only && self.host_signals() {
+            let tx = tx.clone();
+            let included = included.clone();
+            let root = root.clone();
+            self.background.spawn(move || {
+                for provider in hints::dns::identify(&root)
+                    .into_iter()
+                    .filter(|p| included.contains(p))
+                {
+                    let m = Match::new(provider, Confidence::Unverified).source("dns");
+
+                    if let Err(err) = tx.send(m) {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        // A bounded pool of threads takes providers in order of likelihood
+        let providers: Vec<(P, Vec<Check>)> = self
+            .ranked_providers()
+            .into_iter()
+            .map(|provider| (provider, self.checks(provider)))
+            .collect();
+
+        // Providers still queued when detection times out are reported as skipped
+        for (provider, checks) in &providers {
+            budget.progress.queue(provider.identifier(), checks);
+        }
+
+        let queue = Arc::new(Mutex::new(providers.into_iter()));
+
+        for _ in 0..self.concurrency() {
+            let tx = tx.clone();
+            let queue = queue.clone();
+            let budget = budget.clone();
+            let smbios = smbios.clone();
+            let cancellation = cancellation.clone();
+
+            self.background.spawn(move || loop {
+                if cancellation.is_cancelled() {
+                    return;
+                }
+
+                let Some((provider, checks)) = queue.lock().ok().and_then(|mut queue| queue.next())
+                else {
+                    return;
+                };
+
+                let tx = tx.clone();
+                identify(provider, checks, tx, &budget, &smbios, &cancellation);
+            });
+        }
+
+        // Only the spawned threads hold senders now, so the channel disconnects once they have all finished
+        drop(tx);
+
+        let mut matches = Vec::new();
+        let mut timed_out = false;
+
+        // Generic platforms don't open the resolution window: bare KVM matches from CPUID right away on most clouds,
+        // while the specific provider it runs may still be waiting on its metadata server. The deadline moves as the
+        // local phases of providers are recorded.
+        let specific = loop {
+            match rx.recv_timeout(budget.remaining()) {
+                Ok(m) => {
+                    let generic = m.provider.genericity() > 0;
+                    matches.push(m);
+
+                    if !generic {
+                        break true;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) if budget.remaining().is_zero() => {
+                    debug!("Detection timed out");
+                    timed_out = true;
+                    break false;
+                }
+                Err(RecvTimeoutError::Timeout) => continue,
+                Err(RecvTimeoutError::Disconnected) => {
+                    debug!("All providers have finished identifying");
+                    break false;
+                }
+            }
+        };
+
+        if matches.is_empty() {
+            let detection = Detection::default().skipped(budget.progress.skipped(timed_out));
+            #[cfg(feature = "metrics")]
+            if timed_out {
+                crate::metrics::record_timeout();
+            } else {
+                crate::metrics::record_detection(&detection);
+            }
+            return detection;
+        }
+
+        // Collect any other matches that arrive within the resolution window
+        let window = Instant::now() + budget.remaining().min(RESOLUTION_WINDOW);
+
+        if specific {
+            while let Some(remaining) = window.checked_duration_since(Instant::now()) {
+                match rx.recv_timeout(remaining) {
+                    Ok(m) => matches.push(m),
+                    Err(_) => break,
+                }
+            }
+        }
+
+        #[cfg(feature = "systemd")]
+        {
+            let remaining = window.saturating_duration_since(Instant::now());
+            if let Ok(Some(hypervisor)) = hypervisor
+                .recv_timeout(remaining)
+                .map(|hypervisor| hypervisor.filter(|_| self.host_signals()))
+            {
+                crate::corroborate(&mut matches, hypervisor);
+            }
+        }
+
+        #[cfg(feature = "network")]
+        {
+            let remaining = window.saturating_duration_since(Instant::now());
+            if let Ok(Some(gateway)) = gateway
+                .recv_timeout(remaining)
+                .map(|gateway| gateway.filter(|_| self.host_signals()))
+            {
+                crate::corroborate(&mut matches, gateway);
+            }
+        }
+
+        let provider = self.resolve(&matches);
+        debug!("Resolved {:?} to {}", matches, provider);
+
+        let detection = self
+            .detection(provider, &matches, &smbios)
+            .skipped(budget.progress.skipped(timed_out));
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_detection(&detection);
+
+        detection
+    }
+}
+
+/// Fetches the primary metadata document of a provider from its metadata server, as raw JSON.
+///
+/// Unlike the typed metadata returned elsewhere, no fields are lost (see [fetch_raw_metadata](crate::fetch_raw_metadata())).
+///
+/// # Arguments
+///
+/// * `provider` - The provider of the host, which is not detected.
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::fetch_raw_metadata;
+/// use cloud_detect::ProviderId;
+///
+/// let metadata = fetch_raw_metadata(ProviderId::AWS, None).unwrap();
+/// println!("Kernel ID: {:?}", metadata.get("kernelId"));
+/// ```
+#[instrument]
+pub fn fetch_raw_metadata(provider: ProviderId, timeout: Option<u64>) -> Result<Value> {
+    checks::fetch(&crate::raw_metadata(provider)?, timeout_or_default(timeout))
+}
+
+/// Checks the health of the metadata server of a provider, reporting its status and latency.
+///
+/// Returns `None` if the provider isn't supported or has no metadata server. Unlike detection, the response isn't
+/// required to identify the provider, only to be successful, and metadata server opt-outs are ignored.
+///
+/// # Arguments
+///
+/// * `provider` - The provider whose metadata server is checked.
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::metadata_health;
+/// use cloud_detect::ProviderId;
+///
+/// if let Some(health) = metadata_health(ProviderId::AWS, Some(1)) {
+///     println!("{}", health);
+/// }
+/// ```
+#[instrument]
+pub fn metadata_health(provider: ProviderId, timeout: Option<u64>) -> Option<MetadataHealth> {
+    let request = health::health_request(provider)?;
+    let start = Instant::now();
+    let result = checks::send(&request, timeout_or_default(timeout));
+
+    Some(MetadataHealth::new(
+        provider,
+        &request,
+        result,
+        start.elapsed(),
+    ))
+}
+
+/// Runs every check of every provider to completion, reporting the outcome of each one.
+///
+/// Unlike [detect], this does not stop at the first match, so it takes as long as the slowest provider. Addresses,
+/// identifiers and tokens are redacted unless disabled with [set_redaction](crate::set_redaction), so the report can be
+/// attached to bug reports.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::diagnose;
+///
+/// let diagnostics = diagnose(Some(1));
+/// println!("{}", diagnostics);
+/// ```
+#[instrument]
+pub fn diagnose(timeout: Option<u64>) -> Diagnostics {
+    bridge::block(|| diagnose_blocking(timeout))
+}
+
+/// Runs every check of every provider to completion on the current thread (see [diagnose]).
+fn diagnose_blocking(timeout: Option<u64>) -> Diagnostics {
+    let timeout = timeout_or_default(timeout);
+    let root = Path::new(host_root::ROOT);
+    let cloudinit = std::thread::spawn(|| hints::cloudinit::identify(root));
+    let hints = std::thread::spawn(|| hints::prior::identify(PROVIDERS, root));
+
+    let handles: Vec<_> = PROVIDERS
+        .iter()
+        .map(|&provider| {
+            std::thread::spawn(move || {
+                Detector::new()
+                    .checks(provider)
+                    .iter()
+                    .map(|check| {
+                        let start = Instant::now();
+                        let inspection = checks::inspect(check, timeout);
+                        ProbeOutcome::new(provider.identifier(), check, inspection, start.elapsed())
+                    })
+                    .collect::<Vec<_>>()
+            })
+        })
+        .collect();
+
+    let mut probes = Vec::new();
+
+    for handle in handles {
+        match handle.join() {
+            Ok(outcomes) => probes.extend(outcomes),
+            Err(err) => error!("Error diagnosing provider: {:?}", err),
+        }
+    }
+
+    Diagnostics::new(
+        cloudinit.join().ok().flatten(),
+        hints.join().unwrap_or_default(),
+        probes,
+    )
+}
+
+/// Detects the region (and zone, where available) of the host.
+///
+/// Returns `None` if the provider could not be detected, does not report its region, or the region could not be
+/// fetched from its metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and then for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::detect_region;
+///
+/// if let Some(region) = detect_region(Some(1)).unwrap() {
+///     println!("Detected region: {} ({:?})", region.region, region.zone);
+/// }
+/// ```
+#[instrument]
+pub fn detect_region(timeout: Option<u64>) -> Result<Option<Region>> {
+    detect_and_fetch(timeout, |provider| provider.region())
+}
+
+/// Detects the instance type of the host (e.g. `m5.large` on AWS, `e2-standard-4` on GCP or `Standard_D4s_v5` on
+/// Azure).
+///
+/// Returns `None` if the provider could not be detected, does not report its instance type, or the instance type could
+/// not be fetched from its metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and then for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::detect_instance_type;
+///
+/// if let Some(instance_type) = detect_instance_type(Some(1)).unwrap() {
+///     println!("Detected instance type: {}", instance_type);
+/// }
+/// ```
+#[instrument]
+pub fn detect_instance_type(timeout: Option<u64>) -> Result<Option<String>> {
+    detect_and_fetch(timeout, |provider| provider.instance_type())
+}
+
+/// Fetches the account of the host (e.g. the AWS account, Azure subscription or GCP project).
+///
+/// Returns `None` if the provider could not be detected, does not report its account, or the account could not be
+/// fetched from its metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and then for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::fetch_account_info;
+///
+/// if let Some(account) = fetch_account_info(Some(1)).unwrap() {
+///     println!(
+///         "Running in {} account {}",
+///         account.provider, account.account_id
+///     );
+/// }
+/// ```
+#[instrument]
+pub fn fetch_account_info(timeout: Option<u64>) -> Result<Option<AccountInfo>> {
+    detect_and_fetch(timeout, |provider| provider.account_info())
+}
+
+/// Fetches the network configuration of the host's primary network interface (local and public IP addresses, MAC
+/// address, virtual network and subnet).
+///
+/// Returns `None` if the provider could not be detected, does not report its network configuration, or the network
+/// configuration could not be fetched from its metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and then for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::fetch_network_info;
+///
+/// if let Some(network) = fetch_network_info(Some(1)).unwrap() {
+///     println!(
+///         "Local IPv4: {:?}, public IPv4: {:?}",
+///         network.local_ipv4, network.public_ipv4
+///     );
+/// }
+/// ```
+#[instrument]
+pub fn fetch_network_info(timeout: Option<u64>) -> Result<Option<NetworkInfo>> {
+    detect_and_fetch(timeout, |provider| provider.network_info())
+}
+
+/// Checks whether the host's metadata server exposes credentials (e.g. an AWS IAM role or a GCP service account) to
+/// the host.
+///
+/// This never retrieves the credentials themselves: only the endpoints listing the identities credentials are
+/// available for are queried.
+///
+/// Returns `None` if the provider could not be detected, can't expose credentials through its metadata server, or the
+/// metadata server could not be queried.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and then for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::blocking::fetch_credentials_info;
+///
+/// if let Some(credentials) = fetch_credentials_info(Some(1)).unwrap() {
+///     println!("Credentials exposed: {}", credentials.exposed);
+/// }
+/// ```
+#[instrument]
+pub fn fetch_credentials_info(timeout: Option<u64>) -> Result<Option<CredentialsInfo>> {
+    detect_and_fetch(timeout, |provider| provider.credentials_info())
+}
+
+/// Detects the host's cloud provider, then fetches metadata from its metadata server, if the provider exposes it.
+fn detect_and_fetch<T>(
+    timeout: Option<u64>,
+    fetch: impl FnOnce(P) -> Option<Fetch<T>>,
+) -> Result<Option<T>> {
+    let Some(provider) = provider(detect(timeout)?) else {
+        return Ok(None);
+    };
+
+    if !Detector::new().metadata_enabled(provider.identifier()) {
+        debug!("Skipping metadata fetch for {}", provider.identifier());
+        return Ok(None);
+    }
+
+    let Some(fetch) = fetch(provider) else {
+        return Ok(None);
+    };
+
+    match checks::fetch(&fetch, timeout_or_default(timeout)) {
+        Ok(value) => Ok(Some(value)),
+        Err(err) => {
+            error!("Error fetching metadata: {:?}", Redacted(&err));
+            Ok(None)
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use anyhow::Result;
-
     use super::*;
+    use crate::providers::*;
+
+    #[test]
+    fn test_supported_providers() {
+        let providers = supported_providers();
+        assert_eq!(providers.len(), 18);
+        assert!(providers.contains(&akamai::IDENTIFIER));
+        assert!(providers.contains(&alibaba::IDENTIFIER));
+        assert!(providers.contains(&aws::IDENTIFIER));
+        assert!(providers.contains(&azure::IDENTIFIER));
+        assert!(providers.contains(&civo::IDENTIFIER));
+        assert!(providers.contains(&digitalocean::IDENTIFIER));
+        assert!(providers.contains(&gcp::IDENTIFIER));
+        assert!(providers.contains(&kvm::IDENTIFIER));
+        assert!(providers.contains(&naver::IDENTIFIER));
+        assert!(providers.contains(&nhn::IDENTIFIER));
+        assert!(providers.contains(&nutanix::IDENTIFIER));
+        assert!(providers.contains(&oci::IDENTIFIER));
+        assert!(providers.contains(&openstack::IDENTIFIER));
+        assert!(providers.contains(&proxmox::IDENTIFIER));
+        assert!(providers.contains(&vkcloud::IDENTIFIER));
+        assert!(providers.contains(&vmware::IDENTIFIER));
+        assert!(providers.contains(&vultr::IDENTIFIER));
+        assert!(providers.contains(&yandex::IDENTIFIER));
+    }
+
+    #[test]
+    fn test_detect_with_providers_unsupported() {
+        assert_eq!(
+            detect_with_providers(&[ProviderId::Unknown], Some(1)).ok(),
+            Some(ProviderId::Unknown)
+        );
+    }
 
     #[test]
-    fn test_supported_providers() -> Result<()> {
-        let providers = supported_providers()?;
-        assert_eq!(providers.len(), 8);
-        assert!(providers.contains(&alibaba::IDENTIFIER.to_string()));
-        assert!(providers.contains(&aws::IDENTIFIER.to_string()));
-        assert!(providers.contains(&azure::IDENTIFIER.to_string()));
-        assert!(providers.contains(&digitalocean::IDENTIFIER.to_string()));
-        assert!(providers.contains(&gcp::IDENTIFIER.to_string()));
-        assert!(providers.contains(&oci::IDENTIFIER.to_string()));
-        assert!(providers.contains(&openstack::IDENTIFIER.to_string()));
-        assert!(providers.contains(&vultr::IDENTIFIER.to_string()));
-
-        Ok(())
+    fn test_metadata_health_unknown() {
+        assert!(metadata_health(ProviderId::Unknown, Some(1)).is_none());
+    }
+
+    #[test]
+    fn test_fetch_raw_metadata_unknown() {
+        assert!(fetch_raw_metadata(ProviderId::Unknown, Some(1)).is_err());
+    }
+
+    #[cfg(feature = "blocking-ureq")]
+    #[test]
+    fn test_detect_without_tokio_runtime() {
+        // Detection panics if a tokio runtime is required but not running
+        let provider = std::thread::spawn(|| {
+            assert!(tokio::runtime::Handle::try_current().is_err());
+            detect_with_providers(&[naver::IDENTIFIER], Some(1))
+        })
+        .join()
+        .unwrap();
+
+        assert!(provider.is_ok());
     }
 }
@@ -0,0 +1,261 @@
+//! Parity tests between the async and blocking check executors.
+//!
+//! Every check and metadata fetch of every provider is run through both executors against identical inputs, and both
+//! must agree.
+
+use std::fmt::Debug;
+use std::io::Write;
+use std::time::Duration;
+
+use mockito::{Matcher, Server};
+use tempfile::{NamedTempFile, TempDir};
+
+use crate::checks::{Check, Fetch, Probe};
+use crate::provider_fixtures as fixtures;
+use crate::providers::*;
+use crate::{blocking, PROVIDERS};
+
+const TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Inputs served to every check, covering the fixtures of all providers.
+const INPUTS: &[&str] = &[
+    fixtures::MALFORMED,
+    fixtures::CAPTIVE_PORTAL,
+    "",
+    fixtures::akamai::TOKEN,
+    fixtures::akamai::METADATA,
+    fixtures::akamai::METADATA_MISMATCH,
+    fixtures::akamai::VENDOR,
+    fixtures::akamai::VENDOR_LEGACY,
+    fixtures::alibaba::TOKEN,
+    fixtures::alibaba::METADATA,
+    fixtures::alibaba::VENDOR,
+    fixtures::aws::TOKEN,
+    fixtures::aws::MAC,
+    fixtures::aws::IDENTITY_DOCUMENT,
+    fixtures::aws::IDENTITY_DOCUMENT_MISMATCH,
+    fixtures::aws::VENDOR,
+    fixtures::azure::METADATA,
+    fixtures::azure::METADATA_MISMATCH,
+    fixtures::azure::VENDOR,
+    fixtures::civo::VENDOR,
+    fixtures::digitalocean::METADATA,
+    fixtures::digitalocean::METADATA_MISMATCH,
+    fixtures::digitalocean::ID,
+    fixtures::digitalocean::VENDOR,
+    fixtures::gcp::VENDOR,
+    fixtures::gcp::ZONE,
+    fixtures::gcp::MACHINE_TYPE,
+    fixtures::gcp::PROJECT_ID,
+    fixtures::gcp::SERVICE_ACCOUNTS,
+    fixtures::gcp::NETWORK_INTERFACES,
+    fixtures::gcp::METADATA,
+    fixtures::kvm::HARVESTER_VENDOR,
+    fixtures::kvm::KUBEVIRT_VENDOR,
+    fixtures::kvm::QEMU_VENDOR,
+    fixtures::naver::INSTANCE_ID,
+    fixtures::naver::VENDOR,
+    fixtures::nhn::METADATA,
+    fixtures::nutanix::VENDOR,
+    fixtures::nutanix::PRODUCT_NAME,
+    fixtures::oci::METADATA_V1,
+    fixtures::oci::METADATA_V1_MISMATCH,
+    fixtures::oci::METADATA_V2,
+    fixtures::oci::METADATA_V2_MISMATCH,
+    fixtures::oci::VNICS,
+    fixtures::oci::VENDOR,
+    fixtures::openstack::CONFIG_DRIVE_METADATA,
+    fixtures::openstack::PRODUCT_NAME,
+    fixtures::openstack::CHASSIS_ASSET_TAG,
+    fixtures::proxmox::BIOS_VENDOR,
+    fixtures::proxmox::VENDOR,
+    fixtures::vkcloud::METADATA,
+    fixtures::vmware::VENDOR,
+    fixtures::vmware::PRODUCT_NAME,
+    fixtures::vultr::METADATA,
+    fixtures::vultr::METADATA_BARE_METAL,
+    fixtures::vultr::METADATA_MISMATCH,
+    fixtures::vultr::VENDOR,
+    fixtures::yandex::IDENTITY_DOCUMENT,
+    fixtures::yandex::IDENTITY_DOCUMENT_MISMATCH,
+    fixtures::yandex::VENDOR,
+];
+
+/// Runs a check through both executors and asserts that they agree.
+fn assert_parity(runtime: &tokio::runtime::Runtime, check: &Check, input: &str) {
+    let async_result = runtime.block_on(check.run(TIMEOUT));
+    let blocking_result = blocking::checks::run(check, TIMEOUT);
+
+    assert_eq!(
+        async_result, blocking_result,
+        "check: {}, input: {:?}",
+        check.name, input
+    );
+}
+
+/// Serves each input to both executors fetching the same metadata, and asserts that they agree.
+fn assert_fetch_parity<T, F>(fetch: F)
+where
+    T: Debug + PartialEq + 'static,
+    F: Fn(&str) -> Fetch<T>,
+{
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    for &input in INPUTS {
+        let mut server = Server::new();
+        let url = server.url();
+        // Session tokens are retrieved with PUT requests
+        for method in ["GET", "PUT"] {
+            server
+                .mock(method, Matcher::Any)
+                .with_status(200)
+                .with_body(input)
+                .create();
+        }
+
+        let fetch = fetch(&url);
+        let async_result = runtime.block_on(fetch.run(TIMEOUT)).ok();
+        let blocking_result = blocking::checks::fetch(&fetch, TIMEOUT).ok();
+
+        assert_eq!(async_result, blocking_result, "input: {:?}", input);
+    }
+}
+
+#[test]
+fn test_fetch_parity() {
+    assert_fetch_parity(akamai::metadata);
+    assert_fetch_parity(alibaba::identity_document);
+    assert_fetch_parity(aws::identity_document);
+    assert_fetch_parity(azure::instance_metadata);
+    assert_fetch_parity(aws::partition);
+    assert_fetch_parity(azure::cloud_environment);
+    assert_fetch_parity(digitalocean::metadata);
+    assert_fetch_parity(gcp::instance_attributes);
+    assert_fetch_parity(gcp::metadata);
+    assert_fetch_parity(naver::instance_id);
+    assert_fetch_parity(oci::instance);
+    assert_fetch_parity(openstack::metadata);
+    assert_fetch_parity(vultr::metadata);
+    assert_fetch_parity(yandex::identity_document);
+}
+
+#[test]
+fn test_region_parity() {
+    assert_fetch_parity(akamai::region);
+    assert_fetch_parity(alibaba::region);
+    assert_fetch_parity(aws::region);
+    assert_fetch_parity(azure::region);
+    assert_fetch_parity(digitalocean::region);
+    assert_fetch_parity(gcp::region);
+    assert_fetch_parity(nhn::region);
+    assert_fetch_parity(oci::region);
+    assert_fetch_parity(vultr::region);
+    assert_fetch_parity(yandex::region);
+}
+
+#[test]
+fn test_instance_type_parity() {
+    assert_fetch_parity(akamai::instance_type);
+    assert_fetch_parity(alibaba::instance_type);
+    assert_fetch_parity(aws::instance_type);
+    assert_fetch_parity(azure::instance_type);
+    assert_fetch_parity(gcp::instance_type);
+    assert_fetch_parity(oci::instance_type);
+    assert_fetch_parity(vultr::instance_type);
+}
+
+#[test]
+fn test_account_info_parity() {
+    assert_fetch_parity(alibaba::account_info);
+    assert_fetch_parity(aws::account_info);
+    assert_fetch_parity(azure::account_info);
+    assert_fetch_parity(gcp::account_info);
+    assert_fetch_parity(nhn::account_info);
+    assert_fetch_parity(oci::account_info);
+    assert_fetch_parity(openstack::account_info);
+    assert_fetch_parity(vkcloud::account_info);
+}
+
+#[test]
+fn test_network_info_parity() {
+    assert_fetch_parity(alibaba::network_info);
+    assert_fetch_parity(aws::network_info);
+    assert_fetch_parity(azure::network_info);
+    assert_fetch_parity(digitalocean::network_info);
+    assert_fetch_parity(gcp::network_info);
+    assert_fetch_parity(oci::network_info);
+}
+
+#[test]
+fn test_credentials_info_parity() {
+    assert_fetch_parity(alibaba::credentials_info);
+    assert_fetch_parity(aws::credentials_info);
+    assert_fetch_parity(azure::credentials_info);
+    assert_fetch_parity(gcp::credentials_info);
+}
+
+#[test]
+fn test_metadata_parity() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    for &input in INPUTS {
+        let mut server = Server::new();
+        let url = server.url();
+        // Session tokens are retrieved with PUT requests
+        for method in ["GET", "PUT"] {
+            server
+                .mock(method, Matcher::Any)
+                .with_status(200)
+                .with_body(input)
+                .create();
+        }
+
+        for provider in PROVIDERS {
+            for mut check in provider.checks() {
+                if let Probe::Http { request, .. } = &mut check.probe {
+                    request.base_uri = url.clone();
+                    assert_parity(&runtime, &check, input);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_file_parity() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+
+    for &input in INPUTS {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(input.as_bytes()).unwrap();
+
+        for provider in PROVIDERS {
+            for mut check in provider.checks() {
+                match &mut check.probe {
+                    Probe::Dmi { path, .. } | Probe::File { path, .. } => {
+                        *path = file.path().to_path_buf();
+                        assert_parity(&runtime, &check, input);
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_exists_parity() {
+    let runtime = tokio::runtime::Runtime::new().unwrap();
+    let dir = TempDir::new().unwrap();
+
+    for path in [dir.path().to_path_buf(), dir.path().join("missing")] {
+        for provider in PROVIDERS {
+            for mut check in provider.checks() {
+                if let Probe::Exists { path: p } = &mut check.probe {
+                    *p = path.clone();
+                    assert_parity(&runtime, &check, &path.display().to_string());
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,29 @@
+//! Akamai Cloud (formerly Linode).
+
+use anyhow::Result;
+
+use crate::blocking::checks;
+use crate::providers::akamai;
+pub use crate::providers::akamai::Metadata;
+use crate::timeout_or_default;
+
+/// Fetches the metadata of the instance from the metadata service.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::providers::akamai::fetch_metadata;
+///
+/// let metadata = fetch_metadata(None).unwrap();
+/// println!("Linode ID: {}", metadata.id);
+/// ```
+pub fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    checks::fetch(
+        &akamai::metadata(akamai::METADATA_URI),
+        timeout_or_default(timeout),
+    )
+}
@@ -1,151 +1,65 @@
 //! Google Cloud Platform (GCP).
 
-use std::fs;
-use std::path::Path;
-use std::sync::mpsc::SyncSender;
-use std::time::Duration;
-
-use reqwest::blocking::Client;
-use tracing::{debug, error, info, instrument};
-
-use crate::blocking::Provider;
-use crate::ProviderId;
-
-const METADATA_URI: &str = "http://metadata.google.internal";
-const METADATA_PATH: &str = "/computeMetadata/v1/instance/tags";
-const VENDOR_FILE: &str = "/sys/class/dmi/id/product_name";
-pub(crate) const IDENTIFIER: ProviderId = ProviderId::GCP;
-
-pub struct Gcp;
-
-impl Provider for Gcp {
-    fn identifier(&self) -> ProviderId {
-        IDENTIFIER
-    }
-
-    /// Tries to identify GCP using all the implemented options.
-    #[instrument(skip_all)]
-    fn identify(&self, tx: SyncSender<ProviderId>, timeout: Duration) {
-        info!("Checking Google Cloud Platform");
-        if self.check_vendor_file(VENDOR_FILE) || self.check_metadata_server(METADATA_URI, timeout)
-        {
-            info!("Identified Google Cloud Platform");
-            if let Err(err) = tx.send(IDENTIFIER) {
-                error!("Error sending message: {:?}", err);
-            }
-        }
-    }
+use std::collections::HashMap;
+
+use anyhow::Result;
+
+use crate::blocking::checks;
+use crate::providers::gcp::{self, Metadata};
+use crate::timeout_or_default;
+
+/// Fetches the custom metadata attributes of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::providers::gcp::fetch_instance_attributes;
+///
+/// let attributes = fetch_instance_attributes(None).unwrap();
+/// println!("Attributes: {:?}", attributes);
+/// ```
+pub fn fetch_instance_attributes(timeout: Option<u64>) -> Result<HashMap<String, String>> {
+    checks::fetch(
+        &gcp::instance_attributes(gcp::METADATA_URI),
+        timeout_or_default(timeout),
+    )
 }
 
-impl Gcp {
-    /// Tries to identify GCP via metadata server.
-    #[instrument(skip_all)]
-    fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-
-        let req = client.get(url).header("Metadata-Flavor", "Google");
-        let resp = req.send();
-
-        match resp {
-            Ok(resp) => resp.status().is_success(),
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
-    }
-
-    /// Tries to identify GCP using vendor file(s).
-    #[instrument(skip_all)]
-    fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
-
-        if vendor_file.as_ref().exists() {
-            return match fs::read_to_string(vendor_file) {
-                Ok(content) => content.contains("Google"),
-                Err(err) => {
-                    error!("Error reading vendor file: {:?}", err);
-                    false
-                }
-            };
-        }
-
-        false
+/// Fetches the metadata tree of the instance and its project (e.g. the ID, zone, machine type and service accounts of
+/// the instance) from the metadata server, in a single request.
+///
+/// The tree is cached once fetched, so later calls (including from the async API) don't query the metadata server
+/// again. Custom metadata attributes aren't kept (see [fetch_instance_attributes]).
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::providers::gcp::fetch_metadata;
+///
+/// let metadata = fetch_metadata(None).unwrap();
+/// println!(
+///     "Instance {} in {}",
+///     metadata.instance.id, metadata.instance.zone
+/// );
+/// ```
+pub fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    if let Some(metadata) = gcp::cached_metadata(gcp::METADATA_URI) {
+        return Ok(metadata);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use std::io::Write;
-
-    use anyhow::Result;
-    use mockito::Server;
-    use tempfile::NamedTempFile;
-
-    use super::*;
 
-    #[test]
-    fn test_check_metadata_server_success() {
-        let mut server = Server::new();
-        let url = server.url();
+    let metadata = checks::fetch(
+        &gcp::metadata(gcp::METADATA_URI),
+        timeout_or_default(timeout),
+    )?;
+    gcp::cache_metadata(gcp::METADATA_URI, &metadata);
 
-        let mock = server.mock("GET", METADATA_PATH).with_status(200).create();
-
-        let provider = Gcp;
-        let result = provider.check_metadata_server(&url, Duration::from_secs(1));
-
-        mock.assert();
-        assert!(result);
-    }
-
-    #[test]
-    fn test_check_metadata_server_failure() {
-        let mut server = Server::new();
-        let url = server.url();
-
-        let mock = server.mock("GET", METADATA_PATH).with_status(500).create();
-
-        let provider = Gcp;
-        let result = provider.check_metadata_server(&url, Duration::from_secs(1));
-
-        mock.assert();
-        assert!(!result);
-    }
-
-    #[test]
-    fn test_check_vendor_file_success() -> Result<()> {
-        let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"Google")?;
-
-        let provider = Gcp;
-        let result = provider.check_vendor_file(vendor_file.path());
-
-        assert!(result);
-
-        Ok(())
-    }
-
-    #[test]
-    fn test_check_vendor_file_failure() -> Result<()> {
-        let vendor_file = NamedTempFile::new()?;
-
-        let provider = Gcp;
-        let result = provider.check_vendor_file(vendor_file.path());
-
-        assert!(!result);
-
-        Ok(())
-    }
+    Ok(metadata)
 }
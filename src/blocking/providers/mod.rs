@@ -1,10 +1,17 @@
-//! Provider modules.
+//! Blocking access to the metadata server of each provider.
+//!
+//! Mirrors [crate::providers].
 
-pub(crate) mod alibaba;
-pub(crate) mod aws;
-pub(crate) mod azure;
-pub(crate) mod digitalocean;
-pub(crate) mod gcp;
-pub(crate) mod oci;
-pub(crate) mod openstack;
-pub(crate) mod vultr;
+pub mod akamai;
+pub mod alibaba;
+pub mod aws;
+pub mod azure;
+pub mod digitalocean;
+pub mod gcp;
+pub mod naver;
+pub mod nhn;
+pub mod oci;
+pub mod openstack;
+pub mod vkcloud;
+pub mod vultr;
+pub mod yandex;
@@ -0,0 +1,28 @@
+//! Naver Cloud Platform.
+
+use anyhow::Result;
+
+use crate::blocking::checks;
+use crate::providers::naver;
+use crate::timeout_or_default;
+
+/// Fetches the server instance number of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::providers::naver::fetch_instance_id;
+///
+/// let instance_id = fetch_instance_id(None).unwrap();
+/// println!("Server instance number: {}", instance_id);
+/// ```
+pub fn fetch_instance_id(timeout: Option<u64>) -> Result<u64> {
+    checks::fetch(
+        &naver::instance_id(naver::METADATA_URI),
+        timeout_or_default(timeout),
+    )
+}
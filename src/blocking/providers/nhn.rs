@@ -0,0 +1,29 @@
+//! NHN Cloud.
+
+use anyhow::Result;
+
+use crate::blocking::checks;
+pub use crate::providers::nhn::Metadata;
+use crate::providers::{nhn, openstack};
+use crate::timeout_or_default;
+
+/// Fetches the OpenStack metadata of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::providers::nhn::fetch_metadata;
+///
+/// let metadata = fetch_metadata(None).unwrap();
+/// println!("Instance UUID: {}", metadata.uuid);
+/// ```
+pub fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    checks::fetch(
+        &openstack::metadata(nhn::METADATA_URI),
+        timeout_or_default(timeout),
+    )
+}
@@ -0,0 +1,29 @@
+//! Yandex Cloud.
+
+use anyhow::Result;
+
+use crate::blocking::checks;
+use crate::providers::yandex;
+pub use crate::providers::yandex::IdentityDocument;
+use crate::timeout_or_default;
+
+/// Fetches the identity document of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::blocking::providers::yandex::fetch_identity_document;
+///
+/// let document = fetch_identity_document(None).unwrap();
+/// println!("Instance ID: {}", document.instance_id);
+/// ```
+pub fn fetch_identity_document(timeout: Option<u64>) -> Result<IdentityDocument> {
+    checks::fetch(
+        &yandex::identity_document(yandex::METADATA_URI),
+        timeout_or_default(timeout),
+    )
+}
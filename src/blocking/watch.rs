@@ -0,0 +1,169 @@
+//! Watching for changes of the host's cloud provider.
+//!
+//! Mirrors [watch](crate::watch()), calling back on each change from a dedicated thread instead of yielding a stream.
+
+use std::sync::mpsc;
+use std::sync::mpsc::{RecvTimeoutError, SyncSender};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+use tracing::debug;
+
+use super::{checks, detect};
+use crate::{provider, timeout_or_default, Detector, ProviderId, P};
+
+/// Handle to a thread watching the host's cloud provider.
+///
+/// Watching stops when the handle is dropped, or with [Watcher::stop].
+#[derive(Debug)]
+pub struct Watcher {
+    stop: Option<SyncSender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Watcher {
+    /// Starts watching, running `redetect` (with the last detected provider, if any) every `interval`.
+    fn spawn<D, F>(interval: Duration, mut redetect: D, mut on_change: F) -> Self
+    where
+        D: FnMut(Option<ProviderId>) -> ProviderId + Send + 'static,
+        F: FnMut(ProviderId) + Send + 'static,
+    {
+        let (stop, stopped) = mpsc::sync_channel::<()>(1);
+
+        let thread = std::thread::spawn(move || {
+            let mut last = None;
+
+            loop {
+                let provider = redetect(last);
+
+                if last != Some(provider) {
+                    debug!("Provider changed from {:?} to {}", last, provider);
+                    last = Some(provider);
+                    on_change(provider);
+                }
+
+                // Sleeps until the next detection, waking up early if stopped
+                match stopped.recv_timeout(interval) {
+                    Err(RecvTimeoutError::Timeout) => continue,
+                    _ => break,
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+
+    /// Stops watching, waiting for a detection in progress to finish.
+    pub fn stop(mut self) {
+        self.stop.take();
+
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for Watcher {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up; it exits once any detection in progress has finished
+        self.stop.take();
+    }
+}
+
+/// Whether the provider is still identified by any of its checks.
+fn verify(provider: P, timeout: Duration) -> bool {
+    Detector::new()
+        .checks(provider)
+        .iter()
+        .any(|check| checks::run(check, timeout))
+}
+
+/// Re-checks the last detected provider, running a full detection only if it no longer matches.
+fn redetect(last: Option<ProviderId>, timeout: Option<u64>) -> ProviderId {
+    if let Some(last) = last.and_then(provider) {
+        if verify(last, timeout_or_default(timeout)) {
+            debug!("Still running on {}", last.identifier());
+            return last.identifier();
+        }
+    }
+
+    detect(timeout).unwrap_or_default()
+}
+
+/// Watches the host's cloud provider for changes.
+///
+/// Detection runs every `interval` on a dedicated thread, calling `on_change` with the detected provider and then on
+/// every change of it.
+///
+/// # Arguments
+///
+/// * `interval` - Time between detections.
+/// * `timeout` - Maximum time (seconds) allowed for each detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+/// * `on_change` - Called with the provider whenever it changes.
+///
+/// # Examples
+///
+/// Print the provider whenever it changes, for an hour.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use cloud_detect::blocking::watch;
+///
+/// let watcher = watch(Duration::from_secs(60), None, |provider| {
+///     println!("Running on: {}", provider);
+/// });
+///
+/// std::thread::sleep(Duration::from_secs(3600));
+/// watcher.stop();
+/// ```
+pub fn watch<F>(interval: Duration, timeout: Option<u64>, on_change: F) -> Watcher
+where
+    F: FnMut(ProviderId) + Send + 'static,
+{
+    Watcher::spawn(interval, move |last| redetect(last, timeout), on_change)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watch_calls_back_on_changes() {
+        let mut results = vec![
+            ProviderId::AWS,
+            ProviderId::AWS,
+            ProviderId::Unknown,
+            ProviderId::GCP,
+        ]
+        .into_iter();
+        let (tx, rx) = mpsc::channel();
+
+        let watcher = Watcher::spawn(
+            Duration::from_millis(10),
+            move |_| results.next().unwrap_or(ProviderId::GCP),
+            move |provider| tx.send(provider).unwrap(),
+        );
+
+        let timeout = Duration::from_secs(1);
+        assert_eq!(rx.recv_timeout(timeout), Ok(ProviderId::AWS));
+        assert_eq!(rx.recv_timeout(timeout), Ok(ProviderId::Unknown));
+        assert_eq!(rx.recv_timeout(timeout), Ok(ProviderId::GCP));
+
+        watcher.stop();
+        assert!(rx.recv_timeout(Duration::from_millis(50)).is_err());
+    }
+
+    #[test]
+    fn test_watch_stops_promptly() {
+        let watcher = Watcher::spawn(Duration::from_secs(60), |_| ProviderId::AWS, |_| {});
+
+        let start = std::time::Instant::now();
+        watcher.stop();
+
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
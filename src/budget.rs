@@ -0,0 +1,344 @@
+//! Detection budget.
+//!
+//! Each provider is probed in two phases: its local checks (DMI attributes, files, environment variables) first, then
+//! its metadata server. The phases have separate budgets, so that slow file I/O (e.g. `/sys` mounted over NFS in a
+//! container) can't eat into the time left for the metadata servers. Detection times out once the network budget has
+//! elapsed after the slowest local phase, which is itself capped by the local budget.
+//!
+//! Checks cut short by either budget are recorded (see [Progress]), so that a detection that timed out reports which
+//! providers never got to run.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tracing::info;
+
+use crate::checks::Check;
+#[cfg(feature = "async")]
+use crate::clock;
+use crate::clock::{Instant, FAR_FUTURE};
+use crate::precheck::Precheck;
+use crate::{ProviderId, SkipReason, SkippedProbe};
+
+/// Maximum time allowed for the local checks of each provider.
+pub const DEFAULT_LOCAL_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Represents the time allowed for a detection, shared by the tasks probing each provider.
+#[derive(Debug, Clone)]
+pub(crate) struct Budget {
+    /// Maximum time allowed for the local checks of each provider.
+    pub(crate) local: Duration,
+    /// Maximum time allowed for the metadata server checks, once the local checks are done.
+    pub(crate) network: Duration,
+    start: Instant,
+    /// Time (nanoseconds) taken by the slowest local phase so far.
+    spent: Arc<AtomicU64>,
+    /// Reachability pre-check of the metadata servers, if enabled (see [Precheck]).
+    pub(crate) precheck: Option<Arc<Precheck>>,
+    /// Checks yet to complete, and those skipped so far.
+    pub(crate) progress: Arc<Progress>,
+}
+
+/// Represents the progress of the checks of a detection, recording those cut short by a budget.
+#[derive(Debug, Default)]
+pub(crate) struct Progress {
+    /// Checks of each provider that haven't completed yet.
+    pending: Mutex<Vec<(ProviderId, &'static str)>>,
+    /// Checks skipped so far.
+    skipped: Mutex<Vec<SkippedProbe>>,
+}
+
+impl Budget {
+    /// Starts a detection with the given budgets.
+    pub(crate) fn new(local: Duration, network: Duration) -> Self {
+        Self {
+            local,
+            network,
+            start: Instant::now(),
+            spent: Default::default(),
+            precheck: None,
+            progress: Default::default(),
+        }
+    }
+
+    /// Pre-checks the reachability of each metadata server before querying it, connecting within the given time.
+    pub(crate) fn precheck(mut self, timeout: Duration) -> Self {
+        self.precheck = Some(Arc::new(Precheck::new(timeout)));
+        self
+    }
+
+    /// Records the time taken by the local phase of a provider, up to the local budget.
+    pub(crate) fn spend_local(&self, elapsed: Duration) {
+        let elapsed = elapsed.min(self.local).as_nanos() as u64;
+        self.spent.fetch_max(elapsed, Ordering::Relaxed);
+    }
+
+    /// Time left in the local phase of a provider, given the time it has taken so far, if any.
+    pub(crate) fn local_remaining(&self, elapsed: Duration) -> Option<Duration> {
+        self.local
+            .checked_sub(elapsed)
+            .filter(|remaining| !remaining.is_zero())
+    }
+
+    /// Instant the detection times out at, as of the local phases recorded so far.
+    ///
+    /// The deadline saturates rather than overflowing the clock, so that unbounded timeouts never time out.
+    pub(crate) fn deadline(&self) -> Instant {
+        let allowed =
+            Duration::from_nanos(self.spent.load(Ordering::Relaxed)).saturating_add(self.network);
+
+        self.start + allowed.min(FAR_FUTURE)
+    }
+
+    /// Time left until the detection times out, as of the local phases recorded so far.
+    pub(crate) fn remaining(&self) -> Duration {
+        self.deadline().saturating_duration_since(Instant::now())
+    }
+
+    /// Time allowed for the requests of a check, until the earlier of the deadline of the detection and the end of the
+    /// network phase of its provider, so that checks run one after the other (e.g. IMDSv2, then IMDSv1) can't outlast
+    /// either. Zero once either has passed.
+    pub(crate) fn request_timeout(&self, phase_end: Instant) -> Duration {
+        self.deadline()
+            .min(phase_end)
+            .saturating_duration_since(Instant::now())
+    }
+
+    /// Completes once the detection has timed out, following the deadline as local phases are recorded.
+    #[cfg(feature = "async")]
+    pub(crate) async fn expired(&self) {
+        loop {
+            let deadline = self.deadline();
+            clock::sleep_until(deadline).await;
+
+            if self.deadline() <= deadline {
+                return;
+            }
+        }
+    }
+}
+
+impl Progress {
+    /// Records the checks of a provider as pending, before it is probed.
+    pub(crate) fn queue(&self, provider: ProviderId, checks: &[Check]) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        pending.extend(checks.iter().map(|check| (provider, check.name)));
+    }
+
+    /// Records a check of a provider as completed.
+    pub(crate) fn done(&self, provider: ProviderId, check: &'static str) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Some(i) = pending.iter().position(|&p| p == (provider, check)) {
+            pending.remove(i);
+        }
+    }
+
+    /// Records the checks of a provider still pending as no longer needed, once it has finished.
+    pub(crate) fn finish(&self, provider: ProviderId) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        pending.retain(|&(p, _)| p != provider);
+    }
+
+    /// Records checks of a provider as skipped.
+    pub(crate) fn skip<'a, I>(&self, provider: ProviderId, checks: I, reason: SkipReason)
+    where
+        I: IntoIterator<Item = &'a Check>,
+    {
+        for check in checks {
+            self.done(provider, check.name);
+
+            let mut skipped = self.skipped.lock().unwrap_or_else(|err| err.into_inner());
+            skipped.push(SkippedProbe::new(provider, check.name, reason));
+        }
+    }
+
+    /// Records the checks of a provider still pending as skipped.
+    pub(crate) fn skip_pending(&self, provider: ProviderId, reason: SkipReason) {
+        let mut pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+        let mut skipped = self.skipped.lock().unwrap_or_else(|err| err.into_inner());
+
+        pending.retain(|&(p, check)| {
+            if p == provider {
+                skipped.push(SkippedProbe::new(provider, check, reason));
+            }
+
+            p != provider
+        });
+    }
+
+    /// Returns the checks skipped by the detection, logging them. If it `timed_out`, the checks still pending are
+    /// skipped too, as they were cancelled or never ran.
+    pub(crate) fn skipped(&self, timed_out: bool) -> Vec<SkippedProbe> {
+        let mut skipped = self
+            .skipped
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone();
+
+        if timed_out {
+            let pending = self.pending.lock().unwrap_or_else(|err| err.into_inner());
+            skipped.extend(
+                pending.iter().map(|&(provider, check)| {
+                    SkippedProbe::new(provider, check, SkipReason::Timeout)
+                }),
+            );
+        }
+
+        if !skipped.is_empty() {
+            let probes: Vec<String> = skipped.iter().map(ToString::to_string).collect();
+            info!(
+                "Skipped {} probes due to the deadline: {}",
+                skipped.len(),
+                probes.join(", ")
+            );
+        }
+
+        skipped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "test_support")]
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline() {
+        let budget = Budget::new(Duration::from_secs(1), Duration::from_secs(5));
+        let start = Instant::now();
+
+        assert_eq!(budget.deadline(), start + Duration::from_secs(5));
+
+        budget.spend_local(Duration::from_millis(300));
+        budget.spend_local(Duration::from_millis(200));
+        assert_eq!(budget.deadline(), start + Duration::from_millis(5300));
+
+        // The local phase is capped by its budget
+        budget.spend_local(Duration::from_secs(10));
+        assert_eq!(budget.deadline(), start + Duration::from_secs(6));
+    }
+
+    #[test]
+    fn test_local_remaining() {
+        let budget = Budget::new(Duration::from_secs(1), Duration::from_secs(5));
+
+        assert_eq!(
+            budget.local_remaining(Duration::from_millis(400)),
+            Some(Duration::from_millis(600))
+        );
+        assert_eq!(budget.local_remaining(Duration::from_secs(1)), None);
+        assert_eq!(budget.local_remaining(Duration::from_secs(2)), None);
+    }
+
+    #[cfg(feature = "test_support")]
+    #[tokio::test(start_paused = true)]
+    async fn test_expired_follows_deadline() {
+        let budget = Budget::new(Duration::from_secs(1), Duration::from_secs(5));
+        let start = Instant::now();
+
+        let spender = budget.clone();
+        tokio::spawn(async move {
+            clock::sleep(Duration::from_secs(2)).await;
+            spender.spend_local(Duration::from_millis(500));
+        });
+
+        budget.expired().await;
+
+        assert_eq!(start.elapsed(), Duration::from_millis(5500));
+    }
+
+    #[cfg(feature = "test_support")]
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_clock_jump() {
+        let budget = Budget::new(Duration::from_secs(1), Duration::from_secs(5));
+        let deadline = budget.deadline();
+
+        // The monotonic clock leaps far past the deadline (e.g. a VM paused for a live migration catches up on resume),
+        // which neither moves nor overflows the deadline
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        assert_eq!(budget.deadline(), deadline);
+        assert_eq!(budget.remaining(), Duration::ZERO);
+        assert_eq!(budget.local_remaining(Duration::from_secs(3600)), None);
+
+        let start = Instant::now();
+        budget.expired().await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+    }
+
+    #[cfg(feature = "test_support")]
+    #[tokio::test(start_paused = true)]
+    async fn test_deadline_unbounded() {
+        let budget = Budget::new(Duration::MAX, Duration::from_secs(u64::MAX));
+        budget.spend_local(Duration::MAX);
+
+        assert_eq!(budget.deadline(), budget.start + FAR_FUTURE);
+        assert!(budget.remaining() > Duration::from_secs(365 * 24 * 3600));
+    }
+
+    #[cfg(feature = "test_support")]
+    #[tokio::test(start_paused = true)]
+    async fn test_request_timeout() {
+        let budget = Budget::new(Duration::from_secs(1), Duration::from_secs(5));
+        let phase_end = clock::deadline(Duration::from_secs(5));
+        assert_eq!(budget.request_timeout(phase_end), Duration::from_secs(5));
+
+        // Checks run after a slow one only get the time left
+        clock::sleep(Duration::from_secs(4)).await;
+        assert_eq!(budget.request_timeout(phase_end), Duration::from_secs(1));
+
+        // A phase ending before the deadline (e.g. after a slow local phase of another provider) caps it too
+        budget.spend_local(Duration::from_millis(500));
+        assert_eq!(budget.request_timeout(phase_end), Duration::from_secs(1));
+        assert_eq!(
+            budget.request_timeout(clock::deadline(Duration::from_millis(300))),
+            Duration::from_millis(300)
+        );
+
+        clock::sleep(Duration::from_secs(2)).await;
+        assert_eq!(budget.request_timeout(phase_end), Duration::ZERO);
+    }
+
+    #[test]
+    fn test_progress() {
+        let checks = |names: &[&'static str]| -> Vec<Check> {
+            names
+                .iter()
+                .map(|&name| Check::env(name, crate::Confidence::Unverified, "HOME", |_| true))
+                .collect()
+        };
+        let progress = Progress::default();
+        let aws = checks(&["vendor_file", "metadata_server"]);
+        let azure = checks(&["vendor_file", "metadata_server"]);
+
+        progress.queue(ProviderId::AWS, &aws);
+        progress.queue(ProviderId::Azure, &azure);
+        progress.queue(ProviderId::GCP, &checks(&["metadata_server"]));
+
+        // AWS ran out of local budget, Azure completed and GCP was cut short by the deadline
+        progress.skip(ProviderId::AWS, &aws[..1], SkipReason::LocalTimeout);
+        progress.done(ProviderId::AWS, "metadata_server");
+        progress.done(ProviderId::Azure, "vendor_file");
+        progress.finish(ProviderId::Azure);
+
+        assert_eq!(
+            progress.skipped(false),
+            [SkippedProbe::new(
+                ProviderId::AWS,
+                "vendor_file",
+                SkipReason::LocalTimeout
+            )]
+        );
+        assert_eq!(
+            progress.skipped(true),
+            [
+                SkippedProbe::new(ProviderId::AWS, "vendor_file", SkipReason::LocalTimeout),
+                SkippedProbe::new(ProviderId::GCP, "metadata_server", SkipReason::Timeout),
+            ]
+        );
+
+        progress.skip_pending(ProviderId::GCP, SkipReason::Timeout);
+        assert_eq!(progress.skipped(true), progress.skipped(false));
+    }
+}
@@ -0,0 +1,1683 @@
+//! Provider checks.
+//!
+//! Providers describe the ways they can be identified as a list of [Check]s without performing any I/O themselves.
+//! The async API runs checks with [Check::run], and the blocking API runs the very same checks with its own executor,
+//! so the logic of each provider only exists once. Requests to metadata servers are described the same way by
+//! [Request], which is also used to fetch metadata.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use anyhow::anyhow;
+use anyhow::{bail, Context, Error, Result};
+use http::header::{HeaderMap, CONTENT_TYPE};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+#[cfg(feature = "async")]
+use tracing::instrument;
+use tracing::{debug, error};
+
+use crate::bind::Binding;
+#[cfg(feature = "async")]
+use crate::clock::{self, sleep, Instant};
+#[cfg(feature = "async")]
+use crate::probe_log::probe_log;
+use crate::redact::Redacted;
+use crate::smbios::SmbiosData;
+#[cfg(feature = "test_support")]
+use crate::test_support::FakeTransport;
+#[cfg(feature = "async")]
+use crate::{cpuid, retry, smbios, tokens};
+use crate::{Confidence, Outcome, ProbeLogLevel};
+
+/// Matches the contents of a file.
+pub(crate) type ContentMatcher = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// Matches the response of a metadata server.
+pub(crate) type ResponseMatcher = Arc<dyn Fn(&Response) -> bool + Send + Sync>;
+
+/// Parses the next step of a fetch out of a response from a metadata server.
+pub(crate) type StepParser<T> = Arc<dyn Fn(&Response) -> Result<Step<T>> + Send + Sync>;
+
+/// Handles the failure of every request of a fetch.
+pub(crate) type StepRecovery<T> = Arc<dyn Fn(Error) -> Result<Step<T>> + Send + Sync>;
+
+/// Whether requests can be made over HTTPS, which requires the `rustls-tls` or `native-tls` feature. Metadata servers
+/// are all served over plain HTTP, so only user-defined endpoints can need it.
+pub(crate) const TLS: bool = cfg!(any(feature = "rustls-tls", feature = "native-tls"));
+
+/// Whether requests can be made to URLs with the given scheme.
+pub(crate) fn supports_scheme(scheme: &str) -> bool {
+    scheme == "http" || (TLS && scheme == "https")
+}
+
+/// Continues a fetch once its metadata (or the failure to fetch it) is known.
+type Continuation<T, U> = Arc<dyn Fn(Result<T>) -> Result<Step<U>> + Send + Sync>;
+
+/// Represents a single way of identifying a provider.
+#[derive(Clone)]
+pub(crate) struct Check {
+    /// Short name of the check, used for logging.
+    pub(crate) name: &'static str,
+    /// Confidence of a match made by this check.
+    pub(crate) confidence: Confidence,
+    /// What the check inspects.
+    pub(crate) probe: Probe,
+    /// Confidence of a match made by an authentication error (`401` or `403`) from the metadata server, if such an error
+    /// proves the provider is present (e.g. a metadata server only allowing requests with a session token).
+    pub(crate) unauthorized: Option<Confidence>,
+    /// Whether a match identifies the provider on its own, so that detection can stop without querying any metadata
+    /// server (see [Check::strong]).
+    pub(crate) strong: bool,
+    /// Level the expected failures of the check are logged at (see [crate::probe_log]).
+    pub(crate) log_level: ProbeLogLevel,
+}
+
+/// Represents what a check inspects, and how the result is matched.
+#[derive(Clone)]
+pub(crate) enum Probe {
+    /// Matches the contents of a DMI attribute file (see [smbios::read_field]).
+    Dmi {
+        path: PathBuf,
+        matches: ContentMatcher,
+    },
+    /// Matches the contents of a regular file.
+    File {
+        path: PathBuf,
+        matches: ContentMatcher,
+    },
+    /// Matches the contents of the regular files matching a glob pattern, if any of them matches.
+    Glob {
+        pattern: String,
+        matches: ContentMatcher,
+    },
+    /// Matches if a path exists.
+    Exists { path: PathBuf },
+    /// Matches the value of an environment variable, if set.
+    Env {
+        name: &'static str,
+        matches: ContentMatcher,
+    },
+    /// Matches the vendor signature of the hypervisor reported by CPUID, if any (see [cpuid::hypervisor_vendor]).
+    Cpuid { matches: ContentMatcher },
+    /// Matches the response of a metadata server.
+    Http {
+        request: Request,
+        matches: ResponseMatcher,
+    },
+}
+
+/// Represents the detailed outcome of running a check, used for diagnostics.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Inspection {
+    /// What the check found.
+    pub(crate) outcome: Outcome,
+    /// Status code of the response from the metadata server, if any.
+    pub(crate) status: Option<u16>,
+    /// Whether the check matched an authentication error from the metadata server, rather than the expected response.
+    pub(crate) unauthorized: bool,
+    /// Error that prevented the check from completing, if any.
+    pub(crate) error: Option<String>,
+    /// Contents of the inspected file or body of the response, if any.
+    pub(crate) content: Option<String>,
+}
+
+/// Represents a GET request to a metadata server.
+#[derive(Debug, Clone)]
+pub(crate) struct Request {
+    pub(crate) base_uri: String,
+    pub(crate) path: String,
+    pub(crate) headers: Vec<(&'static str, String)>,
+    pub(crate) token: Option<TokenRequest>,
+    /// Interface and source address the request is sent from, if bound (see [crate::bind]).
+    pub(crate) binding: Option<Arc<Binding>>,
+    /// Transport answering the request in place of the network, for tests.
+    #[cfg(feature = "test_support")]
+    pub(crate) transport: Option<Arc<FakeTransport>>,
+}
+
+/// Represents a session token that must be retrieved (with a PUT request) before querying a metadata server.
+///
+/// Tokens are cached and reused for their lifetime (see [crate::tokens]).
+#[derive(Debug, Clone)]
+pub(crate) struct TokenRequest {
+    /// Path of the token endpoint, relative to the metadata server.
+    pub(crate) path: String,
+    /// Header used to request the lifetime of the token.
+    pub(crate) ttl_header: &'static str,
+    /// Lifetime of the token.
+    pub(crate) ttl: Duration,
+    /// Header used to pass the token to the metadata server.
+    pub(crate) header: &'static str,
+}
+
+/// Represents metadata to fetch from a metadata server.
+///
+/// A fetch may take several steps, each one depending on the metadata fetched by the previous one (e.g. looking up
+/// the network of an interface once its MAC address is known). Executors run the requests of each step until one is
+/// parsed successfully, and carry on with the next step until the metadata is done.
+pub(crate) struct Fetch<T> {
+    /// Requests for the same metadata, in order of preference.
+    pub(crate) requests: Vec<Request>,
+    pub(crate) parse: StepParser<T>,
+    pub(crate) recover: StepRecovery<T>,
+}
+
+/// Represents the outcome of one step of a fetch.
+pub(crate) enum Step<T> {
+    /// The metadata has been fetched.
+    Done(T),
+    /// More metadata must be fetched first.
+    Next(Fetch<T>),
+}
+
+/// Represents a response from a metadata server.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Response {
+    pub(crate) status: u16,
+    pub(crate) body: String,
+    pub(crate) headers: HeaderMap,
+    /// Delay requested by the metadata server before retrying (with the `Retry-After` header), if any.
+    pub(crate) retry_after: Option<Duration>,
+}
+
+impl Check {
+    /// Creates a check matching the contents of a DMI attribute file.
+    pub(crate) fn dmi<P, F>(name: &'static str, confidence: Confidence, path: P, matches: F) -> Self
+    where
+        P: Into<PathBuf>,
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            confidence,
+            probe: Probe::Dmi {
+                path: path.into(),
+                matches: Arc::new(matches),
+            },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Creates a check matching the contents of a regular file.
+    pub(crate) fn file<P, F>(
+        name: &'static str,
+        confidence: Confidence,
+        path: P,
+        matches: F,
+    ) -> Self
+    where
+        P: Into<PathBuf>,
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            confidence,
+            probe: Probe::File {
+                path: path.into(),
+                matches: Arc::new(matches),
+            },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Creates a check matching the contents of the regular files matching a glob pattern.
+    pub(crate) fn glob<F>(
+        name: &'static str,
+        confidence: Confidence,
+        pattern: &str,
+        matches: F,
+    ) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            confidence,
+            probe: Probe::Glob {
+                pattern: pattern.to_string(),
+                matches: Arc::new(matches),
+            },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Creates a check matching if a path exists.
+    pub(crate) fn exists<P: Into<PathBuf>>(
+        name: &'static str,
+        confidence: Confidence,
+        path: P,
+    ) -> Self {
+        Self {
+            name,
+            confidence,
+            probe: Probe::Exists { path: path.into() },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Creates a check matching the value of an environment variable.
+    pub(crate) fn env<F>(
+        name: &'static str,
+        confidence: Confidence,
+        var: &'static str,
+        matches: F,
+    ) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            confidence,
+            probe: Probe::Env {
+                name: var,
+                matches: Arc::new(matches),
+            },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Creates a check matching the vendor signature of the hypervisor reported by CPUID.
+    pub(crate) fn cpuid<F>(name: &'static str, confidence: Confidence, matches: F) -> Self
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            confidence,
+            probe: Probe::Cpuid {
+                matches: Arc::new(matches),
+            },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Creates a check matching the response of a metadata server.
+    pub(crate) fn http<F>(
+        name: &'static str,
+        confidence: Confidence,
+        request: Request,
+        matches: F,
+    ) -> Self
+    where
+        F: Fn(&Response) -> bool + Send + Sync + 'static,
+    {
+        Self {
+            name,
+            confidence,
+            probe: Probe::Http {
+                request,
+                matches: Arc::new(matches),
+            },
+            unauthorized: None,
+            strong: false,
+            log_level: ProbeLogLevel::default(),
+        }
+    }
+
+    /// Also matches authentication errors (`401 Unauthorized` or `403 Forbidden`) from the metadata server, with the
+    /// given confidence, as they prove the provider is present even though it can't be queried.
+    pub(crate) fn or_unauthorized(mut self, confidence: Confidence) -> Self {
+        self.unauthorized = Some(confidence);
+        self
+    }
+
+    /// Marks the check as a strong signal: a value that only the provider sets (e.g. a DMI vendor of `DigitalOcean`),
+    /// so that a match identifies it without confirmation from its metadata server. Only DMI checks can be strong.
+    pub(crate) fn strong(mut self) -> Self {
+        self.strong = matches!(self.probe, Probe::Dmi { .. });
+        self
+    }
+
+    /// Logs the expected failures of the check at the given level.
+    pub(crate) fn log_level(mut self, level: ProbeLogLevel) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// Runs the check against DMI attributes already read, if it's a DMI check.
+    pub(crate) fn inspect_dmi(&self, smbios: &SmbiosData) -> Option<Inspection> {
+        match &self.probe {
+            Probe::Dmi { path, matches } => {
+                debug!("Checking DMI attribute: {}", path.display());
+                Some(Inspection::content(smbios.field(path), matches))
+            }
+            _ => None,
+        }
+    }
+
+    /// Runs the check if it's a strong signal, ahead of the other checks.
+    pub(crate) fn inspect_strong(&self, smbios: &SmbiosData) -> Option<Inspection> {
+        self.inspect_dmi(smbios).filter(|_| self.strong)
+    }
+
+    /// Returns the confidence of a match made by this check.
+    pub(crate) fn match_confidence(&self, inspection: &Inspection) -> Confidence {
+        match self.unauthorized {
+            Some(confidence) if inspection.unauthorized => confidence,
+            _ => self.confidence,
+        }
+    }
+
+    /// Whether the check only reads the host, rather than querying a metadata server.
+    pub(crate) fn is_local(&self) -> bool {
+        !matches!(self.probe, Probe::Http { .. })
+    }
+
+    /// Runs the check.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time allowed for requests to a metadata server.
+    #[cfg(feature = "async")]
+    pub(crate) async fn run(&self, timeout: Duration) -> bool {
+        self.inspect(timeout).await.matched()
+    }
+
+    /// Runs the check, keeping the details of what was inspected.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time allowed for requests to a metadata server.
+    #[cfg(feature = "async")]
+    #[instrument(skip_all, fields(check = self.name))]
+    pub(crate) async fn inspect(&self, timeout: Duration) -> Inspection {
+        match &self.probe {
+            Probe::Dmi { path, matches } => {
+                debug!("Checking DMI file: {}", path.display());
+                Inspection::content(smbios::read_field(path), matches)
+            }
+            Probe::File { path, matches } => {
+                debug!("Checking file: {}", path.display());
+
+                if !path.is_file() {
+                    return Inspection::default();
+                }
+
+                match tokio::fs::read_to_string(path).await {
+                    Ok(content) => Inspection::content(Some(content), matches),
+                    Err(err) => {
+                        error!("Error reading file: {:?}", err);
+                        Inspection::error(err.into())
+                    }
+                }
+            }
+            Probe::Glob { pattern, matches } => {
+                debug!("Checking files matching: {}", pattern);
+
+                let mut contents = Vec::new();
+                for path in glob_files(pattern) {
+                    match tokio::fs::read_to_string(&path).await {
+                        Ok(content) => contents.push(content),
+                        Err(err) => error!("Error reading file: {:?}", err),
+                    }
+                }
+
+                Inspection::contents(contents, matches)
+            }
+            Probe::Exists { path } => {
+                debug!("Checking path: {}", path.display());
+                Inspection::presence(path.exists())
+            }
+            Probe::Env { name, matches } => {
+                debug!("Checking environment variable: {}", name);
+                Inspection::content(std::env::var(name).ok(), matches)
+            }
+            Probe::Cpuid { matches } => {
+                debug!("Checking hypervisor vendor signature");
+                Inspection::content(cpuid::hypervisor_vendor(), matches)
+            }
+            Probe::Http { request, matches } => match request.send(timeout).await {
+                Ok(resp) => Inspection::response(resp, matches).or_unauthorized(self.unauthorized),
+                Err(err) => {
+                    probe_log!(self.log_level, "Error making request: {:?}", Redacted(&err));
+                    Inspection::unreachable(err)
+                }
+            },
+        }
+    }
+}
+
+impl Probe {
+    /// Returns what the probe inspects, as a path or URL.
+    pub(crate) fn target(&self) -> String {
+        match self {
+            Probe::Dmi { path, .. } | Probe::File { path, .. } | Probe::Exists { path } => {
+                path.display().to_string()
+            }
+            Probe::Glob { pattern, .. } => pattern.clone(),
+            Probe::Env { name, .. } => format!("${name}"),
+            Probe::Cpuid { .. } => "cpuid".to_string(),
+            Probe::Http { request, .. } => request.url(),
+        }
+    }
+}
+
+/// Returns the regular files matching a glob pattern, in alphabetical order.
+pub(crate) fn glob_files(pattern: &str) -> Vec<PathBuf> {
+    match glob::glob(pattern) {
+        Ok(paths) => paths.flatten().filter(|path| path.is_file()).collect(),
+        Err(err) => {
+            error!("Invalid glob pattern {}: {:?}", pattern, err);
+            Vec::new()
+        }
+    }
+}
+
+impl Inspection {
+    /// Inspection of the contents of a file, if it could be read.
+    pub(crate) fn content(content: Option<String>, matches: &ContentMatcher) -> Self {
+        let outcome = match content.as_deref() {
+            Some(content) if matches(content) => Outcome::Matched,
+            Some(_) => Outcome::Mismatched,
+            None => Outcome::Absent,
+        };
+
+        Self {
+            outcome,
+            content,
+            ..Default::default()
+        }
+    }
+
+    /// Inspection of the contents of several files, keeping the first one that matches.
+    pub(crate) fn contents(contents: Vec<String>, matches: &ContentMatcher) -> Self {
+        let mut inspections = contents
+            .into_iter()
+            .map(|content| Self::content(Some(content), matches))
+            .peekable();
+
+        if inspections.peek().is_none() {
+            return Self::default();
+        }
+
+        inspections
+            .find(Self::matched)
+            .unwrap_or_else(|| Self::outcome(Outcome::Mismatched))
+    }
+
+    /// Inspection of a response from a metadata server.
+    ///
+    /// Responses that can't come from a metadata server (see [Response::is_spoofed]) never match.
+    pub(crate) fn response(resp: Response, matches: &ResponseMatcher) -> Self {
+        let outcome = if resp.is_spoofed() {
+            debug!("Rejecting response not served by a metadata server");
+            Outcome::Rejected
+        } else if matches(&resp) {
+            Outcome::Matched
+        } else if matches!(resp.status, 401 | 403) {
+            Outcome::AuthRequired
+        } else {
+            Outcome::Mismatched
+        };
+
+        Self {
+            outcome,
+            status: Some(resp.status),
+            content: Some(resp.body),
+            ..Default::default()
+        }
+    }
+
+    /// Matches an authentication error from the metadata server, if it proves the provider is present (see
+    /// [Check::or_unauthorized]).
+    pub(crate) fn or_unauthorized(mut self, confidence: Option<Confidence>) -> Self {
+        if self.outcome == Outcome::AuthRequired && confidence.is_some() {
+            debug!("Metadata server present, but denied access");
+            self.unauthorized = true;
+        }
+
+        self
+    }
+
+    /// Inspection with the given outcome, and nothing else to report.
+    pub(crate) fn outcome(outcome: Outcome) -> Self {
+        Self {
+            outcome,
+            ..Default::default()
+        }
+    }
+
+    /// Inspection of whether a path exists.
+    pub(crate) fn presence(exists: bool) -> Self {
+        Self::outcome(if exists {
+            Outcome::Matched
+        } else {
+            Outcome::Absent
+        })
+    }
+
+    /// Inspection that failed with an error.
+    pub(crate) fn error(err: Error) -> Self {
+        Self {
+            outcome: Outcome::Failed,
+            error: Some(format!("{err:#}")),
+            ..Default::default()
+        }
+    }
+
+    /// Inspection of a metadata server that couldn't be reached.
+    pub(crate) fn unreachable(err: Error) -> Self {
+        Self {
+            outcome: Outcome::Unreachable,
+            ..Self::error(err)
+        }
+    }
+
+    /// Whether the check matched, either the expected content or an authentication error proving the provider is
+    /// present.
+    pub(crate) fn matched(&self) -> bool {
+        self.outcome == Outcome::Matched || self.unauthorized
+    }
+}
+
+impl Request {
+    /// Creates a GET request to a metadata server.
+    pub(crate) fn get(base_uri: &str, path: &str) -> Self {
+        Self {
+            base_uri: base_uri.to_string(),
+            path: path.to_string(),
+            headers: Vec::new(),
+            token: None,
+            binding: None,
+            #[cfg(feature = "test_support")]
+            transport: None,
+        }
+    }
+
+    /// Adds a header to the request.
+    pub(crate) fn header(mut self, name: &'static str, value: &str) -> Self {
+        self.headers.push((name, value.to_string()));
+        self
+    }
+
+    /// Retrieves a session token before making the request.
+    pub(crate) fn token(mut self, token: TokenRequest) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Answers the request with a fake transport instead of the network.
+    #[cfg(feature = "test_support")]
+    pub(crate) fn transport(mut self, transport: Arc<FakeTransport>) -> Self {
+        self.transport = Some(transport);
+        self
+    }
+
+    /// Returns the full URL of the request.
+    pub(crate) fn url(&self) -> String {
+        format!("{}{}", self.base_uri, self.path)
+    }
+
+    /// Returns the full URL of the token request, if any.
+    pub(crate) fn token_url(&self) -> Option<String> {
+        self.token
+            .as_ref()
+            .map(|token| format!("{}{}", self.base_uri, token.path))
+    }
+
+    /// Sends the request, retrieving a session token first if required.
+    ///
+    /// A cached token is reused if available. If the metadata server rejects it, a new token is retrieved and the
+    /// request is sent again. Transient failures (e.g. throttling) are retried with backoff (see [crate::retry]).
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time allowed for the request, shared by the requests chained to it (retrieving a session
+    ///   token, and retries), so that each only gets the time left by the previous ones.
+    #[cfg(feature = "async")]
+    pub(crate) async fn send(&self, timeout: Duration) -> Result<Response> {
+        #[cfg(feature = "test_support")]
+        if let Some(transport) = &self.transport {
+            return transport.send(self, timeout).await;
+        }
+
+        let deadline = clock::deadline(timeout);
+        let builder = reqwest::Client::builder().timeout(timeout);
+        // rustls is preferred if both TLS backends are enabled
+        #[cfg(feature = "rustls-tls")]
+        let builder = builder.use_rustls_tls();
+        let builder = match &self.binding {
+            Some(binding) => binding.client(builder)?,
+            None => builder,
+        };
+        let client = builder.build().context("Error creating client")?;
+
+        let (Some(token), Some(token_url)) = (&self.token, self.token_url()) else {
+            return self.send_with_token(&client, None, deadline).await;
+        };
+
+        let value = match tokens::get(&token_url) {
+            Some(value) => value,
+            None => retrieve_token(&client, token, &token_url, deadline).await?,
+        };
+        let resp = self
+            .send_with_token(&client, Some(&value), deadline)
+            .await?;
+
+        if resp.status != 401 {
+            return Ok(resp);
+        }
+
+        debug!("Token rejected, retrieving a new one");
+        tokens::invalidate(&token_url);
+
+        let value = retrieve_token(&client, token, &token_url, deadline).await?;
+        self.send_with_token(&client, Some(&value), deadline).await
+    }
+
+    /// Sends the request, passing the given session token, and retries transient failures until the deadline.
+    #[cfg(feature = "async")]
+    async fn send_with_token(
+        &self,
+        client: &reqwest::Client,
+        token: Option<&str>,
+        deadline: Instant,
+    ) -> Result<Response> {
+        let start = Instant::now();
+        let timeout = deadline.saturating_duration_since(start);
+        let mut attempt = 1;
+
+        loop {
+            let resp = self.send_once(client, token, deadline).await?;
+
+            let Some(delay) = retry::delay(&resp, attempt, start.elapsed(), timeout) else {
+                return Ok(resp);
+            };
+
+            debug!(
+                "Transient status code {}, retrying in {:?}",
+                resp.status, delay
+            );
+            sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// Sends the request once, passing the given session token, within the time left until the deadline.
+    #[cfg(feature = "async")]
+    async fn send_once(
+        &self,
+        client: &reqwest::Client,
+        token: Option<&str>,
+        deadline: Instant,
+    ) -> Result<Response> {
+        let mut req = client.get(self.url()).timeout(clock::remaining(deadline)?);
+
+        for (name, value) in &self.headers {
+            req = req.header(*name, value);
+        }
+
+        if let (Some(token), Some(value)) = (&self.token, token) {
+            req = req.header(token.header, value);
+        }
+
+        debug!("Requesting metadata using url: {}", Redacted(self.url()));
+
+        let resp = req.send().await?;
+        let status = resp.status().as_u16();
+        let headers = resp.headers().clone();
+        let retry_after = retry::retry_after(&headers);
+        let body = resp.text().await?;
+
+        Ok(Response {
+            status,
+            body,
+            headers,
+            retry_after,
+        })
+    }
+}
+
+/// Retrieves a session token within the time left until the deadline, and caches it for its lifetime.
+#[cfg(feature = "async")]
+async fn retrieve_token(
+    client: &reqwest::Client,
+    token: &TokenRequest,
+    token_url: &str,
+    deadline: Instant,
+) -> Result<String> {
+    debug!("Retrieving token from: {}", Redacted(token_url));
+
+    let resp = client
+        .put(token_url)
+        .timeout(clock::remaining(deadline)?)
+        .header(token.ttl_header, token.ttl.as_secs().to_string())
+        .send()
+        .await?;
+
+    if !resp.status().is_success() {
+        bail!("Unexpected status code for token: {}", resp.status());
+    }
+
+    let value = resp.text().await?;
+
+    if value.is_empty() {
+        bail!("Token is empty");
+    }
+
+    tokens::insert(token_url, &value, token.ttl);
+    Ok(value)
+}
+
+impl Response {
+    /// Whether the status code is in the 2xx range.
+    pub(crate) fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    /// Returns the value of a header, if set to a valid string.
+    pub(crate) fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    /// Whether the response is an HTML page, which no metadata server serves.
+    pub(crate) fn is_html(&self) -> bool {
+        self.header(CONTENT_TYPE.as_str())
+            .is_some_and(|content_type| content_type.starts_with("text/html"))
+            || self.body.trim_start().starts_with('<')
+    }
+
+    /// Whether the response can't come from a metadata server, despite being successful.
+    ///
+    /// On some networks (e.g. hotels), requests to the link-local address of metadata servers are answered by a
+    /// captive portal, serving its login page with `200 OK` whatever the request. Metadata servers only serve HTML for
+    /// errors (e.g. AWS on `404 Not Found`), which aren't rejected so that authentication errors still prove presence.
+    pub(crate) fn is_spoofed(&self) -> bool {
+        self.is_success() && self.is_html()
+    }
+
+    /// Fails unless the status code is in the 2xx range.
+    ///
+    /// A metadata server that is still unavailable once retries are exhausted is reported as such, rather than as an
+    /// unexpected response, as it is present and may recover.
+    pub(crate) fn ensure_success(&self) -> Result<()> {
+        if self.is_success() {
+            return Ok(());
+        }
+
+        if self.status == 503 {
+            match self.retry_after {
+                Some(delay) => bail!("Metadata server unavailable, retry after {:?}", delay),
+                None => bail!("Metadata server unavailable"),
+            }
+        }
+
+        bail!("Unexpected status code: {}", self.status)
+    }
+
+    /// Deserializes the body as JSON, logging any error.
+    pub(crate) fn json<T: DeserializeOwned>(&self) -> Option<T> {
+        match serde_json::from_str(&self.body) {
+            Ok(value) => Some(value),
+            // Metadata servers of other providers answer with documents of their own
+            Err(err) => {
+                debug!("Error reading response: {:?}", Redacted(&err));
+                None
+            }
+        }
+    }
+
+    /// Deserializes the body of a successful response as JSON.
+    pub(crate) fn parse<T: DeserializeOwned>(&self) -> Result<T> {
+        self.ensure_success()?;
+
+        serde_json::from_str(&self.body).context("Error reading response")
+    }
+
+    /// Parses the body of a listing of metadata entries, one per line, with any trailing `/` removed.
+    ///
+    /// A missing listing (`404 Not Found`) is empty.
+    pub(crate) fn listing(&self) -> Result<Vec<String>> {
+        if self.status == 404 {
+            return Ok(Vec::new());
+        }
+
+        self.ensure_success()?;
+
+        Ok(self
+            .body
+            .lines()
+            .map(|line| line.trim().trim_end_matches('/'))
+            .filter(|line| !line.is_empty())
+            .map(str::to_string)
+            .collect())
+    }
+}
+
+impl<T> Clone for Fetch<T> {
+    fn clone(&self) -> Self {
+        Self {
+            requests: self.requests.clone(),
+            parse: self.parse.clone(),
+            recover: self.recover.clone(),
+        }
+    }
+}
+
+impl<T: 'static> Fetch<T> {
+    /// Creates a fetch, parsing the first successful response with `parse`.
+    pub(crate) fn new<F>(requests: Vec<Request>, parse: F) -> Self
+    where
+        F: Fn(&Response) -> Result<T> + Send + Sync + 'static,
+    {
+        Self {
+            requests,
+            parse: Arc::new(move |resp| parse(resp).map(Step::Done)),
+            recover: Arc::new(Err),
+        }
+    }
+
+    /// Transforms the fetched metadata.
+    pub(crate) fn map<U: 'static, F>(self, f: F) -> Fetch<U>
+    where
+        F: Fn(T) -> Result<U> + Send + Sync + 'static,
+    {
+        self.continue_with(Arc::new(move |result| result.and_then(&f).map(Step::Done)))
+    }
+
+    /// Fetches more metadata, depending on the fetched metadata.
+    pub(crate) fn and_then<U: 'static, F>(self, f: F) -> Fetch<U>
+    where
+        F: Fn(T) -> Fetch<U> + Send + Sync + 'static,
+    {
+        self.continue_with(Arc::new(move |result| {
+            result.map(|value| Step::Next(f(value)))
+        }))
+    }
+
+    /// Fetches metadata that may not be available, yielding `None` instead of failing.
+    pub(crate) fn optional(self) -> Fetch<Option<T>> {
+        self.continue_with(Arc::new(|result| Ok(Step::Done(result.ok()))))
+    }
+
+    /// Fetches `fallback` instead when this metadata can't be fetched.
+    pub(crate) fn or_else(self, fallback: Fetch<T>) -> Fetch<T> {
+        self.continue_with(Arc::new(move |result| match result {
+            Ok(value) => Ok(Step::Done(value)),
+            Err(_) => Ok(Step::Next(fallback.clone())),
+        }))
+    }
+
+    /// Fetches this metadata, then `other`.
+    pub(crate) fn zip<U: 'static>(self, other: Fetch<U>) -> Fetch<(T, U)>
+    where
+        T: Clone + Send + Sync,
+    {
+        self.and_then(move |value| {
+            other
+                .clone()
+                .map(move |other_value| Ok((value.clone(), other_value)))
+        })
+    }
+
+    /// Continues with `continuation` once the fetched metadata, or the failure to fetch it, is known.
+    fn continue_with<U: 'static>(self, continuation: Continuation<T, U>) -> Fetch<U> {
+        let parse = self.parse;
+        let recover = self.recover;
+        let then = {
+            let continuation = continuation.clone();
+            move |step| match step {
+                Step::Done(value) => continuation(Ok(value)),
+                Step::Next(next) => Ok(Step::Next(next.continue_with(continuation.clone()))),
+            }
+        };
+        let then = Arc::new(then);
+
+        Fetch {
+            requests: self.requests,
+            parse: {
+                let then = then.clone();
+                Arc::new(move |resp| parse(resp).and_then(&*then))
+            },
+            recover: Arc::new(move |err| match recover(err) {
+                Ok(step) => then(step),
+                Err(err) => continuation(Err(err)),
+            }),
+        }
+    }
+
+    /// Fetches the metadata, trying the requests of each step in order until one succeeds.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Maximum time allowed for each request.
+    #[cfg(feature = "async")]
+    pub(crate) async fn run(&self, timeout: Duration) -> Result<T> {
+        let mut step = self.step(timeout).await?;
+
+        loop {
+            match step {
+                Step::Done(value) => return Ok(value),
+                Step::Next(next) => step = next.step(timeout).await?,
+            }
+        }
+    }
+
+    /// Runs a single step of the fetch.
+    #[cfg(feature = "async")]
+    async fn step(&self, timeout: Duration) -> Result<Step<T>> {
+        let mut last_err = None;
+
+        for request in &self.requests {
+            match request
+                .send(timeout)
+                .await
+                .and_then(|resp| (self.parse)(&resp))
+            {
+                Ok(step) => return Ok(step),
+                Err(err) => {
+                    debug!(
+                        "Error fetching {}: {:?}",
+                        Redacted(request.url()),
+                        Redacted(&err)
+                    );
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        (self.recover)(last_err.unwrap_or_else(|| anyhow!("No requests to send")))
+    }
+}
+
+impl<T: DeserializeOwned + 'static> Fetch<T> {
+    /// Creates a fetch of a JSON document.
+    pub(crate) fn json(requests: Vec<Request>) -> Self {
+        Self::new(requests, Response::parse)
+    }
+
+    /// Fetch of the same JSON document, without deserializing it into a struct (so no fields are lost).
+    ///
+    /// Only the requests of the first step are kept.
+    pub(crate) fn raw(&self) -> Fetch<Value> {
+        Fetch::json(self.requests.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use tempfile::{NamedTempFile, TempDir};
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+    use crate::PROVIDERS;
+
+    const TIMEOUT: Duration = Duration::from_secs(1);
+
+    #[tokio::test]
+    async fn test_run_file() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all(b"abc")?;
+
+        let check = Check::file("file", Confidence::Unverified, file.path(), |c| c == "abc");
+        assert!(check.run(TIMEOUT).await);
+
+        let check = Check::file("file", Confidence::Unverified, file.path(), |c| c == "def");
+        assert!(!check.run(TIMEOUT).await);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inspect_strong() -> Result<()> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all(b"abc")?;
+
+        let smbios = SmbiosData::read([file.path()]);
+
+        let check = Check::dmi("dmi", Confidence::Unverified, file.path(), |c| c == "abc");
+        assert!(check.inspect_strong(&smbios).is_none());
+        assert!(check
+            .strong()
+            .inspect_strong(&smbios)
+            .is_some_and(|i| i.matched()));
+
+        // Only DMI checks can be strong
+        let check = Check::file("file", Confidence::Unverified, file.path(), |c| c == "abc");
+        assert!(check.strong().inspect_strong(&smbios).is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_exists() -> Result<()> {
+        let dir = TempDir::new()?;
+
+        let check = Check::exists("exists", Confidence::Unverified, dir.path());
+        assert!(check.run(TIMEOUT).await);
+
+        let check = Check::exists("exists", Confidence::Unverified, dir.path().join("abc"));
+        assert!(!check.run(TIMEOUT).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_run_http_with_headers() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata"))
+            .and(header("Metadata", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata").header("Metadata", "true");
+        let check = Check::http("http", Confidence::Verified, request, |resp| {
+            resp.is_success() && resp.body == "abc"
+        });
+
+        assert!(check.run(TIMEOUT).await);
+    }
+
+    #[tokio::test]
+    async fn test_run_http_with_token() {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}/token", mock_server.uri()));
+        Mock::given(method("PUT"))
+            .and(path("/token"))
+            .and(header("X-Token-TTL", "60"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("123abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .and(header("X-Token", "123abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+        let check = Check::http("http", Confidence::Verified, request, |resp| {
+            resp.body == "abc"
+        });
+
+        assert!(check.run(TIMEOUT).await);
+    }
+
+    #[tokio::test]
+    async fn test_run_http_with_empty_token() {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}/token", mock_server.uri()));
+        Mock::given(path("/token"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(0)
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+        let check = Check::http("http", Confidence::Verified, request, |_| true);
+
+        assert!(!check.run(TIMEOUT).await);
+    }
+
+    #[tokio::test]
+    async fn test_send_reuses_token() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}/token", mock_server.uri()));
+        Mock::given(method("PUT"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("123abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .and(header("X-Token", "123abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+
+        assert_eq!(request.send(TIMEOUT).await?.body, "abc");
+        assert_eq!(request.send(TIMEOUT).await?.body, "abc");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_refreshes_rejected_token() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}/token", mock_server.uri()));
+        Mock::given(method("PUT"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("expired"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("123abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .and(header("X-Token", "expired"))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .and(header("X-Token", "123abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+        let resp = request.send(TIMEOUT).await?;
+
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, "abc");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_shares_timeout_with_token() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}/token", mock_server.uri()));
+        Mock::given(method("PUT"))
+            .and(path("/token"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("123abc")
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string("abc")
+                    .set_delay(Duration::from_millis(300)),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata").token(TokenRequest {
+            path: "/token".to_string(),
+            ttl_header: "X-Token-TTL",
+            ttl: Duration::from_secs(60),
+            header: "X-Token",
+        });
+
+        // Each request fits in the timeout, but not both
+        let start = Instant::now();
+        assert!(request.send(Duration::from_millis(500)).await.is_err());
+        assert!(start.elapsed() < Duration::from_millis(600));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_retries_throttling() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(429))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let resp = Request::get(&mock_server.uri(), "/metadata")
+            .send(TIMEOUT)
+            .await?;
+
+        assert_eq!(resp.status, 200);
+        assert_eq!(resp.body, "abc");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_honors_retry_after() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/busy"))
+            .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "60"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let resp = Request::get(&mock_server.uri(), "/metadata")
+            .send(TIMEOUT)
+            .await?;
+        assert_eq!(resp.body, "abc");
+
+        let resp = Request::get(&mock_server.uri(), "/busy")
+            .send(TIMEOUT)
+            .await?;
+        assert_eq!(resp.status, 503);
+        assert_eq!(resp.retry_after, Some(Duration::from_secs(60)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_send_gives_up_retrying() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(u64::from(retry::MAX_ATTEMPTS))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let resp = Request::get(&mock_server.uri(), "/metadata")
+            .send(TIMEOUT)
+            .await?;
+        assert_eq!(resp.status, 503);
+
+        let resp = Request::get(&mock_server.uri(), "/missing")
+            .send(TIMEOUT)
+            .await?;
+        assert_eq!(resp.status, 404);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_inspect_http() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata"))
+            .respond_with(ResponseTemplate::new(404).set_body_string("Not Found"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let request = Request::get(&mock_server.uri(), "/metadata");
+        let check = Check::http("http", Confidence::Verified, request, |resp| {
+            resp.is_success()
+        });
+        let inspection = check.inspect(TIMEOUT).await;
+
+        assert!(!inspection.matched());
+        assert_eq!(inspection.status, Some(404));
+        assert_eq!(inspection.error, None);
+        assert_eq!(inspection.content.as_deref(), Some("Not Found"));
+        assert_eq!(
+            check.probe.target(),
+            format!("{}/metadata", mock_server.uri())
+        );
+    }
+
+    #[tokio::test]
+    async fn test_fetch_falls_back() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/v2"))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/v1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"id": "abc"}"#))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let fetch = Fetch::<serde_json::Value>::json(vec![
+            Request::get(&mock_server.uri(), "/v2"),
+            Request::get(&mock_server.uri(), "/v1"),
+        ]);
+        let value = fetch.run(TIMEOUT).await?;
+
+        assert_eq!(value["id"], "abc");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_failure() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/v1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let fetch = Fetch::<serde_json::Value>::json(vec![Request::get(&mock_server.uri(), "/v1")]);
+        let result = fetch.run(TIMEOUT).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_fetch_map() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/v1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"id": "abc"}"#))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let fetch = Fetch::<serde_json::Value>::json(vec![Request::get(&mock_server.uri(), "/v1")])
+            .map(|value| Ok(value["id"].as_str().unwrap_or_default().len()));
+
+        assert_eq!(fetch.run(TIMEOUT).await?, 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_and_then() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/mac"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/macs/abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("def"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let uri = mock_server.uri();
+        let fetch = Fetch::new(vec![Request::get(&uri, "/mac")], |resp| {
+            Ok(resp.body.clone())
+        })
+        .and_then(move |mac| {
+            Fetch::new(vec![Request::get(&uri, &format!("/macs/{mac}"))], |resp| {
+                Ok(resp.body.clone())
+            })
+        })
+        .map(|value| Ok(value.to_uppercase()));
+
+        assert_eq!(fetch.run(TIMEOUT).await?, "DEF");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_optional() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/v1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"id": "abc"}"#))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let uri = mock_server.uri();
+        let fetch = Fetch::<serde_json::Value>::json(vec![Request::get(&uri, "/v1")])
+            .optional()
+            .zip(Fetch::<serde_json::Value>::json(vec![Request::get(&uri, "/v2")]).optional());
+
+        let (found, missing) = fetch.run(TIMEOUT).await?;
+
+        assert_eq!(found.map(|value| value["id"].clone()), Some("abc".into()));
+        assert_eq!(missing, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_or_else() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/v1.json"))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path("/v1/id"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let uri = mock_server.uri();
+        let fetch = Fetch::<serde_json::Value>::json(vec![Request::get(&uri, "/v1.json")])
+            .map(|value| Ok(value["id"].as_str().unwrap_or_default().to_string()))
+            .or_else(Fetch::new(vec![Request::get(&uri, "/v1/id")], |resp| {
+                Ok(resp.body.clone())
+            }));
+
+        assert_eq!(fetch.run(TIMEOUT).await?, "abc");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_inspection_or_unauthorized() {
+        let check = Check::http(
+            "http",
+            Confidence::Verified,
+            Request::get("http://localhost", "/"),
+            |resp| resp.is_success(),
+        )
+        .or_unauthorized(Confidence::Unverified);
+        let Probe::Http { matches, .. } = &check.probe else {
+            unreachable!();
+        };
+        let inspect = |status| {
+            let resp = Response {
+                status,
+                ..Default::default()
+            };
+            Inspection::response(resp, matches).or_unauthorized(check.unauthorized)
+        };
+
+        let inspection = inspect(200);
+        assert!(inspection.matched());
+        assert_eq!(check.match_confidence(&inspection), Confidence::Verified);
+
+        let inspection = inspect(403);
+        assert!(inspection.matched());
+        assert_eq!(inspection.outcome, Outcome::AuthRequired);
+        assert_eq!(check.match_confidence(&inspection), Confidence::Unverified);
+
+        assert!(!inspect(404).matched());
+        assert_eq!(inspect(404).outcome, Outcome::Mismatched);
+
+        let inspection = Inspection::response(
+            Response {
+                status: 401,
+                ..Default::default()
+            },
+            matches,
+        )
+        .or_unauthorized(None);
+        assert!(!inspection.matched());
+        assert_eq!(inspection.outcome, Outcome::AuthRequired);
+    }
+
+    #[test]
+    fn test_inspection_outcome() {
+        let matches: ContentMatcher = Arc::new(|content| content == "Google");
+        let outcome = |content: Option<&str>| {
+            Inspection::content(content.map(str::to_string), &matches).outcome
+        };
+
+        assert_eq!(outcome(Some("Google")), Outcome::Matched);
+        assert_eq!(outcome(Some("Amazon EC2")), Outcome::Mismatched);
+        assert_eq!(outcome(None), Outcome::Absent);
+
+        let contents = |contents: &[&str]| {
+            let contents = contents.iter().map(|c| c.to_string()).collect();
+            Inspection::contents(contents, &matches).outcome
+        };
+
+        assert_eq!(contents(&["Amazon EC2", "Google"]), Outcome::Matched);
+        assert_eq!(contents(&["Amazon EC2"]), Outcome::Mismatched);
+        assert_eq!(contents(&[]), Outcome::Absent);
+
+        assert_eq!(Inspection::presence(false).outcome, Outcome::Absent);
+        assert_eq!(
+            Inspection::unreachable(anyhow!("Connection refused")).outcome,
+            Outcome::Unreachable
+        );
+        assert_eq!(
+            Inspection::error(anyhow!("Permission denied")).outcome,
+            Outcome::Failed
+        );
+    }
+
+    #[test]
+    fn test_inspection_rejects_spoofed() {
+        let check = Check::http(
+            "http",
+            Confidence::Verified,
+            Request::get("http://localhost", "/"),
+            |resp| resp.is_success(),
+        )
+        .or_unauthorized(Confidence::Unverified);
+        let Probe::Http { matches, .. } = &check.probe else {
+            unreachable!();
+        };
+        let inspect = |status, body: &str| {
+            let resp = Response {
+                status,
+                body: body.to_string(),
+                ..Default::default()
+            };
+            Inspection::response(resp, matches).or_unauthorized(check.unauthorized)
+        };
+
+        let inspection = inspect(200, fixtures::CAPTIVE_PORTAL);
+        assert!(!inspection.matched());
+        assert_eq!(inspection.outcome, Outcome::Rejected);
+
+        let inspection = inspect(401, fixtures::CAPTIVE_PORTAL);
+        assert!(inspection.matched());
+        assert!(inspection.unauthorized);
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "text/html; charset=utf-8".parse().unwrap());
+        let resp = Response {
+            status: 200,
+            body: "Welcome".to_string(),
+            headers,
+            ..Default::default()
+        };
+        assert!(resp.is_spoofed());
+    }
+
+    #[tokio::test]
+    async fn test_providers_reject_captive_portal() {
+        let mock_server = MockServer::start().await;
+        Mock::given(wiremock::matchers::any())
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .insert_header("Content-Type", "text/html")
+                    .set_body_string(fixtures::CAPTIVE_PORTAL),
+            )
+            .mount(&mock_server)
+            .await;
+
+        for provider in PROVIDERS {
+            for mut check in provider.checks() {
+                if let Probe::Http { request, .. } = &mut check.probe {
+                    request.base_uri = mock_server.uri();
+                    assert!(!check.run(TIMEOUT).await, "check: {}", check.name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_response_listing() -> Result<()> {
+        let resp = Response {
+            status: 200,
+            body: "default/\nabc@example.com/\n".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(resp.listing()?, ["default", "abc@example.com"]);
+
+        let resp = Response {
+            status: 404,
+            body: "Not Found".to_string(),
+            ..Default::default()
+        };
+        assert!(resp.listing()?.is_empty());
+
+        let resp = Response {
+            status: 500,
+            ..Default::default()
+        };
+        assert!(resp.listing().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_response_unavailable() {
+        let resp = Response {
+            status: 503,
+            retry_after: Some(Duration::from_secs(5)),
+            ..Default::default()
+        };
+        let err = resp.parse::<serde_json::Value>().unwrap_err();
+
+        assert_eq!(
+            err.to_string(),
+            "Metadata server unavailable, retry after 5s"
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! Clock.
+//!
+//! Deadlines, delays and token lifetimes are measured with tokio's clock instead of the system clock, so that tests
+//! pausing time (see `tokio::time::pause`) don't have to sleep for real. Unless time is paused, tokio's clock is the
+//! monotonic system clock, so the blocking API can use it as well. Without the `async` feature, there is no tokio, and
+//! the monotonic system clock is used directly.
+//!
+//! Every deadline of a detection is an [Instant] of this clock, never the wall clock: setting the wall clock (e.g. when
+//! a live-migrated VM resynchronizes it, or after a laptop resumes) neither fires deadlines early nor pushes them back.
+//! The monotonic clock doesn't advance while the host is suspended, so a detection interrupted by a suspend gets its
+//! full budget once resumed, rather than timing out on resume.
+
+use std::time::Duration;
+#[cfg(not(feature = "async"))]
+pub(crate) use std::time::Instant;
+
+use anyhow::{bail, Result};
+#[cfg(feature = "async")]
+pub(crate) use tokio::time::{sleep, sleep_until, Instant};
+
+/// Time from now to deadlines too far to be represented by the clock (e.g. for a timeout of `u64::MAX` seconds), which
+/// are never reached in practice.
+pub(crate) const FAR_FUTURE: Duration = Duration::from_secs(100 * 365 * 24 * 3600);
+
+/// Returns the deadline of work starting now and allowed the given time, saturating rather than overflowing the clock.
+pub(crate) fn deadline(timeout: Duration) -> Instant {
+    Instant::now() + timeout.min(FAR_FUTURE)
+}
+
+/// Returns the time left until a deadline, failing once it has passed.
+pub(crate) fn remaining(deadline: Instant) -> Result<Duration> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+
+    if remaining.is_zero() {
+        bail!("Deadline exceeded");
+    }
+
+    Ok(remaining)
+}
@@ -0,0 +1,684 @@
+//! Detection tuning loaded from a configuration file.
+//!
+//! A [Config] tunes the behavior of a [Detector] beyond its builder: which providers are probed and in which order,
+//! how matches of each confidence are weighted when resolving the detected provider, additional vendor strings
+//! identifying a provider, and alternative metadata server endpoints (e.g. behind a proxy). Configuration files are
+//! written in TOML or JSON, told apart by their extension.
+//!
+//! # Examples
+//!
+//! ```toml
+//! timeout = 2
+//! local_timeout_ms = 500
+//! max_concurrency = 2
+//!
+//! # Cloud-init doesn't outweigh the metadata server
+//! [weights]
+//! authoritative = 2
+//!
+//! [providers.aws]
+//! priority = 10
+//! endpoint = "http://imds-proxy.internal:8080"
+//!
+//! [providers.openstack]
+//! vendors = ["Acme Private Cloud"]
+//!
+//! [providers.vultr]
+//! enabled = false
+//!
+//! # A private cloud, told apart by its chassis asset tag
+//! [[custom]]
+//! name = "acme"
+//! files = [{ glob = "/sys/class/dmi/id/chassis_asset_tag", contains = ["ACME"] }]
+//! endpoint = { url = "http://169.254.169.254/acme/v1/instance", pointer = "/cloud", expected = "acme" }
+//! ```
+
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, instrument};
+
+use crate::checks::{self, Check, Probe};
+use crate::{supported_providers, Confidence, CustomProvider, Detector, Match, ProviderId, P};
+
+/// DMI attribute files matched against custom vendor strings.
+const VENDOR_FILES: &[&str] = &[
+    "/sys/class/dmi/id/sys_vendor",
+    "/sys/class/dmi/id/product_name",
+    "/sys/class/dmi/id/chassis_vendor",
+    "/sys/class/dmi/id/chassis_asset_tag",
+    "/sys/class/dmi/id/bios_vendor",
+];
+
+/// Represents the tuning of a detector, as loaded from a configuration file.
+///
+/// Every setting is optional, and a detector configured with an empty configuration behaves like [Detector::new].
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Maximum time (seconds) allowed for detection (see [Detector::timeout]).
+    pub timeout: Option<u64>,
+    /// Maximum time (milliseconds) allowed for the local checks of each provider (see [Detector::local_timeout]).
+    pub local_timeout_ms: Option<u64>,
+    /// Maximum time (milliseconds) allowed for connecting to each metadata server before querying it, if pre-checked
+    /// (see [Detector::precheck_timeout]).
+    pub precheck_timeout_ms: Option<u64>,
+    /// Maximum number of providers probed concurrently (see [Detector::max_concurrency]).
+    pub max_concurrency: Option<usize>,
+    /// Directory the host is mounted at, when detecting from a container (see [Detector::host_root]).
+    pub host_root: Option<PathBuf>,
+    /// Whether to inspect the node a pod runs on through the root filesystem of its init process (see
+    /// [Detector::node_agent]).
+    pub node_agent: bool,
+    /// Network interface the requests to metadata servers are sent from (see [Detector::interface]).
+    pub interface: Option<String>,
+    /// Source address of the requests to metadata servers (see [Detector::local_address]).
+    pub local_address: Option<IpAddr>,
+    /// Whether to only run checks local to the host (see [Detector::local_only]).
+    pub local_only: bool,
+    /// Whether to run the checks of every provider, instead of stopping at the first strong local signal (see
+    /// [Detector::thorough]).
+    pub thorough: bool,
+    /// Weights of matches by confidence.
+    pub weights: Weights,
+    /// Settings of each provider, by identifier (e.g. `aws`) or alias (e.g. `amazon`), ignoring case (see
+    /// [ProviderId::parse_lenient]).
+    pub providers: BTreeMap<String, ProviderConfig>,
+    /// User-defined providers (see [CustomProvider]).
+    pub custom: Vec<CustomProvider>,
+}
+
+/// Represents the weights of matches by confidence, used to resolve the detected provider.
+///
+/// Among specific providers (or generic platforms, if no specific provider matched), the match with the highest weight
+/// wins. Ties go to the provider with the highest priority, then to the provider listed first in
+/// [supported_providers].
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Weights {
+    /// Weight of matches made on a heuristic, such as a vendor string. Defaults to 1.
+    pub unverified: u32,
+    /// Weight of matches made on provider-specific content returned by the metadata server. Defaults to 2.
+    pub verified: u32,
+    /// Weight of matches named directly by tooling that already identified the provider, such as cloud-init. Defaults
+    /// to 3.
+    pub authoritative: u32,
+}
+
+impl Default for Weights {
+    fn default() -> Self {
+        Self {
+            unverified: 1,
+            verified: 2,
+            authoritative: 3,
+        }
+    }
+}
+
+/// Represents the settings of a provider.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProviderConfig {
+    /// Whether the provider is probed. Defaults to `true`.
+    pub enabled: bool,
+    /// Priority of the provider. Providers with higher priorities are probed first and win ties. Defaults to 0.
+    pub priority: i32,
+    /// Whether the metadata server of the provider is queried, regardless of the environment (see
+    /// [Detector::metadata]).
+    pub metadata: Option<bool>,
+    /// Additional vendor strings identifying the provider, matched against the DMI attributes of the host.
+    pub vendors: Vec<String>,
+    /// Endpoint of the metadata server of the provider (e.g. `http://169.254.169.254`), replacing the default one.
+    pub endpoint: Option<String>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            priority: 0,
+            metadata: None,
+            vendors: Vec::new(),
+            endpoint: None,
+        }
+    }
+}
+
+impl Weights {
+    /// Creates the default weights of matches by confidence.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the weight of unverified matches.
+    pub fn unverified(mut self, weight: u32) -> Self {
+        self.unverified = weight;
+        self
+    }
+
+    /// Sets the weight of verified matches.
+    pub fn verified(mut self, weight: u32) -> Self {
+        self.verified = weight;
+        self
+    }
+
+    /// Sets the weight of authoritative matches.
+    pub fn authoritative(mut self, weight: u32) -> Self {
+        self.authoritative = weight;
+        self
+    }
+}
+
+impl ProviderConfig {
+    /// Creates the default settings of a provider, probed with no priority.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether the provider is probed.
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Sets the priority of the provider.
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    /// Sets whether the metadata server of the provider is queried.
+    pub fn metadata(mut self, enabled: bool) -> Self {
+        self.metadata = Some(enabled);
+        self
+    }
+
+    /// Adds a vendor string identifying the provider.
+    pub fn vendor(mut self, vendor: &str) -> Self {
+        self.vendors.push(vendor.to_string());
+        self
+    }
+
+    /// Replaces the endpoint of the metadata server of the provider.
+    pub fn endpoint(mut self, endpoint: &str) -> Self {
+        self.endpoint = Some(endpoint.to_string());
+        self
+    }
+}
+
+impl Config {
+    /// Creates an empty configuration, tuning nothing.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the weights of matches by confidence.
+    pub fn weights(mut self, weights: Weights) -> Self {
+        self.weights = weights;
+        self
+    }
+
+    /// Sets the settings of a provider, by identifier (e.g. `aws`) or alias (e.g. `amazon`).
+    pub fn provider(mut self, name: &str, config: ProviderConfig) -> Self {
+        self.providers.insert(name.to_string(), config);
+        self
+    }
+
+    /// Adds a user-defined provider.
+    pub fn custom_provider(mut self, provider: CustomProvider) -> Self {
+        self.custom.push(provider);
+        self
+    }
+
+    /// Loads a configuration file, in TOML (`.toml`) or JSON (`.json`), and validates it.
+    #[instrument(skip_all, fields(path = %path.as_ref().display()))]
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Error reading {}", path.display()))?;
+
+        let config = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => Self::from_toml(&content)?,
+            Some("json") => Self::from_json(&content)?,
+            _ => bail!("Unsupported configuration format: {}", path.display()),
+        };
+        debug!("Loaded configuration: {:?}", config);
+
+        Ok(config)
+    }
+
+    /// Parses and validates a TOML configuration.
+    pub(crate) fn from_toml(content: &str) -> Result<Self> {
+        let config: Self = toml::from_str(content).context("Error parsing configuration")?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Parses and validates a JSON configuration.
+    pub(crate) fn from_json(content: &str) -> Result<Self> {
+        let config: Self = serde_json::from_str(content).context("Error parsing configuration")?;
+        config.validate()?;
+
+        Ok(config)
+    }
+
+    /// Checks the settings that can't be enforced by the schema alone.
+    pub fn validate(&self) -> Result<()> {
+        if self.timeout == Some(0) {
+            bail!("timeout must be at least 1 second");
+        }
+
+        if self.max_concurrency == Some(0) {
+            bail!("max_concurrency must be at least 1");
+        }
+
+        for (name, provider) in &self.providers {
+            if provider_id(name).is_none() {
+                bail!("Unsupported provider: {name}");
+            }
+
+            if provider
+                .vendors
+                .iter()
+                .any(|vendor| vendor.trim().is_empty())
+            {
+                bail!("Empty vendor string for provider {name}");
+            }
+
+            if let Some(endpoint) = &provider.endpoint {
+                if !endpoint
+                    .split_once("://")
+                    .is_some_and(|(scheme, _)| checks::supports_scheme(scheme))
+                {
+                    bail!(
+                        "Endpoint of provider {name} is not an HTTP URL, or HTTPS without TLS \
+                         support: {endpoint}"
+                    );
+                }
+            }
+        }
+
+        for provider in &self.custom {
+            provider.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the settings of a provider, if configured.
+    fn settings(&self, provider: ProviderId) -> Option<&ProviderConfig> {
+        self.providers
+            .iter()
+            .find(|(name, _)| provider_id(name) == Some(provider))
+            .map(|(_, config)| config)
+    }
+
+    /// Returns the priority of a provider.
+    pub(crate) fn priority(&self, provider: ProviderId) -> i32 {
+        self.settings(provider).map_or(0, |config| config.priority)
+    }
+
+    /// Returns the weight of a match.
+    pub(crate) fn weight(&self, m: &Match) -> u32 {
+        match m.confidence {
+            Confidence::Unverified => self.weights.unverified,
+            Confidence::Verified => self.weights.verified,
+            Confidence::Authoritative => self.weights.authoritative,
+        }
+    }
+
+    /// Adds the checks for the custom vendor strings of a provider, and moves its metadata server checks to the
+    /// configured endpoint.
+    pub(crate) fn tune_checks(&self, provider: P, checks: &mut Vec<Check>) {
+        let Some(config) = self.settings(provider.identifier()) else {
+            return;
+        };
+
+        if let Some(endpoint) = &config.endpoint {
+            for check in checks.iter_mut() {
+                if let Probe::Http { request, .. } = &mut check.probe {
+                    request.base_uri = endpoint.trim_end_matches('/').to_string();
+                }
+            }
+        }
+
+        if !config.vendors.is_empty() {
+            let vendors = Arc::new(config.vendors.clone());
+            let vendor_checks = VENDOR_FILES.iter().map(|file| {
+                let vendors = vendors.clone();
+                Check::dmi(
+                    "custom_vendor",
+                    Confidence::Unverified,
+                    file,
+                    move |content| {
+                        vendors
+                            .iter()
+                            .any(|vendor| content.contains(vendor.as_str()))
+                    },
+                )
+            });
+
+            checks.splice(0..0, vendor_checks);
+        }
+    }
+}
+
+/// Returns the supported provider with the given identifier (e.g. `aws`) or alias (e.g. `amazon`), ignoring case.
+fn provider_id(name: &str) -> Option<ProviderId> {
+    ProviderId::parse_lenient(name).filter(|provider| supported_providers().contains(provider))
+}
+
+impl Detector {
+    /// Creates a detector tuned by a configuration file, in TOML (`.toml`) or JSON (`.json`).
+    ///
+    /// Fails if the file can't be read, or doesn't match the schema of [Config] (including unknown settings and
+    /// unsupported providers).
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use cloud_detect::Detector;
+    ///
+    /// #[tokio::main]
+    /// async fn main() -> anyhow::Result<()> {
+    ///     let provider = Detector::from_config("/etc/cloud-detect.toml")?
+    ///         .detect()
+    ///         .await;
+    ///     println!("Detected provider: {}", provider);
+    ///
+    ///     Ok(())
+    /// }
+    /// ```
+    pub fn from_config<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Ok(Self::new().config(Config::load(path)?))
+    }
+
+    /// Tunes the detector with a configuration, overriding settings made previously.
+    ///
+    /// Invalid custom providers are ignored (see [Config::validate]).
+    pub fn config(mut self, config: Config) -> Self {
+        if config.timeout.is_some() {
+            self.timeout = config.timeout;
+        }
+
+        if let Some(local_timeout_ms) = config.local_timeout_ms {
+            self = self.local_timeout(Duration::from_millis(local_timeout_ms));
+        }
+
+        if let Some(precheck_timeout_ms) = config.precheck_timeout_ms {
+            self = self.precheck_timeout(Duration::from_millis(precheck_timeout_ms));
+        }
+
+        if let Some(max_concurrency) = config.max_concurrency {
+            self = self.max_concurrency(max_concurrency);
+        }
+
+        if let Some(host_root) = &config.host_root {
+            self = self.host_root(host_root);
+        }
+
+        if config.node_agent {
+            self = self.node_agent();
+        }
+
+        if let Some(interface) = &config.interface {
+            self = self.interface(interface);
+        }
+
+        if let Some(local_address) = config.local_address {
+            self = self.local_address(local_address);
+        }
+
+        if config.local_only {
+            self = self.local_only();
+        }
+
+        if config.thorough {
+            self = self.thorough();
+        }
+
+        for (name, provider) in &config.providers {
+            if let (Some(id), Some(enabled)) = (provider_id(name), provider.metadata) {
+                self = self.metadata(id, enabled);
+            }
+        }
+
+        let disabled: Vec<ProviderId> = config
+            .providers
+            .iter()
+            .filter(|(_, provider)| !provider.enabled)
+            .filter_map(|(name, _)| provider_id(name))
+            .collect();
+
+        if !disabled.is_empty() {
+            let enabled: Vec<ProviderId> = self
+                .supported_providers()
+                .into_iter()
+                .filter(|provider| !disabled.contains(provider))
+                .collect();
+            self = self.providers(&enabled);
+        }
+
+        for provider in &config.custom {
+            match self.clone().custom_provider(provider.clone()) {
+                Ok(detector) => self = detector,
+                Err(err) => error!("Ignoring custom provider: {:?}", err),
+            }
+        }
+
+        self.config = Some(Arc::new(config));
+        self
+    }
+
+    /// Orders providers by their configured priority, keeping the order of providers with the same priority.
+    pub(crate) fn prioritize(&self, providers: &mut [P]) {
+        if let Some(config) = &self.config {
+            providers.sort_by_key(|provider| Reverse(config.priority(provider.identifier())));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::Builder;
+
+    use super::*;
+    use crate::providers::{aws, openstack, vultr};
+    use crate::HostSnapshot;
+
+    const TOML: &str = r#"
+timeout = 2
+local_timeout_ms = 500
+precheck_timeout_ms = 50
+host_root = "/host"
+node_agent = false
+interface = "eth1"
+local_address = "10.0.1.5"
+
+[weights]
+authoritative = 2
+
+[providers.aws]
+priority = 10
+endpoint = "http://imds-proxy.internal:8080/"
+
+[providers.openstack]
+vendors = ["Acme Private Cloud"]
+
+[providers.vultr]
+enabled = false
+
+[[custom]]
+name = "acme"
+files = [{ glob = "/sys/class/dmi/id/chassis_asset_tag", contains = ["ACME"] }]
+"#;
+
+    #[test]
+    fn test_load() -> Result<()> {
+        let mut file = Builder::new().suffix(".toml").tempfile()?;
+        file.write_all(TOML.as_bytes())?;
+
+        let config = Config::load(file.path())?;
+
+        assert_eq!(config.timeout, Some(2));
+        assert_eq!(config.weights.authoritative, 2);
+        assert_eq!(config.weights.verified, 2);
+        assert_eq!(config.priority(ProviderId::AWS), 10);
+        assert!(!config.providers["vultr"].enabled);
+        assert_eq!(config.host_root.as_deref(), Some(Path::new("/host")));
+        assert_eq!(config.interface.as_deref(), Some("eth1"));
+        assert_eq!(config.local_address, Some(IpAddr::from([10, 0, 1, 5])));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_unsupported_format() -> Result<()> {
+        let file = Builder::new().suffix(".yaml").tempfile()?;
+
+        assert!(Config::load(file.path()).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_json() -> Result<()> {
+        let config = Config::from_json(r#"{"providers": {"gcp": {"metadata": false}}}"#)?;
+
+        assert_eq!(config.providers["gcp"].metadata, Some(false));
+        assert!(config.providers["gcp"].enabled);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(Config::from_toml("timeout = 0").is_err());
+        assert!(Config::from_toml("max_concurrency = 0").is_err());
+        assert!(Config::from_toml("unknown = true").is_err());
+        assert!(Config::from_toml("[providers.acme]").is_err());
+        assert!(Config::from_toml("[providers.unknown]").is_err());
+        assert!(Config::from_toml("[providers.aws]\nvendors = [\" \"]").is_err());
+        assert!(Config::from_toml("[providers.aws]\nendpoint = \"169.254.169.254\"").is_err());
+        assert!(Config::from_toml("[providers.aws]\nweight = 1").is_err());
+        assert!(Config::from_toml("[[custom]]\nname = \"acme\"").is_err());
+        assert!(Config::from_toml("[[custom]]\nname = \"aws\"\nfiles = []").is_err());
+    }
+
+    #[test]
+    fn test_builders() -> Result<()> {
+        let config = Config::new()
+            .weights(Weights::new().authoritative(2))
+            .provider(
+                "aws",
+                ProviderConfig::new()
+                    .priority(10)
+                    .endpoint("http://imds-proxy.internal:8080/"),
+            )
+            .provider(
+                "openstack",
+                ProviderConfig::new().vendor("Acme Private Cloud"),
+            )
+            .provider("vultr", ProviderConfig::new().enabled(false))
+            .custom_provider(
+                CustomProvider::new("acme").file("/sys/class/dmi/id/chassis_asset_tag", &["ACME"]),
+            );
+
+        let expected = Config::from_toml(TOML)?;
+        assert_eq!(config.weights, expected.weights);
+        assert_eq!(config.providers, expected.providers);
+        assert_eq!(config.custom, expected.custom);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_provider_aliases() -> Result<()> {
+        let config = Config::from_toml("[providers.Amazon]\npriority = 10")?;
+
+        assert_eq!(config.priority(ProviderId::AWS), 10);
+        assert_eq!(config.priority(ProviderId::Azure), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detector_config() -> Result<()> {
+        let detector = Detector::new().config(Config::from_toml(TOML)?);
+
+        assert_eq!(detector.timeout, Some(2));
+        assert_eq!(detector.local_timeout, Some(Duration::from_millis(500)));
+        assert_eq!(detector.precheck_timeout, Some(Duration::from_millis(50)));
+        assert!(!detector.supported_providers().contains(&ProviderId::Vultr));
+        assert!(detector
+            .supported_providers()
+            .contains(&ProviderId::Custom("acme")));
+        assert_eq!(detector.ranked_providers()[0].identifier(), ProviderId::AWS);
+
+        let endpoints: Vec<String> = detector
+            .checks(&aws::Aws)
+            .into_iter()
+            .filter_map(|check| match check.probe {
+                Probe::Http { request, .. } => Some(request.base_uri),
+                _ => None,
+            })
+            .collect();
+        assert!(endpoints
+            .iter()
+            .all(|endpoint| endpoint == "http://imds-proxy.internal:8080"));
+
+        assert!(detector
+            .checks(&openstack::OpenStack)
+            .iter()
+            .any(|check| check.name == "custom_vendor"));
+        assert!(detector
+            .checks(&vultr::Vultr)
+            .iter()
+            .all(|check| check.name != "custom_vendor"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detector_config_vendors() -> Result<()> {
+        let detector = Detector::new().config(Config::from_toml(TOML)?);
+        let snapshot =
+            HostSnapshot::new().file("/sys/class/dmi/id/chassis_vendor", "Acme Private Cloud\n");
+
+        assert_eq!(detector.detect_snapshot(&snapshot), ProviderId::OpenStack);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detector_config_weights() -> Result<()> {
+        let matches = [
+            Match::new(ProviderId::Azure, Confidence::Verified),
+            Match::new(ProviderId::AWS, Confidence::Authoritative),
+        ];
+
+        assert_eq!(Detector::new().resolve(&matches), ProviderId::AWS);
+
+        // With equal weights, the provider with the highest priority wins
+        let detector = Detector::new().config(Config::from_toml(
+            "[weights]\nauthoritative = 2\n\n[providers.azure]\npriority = 1",
+        )?);
+
+        assert_eq!(detector.resolve(&matches), ProviderId::Azure);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,605 @@
+//! Data model of detection results.
+//!
+//! The provider identifiers and the metadata structs returned by detection only depend on `core` and `alloc`, so they
+//! are available without the `std` feature (e.g. for sharing them with a `no_std` protocol crate). The probing engine,
+//! which requires `std` along with a transport (`async` for tokio and reqwest, or `blocking-ureq`), is layered on top of
+//! them.
+
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+use strum::Display;
+
+/// Represents an identifier for a cloud service provider.
+#[non_exhaustive]
+#[derive(Debug, Default, Display, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProviderId {
+    /// Unknown cloud service provider.
+    #[default]
+    #[strum(serialize = "unknown")]
+    Unknown,
+    /// Akamai Cloud, formerly Linode (see [Naming] for its legacy code).
+    #[strum(serialize = "akamai")]
+    Akamai,
+    /// Alibaba Cloud.
+    #[strum(serialize = "alibaba")]
+    Alibaba,
+    /// Amazon Web Services (AWS).
+    #[strum(serialize = "aws")]
+    AWS,
+    /// Microsoft Azure.
+    #[strum(serialize = "azure")]
+    Azure,
+    /// DigitalOcean.
+    #[strum(serialize = "digitalocean")]
+    DigitalOcean,
+    /// Google Cloud Platform (GCP).
+    #[strum(serialize = "gcp")]
+    GCP,
+    /// Oracle Cloud Infrastructure (OCI).
+    #[strum(serialize = "oci")]
+    OCI,
+    /// OpenStack.
+    #[strum(serialize = "openstack")]
+    OpenStack,
+    /// Vultr.
+    #[strum(serialize = "vultr")]
+    Vultr,
+    /// Naver Cloud Platform.
+    #[strum(serialize = "naver")]
+    Naver,
+    /// NHN Cloud, formerly TOAST.
+    #[strum(serialize = "nhn")]
+    Nhn,
+    /// VK Cloud, formerly Mail.ru Cloud Solutions.
+    #[strum(serialize = "vkcloud")]
+    VkCloud,
+    /// Yandex Cloud.
+    #[strum(serialize = "yandex")]
+    Yandex,
+    /// Civo.
+    #[strum(serialize = "civo")]
+    Civo,
+    /// Huawei Cloud, built on OpenStack (see `Detection::reseller`).
+    #[strum(serialize = "huaweicloud")]
+    HuaweiCloud,
+    /// Open Telekom Cloud, built on OpenStack (see `Detection::reseller`).
+    #[strum(serialize = "opentelekomcloud")]
+    OpenTelekom,
+    /// SAP Converged Cloud, built on OpenStack (see `Detection::reseller`).
+    #[strum(serialize = "sapccloud")]
+    SapCCloud,
+    /// VMware vSphere (or any other VMware hypervisor).
+    #[strum(serialize = "vmware")]
+    VMware,
+    /// Proxmox Virtual Environment.
+    #[strum(serialize = "proxmox")]
+    Proxmox,
+    /// Nutanix AHV.
+    #[strum(serialize = "nutanix")]
+    Nutanix,
+    /// KVM guest not identified as any other provider, such as a plain QEMU guest or a KubeVirt or Harvester virtual
+    /// machine.
+    #[strum(serialize = "kvm")]
+    KVM,
+    /// User-defined provider, by name (see `CustomProvider`).
+    #[strum(default)]
+    Custom(&'static str),
+}
+
+/// Names accepted by [ProviderId::parse_lenient] for the built-in providers, besides their codes.
+const ALIASES: &[(&str, ProviderId)] = &[
+    ("linode", ProviderId::Akamai),
+    ("aliyun", ProviderId::Alibaba),
+    ("amazon", ProviderId::AWS),
+    ("ec2", ProviderId::AWS),
+    ("microsoft", ProviderId::Azure),
+    ("google", ProviderId::GCP),
+    ("gce", ProviderId::GCP),
+    ("oracle", ProviderId::OCI),
+    ("ncp", ProviderId::Naver),
+    ("navercloud", ProviderId::Naver),
+    ("nhncloud", ProviderId::Nhn),
+    ("toast", ProviderId::Nhn),
+    ("vk", ProviderId::VkCloud),
+    ("mcs", ProviderId::VkCloud),
+    ("yandexcloud", ProviderId::Yandex),
+    ("yc", ProviderId::Yandex),
+    ("huawei", ProviderId::HuaweiCloud),
+    ("otc", ProviderId::OpenTelekom),
+    ("ccloud", ProviderId::SapCCloud),
+    ("qemu", ProviderId::KVM),
+];
+
+/// Built-in providers, for parsing their codes.
+const BUILT_IN: &[ProviderId] = &[
+    ProviderId::Akamai,
+    ProviderId::Alibaba,
+    ProviderId::AWS,
+    ProviderId::Azure,
+    ProviderId::DigitalOcean,
+    ProviderId::GCP,
+    ProviderId::OCI,
+    ProviderId::OpenStack,
+    ProviderId::Vultr,
+    ProviderId::Naver,
+    ProviderId::Nhn,
+    ProviderId::VkCloud,
+    ProviderId::Yandex,
+    ProviderId::Civo,
+    ProviderId::HuaweiCloud,
+    ProviderId::OpenTelekom,
+    ProviderId::SapCCloud,
+    ProviderId::VMware,
+    ProviderId::Proxmox,
+    ProviderId::Nutanix,
+    ProviderId::KVM,
+];
+
+/// Represents the naming of providers that have been renamed, for emitting their codes.
+///
+/// Only codes are affected: [Display](core::fmt::Display) always emits the current code, and
+/// [ProviderId::parse_lenient] accepts both.
+#[non_exhaustive]
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Naming {
+    /// Current names (e.g. `akamai`).
+    #[default]
+    Current,
+    /// Names from before the provider was renamed (e.g. `linode`), for configurations and dashboards that predate it.
+    Legacy,
+}
+
+/// Represents the kind of platform a provider is.
+#[non_exhaustive]
+#[derive(Debug, Display, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProviderGroup {
+    /// Public or private cloud, with a metadata server (e.g. AWS or OpenStack).
+    #[strum(serialize = "cloud")]
+    Cloud,
+    /// Virtualization platform without a metadata server, typically run on premises (e.g. VMware vSphere). Clouds may
+    /// be built on such platforms, so a match for a cloud always wins over one for a virtualization platform.
+    #[strum(serialize = "virtualization")]
+    Virtualization,
+}
+
+/// Represents the platform a host runs on within its provider (e.g. a Lambda function on AWS), as named by the
+/// `cloud.platform` attribute of the OpenTelemetry semantic conventions, which [Display](core::fmt::Display) emits.
+#[non_exhaustive]
+#[derive(Debug, Display, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Platform {
+    /// Amazon EC2 instance.
+    #[strum(serialize = "aws_ec2")]
+    Ec2,
+    /// Amazon ECS task running on EC2 instances.
+    #[strum(serialize = "aws_ecs")]
+    Ecs,
+    /// Amazon ECS task running on AWS Fargate (named `aws_ecs` too, as the conventions don't tell launch types apart).
+    #[strum(serialize = "aws_ecs")]
+    Fargate,
+    /// Amazon EKS node or pod.
+    #[strum(serialize = "aws_eks")]
+    Eks,
+    /// AWS Lambda function.
+    #[strum(serialize = "aws_lambda")]
+    Lambda,
+    /// Azure virtual machine.
+    #[strum(serialize = "azure_vm")]
+    AzureVm,
+    /// Azure App Service.
+    #[strum(serialize = "azure_app_service")]
+    AppService,
+    /// Azure Container Apps.
+    #[strum(serialize = "azure_container_apps")]
+    ContainerApps,
+    /// Azure Functions.
+    #[strum(serialize = "azure_functions")]
+    AzureFunctions,
+    /// Azure Kubernetes Service (AKS) node or pod.
+    #[strum(serialize = "azure_aks")]
+    Aks,
+    /// Google Compute Engine instance.
+    #[strum(serialize = "gcp_compute_engine")]
+    Gce,
+    /// Google Kubernetes Engine (GKE) node or pod.
+    #[strum(serialize = "gcp_kubernetes_engine")]
+    Gke,
+    /// Cloud Run service or job.
+    #[strum(serialize = "gcp_cloud_run")]
+    CloudRun,
+    /// Cloud Functions.
+    #[strum(serialize = "gcp_cloud_functions")]
+    CloudFunctions,
+    /// App Engine, standard or flexible environment.
+    #[strum(serialize = "gcp_app_engine")]
+    AppEngine,
+    /// Alibaba Cloud Elastic Compute Service (ECS) instance.
+    #[strum(serialize = "alibaba_cloud_ecs")]
+    AlibabaEcs,
+    /// Oracle Cloud Infrastructure compute instance.
+    #[strum(serialize = "oracle_cloud_compute")]
+    OciCompute,
+    /// Linode Kubernetes Engine (LKE) node (named `akamai_lke`, as the conventions don't cover Akamai Cloud).
+    #[strum(serialize = "akamai_lke")]
+    Lke,
+}
+
+impl Platform {
+    /// Returns the provider of the platform.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{Platform, ProviderId};
+    ///
+    /// assert_eq!(Platform::Lambda.provider(), ProviderId::AWS);
+    /// assert_eq!(Platform::Lambda.to_string(), "aws_lambda");
+    /// ```
+    pub fn provider(&self) -> ProviderId {
+        match self {
+            Self::Ec2 | Self::Ecs | Self::Fargate | Self::Eks | Self::Lambda => ProviderId::AWS,
+            Self::AzureVm
+            | Self::AppService
+            | Self::ContainerApps
+            | Self::AzureFunctions
+            | Self::Aks => ProviderId::Azure,
+            Self::Gce | Self::Gke | Self::CloudRun | Self::CloudFunctions | Self::AppEngine => {
+                ProviderId::GCP
+            }
+            Self::AlibabaEcs => ProviderId::Alibaba,
+            Self::OciCompute => ProviderId::OCI,
+            Self::Lke => ProviderId::Akamai,
+        }
+    }
+}
+
+impl ProviderId {
+    /// Returns the kind of platform the provider is, or `None` for [ProviderId::Unknown].
+    ///
+    /// User-defined providers are assumed to be (private) clouds.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{ProviderGroup, ProviderId};
+    ///
+    /// assert_eq!(ProviderId::AWS.group(), Some(ProviderGroup::Cloud));
+    /// assert_eq!(
+    ///     ProviderId::VMware.group(),
+    ///     Some(ProviderGroup::Virtualization)
+    /// );
+    /// ```
+    pub fn group(&self) -> Option<ProviderGroup> {
+        match self {
+            Self::Unknown => None,
+            Self::VMware | Self::Proxmox | Self::Nutanix | Self::KVM => {
+                Some(ProviderGroup::Virtualization)
+            }
+            _ => Some(ProviderGroup::Cloud),
+        }
+    }
+
+    /// Returns the machine-readable code of the provider (e.g. `aws`), as also returned by its
+    /// [Display](core::fmt::Display) implementation.
+    ///
+    /// Codes are stable, so unlike [ProviderId::display_name], they are suitable as keys for looking up localized
+    /// labels.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::ProviderId;
+    ///
+    /// assert_eq!(ProviderId::AWS.short_code(), "aws");
+    /// assert_eq!(ProviderId::Custom("acme").short_code(), "acme");
+    /// ```
+    pub fn short_code(&self) -> &'static str {
+        match self {
+            Self::Unknown => "unknown",
+            Self::Akamai => "akamai",
+            Self::Alibaba => "alibaba",
+            Self::AWS => "aws",
+            Self::Azure => "azure",
+            Self::DigitalOcean => "digitalocean",
+            Self::GCP => "gcp",
+            Self::OCI => "oci",
+            Self::OpenStack => "openstack",
+            Self::Vultr => "vultr",
+            Self::Naver => "naver",
+            Self::Nhn => "nhn",
+            Self::VkCloud => "vkcloud",
+            Self::Yandex => "yandex",
+            Self::Civo => "civo",
+            Self::HuaweiCloud => "huaweicloud",
+            Self::OpenTelekom => "opentelekomcloud",
+            Self::SapCCloud => "sapccloud",
+            Self::VMware => "vmware",
+            Self::Proxmox => "proxmox",
+            Self::Nutanix => "nutanix",
+            Self::KVM => "kvm",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// Parses the code of a built-in provider (e.g. `aws`), or one of its common aliases (e.g. `amazon` or `ec2`),
+    /// ignoring case and surrounding whitespace.
+    ///
+    /// Returns `None` for unrecognized names, including those of user-defined providers, and for `unknown`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::ProviderId;
+    ///
+    /// assert_eq!(ProviderId::parse_lenient("AWS"), Some(ProviderId::AWS));
+    /// assert_eq!(ProviderId::parse_lenient("ec2"), Some(ProviderId::AWS));
+    /// assert_eq!(ProviderId::parse_lenient(" Google "), Some(ProviderId::GCP));
+    /// assert_eq!(
+    ///     ProviderId::parse_lenient("Linode"),
+    ///     Some(ProviderId::Akamai)
+    /// );
+    /// assert_eq!(ProviderId::parse_lenient("acme"), None);
+    /// ```
+    pub fn parse_lenient(name: &str) -> Option<Self> {
+        let name = name.trim();
+
+        BUILT_IN
+            .iter()
+            .map(|&provider| (provider.short_code(), provider))
+            .chain(ALIASES.iter().copied())
+            .find(|(code, _)| code.eq_ignore_ascii_case(name))
+            .map(|(_, provider)| provider)
+    }
+
+    /// Returns the code of the provider in the given naming, which only differs from [ProviderId::short_code] for
+    /// renamed providers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{Naming, ProviderId};
+    ///
+    /// assert_eq!(ProviderId::Akamai.code(Naming::Current), "akamai");
+    /// assert_eq!(ProviderId::Akamai.code(Naming::Legacy), "linode");
+    /// assert_eq!(ProviderId::AWS.code(Naming::Legacy), "aws");
+    /// ```
+    pub fn code(&self, naming: Naming) -> &'static str {
+        match (self, naming) {
+            (Self::Akamai, Naming::Legacy) => "linode",
+            _ => self.short_code(),
+        }
+    }
+
+    /// Returns the human-readable (English) name of the provider (e.g. `Amazon Web Services`), for displaying in user
+    /// interfaces.
+    ///
+    /// User-defined providers are named by their code.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::ProviderId;
+    ///
+    /// assert_eq!(ProviderId::AWS.display_name(), "Amazon Web Services");
+    /// assert_eq!(ProviderId::Custom("acme").display_name(), "acme");
+    /// ```
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            Self::Unknown => "Unknown",
+            Self::Akamai => "Akamai Cloud",
+            Self::Alibaba => "Alibaba Cloud",
+            Self::AWS => "Amazon Web Services",
+            Self::Azure => "Microsoft Azure",
+            Self::DigitalOcean => "DigitalOcean",
+            Self::GCP => "Google Cloud Platform",
+            Self::OCI => "Oracle Cloud Infrastructure",
+            Self::OpenStack => "OpenStack",
+            Self::Vultr => "Vultr",
+            Self::Naver => "Naver Cloud Platform",
+            Self::Nhn => "NHN Cloud",
+            Self::VkCloud => "VK Cloud",
+            Self::Yandex => "Yandex Cloud",
+            Self::Civo => "Civo",
+            Self::HuaweiCloud => "Huawei Cloud",
+            Self::OpenTelekom => "Open Telekom Cloud",
+            Self::SapCCloud => "SAP Converged Cloud",
+            Self::VMware => "VMware",
+            Self::Proxmox => "Proxmox Virtual Environment",
+            Self::Nutanix => "Nutanix AHV",
+            Self::KVM => "KVM",
+            Self::Custom(name) => name,
+        }
+    }
+}
+
+/// Represents the region, and availability zone where available, of a host.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Region {
+    /// Provider the region belongs to.
+    pub provider: ProviderId,
+    /// Provider-specific name of the region (e.g. `us-east-1`).
+    pub region: String,
+    /// Provider-specific name of the availability zone (e.g. `us-east-1a`), if any.
+    pub zone: Option<String>,
+    /// Provider-specific partition the region belongs to (e.g. `aws-us-gov`), if any. Regions of different partitions
+    /// are isolated from each other and use different service endpoints.
+    pub partition: Option<String>,
+}
+
+/// Represents the account a host belongs to, for attributing it to a tenant.
+///
+/// The account is the provider's unit of ownership and billing:
+///
+/// * Alibaba Cloud: owner account ID.
+/// * AWS: account ID.
+/// * Azure: subscription ID.
+/// * GCP: project ID.
+/// * OCI: compartment OCID.
+/// * OpenStack: project ID.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct AccountInfo {
+    /// Provider the account belongs to.
+    pub provider: ProviderId,
+    /// Provider-specific identifier of the account.
+    pub account_id: String,
+}
+
+/// Represents the network configuration of the primary network interface of a host.
+///
+/// Every field is optional, as not every provider exposes all of them through its metadata server.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct NetworkInfo {
+    /// Provider the network belongs to.
+    pub provider: ProviderId,
+    /// Local (private) IPv4 address.
+    pub local_ipv4: Option<Ipv4Addr>,
+    /// Local IPv6 address.
+    pub local_ipv6: Option<Ipv6Addr>,
+    /// Public IPv4 address, if the host has one.
+    pub public_ipv4: Option<Ipv4Addr>,
+    /// MAC address, in lowercase colon-separated form (e.g. `0e:1f:2a:3b:4c:5d`).
+    pub mac: Option<String>,
+    /// Provider-specific identifier of the virtual network (e.g. the AWS VPC or GCP network).
+    pub network_id: Option<String>,
+    /// Provider-specific identifier of the subnet (e.g. the AWS subnet or Alibaba Cloud vSwitch).
+    pub subnet_id: Option<String>,
+}
+
+/// Represents the cloud credentials a host's metadata server exposes, without any of the secrets themselves.
+///
+/// Only the endpoints listing the identities that credentials are available for are queried, never the endpoints
+/// serving the credentials.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub struct CredentialsInfo {
+    /// Provider the credentials belong to.
+    pub provider: ProviderId,
+    /// Whether the metadata server exposes credentials.
+    pub exposed: bool,
+    /// Identities credentials are exposed for (e.g. AWS IAM role names or GCP service account emails), where the
+    /// provider lists them.
+    pub identities: Vec<String>,
+}
+
+impl Region {
+    /// Creates a region of a provider, without an availability zone or partition.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{ProviderId, Region};
+    ///
+    /// let region = Region::new(ProviderId::AWS, "us-east-1").zone("us-east-1a");
+    /// assert_eq!(region.zone.as_deref(), Some("us-east-1a"));
+    /// ```
+    pub fn new(provider: ProviderId, region: &str) -> Self {
+        Self {
+            provider,
+            region: region.to_string(),
+            zone: None,
+            partition: None,
+        }
+    }
+
+    /// Sets the availability zone of the region.
+    pub fn zone(mut self, zone: &str) -> Self {
+        self.zone = Some(zone.to_string());
+        self
+    }
+
+    /// Sets the partition the region belongs to.
+    pub fn partition(mut self, partition: &str) -> Self {
+        self.partition = Some(partition.to_string());
+        self
+    }
+}
+
+impl AccountInfo {
+    /// Creates the account of a provider.
+    pub fn new(provider: ProviderId, account_id: &str) -> Self {
+        Self {
+            provider,
+            account_id: account_id.to_string(),
+        }
+    }
+}
+
+impl NetworkInfo {
+    /// Creates the network configuration of a provider, without any addresses or identifiers.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use core::net::Ipv4Addr;
+    ///
+    /// use cloud_detect::{NetworkInfo, ProviderId};
+    ///
+    /// let network = NetworkInfo::new(ProviderId::GCP)
+    ///     .local_ipv4(Ipv4Addr::new(10, 128, 0, 2))
+    ///     .network_id("default");
+    /// assert!(network.public_ipv4.is_none());
+    /// ```
+    pub fn new(provider: ProviderId) -> Self {
+        Self {
+            provider,
+            ..Self::default()
+        }
+    }
+
+    /// Sets the local IPv4 address.
+    pub fn local_ipv4(mut self, addr: Ipv4Addr) -> Self {
+        self.local_ipv4 = Some(addr);
+        self
+    }
+
+    /// Sets the local IPv6 address.
+    pub fn local_ipv6(mut self, addr: Ipv6Addr) -> Self {
+        self.local_ipv6 = Some(addr);
+        self
+    }
+
+    /// Sets the public IPv4 address.
+    pub fn public_ipv4(mut self, addr: Ipv4Addr) -> Self {
+        self.public_ipv4 = Some(addr);
+        self
+    }
+
+    /// Sets the MAC address, which is kept as given.
+    pub fn mac(mut self, mac: &str) -> Self {
+        self.mac = Some(mac.to_string());
+        self
+    }
+
+    /// Sets the identifier of the virtual network.
+    pub fn network_id(mut self, network_id: &str) -> Self {
+        self.network_id = Some(network_id.to_string());
+        self
+    }
+
+    /// Sets the identifier of the subnet.
+    pub fn subnet_id(mut self, subnet_id: &str) -> Self {
+        self.subnet_id = Some(subnet_id.to_string());
+        self
+    }
+}
+
+impl CredentialsInfo {
+    /// Creates the credentials a provider's metadata server exposes, or not, without any identities.
+    pub fn new(provider: ProviderId, exposed: bool) -> Self {
+        Self {
+            provider,
+            exposed,
+            identities: Vec::new(),
+        }
+    }
+
+    /// Adds an identity credentials are exposed for.
+    pub fn identity(mut self, identity: &str) -> Self {
+        self.identities.push(identity.to_string());
+        self
+    }
+}
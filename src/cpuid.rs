@@ -0,0 +1,82 @@
+//! Hypervisor identification through CPUID.
+//!
+//! x86 guests are told they run under a hypervisor by bit 31 of ECX in leaf `0x1`, and hypervisors identify themselves
+//! with a 12-byte vendor signature in leaf `0x40000000` (e.g. `VMwareVMware`). Unlike DMI attributes, the signature
+//! can't be changed from the guest's configuration.
+
+/// Leaf reporting the processor features, including whether a hypervisor is present.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const FEATURES_LEAF: u32 = 0x1;
+/// Bit of ECX set in [FEATURES_LEAF] when running under a hypervisor.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const HYPERVISOR_BIT: u32 = 1 << 31;
+/// Leaf reporting the vendor signature of the hypervisor.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+const HYPERVISOR_LEAF: u32 = 0x4000_0000;
+
+/// Returns the vendor signature of the hypervisor the host runs under, if any.
+///
+/// Always `None` on architectures other than x86.
+#[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+pub(crate) fn hypervisor_vendor() -> Option<String> {
+    #[cfg(target_arch = "x86")]
+    use std::arch::x86::__cpuid;
+    #[cfg(target_arch = "x86_64")]
+    use std::arch::x86_64::__cpuid;
+
+    // CPUID is always available on x86_64, and on any x86 processor able to run the standard library. It only became
+    // safe to call in recent Rust versions.
+    #[allow(unused_unsafe)]
+    let features = unsafe { __cpuid(FEATURES_LEAF) };
+
+    if features.ecx & HYPERVISOR_BIT == 0 {
+        return None;
+    }
+
+    #[allow(unused_unsafe)]
+    let leaf = unsafe { __cpuid(HYPERVISOR_LEAF) };
+
+    signature(leaf.ebx, leaf.ecx, leaf.edx)
+}
+
+#[cfg(not(any(target_arch = "x86", target_arch = "x86_64")))]
+pub(crate) fn hypervisor_vendor() -> Option<String> {
+    None
+}
+
+/// Decodes a vendor signature from the registers it's spread across, dropping any NUL padding.
+#[cfg_attr(
+    not(any(target_arch = "x86", target_arch = "x86_64")),
+    allow(dead_code)
+)]
+fn signature(ebx: u32, ecx: u32, edx: u32) -> Option<String> {
+    let bytes: Vec<u8> = [ebx, ecx, edx]
+        .iter()
+        .flat_map(|register| register.to_le_bytes())
+        .collect();
+    let signature = String::from_utf8_lossy(&bytes)
+        .trim_end_matches('\0')
+        .to_string();
+
+    Some(signature).filter(|signature| !signature.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signature() {
+        // "VMwareVMware"
+        assert_eq!(
+            signature(0x6177_4d56, 0x4d56_6572, 0x6572_6177).as_deref(),
+            Some("VMwareVMware")
+        );
+        // "KVMKVMKVM\0\0\0"
+        assert_eq!(
+            signature(0x4b4d_564b, 0x564b_4d56, 0x0000_004d).as_deref(),
+            Some("KVMKVMKVM")
+        );
+        assert_eq!(signature(0, 0, 0), None);
+    }
+}
@@ -0,0 +1,441 @@
+//! User-defined providers.
+//!
+//! Private clouds can usually be told apart by a string in a vendor file (e.g. "if the chassis asset tag contains
+//! `ACME` then it's Acme Cloud"), or by a value served by an internal metadata endpoint. A [CustomProvider] describes
+//! such rules as data, so they can be built at runtime (or loaded from a configuration file with the `config` feature)
+//! instead of implemented in Rust. Custom providers are detected as [ProviderId::Custom].
+
+use std::sync::Mutex;
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::checks::{self, Check, Request};
+use crate::{supported_providers, Confidence, Detector, Provider, ProviderId};
+
+/// Custom providers registered so far, shared by every detector.
+///
+/// Provider identifiers are `Copy`, so the name of each custom provider lives for the rest of the program. Registering
+/// the same provider again (e.g. when a detector is created from the same configuration periodically) reuses the
+/// existing registration.
+static REGISTRY: Mutex<Vec<&'static Registered>> = Mutex::new(Vec::new());
+
+/// Represents a provider defined by matching rules instead of code.
+///
+/// The provider matches if any file matching one of its globs contains one of the expected strings (unverified), or
+/// if the value at the JSON pointer in the response of its endpoint is the expected one (verified). Like OpenStack, a
+/// private cloud may run on top of another platform, and custom providers win over generic platforms.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::{CustomProvider, Detector};
+///
+/// #[tokio::main]
+/// async fn main() -> anyhow::Result<()> {
+///     let acme = CustomProvider::new("acme")
+///         .file("/sys/class/dmi/id/chassis_asset_tag", &["ACME"])
+///         .endpoint("http://169.254.169.254/acme/v1/instance", "/cloud", "acme");
+///
+///     let provider = Detector::new()
+///         .timeout(1)
+///         .custom_provider(acme)?
+///         .detect()
+///         .await;
+///     println!("Detected provider: {}", provider);
+///
+///     Ok(())
+/// }
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CustomProvider {
+    /// Name of the provider, as displayed by [ProviderId::Custom].
+    pub name: String,
+    /// Files identifying the provider.
+    #[serde(default)]
+    pub files: Vec<FileMatch>,
+    /// Metadata endpoint identifying the provider, if any.
+    #[serde(default)]
+    pub endpoint: Option<EndpointMatch>,
+}
+
+/// Represents files identifying a custom provider.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileMatch {
+    /// Glob pattern of the files (e.g. `/sys/class/dmi/id/*_vendor`).
+    pub glob: String,
+    /// Strings any of which the contents of a file must contain.
+    pub contains: Vec<String>,
+}
+
+/// Represents a metadata endpoint identifying a custom provider.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct EndpointMatch {
+    /// URL of the endpoint, which must serve JSON.
+    pub url: String,
+    /// JSON pointer to the value to compare (e.g. `/cloud/name`), as per RFC 6901.
+    pub pointer: String,
+    /// Expected value. Strings are compared without their quotes, other values by their JSON representation.
+    pub expected: String,
+}
+
+impl CustomProvider {
+    /// Creates a provider without any rules.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            files: Vec::new(),
+            endpoint: None,
+        }
+    }
+
+    /// Identifies the provider by files matching a glob pattern that contain any of the given strings.
+    pub fn file(mut self, glob: &str, contains: &[&str]) -> Self {
+        self.files.push(FileMatch {
+            glob: glob.to_string(),
+            contains: contains.iter().map(|s| s.to_string()).collect(),
+        });
+        self
+    }
+
+    /// Identifies the provider by the value at a JSON pointer in the response of a metadata endpoint.
+    pub fn endpoint(mut self, url: &str, pointer: &str, expected: &str) -> Self {
+        self.endpoint = Some(EndpointMatch {
+            url: url.to_string(),
+            pointer: pointer.to_string(),
+            expected: expected.to_string(),
+        });
+        self
+    }
+
+    /// Checks that the provider has a distinct name, and valid rules.
+    pub fn validate(&self) -> Result<()> {
+        let name = &self.name;
+
+        if name.trim().is_empty() {
+            bail!("Custom provider without a name");
+        }
+
+        if name == &ProviderId::Unknown.to_string()
+            || supported_providers().iter().any(|p| &p.to_string() == name)
+        {
+            bail!("Custom provider {name} shadows a built-in provider");
+        }
+
+        if self.files.is_empty() && self.endpoint.is_none() {
+            bail!("Custom provider {name} has no rules");
+        }
+
+        for file in &self.files {
+            glob::Pattern::new(&file.glob)
+                .with_context(|| format!("Invalid glob pattern for {name}: {}", file.glob))?;
+
+            if file.contains.is_empty() || file.contains.iter().any(|s| s.is_empty()) {
+                bail!(
+                    "Custom provider {name} has an empty string to match in {}",
+                    file.glob
+                );
+            }
+        }
+
+        if let Some(endpoint) = &self.endpoint {
+            split_url(&endpoint.url)?;
+
+            if !(endpoint.pointer.is_empty() || endpoint.pointer.starts_with('/')) {
+                bail!("Invalid JSON pointer for {name}: {}", endpoint.pointer);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Splits a URL into the base URI and the path of a request.
+fn split_url(url: &str) -> Result<(&str, &str)> {
+    let (scheme, rest) = url
+        .split_once("://")
+        .filter(|(scheme, _)| checks::supports_scheme(scheme))
+        .with_context(|| format!("Not an HTTP URL, or HTTPS without TLS support: {url}"))?;
+    let host_len = rest.find('/').unwrap_or(rest.len());
+
+    if host_len == 0 {
+        bail!("URL without a host: {url}");
+    }
+
+    let split = scheme.len() + "://".len() + host_len;
+    let path = if split == url.len() {
+        "/"
+    } else {
+        &url[split..]
+    };
+
+    Ok((&url[..split], path))
+}
+
+/// Represents a registered custom provider.
+#[derive(Debug)]
+pub(crate) struct Registered {
+    name: &'static str,
+    definition: CustomProvider,
+}
+
+/// Registers a custom provider, unless already registered.
+fn register(provider: CustomProvider) -> Result<&'static Registered> {
+    provider.validate()?;
+
+    let mut registry = REGISTRY
+        .lock()
+        .map_err(|_| anyhow::anyhow!("Custom provider registry poisoned"))?;
+
+    if let Some(registered) = registry.iter().find(|r| r.definition == provider) {
+        return Ok(registered);
+    }
+
+    let name = match registry.iter().find(|r| r.name == provider.name) {
+        Some(registered) => registered.name,
+        None => Box::leak(provider.name.clone().into_boxed_str()),
+    };
+    let registered: &'static Registered = Box::leak(Box::new(Registered {
+        name,
+        definition: provider,
+    }));
+    registry.push(registered);
+
+    Ok(registered)
+}
+
+impl Provider for Registered {
+    fn identifier(&self) -> ProviderId {
+        ProviderId::Custom(self.name)
+    }
+
+    fn checks(&self) -> Vec<Check> {
+        let mut checks: Vec<Check> = self
+            .definition
+            .files
+            .iter()
+            .map(|file| {
+                let contains = file.contains.clone();
+                Check::glob(
+                    "custom_file",
+                    Confidence::Unverified,
+                    &file.glob,
+                    move |content| contains.iter().any(|s| content.contains(s.as_str())),
+                )
+            })
+            .collect();
+
+        if let Some(endpoint) = &self.definition.endpoint {
+            if let Ok((base_uri, path)) = split_url(&endpoint.url) {
+                let (pointer, expected) = (endpoint.pointer.clone(), endpoint.expected.clone());
+
+                checks.push(Check::http(
+                    "custom_endpoint",
+                    Confidence::Verified,
+                    Request::get(base_uri, path),
+                    move |resp| {
+                        resp.is_success()
+                            && resp
+                                .json::<Value>()
+                                .and_then(|value| value.pointer(&pointer).cloned())
+                                .is_some_and(|value| match value {
+                                    Value::String(value) => value == expected,
+                                    value => serde_json::to_string(&value)
+                                        .is_ok_and(|value| value == expected),
+                                })
+                    },
+                ));
+            }
+        }
+
+        checks
+    }
+}
+
+impl Detector {
+    /// Adds a custom provider to the providers probed by the detector, replacing any custom provider of the same name.
+    ///
+    /// Fails if the provider is invalid (see [CustomProvider::validate]).
+    pub fn custom_provider(mut self, provider: CustomProvider) -> Result<Self> {
+        let registered = register(provider)?;
+
+        self.custom.retain(|r| r.name != registered.name);
+        self.custom.push(registered);
+
+        Ok(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use tempfile::TempDir;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::HostSnapshot;
+
+    /// Detects only the given custom provider.
+    async fn detect(provider: CustomProvider) -> Result<ProviderId> {
+        let detector = Detector::new()
+            .timeout(1)
+            .providers(&[])
+            .custom_provider(provider)?;
+
+        Ok(detector.detect().await)
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(CustomProvider::new("acme")
+            .file("/a", &["x"])
+            .validate()
+            .is_ok());
+        assert!(CustomProvider::new("acme").validate().is_err());
+        assert!(CustomProvider::new(" ")
+            .file("/a", &["x"])
+            .validate()
+            .is_err());
+        assert!(CustomProvider::new("aws")
+            .file("/a", &["x"])
+            .validate()
+            .is_err());
+        assert!(CustomProvider::new("unknown")
+            .file("/a", &["x"])
+            .validate()
+            .is_err());
+        assert!(CustomProvider::new("acme")
+            .file("/a[", &["x"])
+            .validate()
+            .is_err());
+        assert!(CustomProvider::new("acme")
+            .file("/a", &[])
+            .validate()
+            .is_err());
+        assert!(CustomProvider::new("acme")
+            .endpoint("169.254.169.254/acme", "/cloud", "acme")
+            .validate()
+            .is_err());
+        assert!(CustomProvider::new("acme")
+            .endpoint("http://169.254.169.254/acme", "cloud", "acme")
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn test_split_url() -> Result<()> {
+        assert_eq!(
+            split_url("http://169.254.169.254/acme/v1?x=1")?,
+            ("http://169.254.169.254", "/acme/v1?x=1")
+        );
+        if checks::TLS {
+            assert_eq!(
+                split_url("https://metadata.internal:8443")?,
+                ("https://metadata.internal:8443", "/")
+            );
+        } else {
+            assert!(split_url("https://metadata.internal:8443").is_err());
+        }
+        assert!(split_url("http:///acme").is_err());
+        assert!(split_url("ftp://host/acme").is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_register_reuses_registration() -> Result<()> {
+        let first = register(CustomProvider::new("reused").file("/a", &["x"]))?;
+        let second = register(CustomProvider::new("reused").file("/a", &["x"]))?;
+        let third = register(CustomProvider::new("reused").file("/b", &["x"]))?;
+
+        assert!(std::ptr::eq(first, second));
+        assert!(!std::ptr::eq(first, third));
+        assert!(std::ptr::eq(first.name, third.name));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_provider_descriptors() -> Result<()> {
+        let provider = CustomProvider::new("acme").endpoint("http://10.0.0.1/v1", "/cloud", "acme");
+        let detector = Detector::new().providers(&[]).custom_provider(provider)?;
+
+        let descriptors = detector.provider_descriptors();
+        assert_eq!(descriptors.len(), 1);
+
+        let acme = descriptors[0];
+        assert_eq!(acme.id, ProviderId::Custom("acme"));
+        assert_eq!(acme.source, crate::ProviderSource::Custom);
+        assert!(!acme.supports_local && acme.supports_network && !acme.requires_token);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_file() -> Result<()> {
+        let root = TempDir::new()?;
+        std::fs::File::create(root.path().join("asset_tag"))?.write_all(b"ACME-1234\n")?;
+
+        let glob = format!("{}/asset_*", root.path().display());
+        let provider = detect(CustomProvider::new("acme").file(&glob, &["ACME"])).await?;
+
+        assert_eq!(provider, ProviderId::Custom("acme"));
+        assert_eq!(provider.to_string(), "acme");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_endpoint() -> Result<()> {
+        let server = MockServer::start().await;
+        Mock::given(path("/acme/v1/instance"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{"cloud": {"name": "acme", "zone": 3}}"#),
+            )
+            .mount(&server)
+            .await;
+
+        let url = format!("{}/acme/v1/instance", server.uri());
+        assert_eq!(
+            detect(CustomProvider::new("acme").endpoint(&url, "/cloud/name", "acme")).await?,
+            ProviderId::Custom("acme")
+        );
+        assert_eq!(
+            detect(CustomProvider::new("zoned").endpoint(&url, "/cloud/zone", "3")).await?,
+            ProviderId::Custom("zoned")
+        );
+        assert_eq!(
+            detect(CustomProvider::new("other").endpoint(&url, "/cloud/name", "other")).await?,
+            ProviderId::Unknown
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_custom_over_generic() -> Result<()> {
+        let detector = Detector::new().custom_provider(
+            CustomProvider::new("acme").file("/sys/class/dmi/id/chassis_asset_tag", &["ACME"]),
+        )?;
+        let snapshot = HostSnapshot::new()
+            .file("/sys/class/dmi/id/product_name", "OpenStack Nova")
+            .file("/sys/class/dmi/id/chassis_asset_tag", "ACME");
+
+        assert_eq!(
+            detector.detect_snapshot(&snapshot),
+            ProviderId::Custom("acme")
+        );
+
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+//! Diagnostics.
+//!
+//! When detection returns [ProviderId::Unknown], the reason is usually buried in debug logs. [diagnose] runs every
+//! check of every provider to completion instead, and reports the outcome of each one, so it can be attached to a bug
+//! report.
+
+use std::fmt;
+#[cfg(feature = "async")]
+use std::path::Path;
+use std::time::Duration;
+
+#[cfg(feature = "async")]
+use tracing::{error, instrument};
+
+use crate::checks::{Check, Inspection};
+#[cfg(feature = "async")]
+use crate::clock::Instant;
+use crate::redact::redact;
+#[cfg(feature = "async")]
+use crate::{hints, host_root, spawn, timeout_or_default, Detector, PROVIDERS};
+use crate::{resolve, Confidence, Match, Outcome, ProviderId};
+
+/// Maximum length (characters) of the contents kept for each probe.
+const SNIPPET_LEN: usize = 256;
+
+/// Columns of the table of outcomes in a report.
+const COLUMNS: [&str; 5] = ["Provider", "Check", "Target", "Outcome", "Latency"];
+
+/// Represents the outcome of every check run to diagnose detection.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    /// Provider that would have been detected from the outcomes below.
+    pub provider: ProviderId,
+    /// Provider identified by cloud-init, if any.
+    pub cloudinit: Option<ProviderId>,
+    /// Providers hinted at by cheap local signals, strongest hint first.
+    pub hints: Vec<ProviderId>,
+    /// Outcome of each check, grouped by provider.
+    pub probes: Vec<ProbeOutcome>,
+}
+
+/// Represents the outcome of a single check.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct ProbeOutcome {
+    /// Provider the check belongs to.
+    pub provider: ProviderId,
+    /// Name of the check.
+    pub check: &'static str,
+    /// Redacted path or URL inspected by the check.
+    pub target: String,
+    /// Whether the check matched.
+    pub matched: bool,
+    /// What the check found, e.g. telling a metadata server that can't be reached from one that responded with
+    /// something else.
+    pub outcome: Outcome,
+    /// Status code of the response from the metadata server, if any.
+    pub status: Option<u16>,
+    /// Redacted error that prevented the check from completing, if any.
+    pub error: Option<String>,
+    /// Redacted and truncated contents of the inspected file or body of the response, if any.
+    pub snippet: Option<String>,
+    /// Time taken by the check.
+    pub elapsed: Duration,
+    pub(crate) confidence: Confidence,
+}
+
+impl Diagnostics {
+    /// Resolves the provider that would have been detected from the outcome of each check.
+    pub(crate) fn new(
+        cloudinit: Option<ProviderId>,
+        hints: Vec<ProviderId>,
+        probes: Vec<ProbeOutcome>,
+    ) -> Self {
+        let mut matches: Vec<Match> = probes
+            .iter()
+            .filter(|probe| probe.matched)
+            .map(|probe| Match::new(probe.provider, probe.confidence))
+            .collect();
+
+        if let Some(provider) = cloudinit {
+            matches.push(Match::new(provider, Confidence::Authoritative));
+        }
+
+        Self {
+            provider: resolve(&matches),
+            cloudinit,
+            hints,
+            probes,
+        }
+    }
+}
+
+impl ProbeOutcome {
+    pub(crate) fn new(
+        provider: ProviderId,
+        check: &Check,
+        inspection: Inspection,
+        elapsed: Duration,
+    ) -> Self {
+        let confidence = check.match_confidence(&inspection);
+
+        Self {
+            provider,
+            check: check.name,
+            target: redact(&check.probe.target()),
+            matched: inspection.matched(),
+            outcome: inspection.outcome,
+            status: inspection.status,
+            error: inspection.error.as_deref().map(redact),
+            snippet: inspection.content.as_deref().map(snippet),
+            elapsed,
+            confidence,
+        }
+    }
+}
+
+impl Diagnostics {
+    /// Formats the report as Markdown, with the outcome of each check in a table, e.g. to paste into a bug report.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::diagnose;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let diagnostics = diagnose(Some(1)).await;
+    ///     println!("{}", diagnostics.to_markdown());
+    /// }
+    /// ```
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+
+        for (label, value) in self.summary() {
+            markdown.push_str(&format!("**{label}:** {value}  \n"));
+        }
+
+        markdown.push('\n');
+        markdown.push_str(&format!("| {} |\n", COLUMNS.join(" | ")));
+        markdown.push_str(&format!("|{}\n", "---|".repeat(COLUMNS.len())));
+
+        for row in self.rows() {
+            let cells: Vec<String> = row.iter().map(|cell| cell.replace('|', "\\|")).collect();
+            markdown.push_str(&format!("| {} |\n", cells.join(" | ")));
+        }
+
+        markdown
+    }
+
+    /// Returns the lines summarizing the report, as labels and values.
+    fn summary(&self) -> [(&'static str, String); 3] {
+        let hints: Vec<String> = self.hints.iter().map(ToString::to_string).collect();
+
+        [
+            ("Detected provider", self.provider.to_string()),
+            (
+                "cloud-init",
+                self.cloudinit
+                    .map_or_else(|| "none".to_string(), |provider| provider.to_string()),
+            ),
+            (
+                "Prior hints",
+                if hints.is_empty() {
+                    "none".to_string()
+                } else {
+                    hints.join(", ")
+                },
+            ),
+        ]
+    }
+
+    /// Returns the cells of the table of outcomes, one row per check (see [COLUMNS]).
+    fn rows(&self) -> Vec<[String; 5]> {
+        self.probes
+            .iter()
+            .map(|probe| {
+                [
+                    probe.provider.to_string(),
+                    probe.check.to_string(),
+                    probe.target.clone(),
+                    probe.outcome(),
+                    format!("{}ms", probe.elapsed.as_millis()),
+                ]
+            })
+            .collect()
+    }
+}
+
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (label, value) in self.summary() {
+            writeln!(f, "{label}: {value}")?;
+        }
+
+        let rows = self.rows();
+        let widths: Vec<usize> = (0..COLUMNS.len())
+            .map(|i| {
+                rows.iter()
+                    .map(|row| row[i].chars().count())
+                    .chain([COLUMNS[i].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+        let line = |f: &mut fmt::Formatter<'_>, cells: &[&str]| {
+            let cells: Vec<String> = cells
+                .iter()
+                .zip(&widths)
+                .map(|(cell, &width)| format!("{cell:width$}"))
+                .collect();
+            writeln!(f, "{}", cells.join("  ").trim_end())
+        };
+
+        writeln!(f)?;
+        line(f, &COLUMNS)?;
+
+        for row in &rows {
+            line(f, &row.each_ref().map(String::as_str))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl ProbeOutcome {
+    /// Summarizes the outcome of the check on a single line, for the table of a report.
+    fn outcome(&self) -> String {
+        if self.matched {
+            return format!("match ({})", confidence_name(self.confidence));
+        }
+
+        let summary = match self.outcome {
+            Outcome::AuthRequired => "auth required",
+            Outcome::Rejected => "rejected",
+            Outcome::Absent => "absent",
+            Outcome::Unreachable => "unreachable",
+            _ if self.error.is_some() => "error",
+            _ => "no match",
+        };
+
+        match (&self.error, self.status) {
+            (Some(error), _) => {
+                format!("{summary}: {}", error.lines().collect::<Vec<_>>().join(" "))
+            }
+            (None, Some(status)) => format!("{summary} ({status})"),
+            (None, None) => summary.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ProbeOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "  [{}] {}/{}: {} ({:?})",
+            if self.matched { "x" } else { " " },
+            self.provider,
+            self.check,
+            self.target,
+            self.elapsed
+        )?;
+
+        if let Some(status) = self.status {
+            writeln!(f, "      status: {status}")?;
+        }
+
+        if let Some(error) = &self.error {
+            writeln!(f, "      error: {error}")?;
+        }
+
+        if let Some(snippet) = &self.snippet {
+            writeln!(f, "      content: {snippet:?}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Name of a confidence level in a report.
+fn confidence_name(confidence: Confidence) -> &'static str {
+    match confidence {
+        Confidence::Unverified => "unverified",
+        Confidence::Verified => "verified",
+        Confidence::Authoritative => "authoritative",
+    }
+}
+
+/// Redacts and truncates the contents of a file or the body of a response.
+fn snippet(content: &str) -> String {
+    let content = redact(content.trim());
+
+    match content.char_indices().nth(SNIPPET_LEN) {
+        Some((end, _)) => format!("{}...", &content[..end]),
+        None => content,
+    }
+}
+
+/// Runs every check of every provider to completion, reporting the outcome of each one.
+///
+/// Unlike [detect](crate::detect()), this does not stop at the first match, so it takes as long as the slowest
+/// provider. Addresses, identifiers and tokens are redacted unless disabled with [set_redaction](crate::set_redaction),
+/// so the report can be attached to bug reports.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::diagnose;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let diagnostics = diagnose(Some(1)).await;
+///     println!("{}", diagnostics);
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn diagnose(timeout: Option<u64>) -> Diagnostics {
+    let timeout = timeout_or_default(timeout);
+    let root = Path::new(host_root::ROOT);
+    let cloudinit = spawn::blocking(|| hints::cloudinit::identify(root));
+    let hints = spawn::blocking(|| hints::prior::identify(PROVIDERS, root));
+
+    let handles: Vec<_> = PROVIDERS
+        .iter()
+        .map(|&provider| {
+            let name = spawn::name("diagnose", provider.identifier());
+
+            spawn::detached(&name, async move {
+                let mut probes = Vec::new();
+
+                for check in Detector::new().checks(provider) {
+                    let start = Instant::now();
+                    let inspection = check.inspect(timeout).await;
+                    probes.push(ProbeOutcome::new(
+                        provider.identifier(),
+                        &check,
+                        inspection,
+                        start.elapsed(),
+                    ));
+                }
+
+                probes
+            })
+        })
+        .collect();
+
+    let mut probes = Vec::new();
+
+    for handle in handles {
+        match handle.await {
+            Ok(outcomes) => probes.extend(outcomes),
+            Err(err) => error!("Error diagnosing provider: {:?}", err),
+        }
+    }
+
+    Diagnostics::new(
+        cloudinit.await.ok().flatten(),
+        hints.await.unwrap_or_default(),
+        probes,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn probe(provider: ProviderId, matched: bool, confidence: Confidence) -> ProbeOutcome {
+        ProbeOutcome {
+            provider,
+            check: "check",
+            target: "/path".to_string(),
+            matched,
+            outcome: if matched {
+                Outcome::Matched
+            } else {
+                Outcome::Mismatched
+            },
+            status: None,
+            error: None,
+            snippet: None,
+            elapsed: Duration::ZERO,
+            confidence,
+        }
+    }
+
+    #[test]
+    fn test_snippet() {
+        assert_eq!(snippet("  Google\n"), "Google");
+
+        let long = "a ".repeat(SNIPPET_LEN);
+        assert_eq!(snippet(&long).chars().count(), SNIPPET_LEN + 3);
+        assert!(snippet(&long).ends_with("..."));
+    }
+
+    #[test]
+    fn test_diagnostics_resolves_matches() {
+        let diagnostics = Diagnostics::new(
+            None,
+            Vec::new(),
+            vec![
+                probe(ProviderId::OpenStack, true, Confidence::Verified),
+                probe(ProviderId::AWS, false, Confidence::Verified),
+                probe(ProviderId::Vultr, true, Confidence::Unverified),
+            ],
+        );
+        assert_eq!(diagnostics.provider, ProviderId::Vultr);
+
+        let diagnostics = Diagnostics::new(Some(ProviderId::GCP), Vec::new(), Vec::new());
+        assert_eq!(diagnostics.provider, ProviderId::GCP);
+
+        let diagnostics = Diagnostics::new(None, Vec::new(), Vec::new());
+        assert_eq!(diagnostics.provider, ProviderId::Unknown);
+    }
+
+    #[test]
+    fn test_diagnostics_display() {
+        let mut failed = probe(ProviderId::AWS, false, Confidence::Verified);
+        failed.status = Some(404);
+        failed.snippet = Some("Not Found".to_string());
+
+        let diagnostics = Diagnostics::new(
+            None,
+            vec![ProviderId::AWS],
+            vec![probe(ProviderId::GCP, true, Confidence::Unverified), failed],
+        );
+        let report = diagnostics.to_string();
+
+        assert!(report.starts_with("Detected provider: gcp\ncloud-init: none\nPrior hints: aws\n"));
+        assert!(report.contains("\nProvider  Check  Target  Outcome             Latency\n"));
+        assert!(report.contains("\ngcp       check  /path   match (unverified)  0ms\n"));
+        assert!(report.contains("\naws       check  /path   no match (404)      0ms\n"));
+    }
+
+    #[test]
+    fn test_probe_outcome_summary() {
+        let mut denied = probe(ProviderId::Azure, false, Confidence::Verified);
+        denied.outcome = Outcome::AuthRequired;
+        denied.status = Some(401);
+        assert_eq!(denied.outcome(), "auth required (401)");
+
+        let mut unreachable = probe(ProviderId::OCI, false, Confidence::Verified);
+        unreachable.outcome = Outcome::Unreachable;
+        unreachable.error = Some("connection refused".to_string());
+        assert_eq!(unreachable.outcome(), "unreachable: connection refused");
+
+        let mut absent = probe(ProviderId::AWS, false, Confidence::Verified);
+        absent.outcome = Outcome::Absent;
+        assert_eq!(absent.outcome(), "absent");
+    }
+
+    #[test]
+    fn test_diagnostics_to_markdown() {
+        let mut failed = probe(ProviderId::AWS, false, Confidence::Verified);
+        failed.error = Some("connection refused |\nretrying".to_string());
+
+        let diagnostics = Diagnostics::new(None, Vec::new(), vec![failed]);
+        let markdown = diagnostics.to_markdown();
+
+        assert!(markdown.starts_with("**Detected provider:** unknown  \n"));
+        assert!(markdown.contains(
+            "\n| Provider | Check | Target | Outcome | Latency |\n|---|---|---|---|---|\n"
+        ));
+        assert!(markdown
+            .ends_with("| aws | check | /path | error: connection refused \\| retrying | 0ms |\n"));
+    }
+}
@@ -0,0 +1,2535 @@
+//! Probing engine.
+//!
+//! Runs the checks of every provider, resolves the matches and fetches metadata from the detected provider's metadata
+//! server.
+
+use std::fmt::{self, Debug};
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use serde_json::Value;
+#[cfg(feature = "async")]
+use tokio::sync::mpsc;
+#[cfg(feature = "async")]
+use tokio::sync::mpsc::Sender;
+#[cfg(feature = "async")]
+use tokio::sync::Semaphore;
+#[cfg(feature = "async")]
+use tokio::task::JoinSet;
+use tracing::{debug, info};
+#[cfg(feature = "async")]
+use tracing::{error, instrument};
+
+use crate::background::Background;
+use crate::bind::{self, Binding};
+use crate::budget::{Budget, DEFAULT_LOCAL_TIMEOUT};
+use crate::checks::{Check, Fetch, Probe};
+#[cfg(feature = "async")]
+use crate::clock::{self, Instant};
+#[cfg(feature = "config")]
+use crate::config::Config;
+use crate::core::*;
+use crate::custom::Registered;
+use crate::platform;
+use crate::precheck;
+#[cfg(feature = "async")]
+use crate::precheck::Precheck;
+#[cfg(feature = "async")]
+use crate::provider_check;
+use crate::provider_check::ProviderCheck;
+use crate::providers::*;
+#[cfg(feature = "async")]
+use crate::redact::Redacted;
+#[cfg(feature = "simulation")]
+use crate::simulation::SimulatedHost;
+use crate::smbios::SmbiosData;
+#[cfg(feature = "async")]
+use crate::spawn;
+#[cfg(feature = "test_support")]
+use crate::test_support::{self, FakeProvider, FakeTransport};
+use crate::{hints, host_root, opt_out, ProbeLogLevel};
+
+/// Maximum time allowed for detection.
+pub const DEFAULT_DETECTION_TIMEOUT: u64 = 5; // seconds
+
+/// Time to keep collecting matches after the first match of a specific provider arrives, so that simultaneous matches
+/// are resolved deterministically instead of by whichever task won the race. Matches of generic platforms (see
+/// [ProviderId::genericity]) neither open the window nor cut it short.
+pub(crate) const RESOLUTION_WINDOW: Duration = Duration::from_millis(100);
+
+/// Time allowed for connecting to the shared metadata server when checking whether the host runs on a cloud, unless a
+/// pre-check timeout is set (see [Detector::is_cloud]).
+pub(crate) const SHARED_PRECHECK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Converts an optional timeout (seconds) to a duration, defaulting to [DEFAULT_DETECTION_TIMEOUT].
+pub(crate) fn timeout_or_default(timeout: Option<u64>) -> Duration {
+    Duration::from_secs(timeout.unwrap_or(DEFAULT_DETECTION_TIMEOUT))
+}
+
+impl ProviderId {
+    /// How generic the provider is, as a platform that other, more specific providers may be built on: `0` for specific
+    /// providers, `1` for generic platforms and `2` for bare KVM, which most other platforms are themselves built on.
+    ///
+    /// Private virtualization platforms are generic too, as public clouds run on the same hypervisors.
+    pub(crate) fn genericity(&self) -> u8 {
+        match self {
+            ProviderId::KVM => 2,
+            ProviderId::OpenStack => 1,
+            _ if self.group() == Some(ProviderGroup::Virtualization) => 1,
+            _ => 0,
+        }
+    }
+}
+
+/// Normalizes a MAC address to lowercase colon-separated form, whatever separators (if any) it uses.
+pub(crate) fn normalize_mac(mac: &str) -> Option<String> {
+    let digits: Vec<char> = mac
+        .chars()
+        .filter(|c| !matches!(c, ':' | '-' | '.'))
+        .map(|c| c.to_ascii_lowercase())
+        .collect();
+
+    if digits.len() != 12 || !digits.iter().all(char::is_ascii_hexdigit) {
+        return None;
+    }
+
+    Some(
+        digits
+            .chunks(2)
+            .map(|pair| pair.iter().collect::<String>())
+            .collect::<Vec<_>>()
+            .join(":"),
+    )
+}
+
+/// Represents how strongly a match identifies a provider.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Confidence {
+    /// Matched on a heuristic, such as a vendor string or a bare response from a metadata endpoint.
+    Unverified,
+    /// Matched on provider-specific content returned by the metadata server.
+    Verified,
+    /// Named directly by tooling that already identified the provider, such as cloud-init.
+    Authoritative,
+}
+
+/// Represents what a check found, rather than only whether it matched: a metadata server that can't be reached tells
+/// a different story than one that responded with something else, or one that requires authentication.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum Outcome {
+    /// The file or response matched what the provider sets or serves.
+    Matched,
+    /// The file was read or the metadata server responded, but with something else.
+    Mismatched,
+    /// The metadata server denied access (`401` or `403`). Checks of metadata servers that only allow requests with
+    /// credentials (e.g. a session token) match on it, as it proves the provider is present.
+    AuthRequired,
+    /// The response was rejected as not served by a metadata server (e.g. the login page of a captive portal).
+    Rejected,
+    /// There was nothing to inspect, such as a missing file or an unset environment variable.
+    #[default]
+    Absent,
+    /// The metadata server couldn't be reached (e.g. the connection was refused or timed out).
+    Unreachable,
+    /// The check failed with an error, such as a file that exists but can't be read.
+    Failed,
+}
+
+/// Represents a provider that matched during detection.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) struct Match {
+    pub(crate) provider: ProviderId,
+    pub(crate) confidence: Confidence,
+    /// What matched: the name of a check, or a host signal such as `cloud-init`.
+    pub(crate) source: &'static str,
+    /// Outcome of the check that matched, [Outcome::Matched] for host signals.
+    pub(crate) outcome: Outcome,
+}
+
+impl Match {
+    pub(crate) fn new(provider: ProviderId, confidence: Confidence) -> Self {
+        Self {
+            provider,
+            confidence,
+            source: "",
+            outcome: Outcome::Matched,
+        }
+    }
+
+    /// Names what matched.
+    pub(crate) fn source(mut self, source: &'static str) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Sets the outcome of the check that matched.
+    pub(crate) fn outcome(mut self, outcome: Outcome) -> Self {
+        self.outcome = outcome;
+        self
+    }
+}
+
+/// Represents the outcome of detection, along with the evidence it was resolved from.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Detection {
+    /// Detected provider, or [ProviderId::Unknown] if detection failed or timed out.
+    pub provider: ProviderId,
+    /// Platform the host runs on within the detected provider (e.g. [Platform::Lambda]), if the provider has any.
+    pub platform: Option<Platform>,
+    /// White-label cloud the host runs on, if the detected provider is resold under a brand of its own (e.g.
+    /// [ProviderId::HuaweiCloud], built on [ProviderId::OpenStack]). The detected provider remains the underlying
+    /// platform, which checks and metadata are specific to.
+    pub reseller: Option<ProviderId>,
+    /// Every match collected before resolving the provider, in the order they arrived, including matches of providers
+    /// that lost (e.g. OpenStack under a public cloud built on it).
+    pub evidence: Vec<Evidence>,
+    /// Checks that never ran, or never completed, because a deadline passed. If many were skipped, raising the
+    /// timeouts (see [Detector::timeout] and [Detector::local_timeout]) may let detection succeed.
+    pub skipped: Vec<SkippedProbe>,
+}
+
+impl Detection {
+    /// Resolves the provider from the matches collected during detection.
+    pub(crate) fn new(provider: ProviderId, matches: &[Match]) -> Self {
+        Self {
+            provider,
+            platform: platform::classify(provider),
+            reseller: None,
+            evidence: matches.iter().map(Evidence::from).collect(),
+            skipped: Vec::new(),
+        }
+    }
+
+    /// Records the platform of the node of a managed Kubernetes cluster the host is, which refines the platform
+    /// classified from environment variables.
+    pub(crate) fn node(mut self, platform: Option<Platform>) -> Self {
+        self.platform = platform.or(self.platform);
+        self
+    }
+
+    /// Records the white-label cloud the host runs on.
+    pub(crate) fn reseller(mut self, reseller: Option<ProviderId>) -> Self {
+        self.reseller = reseller;
+        self
+    }
+
+    /// Records the checks skipped by the detection.
+    pub(crate) fn skipped(mut self, skipped: Vec<SkippedProbe>) -> Self {
+        self.skipped = skipped;
+        self
+    }
+
+    /// Returns the confidence of the strongest evidence for the detected provider, or `None` if no provider was
+    /// detected.
+    pub fn confidence(&self) -> Option<Confidence> {
+        self.evidence
+            .iter()
+            .filter(|evidence| evidence.provider == self.provider)
+            .map(|evidence| evidence.confidence)
+            .max()
+    }
+}
+
+/// Represents the outcome of checking whether the host runs on a given provider (see [Detector::is_provider]).
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct ProviderVerdict {
+    /// Provider checked for.
+    pub provider: ProviderId,
+    /// Whether the host runs on the provider.
+    pub matched: bool,
+    /// Every match collected, including matches of the providers that may prove the provider's own wrong (e.g.
+    /// OpenStack for AWS).
+    pub evidence: Vec<Evidence>,
+}
+
+impl ProviderVerdict {
+    pub(crate) fn new(provider: ProviderId, detection: Detection) -> Self {
+        Self {
+            provider,
+            matched: detection.provider == provider,
+            evidence: detection.evidence,
+        }
+    }
+}
+
+/// Represents a match collected during detection.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct Evidence {
+    /// Provider that matched.
+    pub provider: ProviderId,
+    /// What matched: the name of a check of the provider (e.g. `metadata_server`), or `cloud-init`, `dns` or `kubelet`
+    /// for host signals.
+    pub source: &'static str,
+    /// How strongly the match identifies the provider, after corroboration by auxiliary signals.
+    pub confidence: Confidence,
+    /// What the check found: [Outcome::Matched], or [Outcome::AuthRequired] for a metadata server proving its presence
+    /// by denying access.
+    pub outcome: Outcome,
+}
+
+impl From<&Match> for Evidence {
+    fn from(m: &Match) -> Self {
+        Self {
+            provider: m.provider,
+            source: m.source,
+            confidence: m.confidence,
+            outcome: m.outcome,
+        }
+    }
+}
+
+/// Represents a check skipped during detection because a deadline passed.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct SkippedProbe {
+    /// Provider the check belongs to.
+    pub provider: ProviderId,
+    /// Name of the check (e.g. `metadata_server`).
+    pub check: &'static str,
+    /// Deadline that passed.
+    pub reason: SkipReason,
+}
+
+/// Represents the deadline that caused a check to be skipped.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum SkipReason {
+    /// The budget of the local checks of the provider ran out (see [Detector::local_timeout]).
+    LocalTimeout,
+    /// Detection timed out before the check ran or completed (see [Detector::timeout]).
+    Timeout,
+}
+
+impl SkippedProbe {
+    pub(crate) fn new(provider: ProviderId, check: &'static str, reason: SkipReason) -> Self {
+        Self {
+            provider,
+            check,
+            reason,
+        }
+    }
+}
+
+impl fmt::Display for SkippedProbe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            SkipReason::LocalTimeout => "local timeout",
+            SkipReason::Timeout => "timeout",
+        };
+
+        write!(f, "{}/{} ({})", self.provider, self.check, reason)
+    }
+}
+
+/// Represents a provider probed by a detector, along with how it can be identified.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub struct ProviderDescriptor {
+    /// Identifier of the provider.
+    pub id: ProviderId,
+    /// Whether the provider is built in or user-defined.
+    pub source: ProviderSource,
+    /// Whether the provider can be identified from the host alone (DMI attributes, files, environment variables).
+    pub supports_local: bool,
+    /// Whether the provider can be identified by its metadata server.
+    pub supports_network: bool,
+    /// Whether its metadata server is queried with a session token (e.g. AWS IMDSv2).
+    pub requires_token: bool,
+}
+
+/// Represents where a provider is defined.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum ProviderSource {
+    /// Implemented by this crate.
+    Builtin,
+    /// Registered at runtime (see [Detector::custom_provider]).
+    Custom,
+}
+
+impl ProviderDescriptor {
+    /// Describes a provider from its checks.
+    pub(crate) fn new(provider: P) -> Self {
+        let id = provider.identifier();
+        let checks = provider.checks();
+
+        Self {
+            id,
+            source: match id {
+                ProviderId::Custom(_) => ProviderSource::Custom,
+                _ => ProviderSource::Builtin,
+            },
+            supports_local: checks.iter().any(Check::is_local),
+            supports_network: checks.iter().any(|check| !check.is_local()),
+            requires_token: checks.iter().any(|check| {
+                matches!(&check.probe, Probe::Http { request, .. } if request.token.is_some())
+            }),
+        }
+    }
+}
+
+/// Represents a match that proves matches of another provider wrong, as their checks can pass on its hosts too.
+struct Overrule {
+    /// Provider and check (or host signal) of the overruling match.
+    by: (ProviderId, &'static str),
+    /// Provider and checks of the overruled matches.
+    overruled: (ProviderId, &'static [&'static str]),
+}
+
+/// Sources of GCP matches that Yandex Cloud's GCP-compatible metadata server may produce too.
+const GCP_COMPATIBLE_SOURCES: &[&str] = &[
+    "metadata_server",
+    "instance_id",
+    "project_metadata",
+    "cloud-init",
+];
+
+/// Cross-provider disambiguation rules, applied before matches are ranked.
+const OVERRULES: &[Overrule] = &[
+    // OpenStack's metadata server also serves an EC2-compatible API (under `/latest/`), which the AWS metadata server
+    // checks may match, while AWS never serves OpenStack's own metadata
+    Overrule {
+        by: (ProviderId::OpenStack, "metadata_server"),
+        overruled: (ProviderId::AWS, &["imdsv1", "imdsv2"]),
+    },
+    // Yandex Cloud's metadata server serves GCP's paths and echoes its `Metadata-Flavor` header, and its images may
+    // report the GCE datasource to cloud-init, while GCP never serves Yandex Cloud's identity document
+    Overrule {
+        by: (ProviderId::Yandex, "metadata_server"),
+        overruled: (ProviderId::GCP, GCP_COMPATIBLE_SOURCES),
+    },
+    Overrule {
+        by: (ProviderId::Yandex, "vendor_file"),
+        overruled: (ProviderId::GCP, GCP_COMPATIBLE_SOURCES),
+    },
+];
+
+/// Returns the providers whose matches may prove matches of the given provider wrong (see [OVERRULES]).
+fn overruling(provider: ProviderId) -> impl Iterator<Item = ProviderId> {
+    OVERRULES
+        .iter()
+        .filter(move |rule| rule.overruled.0 == provider)
+        .map(|rule| rule.by.0)
+}
+
+/// Whether a match is proven wrong by another match (see [OVERRULES]).
+fn overruled(m: &Match, matches: &[Match]) -> bool {
+    OVERRULES.iter().any(|rule| {
+        let (provider, sources) = rule.overruled;
+
+        m.provider == provider
+            && sources.contains(&m.source)
+            && matches
+                .iter()
+                .any(|other| (other.provider, other.source) == rule.by)
+    })
+}
+
+/// Picks the winning provider among all matches collected during detection.
+///
+/// Matches proven wrong by another match are left out first: AWS metadata server matches are, if OpenStack's metadata
+/// (`/openstack/latest/meta_data.json`) was served too, as OpenStack also serves an EC2-compatible metadata API, and GCP
+/// metadata server and cloud-init matches are, if Yandex Cloud was identified, as its metadata server is compatible
+/// with GCP's.
+/// Precedence rules, applied in order to the remaining matches:
+///
+/// 1. Specific providers win over generic platforms (e.g. AWS over OpenStack), which win over bare KVM.
+/// 2. Matches with higher confidence win (authoritative over verified over unverified).
+/// 3. Remaining ties go to the provider listed first in [supported_providers].
+pub(crate) fn resolve(matches: &[Match]) -> ProviderId {
+    resolve_by(matches, |m| (m.confidence as u32, 0))
+}
+
+/// Picks the winning provider among all matches, as [resolve] does, with matches weighed by `weigh` (as a weight and
+/// the priority of the provider) instead of by confidence alone.
+pub(crate) fn resolve_by(matches: &[Match], weigh: impl Fn(&Match) -> (u32, i32)) -> ProviderId {
+    let rank = |m: &Match| {
+        let position = SUPPORTED_PROVIDERS
+            .iter()
+            .position(|&p| p == m.provider)
+            .unwrap_or(usize::MAX);
+
+        (
+            m.provider.genericity(),
+            std::cmp::Reverse(weigh(m)),
+            position,
+        )
+    };
+
+    matches
+        .iter()
+        .filter(|m| !overruled(m, matches))
+        .min_by_key(|m| rank(m))
+        .map(|m| m.provider)
+        .unwrap_or_default()
+}
+
+/// Raises unverified matches for the provider named by an auxiliary signal (e.g. the hypervisor found on the host) to
+/// verified.
+#[cfg(any(feature = "systemd", feature = "network"))]
+pub(crate) fn corroborate(matches: &mut [Match], hypervisor: ProviderId) {
+    matches
+        .iter_mut()
+        .filter(|m| m.provider == hypervisor && m.confidence == Confidence::Unverified)
+        .for_each(|m| m.confidence = Confidence::Verified);
+}
+
+/// Represents a cloud service provider.
+///
+/// Providers only describe how they can be identified; the checks are run by [identify] for the async API, and by its
+/// counterpart in the blocking API.
+pub(crate) trait Provider: Send + Sync {
+    fn identifier(&self) -> ProviderId;
+    /// Returns the checks identifying the provider, in the order they should be tried.
+    fn checks(&self) -> Vec<Check>;
+    /// Returns how to fetch the region of the host, if the provider exposes it.
+    fn region(&self) -> Option<Fetch<Region>> {
+        None
+    }
+    /// Returns how to fetch the instance type of the host, if the provider exposes it.
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        None
+    }
+    /// Returns how to fetch the account of the host, if the provider exposes it.
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        None
+    }
+    /// Returns how to fetch the network configuration of the host, if the provider exposes it.
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        None
+    }
+    /// Returns how to check for credentials exposed by the metadata server, if the provider exposes any.
+    fn credentials_info(&self) -> Option<Fetch<CredentialsInfo>> {
+        None
+    }
+    /// Returns how to fetch the primary metadata document of the host, as raw JSON, if the provider exposes one.
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        None
+    }
+}
+
+/// Returns the provider with the given identifier, if supported.
+pub(crate) fn provider(identifier: ProviderId) -> Option<P> {
+    PROVIDERS
+        .iter()
+        .find(|provider| provider.identifier() == identifier)
+        .copied()
+}
+
+/// Tries to identify a provider by running the given checks in order, sending a match for the first one that succeeds.
+///
+/// Local checks run first, within the local budget, and the metadata server checks after them (see [Budget]).
+#[cfg(feature = "async")]
+#[instrument(skip_all, fields(provider = %provider.identifier()))]
+pub(crate) async fn identify(
+    provider: P,
+    checks: Vec<Check>,
+    tx: Sender<Match>,
+    budget: Budget,
+    smbios: SmbiosData,
+) {
+    info!("Checking {}", provider.identifier());
+
+    let id = provider.identifier();
+    let progress = budget.progress.clone();
+    let start = Instant::now();
+    let (local, network): (Vec<Check>, Vec<Check>) = checks.into_iter().partition(Check::is_local);
+    #[cfg(feature = "metrics")]
+    let probes = (!local.is_empty(), !network.is_empty());
+    let mut identified = None;
+    let mut local = local.into_iter();
+
+    while let Some(check) = local.next() {
+        let Some(remaining) = budget.local_remaining(start.elapsed()) else {
+            debug!("Local budget exhausted for {}", id);
+            let rest = [&check].into_iter().chain(local.as_slice());
+            progress.skip(id, rest, SkipReason::LocalTimeout);
+            break;
+        };
+        let inspection = match check.inspect_dmi(&smbios) {
+            Some(inspection) => Ok(inspection),
+            None => tokio::time::timeout(remaining, check.inspect(budget.network)).await,
+        };
+        let Ok(inspection) = inspection else {
+            debug!("Local budget exhausted by {}", check.name);
+            let rest = [&check].into_iter().chain(local.as_slice());
+            progress.skip(id, rest, SkipReason::LocalTimeout);
+            break;
+        };
+
+        progress.done(id, check.name);
+
+        if inspection.matched() {
+            identified = Some((check, inspection));
+            break;
+        }
+    }
+
+    budget.spend_local(start.elapsed());
+
+    #[cfg(feature = "metrics")]
+    if probes.0 {
+        let method = crate::metrics::Method::Local;
+        crate::metrics::record_probe(provider.identifier(), method, start.elapsed());
+    }
+
+    if identified.is_none() {
+        #[cfg(feature = "metrics")]
+        let start = Instant::now();
+        let phase_end = clock::deadline(budget.network);
+        let inspect_network = async {
+            for check in network {
+                if let Some(precheck) = &budget.precheck {
+                    if !precheck.reachable(&check).await {
+                        debug!("Skipping {}: metadata server unreachable", check.name);
+                        progress.done(id, check.name);
+                        continue;
+                    }
+                }
+
+                // Checks after the first get the time left by those before them, rather than a timeout of their own
+                let timeout = budget.request_timeout(phase_end);
+                if timeout.is_zero() {
+                    debug!("Network budget exhausted before {}", check.name);
+                    progress.skip_pending(id, SkipReason::Timeout);
+                    return None;
+                }
+
+                let inspection = check.inspect(timeout).await;
+                progress.done(id, check.name);
+
+                if inspection.matched() {
+                    return Some((check, inspection));
+                }
+            }
+
+            None
+        };
+
+        // Bounds the checks as a whole, on top of each request's own timeout
+        match tokio::time::timeout(budget.network, inspect_network).await {
+            Ok(inspection) => identified = inspection,
+            Err(_) => {
+                debug!("Network budget exhausted for {}", id);
+                progress.skip_pending(id, SkipReason::Timeout);
+            }
+        }
+
+        #[cfg(feature = "metrics")]
+        if probes.1 {
+            let method = crate::metrics::Method::Network;
+            crate::metrics::record_probe(provider.identifier(), method, start.elapsed());
+        }
+    }
+
+    // Checks after the one that matched are no longer needed
+    progress.finish(id);
+
+    let Some((check, inspection)) = identified else {
+        return;
+    };
+
+    info!("Identified {} using {}", provider.identifier(), check.name);
+    let confidence = check.match_confidence(&inspection);
+    let m = Match::new(provider.identifier(), confidence)
+        .source(check.name)
+        .outcome(inspection.outcome);
+    let res = tx.send(m).await;
+
+    if let Err(err) = res {
+        error!("Error sending message: {:?}", err);
+    }
+}
+
+pub(crate) type P = &'static dyn Provider;
+
+/// Identifiers of the providers in [PROVIDERS], in the same order.
+const SUPPORTED_PROVIDERS: &[ProviderId] = &[
+    akamai::IDENTIFIER,
+    alibaba::IDENTIFIER,
+    aws::IDENTIFIER,
+    azure::IDENTIFIER,
+    civo::IDENTIFIER,
+    digitalocean::IDENTIFIER,
+    gcp::IDENTIFIER,
+    kvm::IDENTIFIER,
+    naver::IDENTIFIER,
+    nhn::IDENTIFIER,
+    nutanix::IDENTIFIER,
+    oci::IDENTIFIER,
+    openstack::IDENTIFIER,
+    proxmox::IDENTIFIER,
+    vkcloud::IDENTIFIER,
+    vmware::IDENTIFIER,
+    vultr::IDENTIFIER,
+    yandex::IDENTIFIER,
+];
+
+/// Identifiers of the providers in descending order of likelihood (by market share), which is the order they are
+/// probed in when concurrency is limited. Generic platforms come last.
+const LIKELIHOOD: &[ProviderId] = &[
+    aws::IDENTIFIER,
+    azure::IDENTIFIER,
+    gcp::IDENTIFIER,
+    alibaba::IDENTIFIER,
+    oci::IDENTIFIER,
+    digitalocean::IDENTIFIER,
+    akamai::IDENTIFIER,
+    vultr::IDENTIFIER,
+    civo::IDENTIFIER,
+    yandex::IDENTIFIER,
+    vkcloud::IDENTIFIER,
+    naver::IDENTIFIER,
+    nhn::IDENTIFIER,
+    openstack::IDENTIFIER,
+    vmware::IDENTIFIER,
+    proxmox::IDENTIFIER,
+    nutanix::IDENTIFIER,
+    kvm::IDENTIFIER,
+];
+
+/// Orders providers by the given hints, then by [LIKELIHOOD].
+fn rank_providers(providers: &[P], hinted: &[ProviderId]) -> Vec<P> {
+    let position = |providers: &[ProviderId], provider: P| {
+        providers
+            .iter()
+            .position(|&identifier| identifier == provider.identifier())
+            .unwrap_or(usize::MAX)
+    };
+
+    let mut providers = providers.to_vec();
+    providers.sort_by_key(|&provider| (position(hinted, provider), position(LIKELIHOOD, provider)));
+
+    providers
+}
+
+pub(crate) static PROVIDERS: &[P] = &[
+    &akamai::Akamai,
+    &alibaba::Alibaba,
+    &aws::Aws,
+    &azure::Azure,
+    &civo::Civo,
+    &digitalocean::DigitalOcean,
+    &gcp::Gcp,
+    &kvm::Kvm,
+    &naver::Naver,
+    &nhn::Nhn,
+    &nutanix::Nutanix,
+    &oci::Oci,
+    &openstack::OpenStack,
+    &proxmox::Proxmox,
+    &vkcloud::VkCloud,
+    &vmware::VMware,
+    &vultr::Vultr,
+    &yandex::Yandex,
+];
+
+/// Returns a list of currently supported providers.
+///
+/// This is the set of providers a [Detector] probes by default (see [Detector::providers]).
+///
+/// The list is static, so this neither locks nor allocates. To also list user-defined providers, along with how each
+/// provider can be identified, see [Detector::provider_descriptors].
+///
+/// # Examples
+///
+/// Print the list of supported providers.
+///
+/// ```
+/// use cloud_detect::supported_providers;
+///
+/// let providers = supported_providers();
+/// println!("Supported providers: {:?}", providers);
+/// ```
+pub fn supported_providers() -> &'static [ProviderId] {
+    SUPPORTED_PROVIDERS
+}
+
+/// Detects the host's cloud provider.
+///
+/// Returns [ProviderId::Unknown] if the detection failed or timed out. If the detection was successful, it returns
+/// a value from [ProviderId](enum.ProviderId.html).
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// Detect the cloud provider and print the result (with default timeout).
+///
+/// ```
+/// use cloud_detect::detect;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let provider = detect(None).await;
+///     println!("Detected provider: {}", provider);
+/// }
+/// ```
+///
+/// Detect the cloud provider and print the result (with custom timeout).
+///
+/// ```
+/// use cloud_detect::detect;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let provider = detect(Some(10)).await;
+///     println!("Detected provider: {}", provider);
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn detect(timeout: Option<u64>) -> ProviderId {
+    Detector::new().with_timeout(timeout).detect().await
+}
+
+/// Checks whether the host runs on any cloud, as fast as possible, for when the provider itself doesn't matter (see
+/// [Detector::is_cloud]).
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, if it has to run. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::is_cloud;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     if is_cloud(Some(1)).await {
+///         println!("Running on a cloud");
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn is_cloud(timeout: Option<u64>) -> bool {
+    Detector::new().with_timeout(timeout).is_cloud().await
+}
+
+/// Checks whether the host runs on the given provider, probing that provider only (see [Detector::is_provider]).
+///
+/// # Arguments
+///
+/// * `provider` - Provider to check for.
+/// * `timeout` - Maximum time (seconds) allowed for detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::{is_provider, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let verdict = is_provider(ProviderId::AWS, Some(1)).await;
+///     println!("Running on AWS: {}", verdict.matched);
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn is_provider(provider: ProviderId, timeout: Option<u64>) -> ProviderVerdict {
+    Detector::new()
+        .with_timeout(timeout)
+        .is_provider(provider)
+        .await
+}
+
+/// Detects the host's cloud provider among the given providers only, e.g. when the host is known to run on one of a
+/// few clouds. Other providers are neither probed locally nor queried over the network.
+///
+/// Returns [ProviderId::Unknown] if none of the given providers was detected, or if the detection timed out.
+/// Unsupported providers are ignored (see [Detector::providers]).
+///
+/// # Arguments
+///
+/// * `providers` - Providers to probe.
+/// * `timeout` - Maximum time (seconds) allowed for detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::{detect_with_providers, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let provider = detect_with_providers(&[ProviderId::AWS, ProviderId::GCP], Some(1)).await;
+///     println!("Detected provider: {}", provider);
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn detect_with_providers(providers: &[ProviderId], timeout: Option<u64>) -> ProviderId {
+    Detector::new()
+        .providers(providers)
+        .with_timeout(timeout)
+        .detect()
+        .await
+}
+
+/// Configures and runs detection of the host's cloud provider.
+///
+/// By default, every provider is probed at once, as with [detect]. Constrained hosts can limit how many providers are
+/// probed concurrently, down to probing them one at a time in order of likelihood.
+///
+/// The metadata servers of providers opted out of through the environment, as honored by their own tooling (e.g.
+/// `AWS_EC2_METADATA_DISABLED=true`), are not queried. This can be overridden for each provider with
+/// [Detector::metadata].
+///
+/// Detectors are cheap to clone, and are `Send` and `Sync`, so one can be configured once and shared across threads
+/// (e.g. in a `static` [OnceLock](std::sync::OnceLock)).
+///
+/// Work left running by detection is reaped when the last clone of a detector is dropped, or with
+/// [Detector::shutdown].
+///
+/// # Examples
+///
+/// Detect the cloud provider, probing at most two providers at once.
+///
+/// ```
+/// use cloud_detect::Detector;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let provider = Detector::new().timeout(1).max_concurrency(2).detect().await;
+///     println!("Detected provider: {}", provider);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Detector {
+    pub(crate) timeout: Option<u64>,
+    pub(crate) local_timeout: Option<Duration>,
+    pub(crate) precheck_timeout: Option<Duration>,
+    pub(crate) max_concurrency: Option<usize>,
+    pub(crate) metadata: Vec<(ProviderId, bool)>,
+    pub(crate) local_only: bool,
+    pub(crate) thorough: bool,
+    pub(crate) providers: Option<Vec<ProviderId>>,
+    pub(crate) host_root: Option<PathBuf>,
+    pub(crate) binding: Binding,
+    pub(crate) probe_log_level: ProbeLogLevel,
+    pub(crate) custom: Vec<&'static Registered>,
+    pub(crate) provider_checks: Vec<Arc<dyn ProviderCheck>>,
+    pub(crate) background: Arc<Background>,
+    #[cfg(feature = "test_support")]
+    pub(crate) transport: Option<Arc<FakeTransport>>,
+    #[cfg(feature = "test_support")]
+    pub(crate) fakes: Vec<FakeProvider>,
+    #[cfg(feature = "simulation")]
+    pub(crate) host: Option<SimulatedHost>,
+    #[cfg(feature = "config")]
+    pub(crate) config: Option<Arc<Config>>,
+}
+
+impl Detector {
+    /// Creates a detector with the default configuration.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the maximum time (seconds) allowed for querying metadata servers. Defaults to [DEFAULT_DETECTION_TIMEOUT]
+    /// if not set.
+    ///
+    /// The time is counted from the end of the local checks (see [Detector::local_timeout]), so detection takes at
+    /// most the sum of both.
+    pub fn timeout(self, timeout: u64) -> Self {
+        self.with_timeout(Some(timeout))
+    }
+
+    /// Sets the maximum time allowed for the checks local to the host of each provider, such as reading DMI
+    /// attributes. Defaults to [DEFAULT_LOCAL_TIMEOUT] if not set.
+    ///
+    /// Local checks still pending once it has elapsed are skipped, so that slow file I/O (e.g. `/sys` mounted over
+    /// NFS) doesn't delay the metadata server checks.
+    pub fn local_timeout(mut self, timeout: Duration) -> Self {
+        self.local_timeout = Some(timeout);
+        self
+    }
+
+    /// Enables a TCP pre-check of each distinct metadata server (e.g. `169.254.169.254`), connecting to it within the
+    /// given time before sending any request. Disabled if not set.
+    ///
+    /// The checks of metadata servers that can't be connected to are skipped, so detection fails fast off-cloud, where
+    /// requests would otherwise wait for their timeout. Connecting to a metadata server that is present takes well under
+    /// a millisecond, so an aggressive deadline (e.g. 50 milliseconds) is independent of the HTTP timeouts (see
+    /// [Detector::timeout]).
+    pub fn precheck_timeout(mut self, timeout: Duration) -> Self {
+        self.precheck_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the maximum number of providers probed concurrently (at least one). Unlimited if not set.
+    ///
+    /// Providers are probed in order of likelihood, so with few concurrent probes the common case still finishes
+    /// early.
+    pub fn max_concurrency(mut self, max_concurrency: usize) -> Self {
+        self.max_concurrency = Some(max_concurrency.max(1));
+        self
+    }
+
+    /// Probes providers one at a time, in order of likelihood.
+    pub fn sequential(self) -> Self {
+        self.max_concurrency(1)
+    }
+
+    /// Enables or disables querying the metadata server of a provider, regardless of the environment.
+    ///
+    /// The provider can still be identified by its local checks, such as DMI attributes.
+    pub fn metadata(mut self, provider: ProviderId, enabled: bool) -> Self {
+        self.metadata.retain(|&(p, _)| p != provider);
+        self.metadata.push((provider, enabled));
+        self
+    }
+
+    /// Restricts detection to the given providers, instead of every supported provider. Unsupported providers are
+    /// ignored.
+    ///
+    /// Detectors with different sets of providers can be used side by side in the same process.
+    pub fn providers(mut self, providers: &[ProviderId]) -> Self {
+        self.providers = Some(providers.to_vec());
+        self
+    }
+
+    /// Returns the providers probed by the detector, in the order of [supported_providers], followed by its custom
+    /// providers (see [Detector::custom_provider]).
+    pub fn supported_providers(&self) -> Vec<ProviderId> {
+        self.registry()
+            .iter()
+            .map(|provider| provider.identifier())
+            .collect()
+    }
+
+    /// Describes the providers probed by the detector, in the same order as [Detector::supported_providers], e.g. to
+    /// display which of them can be identified without querying a metadata server.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{Detector, ProviderId};
+    ///
+    /// let descriptors = Detector::new().provider_descriptors();
+    /// let aws = descriptors
+    ///     .iter()
+    ///     .find(|d| d.id == ProviderId::AWS)
+    ///     .unwrap();
+    /// assert!(aws.supports_local && aws.supports_network && aws.requires_token);
+    /// ```
+    pub fn provider_descriptors(&self) -> Vec<ProviderDescriptor> {
+        self.registry()
+            .into_iter()
+            .map(ProviderDescriptor::new)
+            .collect()
+    }
+
+    /// Only runs checks local to the host, never querying metadata servers.
+    ///
+    /// As nothing can be confirmed by a metadata server, providers hinted at by the DNS search domains of the host (e.g.
+    /// `ec2.internal`) are also matched.
+    pub fn local_only(mut self) -> Self {
+        self.local_only = true;
+        self
+    }
+
+    /// Always runs the checks of every provider, instead of stopping at the first strong local signal.
+    ///
+    /// By default, a DMI attribute that only one provider sets (e.g. a system vendor of `DigitalOcean`) identifies it
+    /// right away, without querying any metadata server. Thorough detection confirms it against the metadata servers
+    /// instead, e.g. on hosts whose DMI attributes may be spoofed.
+    pub fn thorough(mut self) -> Self {
+        self.thorough = true;
+        self
+    }
+
+    /// Inspects the files of a host mounted at the given directory (e.g. `/host`, with the host's `/sys` and `/run`
+    /// bind-mounted at `/host/sys` and `/host/run`) instead of the root directory, from an agent running in a
+    /// container.
+    ///
+    /// Every file inspected is moved under the directory: DMI attributes, cloud-init's files, DHCP leases, etc.
+    /// Environment variables and metadata servers are still those of the container.
+    pub fn host_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.host_root = Some(root.into());
+        self
+    }
+
+    /// Inspects the files of the node a pod runs on, for node agents (e.g. of a Kubernetes DaemonSet), through the root
+    /// filesystem of the node's init process at `/proc/1/root`, rather than mounting the node's filesystems into the
+    /// pod (see [Detector::host_root]).
+    ///
+    /// This requires the pod to share the PID namespace of the node (`hostPID: true`) and to be allowed to read the
+    /// root filesystem of other processes (the `SYS_PTRACE` capability, or a privileged container). If it can't be
+    /// read, the files inspected are left unchanged: the pod's own, or those under the directory set by
+    /// [Detector::host_root], if any.
+    pub fn node_agent(mut self) -> Self {
+        self.host_root = host_root::init_root().or(self.host_root);
+        self
+    }
+
+    /// Sends the requests to metadata servers, and their reachability pre-check, from the given network interface (e.g.
+    /// `eth0`), on hosts with several interfaces where link-local traffic may be routed out of the wrong one.
+    ///
+    /// The interface is bound with `SO_BINDTODEVICE`, so this is only supported on Linux (and Android), and requires
+    /// the `CAP_NET_RAW` capability on kernels older than 5.7. Elsewhere, and with the `blocking-ureq` feature, the
+    /// metadata server checks fail with an error instead.
+    pub fn interface(mut self, interface: impl Into<String>) -> Self {
+        self.binding.interface = Some(interface.into());
+        self
+    }
+
+    /// Sends the requests to metadata servers, and their reachability pre-check, from the given source address (e.g.
+    /// the primary address of the interface the metadata server is reachable from).
+    ///
+    /// With the `blocking-ureq` feature, the metadata server checks fail with an error instead.
+    pub fn local_address(mut self, address: IpAddr) -> Self {
+        self.binding.local_address = Some(address);
+        self
+    }
+
+    /// Sets the level probes log their expected failures at (e.g. the refused connection of the metadata server of
+    /// another cloud), `DEBUG` if not set. Genuine faults, such as a file that exists but can't be read, are still
+    /// logged as errors.
+    pub fn probe_log_level(mut self, level: ProbeLogLevel) -> Self {
+        self.probe_log_level = level;
+        self
+    }
+
+    /// Stops probes from logging their expected failures at all, leaving only genuine faults in the logs of the crate
+    /// without having to filter them (see [Detector::probe_log_level]).
+    pub fn quiet(self) -> Self {
+        self.probe_log_level(ProbeLogLevel::Off)
+    }
+
+    /// Probes metadata servers through a fake transport instead of the network, ignoring the host (see
+    /// [test_support]).
+    #[cfg(feature = "test_support")]
+    pub fn transport(mut self, transport: FakeTransport) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+
+    /// Replaces a provider with a fake one with a scripted outcome, ignoring the host (see
+    /// [test_support]).
+    ///
+    /// Providers that aren't faked probe their metadata servers through the fake transport, if any.
+    #[cfg(feature = "test_support")]
+    pub fn fake_provider(mut self, provider: FakeProvider) -> Self {
+        self.fakes
+            .retain(|fake| fake.identifier != provider.identifier);
+        self.fakes.push(provider);
+        self.transport.get_or_insert_with(Default::default);
+        self
+    }
+
+    pub(crate) fn with_timeout(mut self, timeout: Option<u64>) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Starts the budget of a detection (see [Detector::timeout] and [Detector::local_timeout]).
+    pub(crate) fn budget(&self) -> Budget {
+        let budget = Budget::new(
+            self.local_timeout.unwrap_or(DEFAULT_LOCAL_TIMEOUT),
+            timeout_or_default(self.timeout),
+        );
+
+        match self.precheck_timeout {
+            Some(timeout) => budget.precheck(timeout),
+            None => budget,
+        }
+    }
+
+    /// Whether the metadata server of a provider may be queried.
+    pub(crate) fn metadata_enabled(&self, provider: ProviderId) -> bool {
+        if self.local_only {
+            return false;
+        }
+
+        self.metadata
+            .iter()
+            .find(|&&(p, _)| p == provider)
+            .map_or_else(
+                || !opt_out::metadata_disabled(provider),
+                |&(_, enabled)| enabled,
+            )
+    }
+
+    /// Returns the checks of a provider, logging their expected failures at the level set (see
+    /// [Detector::probe_log_level]).
+    pub(crate) fn checks(&self, provider: P) -> Vec<Check> {
+        self.routed_checks(provider)
+            .into_iter()
+            .map(|check| check.log_level(self.probe_log_level))
+            .collect()
+    }
+
+    /// Returns the checks of a provider, routed to the host, simulated host or fake transport probed by the detector,
+    /// without those querying its metadata server if disabled.
+    fn routed_checks(&self, provider: P) -> Vec<Check> {
+        #[cfg(feature = "test_support")]
+        if let Some(transport) = &self.transport {
+            return test_support::checks(provider, transport, &self.fakes);
+        }
+
+        #[cfg(feature = "simulation")]
+        if let Some(host) = &self.host {
+            return host.checks(provider);
+        }
+
+        let mut checks = self.tuned_checks(provider);
+
+        if !self.metadata_enabled(provider.identifier()) {
+            debug!(
+                "Skipping metadata server checks for {}",
+                provider.identifier()
+            );
+            checks.retain(|check| !matches!(check.probe, Probe::Http { .. }));
+        }
+
+        let checks = match &self.host_root {
+            Some(root) => checks
+                .into_iter()
+                .map(|check| host_root::rebase(check, root))
+                .collect(),
+            None => checks,
+        };
+
+        bind::bind(checks, &self.binding)
+    }
+
+    /// Returns the checks of a provider, as tuned by the configuration of the detector, if any.
+    pub(crate) fn tuned_checks(&self, provider: P) -> Vec<Check> {
+        #[allow(unused_mut)]
+        let mut checks = provider.checks();
+
+        #[cfg(feature = "config")]
+        if let Some(config) = &self.config {
+            config.tune_checks(provider, &mut checks);
+        }
+
+        checks
+    }
+
+    /// Picks the winning provider among all matches (see [resolve]), weighed by the configuration of the detector, if
+    /// any.
+    pub(crate) fn resolve(&self, matches: &[Match]) -> ProviderId {
+        #[cfg(feature = "config")]
+        if let Some(config) = &self.config {
+            return resolve_by(matches, |m| (config.weight(m), config.priority(m.provider)));
+        }
+
+        resolve(matches)
+    }
+
+    /// Returns the root directory of the inspected host (see [Detector::host_root]).
+    pub(crate) fn root(&self) -> &Path {
+        self.host_root
+            .as_deref()
+            .unwrap_or(Path::new(host_root::ROOT))
+    }
+
+    /// Whether signals read from the host, such as cloud-init and prior hints, are used. They are ignored when the
+    /// host is simulated, so that detection is deterministic.
+    pub(crate) fn host_signals(&self) -> bool {
+        #[cfg(feature = "test_support")]
+        if self.transport.is_some() {
+            return false;
+        }
+
+        #[cfg(feature = "simulation")]
+        if self.host.is_some() {
+            return false;
+        }
+
+        true
+    }
+
+    /// Returns the providers probed by the detector: the supported providers it includes, followed by its custom
+    /// providers.
+    pub(crate) fn registry(&self) -> Vec<P> {
+        PROVIDERS
+            .iter()
+            .filter(|provider| self.includes(provider.identifier()))
+            .copied()
+            .chain(self.custom.iter().map(|&provider| provider as P))
+            .collect()
+    }
+
+    /// Whether the detector probes a provider.
+    pub(crate) fn includes(&self, provider: ProviderId) -> bool {
+        self.providers
+            .as_ref()
+            .is_none_or(|providers| providers.contains(&provider))
+    }
+
+    /// Returns the providers probed by the detector, in descending order of likelihood.
+    ///
+    /// Providers hinted at by cheap local signals (see [hints::prior]) come first, followed by the rest in the order of
+    /// [LIKELIHOOD].
+    pub(crate) fn ranked_providers(&self) -> Vec<P> {
+        let registry = self.registry();
+
+        #[allow(unused_mut)]
+        let mut providers = if self.host_signals() {
+            rank_providers(&registry, &hints::prior::identify(&registry, self.root()))
+        } else {
+            rank_providers(&registry, &[])
+        };
+
+        #[cfg(feature = "config")]
+        self.prioritize(&mut providers);
+
+        providers
+    }
+
+    /// Reads the DMI attributes matched by the checks of every provider probed, once for all of them.
+    pub(crate) fn smbios(&self) -> SmbiosData {
+        let smbios = SmbiosData::read(
+            self.registry()
+                .into_iter()
+                .flat_map(|provider| self.checks(provider))
+                .filter_map(|check| match check.probe {
+                    Probe::Dmi { path, .. } => Some(path),
+                    _ => None,
+                }),
+        );
+        debug!("Read {} DMI attributes", smbios.len());
+
+        smbios
+    }
+
+    /// Classifies the white-label cloud a host of the detected provider runs on (see [Detection::reseller]), from the
+    /// DMI attributes read for the detection.
+    pub(crate) fn reseller(&self, provider: ProviderId, smbios: &SmbiosData) -> Option<ProviderId> {
+        if provider != openstack::IDENTIFIER {
+            return None;
+        }
+
+        let reseller = openstack::reseller(&self.checks(&openstack::OpenStack), smbios);
+        if let Some(reseller) = reseller {
+            debug!("Identified {} as the reseller of {}", reseller, provider);
+        }
+
+        reseller
+    }
+
+    /// Classifies the platform of the node of a managed Kubernetes cluster the host is (see [platform::classify_node]),
+    /// unless host signals are ignored.
+    pub(crate) fn node(&self, provider: ProviderId) -> Option<Platform> {
+        if !self.host_signals() {
+            return None;
+        }
+
+        let platform = platform::classify_node(provider, self.root());
+        if let Some(platform) = platform {
+            debug!("Identified the host as a {} node", platform);
+        }
+
+        platform
+    }
+
+    /// Resolves the detection of a provider from the matches collected, along with the white-label cloud and node
+    /// platform of the host.
+    pub(crate) fn detection(
+        &self,
+        provider: ProviderId,
+        matches: &[Match],
+        smbios: &SmbiosData,
+    ) -> Detection {
+        Detection::new(provider, matches)
+            .reseller(self.reseller(provider, smbios))
+            .node(self.node(provider))
+    }
+
+    /// Collects the matches of strong local signals (see [Detector::thorough]), one for each provider at most.
+    pub(crate) fn strong_matches(&self, smbios: &SmbiosData) -> Vec<Match> {
+        if self.thorough {
+            return Vec::new();
+        }
+
+        self.registry()
+            .into_iter()
+            .filter_map(|provider| {
+                self.checks(provider)
+                    .into_iter()
+                    .find(|check| check.inspect_strong(smbios).is_some_and(|i| i.matched()))
+                    .map(|check| {
+                        Match::new(provider.identifier(), check.confidence).source(check.name)
+                    })
+            })
+            .collect()
+    }
+
+    /// Whether a strong local signal of any cloud matched (see [Detector::is_cloud]).
+    pub(crate) fn strong_cloud_match(&self, smbios: &SmbiosData) -> bool {
+        let matched = self
+            .strong_matches(smbios)
+            .into_iter()
+            .find(|m| m.provider.group() == Some(ProviderGroup::Cloud));
+        if let Some(m) = &matched {
+            info!(
+                "Identified {} from strong local signals, on a cloud",
+                m.provider
+            );
+        }
+
+        matched.is_some()
+    }
+
+    /// Returns a check of the metadata server shared by most providers probed (see [Detector::is_cloud]), if any.
+    pub(crate) fn shared_metadata_check(&self) -> Option<Check> {
+        let checks: Vec<Check> = self
+            .registry()
+            .into_iter()
+            .flat_map(|provider| self.checks(provider))
+            .collect();
+
+        precheck::shared(&checks).cloned()
+    }
+
+    /// Returns the detector with the pre-check enabled, for detecting the provider once the shared metadata server was
+    /// connected to (see [Detector::is_cloud]).
+    pub(crate) fn shared_precheck(&self) -> Self {
+        let timeout = self.precheck_timeout.unwrap_or(SHARED_PRECHECK_TIMEOUT);
+        self.clone().precheck_timeout(timeout)
+    }
+
+    /// Detects the provider from strong local signals alone (see [Detector::thorough]), if any matched.
+    pub(crate) fn fast_exit(&self, smbios: &SmbiosData) -> Option<Detection> {
+        let matches = self.strong_matches(smbios);
+
+        if matches.is_empty() {
+            return None;
+        }
+
+        let provider = self.resolve(&matches);
+        info!("Identified {} from strong local signals", provider);
+
+        Some(self.detection(provider, &matches, smbios))
+    }
+
+    /// Returns the detection forced for tests, if any (see
+    /// [set_forced_result](crate::test_support::set_forced_result)).
+    pub(crate) fn forced(&self) -> Option<Detection> {
+        #[cfg(feature = "test_support")]
+        if let Some(detection) = test_support::forced_detection() {
+            info!("Detection forced to {}", detection.provider);
+            return Some(detection);
+        }
+
+        None
+    }
+
+    /// Waits for the work left running by previous detections to stop, so that no stray threads or sockets remain (e.g.
+    /// for leak checkers).
+    ///
+    /// Tasks of the asynchronous API are aborted as soon as detection returns. Threads of the blocking API stop at
+    /// their next check instead, so this waits at most for one check each (bounded by the timeout). The detector can
+    /// still be used afterwards.
+    pub fn shutdown(&self) {
+        self.background.shutdown();
+    }
+
+    /// Number of providers probed concurrently.
+    pub(crate) fn concurrency(&self) -> usize {
+        let providers = self.registry().len().max(1);
+
+        self.max_concurrency
+            .unwrap_or(providers)
+            .clamp(1, providers)
+    }
+
+    /// Detects the host's cloud provider.
+    ///
+    /// Returns [ProviderId::Unknown] if the detection failed or timed out.
+    #[cfg(feature = "async")]
+    pub async fn detect(&self) -> ProviderId {
+        self.detect_detailed().await.provider
+    }
+
+    /// Checks whether the host runs on any cloud (see [ProviderGroup::Cloud]), rather than on premises or on a private
+    /// virtualization platform, as fast as possible.
+    ///
+    /// Returns `true` as soon as a strong local signal of any cloud matched, without querying metadata servers.
+    /// Otherwise, the metadata server shared by most providers (`169.254.169.254`) is connected to once, within the
+    /// pre-check timeout (see [Detector::precheck_timeout]) or 100 milliseconds if not set, and `false` is returned if it
+    /// can't be. Only if it can is the provider detected, with the pre-check enabled.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::Detector;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let on_cloud = Detector::new().timeout(1).is_cloud().await;
+    ///     println!("Running on a cloud: {}", on_cloud);
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[instrument]
+    pub async fn is_cloud(&self) -> bool {
+        if let Some(detection) = self.forced() {
+            return detection.provider.group() == Some(ProviderGroup::Cloud);
+        }
+
+        if self.strong_cloud_match(&self.smbios()) {
+            return true;
+        }
+
+        let detector = self.shared_precheck();
+        if let Some(check) = self.shared_metadata_check() {
+            let timeout = detector.precheck_timeout.unwrap_or(SHARED_PRECHECK_TIMEOUT);
+            if !Precheck::new(timeout).reachable(&check).await {
+                info!("Shared metadata server unreachable, not on a cloud");
+                return false;
+            }
+        }
+
+        detector.detect().await.group() == Some(ProviderGroup::Cloud)
+    }
+
+    /// Checks whether the host runs on the given provider, probing only that provider, along with the providers whose
+    /// matches may prove its own wrong (e.g. OpenStack, whose EC2-compatible metadata API may pass for AWS's).
+    ///
+    /// Generic platforms match the hosts of providers built on them too (e.g. OpenStack on VK Cloud).
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::{Detector, ProviderId};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let verdict = Detector::new()
+    ///         .timeout(1)
+    ///         .is_provider(ProviderId::AWS)
+    ///         .await;
+    ///     println!("Running on AWS: {}", verdict.matched);
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[instrument]
+    pub async fn is_provider(&self, provider: ProviderId) -> ProviderVerdict {
+        ProviderVerdict::new(provider, self.targeting(provider).detect_detailed().await)
+    }
+
+    /// Returns the detector restricted to a provider and the providers that may overrule its matches, including the
+    /// provider itself only among its custom providers (see [Detector::is_provider]).
+    pub(crate) fn targeting(&self, provider: ProviderId) -> Self {
+        let providers: Vec<ProviderId> = std::iter::once(provider)
+            .chain(overruling(provider))
+            .collect();
+
+        let mut detector = self.clone().providers(&providers);
+        detector
+            .custom
+            .retain(|custom| custom.identifier() == provider);
+        detector
+    }
+
+    /// Detects the host's cloud provider, along with the evidence it was resolved from.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use cloud_detect::Detector;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let detection = Detector::new().timeout(1).detect_detailed().await;
+    ///     println!("Detected provider: {}", detection.provider);
+    ///
+    ///     for evidence in &detection.evidence {
+    ///         println!(
+    ///             "{} matched {} ({:?})",
+    ///             evidence.source, evidence.provider, evidence.confidence
+    ///         );
+    ///     }
+    /// }
+    /// ```
+    #[cfg(feature = "async")]
+    #[instrument]
+    pub async fn detect_detailed(&self) -> Detection {
+        if let Some(detection) = self.forced() {
+            return detection;
+        }
+
+        let smbios = self.smbios();
+
+        if let Some(detection) = self.fast_exit(&smbios) {
+            #[cfg(feature = "metrics")]
+            crate::metrics::record_detection(&detection);
+            return detection;
+        }
+
+        let budget = self.budget();
+        let (tx, mut rx) = mpsc::channel::<Match>(PROVIDERS.len() + 1);
+        // Tasks still running once detection returns (e.g. providers waiting on their metadata servers after another
+        // one won) are aborted when the set is dropped, rather than left holding sockets
+        let mut tasks = JoinSet::new();
+
+        #[cfg(feature = "systemd")]
+        let hypervisor = spawn::blocking(hints::virt::identify);
+        #[cfg(feature = "network")]
+        let gateway = spawn::blocking(hints::network::identify);
+
+        if self.host_signals() {
+            let tx = tx.clone();
+            let detector = self.clone();
+
+            spawn::named(&mut tasks, &spawn::name("hint", "cloud-init"), async move {
+                debug!("Spawning task for cloud-init hint");
+                let root = detector.root().to_path_buf();
+                // The hint may name a provider the detector doesn't probe
+                if let Ok(Some(provider)) =
+                    spawn::blocking(move || hints::cloudinit::identify(&root))
+                        .await
+                        .map(|provider| provider.filter(|&p| detector.includes(p)))
+                {
+                    let res = tx
+                        .send(Match::new(provider, Confidence::Authoritative).source("cloud-init"))
+                        .await;
+
+                    if let Err(err) = res {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        #[cfg(feature = "kubernetes")]
+        if self.host_signals() {
+            let tx = tx.clone();
+            let detector = self.clone();
+
+            spawn::named(&mut tasks, &spawn::name("hint", "kubelet"), async move {
+                debug!("Spawning task for kubelet hint");
+                let root = detector.root().to_path_buf();
+                if let Ok(Some(provider)) = spawn::blocking(move || hints::kubelet::identify(&root))
+                    .await
+                    .map(|provider| provider.filter(|&p| detector.includes(p)))
+                {
+                    let res = tx
+                        .send(Match::new(provider, Confidence::Verified).source("kubelet"))
+                        .await;
+
+                    if let Err(err) = res {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        for check in self.composed_checks() {
+            let tx = tx.clone();
+            let context = provider_check::context(check.as_ref(), &budget);
+
+            let name = spawn::name("check", check.name());
+
+            spawn::named(&mut tasks, &name, async move {
+                debug!("Spawning task for check: {}", check.name());
+                if let Some(m) = provider_check::identify(check.as_ref(), &context).await {
+                    if let Err(err) = tx.send(m).await {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        if self.local_only && self.host_signals() {
+            let tx = tx.clone();
+            let detector = self.clone();
+
+            spawn::named(&mut tasks, &spawn::name("hint", "dns"), async move {
+                debug!("Spawning task for DNS hints");
+                let root = detector.root().to_path_buf();
+                for provider in spawn::blocking(move || hints::dns::identify(&root))
+                    .await
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|&p| detector.includes(p))
+                {
+                    let m = Match::new(provider, Confidence::Unverified).source("dns");
+
+                    if let Err(err) = tx.send(m).await {
+                        error!("Error sending message: {:?}", err);
+                    }
+                }
+            });
+        }
+
+        {
+            let tx = tx.clone();
+            let detector = self.clone();
+            let budget = budget.clone();
+            let smbios = smbios.clone();
+            let permits = Arc::new(Semaphore::new(self.concurrency()));
+            let name = spawn::name("dispatch", "providers");
+
+            // Providers are spawned in order of likelihood, each once a permit is available
+            spawn::named(&mut tasks, &name, async move {
+                let mut providers = JoinSet::new();
+                let ranked: Vec<(P, Vec<Check>)> = detector
+                    .ranked_providers()
+                    .into_iter()
+                    .map(|provider| (provider, detector.checks(provider)))
+                    .collect();
+
+                // Providers waiting for a permit when detection times out are reported as skipped
+                for (provider, checks) in &ranked {
+                    budget.progress.queue(provider.identifier(), checks);
+                }
+
+                for (provider, checks) in ranked {
+                    let Ok(permit) = permits.clone().acquire_owned().await else {
+                        break;
+                    };
+                    let tx = tx.clone();
+                    let budget = budget.clone();
+                    let smbios = smbios.clone();
+
+                    let name = spawn::name("provider", provider.identifier());
+
+                    spawn::named(&mut providers, &name, async move {
+                        debug!("Spawning task for provider: {}", provider.identifier());
+                        identify(provider, checks, tx, budget, smbios).await;
+                        drop(permit);
+                    });
+                }
+
+                // The provider tasks are aborted along with this one, so it lives until they have all finished
+                while providers.join_next().await.is_some() {}
+            });
+        }
+
+        // Only the spawned tasks hold senders now, so the channel closes once they have all finished
+        drop(tx);
+
+        let mut matches = Vec::new();
+        let mut timed_out = false;
+
+        // Generic platforms don't open the resolution window: bare KVM matches from CPUID right away on most clouds,
+        // while the specific provider it runs may still be waiting on its metadata server
+        let specific = loop {
+            tokio::select! {
+                biased;
+
+                // Priority 1: If we receive a match of a specific provider, start resolving
+                res = rx.recv() => match res {
+                    Some(m) => {
+                        debug!("Received result from channel: {:?}", m);
+                        let generic = m.provider.genericity() > 0;
+                        matches.push(m);
+
+                        if !generic {
+                            break true;
+                        }
+                    }
+                    None => {
+                        debug!("All providers have finished identifying");
+                        break false;
+                    }
+                },
+
+                // Priority 2: If we time out
+                _ = budget.expired() => {
+                    debug!("Detection timed out");
+                    timed_out = true;
+                    break false;
+                }
+            }
+        };
+
+        if matches.is_empty() {
+            let detection = Detection::default().skipped(budget.progress.skipped(timed_out));
+            #[cfg(feature = "metrics")]
+            if timed_out {
+                crate::metrics::record_timeout();
+            } else {
+                crate::metrics::record_detection(&detection);
+            }
+            return detection;
+        }
+
+        // Collect any other matches that arrive within the resolution window
+        let window = Instant::now() + budget.remaining().min(RESOLUTION_WINDOW);
+
+        if specific {
+            loop {
+                tokio::select! {
+                    biased;
+
+                    res = rx.recv() => match res {
+                        Some(m) => {
+                            debug!("Received result from channel: {:?}", m);
+                            matches.push(m);
+                        }
+                        None => break,
+                    },
+
+                    _ = clock::sleep_until(window) => break,
+                }
+            }
+        }
+
+        #[cfg(feature = "systemd")]
+        if let Ok(Ok(Some(hypervisor))) = tokio::time::timeout_at(window, hypervisor).await {
+            if self.host_signals() {
+                corroborate(&mut matches, hypervisor);
+            }
+        }
+
+        #[cfg(feature = "network")]
+        if let Ok(Ok(Some(gateway))) = tokio::time::timeout_at(window, gateway).await {
+            if self.host_signals() {
+                corroborate(&mut matches, gateway);
+            }
+        }
+
+        let provider = self.resolve(&matches);
+        debug!("Resolved {:?} to {}", matches, provider);
+
+        let detection = self
+            .detection(provider, &matches, &smbios)
+            .skipped(budget.progress.skipped(timed_out));
+        #[cfg(feature = "metrics")]
+        crate::metrics::record_detection(&detection);
+
+        detection
+    }
+}
+
+/// Fetches the primary metadata document of a provider from its metadata server, as raw JSON.
+///
+/// Unlike the typed metadata returned elsewhere, no fields are lost. The document is the instance identity document on
+/// AWS and Alibaba Cloud, the instance metadata on Azure and OCI, the whole metadata tree (instance and project) on
+/// GCP, and the instance metadata document on DigitalOcean, OpenStack and Vultr.
+///
+/// # Arguments
+///
+/// * `provider` - The provider of the host, which is not detected.
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::{fetch_raw_metadata, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_raw_metadata(ProviderId::AWS, None).await.unwrap();
+///     println!("Kernel ID: {:?}", metadata.get("kernelId"));
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn fetch_raw_metadata(provider: ProviderId, timeout: Option<u64>) -> Result<Value> {
+    raw_metadata(provider)?
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+/// Returns how to fetch the primary metadata document of a provider, as raw JSON.
+pub(crate) fn raw_metadata(identifier: ProviderId) -> Result<Fetch<Value>> {
+    provider(identifier)
+        .and_then(|provider| provider.raw_metadata())
+        .with_context(|| format!("No metadata document for {identifier}"))
+}
+
+/// Detects the host's cloud provider, then fetches the region of the host from the provider's metadata server.
+///
+/// Returns `None` if no provider was detected, the provider doesn't expose the region, or the metadata server could
+/// not be queried.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// Detect the region and print the result.
+///
+/// ```
+/// use cloud_detect::detect_region;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     match detect_region(Some(1)).await {
+///         Some(region) => println!("Detected region: {} ({:?})", region.region, region.zone),
+///         None => println!("No region detected"),
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn detect_region(timeout: Option<u64>) -> Option<Region> {
+    detect_and_fetch(timeout, |provider| provider.region()).await
+}
+
+/// Detects the host's cloud provider, then fetches the instance type of the host (e.g. `m5.large` on AWS,
+/// `e2-standard-4` on GCP or `Standard_D4s_v5` on Azure) from the provider's metadata server.
+///
+/// Returns `None` if no provider was detected, the provider doesn't expose the instance type, or the metadata server
+/// could not be queried.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// Detect the instance type and print the result.
+///
+/// ```
+/// use cloud_detect::detect_instance_type;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     match detect_instance_type(Some(1)).await {
+///         Some(instance_type) => println!("Detected instance type: {}", instance_type),
+///         None => println!("No instance type detected"),
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn detect_instance_type(timeout: Option<u64>) -> Option<String> {
+    detect_and_fetch(timeout, |provider| provider.instance_type()).await
+}
+
+/// Detects the host's cloud provider, then fetches the account of the host (e.g. the AWS account, Azure subscription
+/// or GCP project) from the provider's metadata server.
+///
+/// Returns `None` if no provider was detected, the provider doesn't expose the account, or the metadata server could
+/// not be queried.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// Fetch the account and print the result.
+///
+/// ```
+/// use cloud_detect::fetch_account_info;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     match fetch_account_info(Some(1)).await {
+///         Some(account) => println!(
+///             "Running in {} account {}",
+///             account.provider, account.account_id
+///         ),
+///         None => println!("No account detected"),
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn fetch_account_info(timeout: Option<u64>) -> Option<AccountInfo> {
+    detect_and_fetch(timeout, |provider| provider.account_info()).await
+}
+
+/// Detects the host's cloud provider, then fetches the network configuration of the host's primary network interface
+/// (local and public IP addresses, MAC address, virtual network and subnet) from the provider's metadata server.
+///
+/// Returns `None` if no provider was detected, the provider doesn't expose its network configuration, or the metadata
+/// server could not be queried.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// Fetch the network configuration and print the result.
+///
+/// ```
+/// use cloud_detect::fetch_network_info;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     match fetch_network_info(Some(1)).await {
+///         Some(network) => println!(
+///             "Local IPv4: {:?}, public IPv4: {:?}",
+///             network.local_ipv4, network.public_ipv4
+///         ),
+///         None => println!("No network configuration detected"),
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn fetch_network_info(timeout: Option<u64>) -> Option<NetworkInfo> {
+    detect_and_fetch(timeout, |provider| provider.network_info()).await
+}
+
+/// Detects the host's cloud provider, then checks whether its metadata server exposes credentials (e.g. an AWS IAM
+/// role or a GCP service account) to the host.
+///
+/// This never retrieves the credentials themselves: only the endpoints listing the identities credentials are
+/// available for are queried.
+///
+/// Returns `None` if no provider was detected, the provider can't expose credentials through its metadata server, or
+/// the metadata server could not be queried.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for detection, and for each metadata request. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
+///
+/// # Examples
+///
+/// Check for exposed credentials and print the result.
+///
+/// ```
+/// use cloud_detect::fetch_credentials_info;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     match fetch_credentials_info(Some(1)).await {
+///         Some(credentials) if credentials.exposed => {
+///             println!("Credentials exposed for: {:?}", credentials.identities)
+///         }
+///         Some(_) => println!("No credentials exposed"),
+///         None => println!("Unable to check for credentials"),
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn fetch_credentials_info(timeout: Option<u64>) -> Option<CredentialsInfo> {
+    detect_and_fetch(timeout, |provider| provider.credentials_info()).await
+}
+
+/// Detects the host's cloud provider, then fetches metadata from its metadata server, if the provider exposes it.
+#[cfg(feature = "async")]
+async fn detect_and_fetch<T: 'static>(
+    timeout: Option<u64>,
+    fetch: impl FnOnce(P) -> Option<Fetch<T>>,
+) -> Option<T> {
+    let provider = provider(detect(timeout).await)?;
+
+    if !Detector::new().metadata_enabled(provider.identifier()) {
+        debug!("Skipping metadata fetch for {}", provider.identifier());
+        return None;
+    }
+
+    let fetch = fetch(provider)?;
+
+    match fetch.run(timeout_or_default(timeout)).await {
+        Ok(value) => Some(value),
+        Err(err) => {
+            error!("Error fetching metadata: {:?}", Redacted(&err));
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_supported_providers() {
+        let providers = supported_providers();
+        assert_eq!(providers.len(), 18);
+        assert!(providers.contains(&akamai::IDENTIFIER));
+        assert!(providers.contains(&alibaba::IDENTIFIER));
+        assert!(providers.contains(&aws::IDENTIFIER));
+        assert!(providers.contains(&azure::IDENTIFIER));
+        assert!(providers.contains(&civo::IDENTIFIER));
+        assert!(providers.contains(&digitalocean::IDENTIFIER));
+        assert!(providers.contains(&gcp::IDENTIFIER));
+        assert!(providers.contains(&kvm::IDENTIFIER));
+        assert!(providers.contains(&naver::IDENTIFIER));
+        assert!(providers.contains(&nhn::IDENTIFIER));
+        assert!(providers.contains(&nutanix::IDENTIFIER));
+        assert!(providers.contains(&oci::IDENTIFIER));
+        assert!(providers.contains(&openstack::IDENTIFIER));
+        assert!(providers.contains(&proxmox::IDENTIFIER));
+        assert!(providers.contains(&vkcloud::IDENTIFIER));
+        assert!(providers.contains(&vmware::IDENTIFIER));
+        assert!(providers.contains(&vultr::IDENTIFIER));
+        assert!(providers.contains(&yandex::IDENTIFIER));
+    }
+
+    #[test]
+    fn test_short_code() {
+        for provider in supported_providers() {
+            assert_eq!(provider.short_code(), provider.to_string());
+        }
+        assert_eq!(
+            ProviderId::Unknown.short_code(),
+            ProviderId::Unknown.to_string()
+        );
+    }
+
+    #[test]
+    fn test_resolve_empty() {
+        assert_eq!(resolve(&[]), ProviderId::Unknown);
+    }
+
+    #[test]
+    fn test_resolve_specific_over_generic() {
+        let matches = [
+            Match::new(ProviderId::OpenStack, Confidence::Verified),
+            Match::new(ProviderId::AWS, Confidence::Unverified),
+        ];
+
+        assert_eq!(resolve(&matches), ProviderId::AWS);
+    }
+
+    #[test]
+    fn test_resolve_openstack_over_ec2_compatible() {
+        let aws = |source| Match::new(ProviderId::AWS, Confidence::Verified).source(source);
+        let openstack =
+            Match::new(ProviderId::OpenStack, Confidence::Verified).source("metadata_server");
+
+        assert_eq!(resolve(&[aws("imdsv2"), openstack]), ProviderId::OpenStack);
+        assert_eq!(resolve(&[openstack, aws("imdsv1")]), ProviderId::OpenStack);
+
+        // Without OpenStack's own metadata, the EC2-compatible API isn't told apart from AWS
+        let config_drive =
+            Match::new(ProviderId::OpenStack, Confidence::Verified).source("config_drive_label");
+        assert_eq!(resolve(&[aws("imdsv2"), config_drive]), ProviderId::AWS);
+
+        // Matches AWS's metadata server can't produce on OpenStack still win
+        assert_eq!(
+            resolve(&[aws("imdsv2"), aws("task_metadata"), openstack]),
+            ProviderId::AWS
+        );
+    }
+
+    #[test]
+    fn test_resolve_yandex_over_gcp_compatible() {
+        let gcp = |source, confidence| Match::new(ProviderId::GCP, confidence).source(source);
+        let yandex = |source| Match::new(ProviderId::Yandex, Confidence::Verified).source(source);
+
+        // GCP's matches win by position or confidence, unless Yandex Cloud was identified
+        let matches = [
+            gcp("instance_id", Confidence::Verified),
+            gcp("cloud-init", Confidence::Authoritative),
+        ];
+        assert_eq!(resolve(&matches), ProviderId::GCP);
+        assert_eq!(
+            resolve(&[matches[0], matches[1], yandex("metadata_server")]),
+            ProviderId::Yandex
+        );
+        assert_eq!(
+            resolve(&[yandex("vendor_file"), matches[0]]),
+            ProviderId::Yandex
+        );
+
+        // The DMI vendor of GCP can't be served by Yandex Cloud
+        assert_eq!(
+            resolve(&[
+                gcp("vendor_file", Confidence::Verified),
+                yandex("metadata_server")
+            ]),
+            ProviderId::GCP
+        );
+    }
+
+    #[test]
+    fn test_resolve_vkcloud_over_openstack() {
+        let matches = [
+            Match::new(ProviderId::OpenStack, Confidence::Verified).source("metadata_server"),
+            Match::new(ProviderId::VkCloud, Confidence::Verified).source("metadata_server"),
+        ];
+
+        assert_eq!(resolve(&matches), ProviderId::VkCloud);
+    }
+
+    #[test]
+    fn test_resolve_cloud_over_virtualization() {
+        let matches = [
+            Match::new(ProviderId::VMware, Confidence::Unverified),
+            Match::new(ProviderId::OCI, Confidence::Unverified),
+        ];
+
+        assert_eq!(resolve(&matches), ProviderId::OCI);
+
+        let matches = [
+            Match::new(ProviderId::KVM, Confidence::Unverified),
+            Match::new(ProviderId::Proxmox, Confidence::Unverified),
+        ];
+
+        assert_eq!(resolve(&matches), ProviderId::Proxmox);
+    }
+
+    #[test]
+    fn test_resolve_verified_over_unverified() {
+        let matches = [
+            Match::new(ProviderId::Azure, Confidence::Unverified),
+            Match::new(ProviderId::Vultr, Confidence::Verified),
+        ];
+
+        assert_eq!(resolve(&matches), ProviderId::Vultr);
+    }
+
+    #[test]
+    fn test_resolve_authoritative_over_verified() {
+        let matches = [
+            Match::new(ProviderId::AWS, Confidence::Verified),
+            Match::new(ProviderId::GCP, Confidence::Authoritative),
+        ];
+
+        assert_eq!(resolve(&matches), ProviderId::GCP);
+    }
+
+    #[test]
+    fn test_resolve_is_order_independent() {
+        let mut matches = vec![
+            Match::new(ProviderId::GCP, Confidence::Unverified),
+            Match::new(ProviderId::Alibaba, Confidence::Unverified),
+            Match::new(ProviderId::OpenStack, Confidence::Unverified),
+        ];
+        let expected = resolve(&matches);
+        matches.reverse();
+
+        assert_eq!(expected, ProviderId::Alibaba);
+        assert_eq!(resolve(&matches), expected);
+    }
+
+    #[cfg(any(feature = "systemd", feature = "network"))]
+    #[test]
+    fn test_corroborate() {
+        let mut matches = [
+            Match::new(ProviderId::Azure, Confidence::Unverified),
+            Match::new(ProviderId::OCI, Confidence::Verified),
+        ];
+        corroborate(&mut matches, ProviderId::Azure);
+
+        assert_eq!(matches[0].confidence, Confidence::Verified);
+        assert_eq!(resolve(&matches), ProviderId::Azure);
+    }
+
+    #[test]
+    fn test_normalize_mac() {
+        assert_eq!(
+            normalize_mac("0E:1F:2A:3B:4C:5D").as_deref(),
+            Some("0e:1f:2a:3b:4c:5d")
+        );
+        assert_eq!(
+            normalize_mac("000D3AF806EC").as_deref(),
+            Some("00:0d:3a:f8:06:ec")
+        );
+        assert_eq!(
+            normalize_mac("00-0d-3a-f8-06-ec").as_deref(),
+            Some("00:0d:3a:f8:06:ec")
+        );
+        assert_eq!(normalize_mac("abc"), None);
+        assert_eq!(normalize_mac("zz:zz:zz:zz:zz:zz"), None);
+    }
+
+    #[test]
+    fn test_rank_providers() {
+        let identifiers = |providers: Vec<P>| -> Vec<ProviderId> {
+            providers.iter().map(|p| p.identifier()).collect()
+        };
+
+        let providers = identifiers(rank_providers(PROVIDERS, &[]));
+        assert_eq!(providers, LIKELIHOOD);
+        assert_eq!(providers.len(), PROVIDERS.len());
+        assert!(providers.last().is_some_and(|p| p.genericity() > 0));
+
+        let providers = identifiers(rank_providers(
+            PROVIDERS,
+            &[ProviderId::Vultr, ProviderId::GCP],
+        ));
+        assert_eq!(providers[..2], [ProviderId::Vultr, ProviderId::GCP]);
+        assert_eq!(providers[2..4], [ProviderId::AWS, ProviderId::Azure]);
+        assert_eq!(providers.len(), PROVIDERS.len());
+    }
+
+    #[test]
+    fn test_detector_metadata() {
+        let is_http = |check: &Check| matches!(check.probe, Probe::Http { .. });
+
+        let checks = Detector::new()
+            .metadata(ProviderId::AWS, false)
+            .checks(&aws::Aws);
+        assert!(!checks.is_empty());
+        assert!(!checks.iter().any(is_http));
+
+        let checks = Detector::new()
+            .metadata(ProviderId::AWS, false)
+            .metadata(ProviderId::AWS, true)
+            .checks(&aws::Aws);
+        assert!(checks.iter().any(is_http));
+    }
+
+    #[test]
+    fn test_detector_local_only() {
+        let detector = Detector::new().metadata(ProviderId::AWS, true).local_only();
+
+        assert!(!detector.metadata_enabled(ProviderId::AWS));
+        assert!(PROVIDERS.iter().all(|&provider| detector
+            .checks(provider)
+            .iter()
+            .all(|check| !matches!(check.probe, Probe::Http { .. }))));
+    }
+
+    #[tokio::test]
+    async fn test_detector_host_root() {
+        let root = tempfile::tempdir().unwrap();
+        let dmi = root.path().join("sys/class/dmi/id");
+        std::fs::create_dir_all(&dmi).unwrap();
+        std::fs::write(
+            dmi.join("sys_vendor"),
+            crate::provider_fixtures::digitalocean::VENDOR,
+        )
+        .unwrap();
+
+        let detector = Detector::new()
+            .providers(&[ProviderId::DigitalOcean, ProviderId::Vultr])
+            .local_only()
+            .host_root(root.path());
+
+        assert!(detector
+            .checks(&digitalocean::DigitalOcean)
+            .iter()
+            .all(|check| !check.probe.target().starts_with("/sys/")));
+        assert_eq!(detector.detect().await, ProviderId::DigitalOcean);
+    }
+
+    #[test]
+    fn test_detector_targeting() {
+        let providers = |provider| Detector::new().targeting(provider).supported_providers();
+
+        assert_eq!(
+            providers(ProviderId::DigitalOcean),
+            [ProviderId::DigitalOcean]
+        );
+        assert_eq!(
+            providers(ProviderId::AWS),
+            [ProviderId::AWS, ProviderId::OpenStack]
+        );
+        assert_eq!(
+            providers(ProviderId::GCP),
+            [ProviderId::GCP, ProviderId::Yandex]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detector_is_provider() {
+        let root = tempfile::tempdir().unwrap();
+        let dmi = root.path().join("sys/class/dmi/id");
+        std::fs::create_dir_all(&dmi).unwrap();
+        std::fs::write(
+            dmi.join("sys_vendor"),
+            crate::provider_fixtures::digitalocean::VENDOR,
+        )
+        .unwrap();
+        let detector = Detector::new().local_only().host_root(root.path());
+
+        let verdict = detector.is_provider(ProviderId::DigitalOcean).await;
+        assert!(verdict.matched);
+        assert_eq!(verdict.evidence[0].source, "vendor_file");
+
+        let verdict = detector.is_provider(ProviderId::Vultr).await;
+        assert!(!verdict.matched);
+        assert!(verdict.evidence.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_detector_is_cloud() {
+        let root = tempfile::tempdir().unwrap();
+        let dmi = root.path().join("sys/class/dmi/id");
+        std::fs::create_dir_all(&dmi).unwrap();
+        let detector = Detector::new()
+            .providers(&[ProviderId::DigitalOcean, ProviderId::VMware])
+            .local_only()
+            .host_root(root.path());
+
+        // Strong local signals of a cloud
+        std::fs::write(
+            dmi.join("sys_vendor"),
+            crate::provider_fixtures::digitalocean::VENDOR,
+        )
+        .unwrap();
+        assert!(detector.is_cloud().await);
+
+        // Private virtualization platforms aren't clouds
+        std::fs::write(
+            dmi.join("sys_vendor"),
+            crate::provider_fixtures::vmware::VENDOR,
+        )
+        .unwrap();
+        assert!(!detector.is_cloud().await);
+    }
+
+    #[test]
+    fn test_detector_shared_metadata_check() {
+        let address = |detector: &Detector| {
+            detector
+                .shared_metadata_check()
+                .map(|check| check.probe.target())
+        };
+
+        assert!(address(&Detector::new()).is_some_and(|target| target.contains("169.254.169.254")));
+        assert_eq!(address(&Detector::new().local_only()), None);
+    }
+
+    #[test]
+    fn test_detector_interface() {
+        let detector = Detector::new().interface("eth1");
+        let bound = |detector: &Detector| {
+            detector
+                .checks(&aws::Aws)
+                .iter()
+                .all(|check| match &check.probe {
+                    Probe::Http { request, .. } => request.binding.is_some(),
+                    _ => true,
+                })
+        };
+
+        assert!(bound(&detector));
+        assert!(!bound(&Detector::new()));
+    }
+
+    #[test]
+    fn test_detector_probe_log_level() {
+        let logs_at = |detector: &Detector, level| {
+            detector
+                .checks(&aws::Aws)
+                .iter()
+                .all(|check| check.log_level == level)
+        };
+
+        assert!(logs_at(&Detector::new(), ProbeLogLevel::Debug));
+        assert!(logs_at(&Detector::new().quiet(), ProbeLogLevel::Off));
+        assert!(logs_at(
+            &Detector::new().probe_log_level(ProbeLogLevel::Error),
+            ProbeLogLevel::Error
+        ));
+    }
+
+    #[test]
+    fn test_detector_node_agent() {
+        let detector = Detector::new().host_root("/host").node_agent();
+        let expected = host_root::init_root().unwrap_or_else(|| PathBuf::from("/host"));
+
+        assert_eq!(detector.root(), expected);
+    }
+
+    #[test]
+    fn test_detector_providers() {
+        let detector =
+            Detector::new().providers(&[ProviderId::GCP, ProviderId::AWS, ProviderId::Unknown]);
+
+        assert_eq!(
+            detector.supported_providers(),
+            [ProviderId::AWS, ProviderId::GCP]
+        );
+        assert!(detector.includes(ProviderId::GCP));
+        assert!(!detector.includes(ProviderId::Azure));
+        assert_eq!(detector.concurrency(), 2);
+        assert_eq!(Detector::new().supported_providers(), supported_providers());
+    }
+
+    #[test]
+    fn test_provider_descriptors() {
+        let descriptors = Detector::new().provider_descriptors();
+        let descriptor = |id| descriptors.iter().find(|d| d.id == id).copied().unwrap();
+
+        assert_eq!(descriptors.len(), supported_providers().len());
+        assert!(descriptors
+            .iter()
+            .all(|d| d.source == ProviderSource::Builtin && d.supports_local));
+
+        let aws = descriptor(ProviderId::AWS);
+        assert!(aws.supports_network && aws.requires_token);
+
+        let azure = descriptor(ProviderId::Azure);
+        assert!(azure.supports_network && !azure.requires_token);
+
+        let vmware = descriptor(ProviderId::VMware);
+        assert!(!vmware.supports_network && !vmware.requires_token);
+    }
+
+    #[tokio::test]
+    async fn test_detect_with_providers_unsupported() {
+        assert_eq!(
+            detect_with_providers(&[ProviderId::Unknown], Some(1)).await,
+            ProviderId::Unknown
+        );
+    }
+
+    #[tokio::test]
+    async fn test_detector_no_providers() {
+        assert_eq!(
+            Detector::new().providers(&[]).detect().await,
+            ProviderId::Unknown
+        );
+    }
+
+    #[tokio::test]
+    async fn test_identify_local_budget() {
+        let checks = || vec![Check::env("path", Confidence::Unverified, "PATH", |_| true)];
+
+        // Local checks are skipped once the local budget is exhausted
+        let (tx, mut rx) = mpsc::channel(1);
+        let budget = Budget::new(Duration::ZERO, Duration::from_secs(1));
+        identify(&aws::Aws, checks(), tx, budget, Default::default()).await;
+        assert!(rx.recv().await.is_none());
+
+        let (tx, mut rx) = mpsc::channel(1);
+        let budget = Budget::new(DEFAULT_LOCAL_TIMEOUT, Duration::from_secs(1));
+        identify(&aws::Aws, checks(), tx, budget, Default::default()).await;
+        assert_eq!(rx.recv().await.map(|m| m.source), Some("path"));
+    }
+
+    #[test]
+    fn test_detector_concurrency() {
+        assert_eq!(Detector::new().concurrency(), PROVIDERS.len());
+        assert_eq!(Detector::new().max_concurrency(2).concurrency(), 2);
+        assert_eq!(Detector::new().max_concurrency(0).concurrency(), 1);
+        assert_eq!(
+            Detector::new().max_concurrency(100).concurrency(),
+            PROVIDERS.len()
+        );
+        assert_eq!(Detector::new().sequential().concurrency(), 1);
+    }
+
+    #[test]
+    fn test_provider() {
+        assert_eq!(
+            provider(ProviderId::AWS).map(|p| p.identifier()),
+            Some(ProviderId::AWS)
+        );
+        assert!(provider(ProviderId::Unknown).is_none());
+    }
+
+    #[test]
+    fn test_raw_metadata() {
+        for provider in PROVIDERS {
+            // Private virtualization platforms and Civo have no metadata server, and Naver Cloud Platform's only
+            // serves plaintext values
+            assert_eq!(
+                provider.raw_metadata().is_some(),
+                provider.identifier().group() == Some(ProviderGroup::Cloud)
+                    && !matches!(provider.identifier(), ProviderId::Naver | ProviderId::Civo),
+                "{}",
+                provider.identifier()
+            );
+        }
+        assert!(raw_metadata(ProviderId::Unknown).is_err());
+    }
+
+    #[test]
+    fn test_supported_providers_match_registry() {
+        let identifiers: Vec<ProviderId> = PROVIDERS.iter().map(|p| p.identifier()).collect();
+        assert_eq!(identifiers, supported_providers());
+    }
+}
@@ -0,0 +1,52 @@
+//! Entry points of the fuzz targets (see `fuzz/`), only built by cargo-fuzz (with `--cfg fuzzing`).
+//!
+//! Responses of metadata servers are parsed by the checks and fetches of each provider, which must reject malformed
+//! bodies rather than panic: requests to the link-local address of a metadata server can be answered by anything.
+
+use crate::checks::{Fetch, Probe, Response, Step};
+use crate::ProviderId;
+
+/// Maximum number of steps of a fetch parsed from the same response.
+const MAX_STEPS: usize = 8;
+
+/// Parses an arbitrary response from a metadata server of the given provider, with every check and fetch of the
+/// provider.
+pub fn parse_response(provider: ProviderId, status: u16, body: &[u8]) {
+    let Some(provider) = crate::provider(provider) else {
+        return;
+    };
+
+    let resp = Response {
+        status,
+        body: String::from_utf8_lossy(body).into_owned(),
+        ..Default::default()
+    };
+
+    for check in provider.checks() {
+        if let Probe::Http { matches, .. } = &check.probe {
+            matches(&resp);
+        }
+    }
+
+    parse(provider.region(), &resp);
+    parse(provider.instance_type(), &resp);
+    parse(provider.account_info(), &resp);
+    parse(provider.network_info(), &resp);
+    parse(provider.credentials_info(), &resp);
+    parse(provider.raw_metadata(), &resp);
+}
+
+/// Parses a response with each step of a fetch, as if every request of the fetch was answered with it.
+fn parse<T>(fetch: Option<Fetch<T>>, resp: &Response) {
+    let mut fetch = fetch;
+
+    for _ in 0..MAX_STEPS {
+        let Some(current) = fetch.take() else {
+            return;
+        };
+
+        if let Ok(Step::Next(next)) = (current.parse)(resp) {
+            fetch = Some(next);
+        }
+    }
+}
@@ -0,0 +1,227 @@
+//! Metadata server health checks.
+//!
+//! Separately from detection, operators monitor whether the metadata server of a known provider stays reachable and
+//! responsive over time. [metadata_health] queries the endpoint the provider is identified by, with the same client and
+//! session token handling as detection, and reports its status and latency.
+
+use std::fmt;
+use std::time::Duration;
+
+use anyhow::Result;
+#[cfg(feature = "async")]
+use tracing::{debug, instrument};
+
+use crate::checks::{Probe, Request, Response};
+#[cfg(feature = "async")]
+use crate::clock::Instant;
+use crate::redact::redact;
+#[cfg(feature = "async")]
+use crate::redact::Redacted;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::ProviderId;
+
+/// Represents the health of the metadata server of a provider.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct MetadataHealth {
+    /// Provider the metadata server belongs to.
+    pub provider: ProviderId,
+    /// Redacted URL of the endpoint queried.
+    pub endpoint: String,
+    /// Whether the metadata server responded successfully (with a 2xx status code).
+    pub healthy: bool,
+    /// Status code of the response, if the metadata server responded.
+    pub status: Option<u16>,
+    /// Time taken to respond (or to fail), including retrieving a session token if required.
+    pub latency: Duration,
+    /// Redacted error that prevented the metadata server from responding, if any.
+    pub error: Option<String>,
+}
+
+impl MetadataHealth {
+    pub(crate) fn new(
+        provider: ProviderId,
+        request: &Request,
+        result: Result<Response>,
+        latency: Duration,
+    ) -> Self {
+        let (status, error) = match result {
+            Ok(resp) => (Some(resp.status), None),
+            Err(err) => (None, Some(redact(&format!("{err:#}")))),
+        };
+
+        Self {
+            provider,
+            endpoint: redact(&request.url()),
+            healthy: status.is_some_and(|status| (200..300).contains(&status)),
+            status,
+            latency,
+            error,
+        }
+    }
+}
+
+impl fmt::Display for MetadataHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {}: {} ({:?})",
+            self.provider,
+            self.endpoint,
+            if self.healthy { "healthy" } else { "unhealthy" },
+            self.latency
+        )?;
+
+        if let Some(status) = self.status {
+            write!(f, ", status {status}")?;
+        }
+
+        if let Some(error) = &self.error {
+            write!(f, ", error: {error}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the request to the endpoint the provider is identified by, if the provider has a metadata server.
+pub(crate) fn health_request(provider: ProviderId) -> Option<Request> {
+    crate::provider(provider)?
+        .checks()
+        .into_iter()
+        .find_map(|check| match check.probe {
+            Probe::Http { request, .. } => Some(request),
+            _ => None,
+        })
+}
+
+/// Checks the health of the metadata server of a provider, reporting its status and latency.
+///
+/// Returns `None` if the provider isn't supported or has no metadata server. Unlike detection, the response isn't
+/// required to identify the provider, only to be successful, and metadata server opt-outs are ignored.
+///
+/// # Arguments
+///
+/// * `provider` - The provider whose metadata server is checked.
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// Check the health of the AWS metadata server every minute.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use cloud_detect::{metadata_health, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     loop {
+///         if let Some(health) = metadata_health(ProviderId::AWS, Some(1)).await {
+///             println!("{}", health);
+///         }
+///
+///         tokio::time::sleep(Duration::from_secs(60)).await;
+///     }
+/// }
+/// ```
+#[cfg(feature = "async")]
+#[instrument]
+pub async fn metadata_health(provider: ProviderId, timeout: Option<u64>) -> Option<MetadataHealth> {
+    let request = health_request(provider)?;
+
+    Some(check(provider, &request, timeout_or_default(timeout)).await)
+}
+
+/// Sends a health check request, timing the response.
+#[cfg(feature = "async")]
+pub(crate) async fn check(
+    provider: ProviderId,
+    request: &Request,
+    timeout: Duration,
+) -> MetadataHealth {
+    let start = Instant::now();
+    let result = request.send(timeout).await;
+    let latency = start.elapsed();
+
+    if let Err(err) = &result {
+        debug!(
+            "Metadata server of {} unreachable: {:?}",
+            provider,
+            Redacted(err)
+        );
+    }
+
+    MetadataHealth::new(provider, request, result, latency)
+}
+
+#[cfg(test)]
+mod tests {
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+
+    #[test]
+    fn test_health_request() {
+        assert!(health_request(ProviderId::AWS).is_some());
+        assert!(health_request(ProviderId::Unknown).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_metadata_health_unknown() {
+        assert!(metadata_health(ProviderId::Unknown, Some(1))
+            .await
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_healthy() {
+        let server = MockServer::start().await;
+        Mock::given(path("/v1.json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("{}"))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut request = health_request(ProviderId::Vultr).unwrap();
+        request.base_uri = server.uri();
+
+        let health = check(ProviderId::Vultr, &request, Duration::from_secs(1)).await;
+
+        assert!(health.healthy);
+        assert_eq!(health.status, Some(200));
+        assert!(health.error.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_check_unhealthy() {
+        let server = MockServer::start().await;
+        Mock::given(path("/v1.json"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+
+        let mut request = health_request(ProviderId::Vultr).unwrap();
+        request.base_uri = server.uri();
+
+        let health = check(ProviderId::Vultr, &request, Duration::from_secs(1)).await;
+
+        assert!(!health.healthy);
+        assert_eq!(health.status, Some(500));
+    }
+
+    #[tokio::test]
+    async fn test_check_unreachable() {
+        let mut request = health_request(ProviderId::Vultr).unwrap();
+        request.base_uri = "http://127.0.0.1:9".to_string();
+
+        let health = check(ProviderId::Vultr, &request, Duration::from_secs(1)).await;
+
+        assert!(!health.healthy);
+        assert!(health.status.is_none());
+        assert!(health.error.is_some());
+        assert!(health.to_string().contains("unhealthy"));
+    }
+}
@@ -0,0 +1,219 @@
+//! cloud-init.
+
+use std::fs;
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use tracing::{debug, error, info, instrument};
+
+use crate::host_root::host_path;
+use crate::redact::Redacted;
+use crate::ProviderId;
+
+const INSTANCE_DATA_FILE: &str = "/run/cloud-init/instance-data.json";
+const CLOUD_ID_FILE: &str = "/run/cloud-init/cloud-id";
+
+/// Files written by cloud-init that identify the provider, in order of preference.
+pub(crate) const FILES: [&str; 2] = [INSTANCE_DATA_FILE, CLOUD_ID_FILE];
+
+#[derive(Serialize, Deserialize)]
+struct InstanceDataV1 {
+    cloud_name: Option<String>,
+    platform: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstanceData {
+    v1: InstanceDataV1,
+}
+
+/// Tries to identify the provider from the results of a previous cloud-init run on the host mounted at `root`.
+#[instrument(skip_all)]
+pub(crate) fn identify(root: &Path) -> Option<ProviderId> {
+    info!("Checking cloud-init");
+    let provider = check_instance_data_file(host_path(root, INSTANCE_DATA_FILE))
+        .or_else(|| check_cloud_id_file(host_path(root, CLOUD_ID_FILE)));
+
+    if let Some(provider) = provider {
+        info!("cloud-init identified {}", provider);
+    }
+
+    provider
+}
+
+/// Tries to identify the provider using the cloud-init instance data file.
+#[instrument(skip_all)]
+fn check_instance_data_file<P: AsRef<Path>>(instance_data_file: P) -> Option<ProviderId> {
+    debug!(
+        "Checking cloud-init instance data file: {}",
+        instance_data_file.as_ref().display()
+    );
+
+    if !instance_data_file.as_ref().is_file() {
+        return None;
+    }
+
+    let content = match fs::read_to_string(instance_data_file) {
+        Ok(content) => content,
+        Err(err) => {
+            error!("Error reading file: {:?}", err);
+            return None;
+        }
+    };
+
+    parse_instance_data(&content)
+}
+
+/// Tries to identify the provider from the contents of cloud-init's files, as returned by `read`, instead of the host.
+pub(crate) fn identify_with(read: impl Fn(&Path) -> Option<String>) -> Option<ProviderId> {
+    read(Path::new(INSTANCE_DATA_FILE))
+        .and_then(|content| parse_instance_data(&content))
+        .or_else(|| read(Path::new(CLOUD_ID_FILE)).and_then(|content| provider_for(&content)))
+}
+
+/// Identifies the provider named in the contents of the cloud-init instance data file.
+fn parse_instance_data(content: &str) -> Option<ProviderId> {
+    match serde_json::from_str::<InstanceData>(content) {
+        Ok(data) => [data.v1.cloud_name, data.v1.platform]
+            .iter()
+            .flatten()
+            .find_map(|name| provider_for(name)),
+        Err(err) => {
+            error!("Error parsing file: {:?}", Redacted(&err));
+            None
+        }
+    }
+}
+
+/// Tries to identify the provider using the cloud-init cloud ID file.
+#[instrument(skip_all)]
+fn check_cloud_id_file<P: AsRef<Path>>(cloud_id_file: P) -> Option<ProviderId> {
+    debug!(
+        "Checking cloud-init cloud ID file: {}",
+        cloud_id_file.as_ref().display()
+    );
+
+    if !cloud_id_file.as_ref().is_file() {
+        return None;
+    }
+
+    match fs::read_to_string(cloud_id_file) {
+        Ok(content) => provider_for(&content),
+        Err(err) => {
+            error!("Error reading file: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Maps a cloud-init cloud name, cloud ID or datasource name to a provider.
+fn provider_for(name: &str) -> Option<ProviderId> {
+    let name = name.trim().to_lowercase();
+    let name = name.strip_prefix("datasource").unwrap_or(&name);
+
+    match name {
+        "akamai" | "linode" => Some(ProviderId::Akamai),
+        "aliyun" | "alibaba" => Some(ProviderId::Alibaba),
+        "aws" | "aws-china" | "aws-gov" | "ec2" => Some(ProviderId::AWS),
+        "azure" | "azure-china" | "azure-gov" => Some(ProviderId::Azure),
+        "digitalocean" => Some(ProviderId::DigitalOcean),
+        "gce" | "gcp" => Some(ProviderId::GCP),
+        "oci" | "oracle" => Some(ProviderId::OCI),
+        "configdrive" | "openstack" => Some(ProviderId::OpenStack),
+        "vmware" => Some(ProviderId::VMware),
+        "vultr" => Some(ProviderId::Vultr),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_check_instance_data_file_success() -> Result<()> {
+        let mut instance_data_file = NamedTempFile::new()?;
+        instance_data_file.write_all(br#"{"v1":{"cloud_name":"aws","platform":"ec2"}}"#)?;
+
+        let result = check_instance_data_file(instance_data_file.path());
+
+        assert_eq!(result, Some(ProviderId::AWS));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_instance_data_file_falls_back_to_platform() -> Result<()> {
+        let mut instance_data_file = NamedTempFile::new()?;
+        instance_data_file.write_all(br#"{"v1":{"cloud_name":"unknown","platform":"gce"}}"#)?;
+
+        let result = check_instance_data_file(instance_data_file.path());
+
+        assert_eq!(result, Some(ProviderId::GCP));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_instance_data_file_failure() -> Result<()> {
+        let mut instance_data_file = NamedTempFile::new()?;
+        instance_data_file.write_all(br#"{"v1":{"cloud_name":"nocloud","platform":"nocloud"}}"#)?;
+
+        let result = check_instance_data_file(instance_data_file.path());
+
+        assert_eq!(result, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_cloud_id_file_success() -> Result<()> {
+        let mut cloud_id_file = NamedTempFile::new()?;
+        cloud_id_file.write_all(b"azure-china\n")?;
+
+        let result = check_cloud_id_file(cloud_id_file.path());
+
+        assert_eq!(result, Some(ProviderId::Azure));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_cloud_id_file_failure() -> Result<()> {
+        let cloud_id_file = NamedTempFile::new()?;
+
+        let result = check_cloud_id_file(cloud_id_file.path());
+
+        assert_eq!(result, None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_identify_with() {
+        let files = [(CLOUD_ID_FILE, "oracle\n")];
+        let read = |path: &Path| {
+            files
+                .iter()
+                .find(|(file, _)| Path::new(file) == path)
+                .map(|(_, content)| content.to_string())
+        };
+
+        assert_eq!(identify_with(read), Some(ProviderId::OCI));
+        assert_eq!(identify_with(|_| None), None);
+    }
+
+    #[test]
+    fn test_provider_for_datasource_names() {
+        assert_eq!(provider_for("DataSourceEc2"), Some(ProviderId::AWS));
+        assert_eq!(provider_for("ConfigDrive"), Some(ProviderId::OpenStack));
+        assert_eq!(provider_for("AliYun"), Some(ProviderId::Alibaba));
+        assert_eq!(provider_for("DataSourceVMware"), Some(ProviderId::VMware));
+        assert_eq!(provider_for("NoCloud"), None);
+    }
+}
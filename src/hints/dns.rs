@@ -0,0 +1,243 @@
+//! DNS resolver configuration.
+//!
+//! Provider DHCP servers hand out well-known DNS search domains (e.g. `ec2.internal`, `google.internal` or
+//! `openstacklocal`), which are read from `/etc/resolv.conf`, or from `ipconfig /all` on Windows. Search domains can be
+//! inherited from elsewhere (e.g. a VPN), so they only hint at a provider, unless detection is limited to local checks.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use tracing::{debug, error, instrument};
+
+use crate::host_root::host_path;
+use crate::ProviderId;
+
+pub(crate) const RESOLV_CONF_FILE: &str = "/etc/resolv.conf";
+const IPCONFIG_COMMAND: &str = "ipconfig";
+
+/// DNS search domains assigned by provider DHCP servers.
+const DNS_DOMAINS: &[(&str, ProviderId)] = &[
+    ("ec2.internal", ProviderId::AWS),
+    ("compute.internal", ProviderId::AWS),
+    ("internal.cloudapp.net", ProviderId::Azure),
+    ("reddog.microsoft.com", ProviderId::Azure),
+    ("google.internal", ProviderId::GCP),
+    ("oraclevcn.com", ProviderId::OCI),
+    ("openstacklocal", ProviderId::OpenStack),
+];
+
+/// Returns the providers hinted at by the DNS search domains of the host mounted at `root`, in the order the domains are
+/// configured.
+#[instrument(skip_all)]
+pub(crate) fn identify(root: &Path) -> Vec<ProviderId> {
+    let domains = if cfg!(windows) {
+        read_ipconfig()
+    } else {
+        read_resolv_conf(host_path(root, RESOLV_CONF_FILE))
+    };
+    debug!("DNS search domains: {:?}", domains);
+
+    hinted_by(&domains)
+}
+
+/// Returns the providers hinted at by the resolver configuration file, as returned by `read`, instead of the host.
+pub(crate) fn identify_with(read: impl Fn(&Path) -> Option<String>) -> Vec<ProviderId> {
+    let domains = read(Path::new(RESOLV_CONF_FILE))
+        .map(|content| parse_resolv_conf(&content))
+        .unwrap_or_default();
+
+    hinted_by(&domains)
+}
+
+/// Returns the providers hinted at by DNS search domains, in order and without duplicates.
+fn hinted_by(domains: &[String]) -> Vec<ProviderId> {
+    let mut hinted = Vec::new();
+
+    for provider in domains
+        .iter()
+        .filter_map(|domain| provider_for_domain(domain))
+    {
+        if !hinted.contains(&provider) {
+            hinted.push(provider);
+        }
+    }
+
+    hinted
+}
+
+/// Reads the DNS search domains of a resolver configuration file.
+fn read_resolv_conf<P: AsRef<Path>>(resolv_conf_file: P) -> Vec<String> {
+    debug!(
+        "Checking resolver configuration file: {}",
+        resolv_conf_file.as_ref().display()
+    );
+
+    if !resolv_conf_file.as_ref().is_file() {
+        return Vec::new();
+    }
+
+    match fs::read_to_string(resolv_conf_file) {
+        Ok(content) => parse_resolv_conf(&content),
+        Err(err) => {
+            error!("Error reading file: {:?}", err);
+            Vec::new()
+        }
+    }
+}
+
+/// Parses the DNS search domains from the `search` and `domain` lines of a resolver configuration file.
+fn parse_resolv_conf(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut words = line.split_whitespace();
+            matches!(words.next(), Some("search" | "domain")).then_some(words)
+        })
+        .flatten()
+        .map(str::to_string)
+        .collect()
+}
+
+/// Reads the DNS search domains reported by `ipconfig /all`.
+fn read_ipconfig() -> Vec<String> {
+    debug!("Checking {}", IPCONFIG_COMMAND);
+
+    match Command::new(IPCONFIG_COMMAND).arg("/all").output() {
+        Ok(output) => parse_ipconfig(&String::from_utf8_lossy(&output.stdout)),
+        Err(err) => {
+            debug!("Error running {}: {:?}", IPCONFIG_COMMAND, err);
+            Vec::new()
+        }
+    }
+}
+
+/// Parses the DNS search domains from the output of `ipconfig /all`.
+///
+/// The search list spans several lines, one domain per line, while the connection-specific suffix of each adapter
+/// fits on a single line.
+fn parse_ipconfig(output: &str) -> Vec<String> {
+    let mut domains = Vec::new();
+    let mut in_search_list = false;
+
+    for line in output.lines() {
+        if let Some((key, value)) = line
+            .split_once(':')
+            .filter(|(key, _)| key.contains("DNS Suffix"))
+        {
+            in_search_list = key.contains("Search List");
+            domains.push(value.trim().to_string());
+        } else if in_search_list && line.starts_with(char::is_whitespace) && !line.contains(':') {
+            domains.push(line.trim().to_string());
+        } else {
+            in_search_list = false;
+        }
+    }
+
+    domains.retain(|domain| !domain.is_empty());
+    domains
+}
+
+/// Maps a DNS search domain to a provider.
+fn provider_for_domain(domain: &str) -> Option<ProviderId> {
+    let domain = domain.trim_end_matches('.').to_lowercase();
+
+    DNS_DOMAINS
+        .iter()
+        .find(|(suffix, _)| {
+            domain == *suffix
+                || domain
+                    .strip_suffix(suffix)
+                    .is_some_and(|prefix| prefix.ends_with('.'))
+        })
+        .map(|&(_, provider)| provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+
+    #[test]
+    fn test_read_resolv_conf() -> Result<()> {
+        let mut resolv_conf_file = NamedTempFile::new()?;
+        resolv_conf_file.write_all(
+            b"# Generated\nnameserver 168.63.129.16\nsearch abc.bx.internal.cloudapp.net. example.com\n",
+        )?;
+
+        let result = read_resolv_conf(resolv_conf_file.path());
+
+        assert_eq!(result, ["abc.bx.internal.cloudapp.net.", "example.com"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_resolv_conf_missing() {
+        assert!(read_resolv_conf("/nonexistent/resolv.conf").is_empty());
+    }
+
+    #[test]
+    fn test_identify_with() {
+        let read =
+            |_: &Path| Some("search google.internal ec2.internal c.google.internal\n".to_string());
+
+        assert_eq!(identify_with(read), [ProviderId::GCP, ProviderId::AWS]);
+        assert!(identify_with(|_| None).is_empty());
+    }
+
+    #[test]
+    fn test_parse_ipconfig() {
+        let output = "\
+Windows IP Configuration
+
+   Host Name . . . . . . . . . . . . : EC2AMAZ-ABC123
+   Primary Dns Suffix  . . . . . . . :
+   DNS Suffix Search List. . . . . . : us-east-1.ec2-utilities.amazonaws.com
+                                       ec2.internal
+   IP Routing Enabled. . . . . . . . : No
+
+Ethernet adapter Ethernet:
+
+   Connection-specific DNS Suffix  . : us-east-1.compute.internal
+   Description . . . . . . . . . . . : Amazon Elastic Network Adapter
+";
+
+        assert_eq!(
+            parse_ipconfig(output),
+            [
+                "us-east-1.ec2-utilities.amazonaws.com",
+                "ec2.internal",
+                "us-east-1.compute.internal"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_provider_for_domain() {
+        assert_eq!(provider_for_domain("ec2.internal"), Some(ProviderId::AWS));
+        assert_eq!(
+            provider_for_domain("us-west-2.compute.internal"),
+            Some(ProviderId::AWS)
+        );
+        assert_eq!(
+            provider_for_domain("abc.bx.internal.cloudapp.net."),
+            Some(ProviderId::Azure)
+        );
+        assert_eq!(provider_for_domain("c.my-project.internal"), None);
+        assert_eq!(
+            provider_for_domain("google.internal"),
+            Some(ProviderId::GCP)
+        );
+        assert_eq!(
+            provider_for_domain("sub.vcn.oraclevcn.com"),
+            Some(ProviderId::OCI)
+        );
+        assert_eq!(provider_for_domain("notoraclevcn.com"), None);
+        assert_eq!(provider_for_domain("example.com"), None);
+    }
+}
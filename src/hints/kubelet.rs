@@ -0,0 +1,143 @@
+//! Kubelet configuration of Kubernetes nodes.
+//!
+//! Managed Kubernetes services often firewall the metadata server from pods, but the configuration the kubelet of the
+//! node is provisioned with still names the provider: in the provider ID of the node (e.g. `aws:///us-east-1a/i-...`),
+//! or in the address of the managed control plane (e.g. `*.eks.amazonaws.com`).
+
+use std::fs;
+use std::path::Path;
+
+use tracing::{debug, error, info, instrument};
+
+use crate::host_root::host_path;
+use crate::ProviderId;
+
+/// Files written when provisioning the kubelet that identify the provider, in order of preference.
+const FILES: [&str; 5] = [
+    "/var/lib/kubelet/config.yaml",
+    "/etc/kubernetes/kubelet/kubelet-config.json",
+    "/var/lib/kubelet/kubeconfig",
+    "/etc/kubernetes/kubelet.conf",
+    "/etc/kubernetes/azure.json",
+];
+
+/// Markers naming the provider in the kubelet configuration: prefixes of provider IDs, and domains of managed control
+/// planes.
+const MARKERS: [(&str, ProviderId); 13] = [
+    ("aws:///", ProviderId::AWS),
+    (".eks.amazonaws.com", ProviderId::AWS),
+    ("azure:///", ProviderId::Azure),
+    (".azmk8s.io", ProviderId::Azure),
+    ("\"aadClientId\"", ProviderId::Azure),
+    ("gce://", ProviderId::GCP),
+    ("digitalocean://", ProviderId::DigitalOcean),
+    ("linode://", ProviderId::Akamai),
+    ("vultr://", ProviderId::Vultr),
+    ("civo://", ProviderId::Civo),
+    ("yandex://", ProviderId::Yandex),
+    ("ocid1.instance.", ProviderId::OCI),
+    ("openstack:///", ProviderId::OpenStack),
+];
+
+/// Tries to identify the provider from the kubelet configuration of the Kubernetes node mounted at `root`.
+#[instrument(skip_all)]
+pub(crate) fn identify(root: &Path) -> Option<ProviderId> {
+    info!("Checking kubelet configuration");
+    let provider = identify_with(|path| {
+        let path = host_path(root, path);
+
+        if !path.is_file() {
+            return None;
+        }
+
+        debug!("Checking kubelet file: {}", path.display());
+
+        match fs::read_to_string(path) {
+            Ok(content) => Some(content),
+            Err(err) => {
+                error!("Error reading file: {:?}", err);
+                None
+            }
+        }
+    });
+
+    if let Some(provider) = provider {
+        info!("Kubelet configuration identified {}", provider);
+    }
+
+    provider
+}
+
+/// Tries to identify the provider from the contents of the kubelet's files, as returned by `read`, instead of the host.
+pub(crate) fn identify_with(read: impl Fn(&Path) -> Option<String>) -> Option<ProviderId> {
+    FILES
+        .iter()
+        .filter_map(|file| read(Path::new(file)))
+        .find_map(|content| provider_for(&content))
+}
+
+/// Identifies the provider named in the contents of a kubelet file.
+fn provider_for(content: &str) -> Option<ProviderId> {
+    MARKERS
+        .iter()
+        .find(|(marker, _)| content.contains(marker))
+        .map(|&(_, provider)| provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_for_provider_ids() {
+        assert_eq!(
+            provider_for("providerID: aws:///us-east-1a/i-0123456789abcdef0"),
+            Some(ProviderId::AWS)
+        );
+        assert_eq!(
+            provider_for(r#"{"providerID": "gce://project/us-central1-a/gke-node"}"#),
+            Some(ProviderId::GCP)
+        );
+        assert_eq!(
+            provider_for("providerID: ocid1.instance.oc1.iad.abc"),
+            Some(ProviderId::OCI)
+        );
+        assert_eq!(provider_for("providerID: kind://docker/kind/node"), None);
+    }
+
+    #[test]
+    fn test_provider_for_control_planes() {
+        assert_eq!(
+            provider_for("    server: https://ABCDEF.gr7.us-east-1.eks.amazonaws.com"),
+            Some(ProviderId::AWS)
+        );
+        assert_eq!(
+            provider_for("    server: https://aks-dns-1234.hcp.eastus.azmk8s.io:443"),
+            Some(ProviderId::Azure)
+        );
+        assert_eq!(provider_for("    server: https://10.0.0.1:6443"), None);
+    }
+
+    #[test]
+    fn test_identify_with() {
+        let files = [
+            (
+                "/var/lib/kubelet/config.yaml",
+                "kind: KubeletConfiguration\n",
+            ),
+            (
+                "/var/lib/kubelet/kubeconfig",
+                "clusters:\n- cluster:\n    server: https://1.hcp.westeurope.azmk8s.io:443\n",
+            ),
+        ];
+        let read = |path: &Path| {
+            files
+                .iter()
+                .find(|(file, _)| Path::new(file) == path)
+                .map(|(_, content)| content.to_string())
+        };
+
+        assert_eq!(identify_with(read), Some(ProviderId::Azure));
+        assert_eq!(identify_with(|_| None), None);
+    }
+}
@@ -0,0 +1,15 @@
+//! Hint modules.
+//!
+//! Hints are signals left on the host by other tooling that name the provider directly. Unlike providers, a single hint
+//! can identify any of the supported providers. Prior hints are weaker, and only decide the order providers are probed
+//! in.
+
+pub(crate) mod cloudinit;
+pub(crate) mod dns;
+#[cfg(feature = "kubernetes")]
+pub(crate) mod kubelet;
+#[cfg(feature = "network")]
+pub(crate) mod network;
+pub(crate) mod prior;
+#[cfg(feature = "systemd")]
+pub(crate) mod virt;
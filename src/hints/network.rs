@@ -0,0 +1,154 @@
+//! Routing table and ARP cache.
+//!
+//! The metadata server is only reachable through a route covering its link-local address, and the gateway of that route
+//! is often run by the provider's virtual network, with a MAC address that identifies it (e.g. the `fa:16:3e` prefix
+//! of OpenStack Neutron ports). Both are read from procfs, without sending any traffic.
+
+use std::fs;
+use std::net::Ipv4Addr;
+
+use tracing::{debug, info, instrument};
+
+use crate::{normalize_mac, ProviderId};
+
+const ROUTE_FILE: &str = "/proc/net/route";
+const ARP_FILE: &str = "/proc/net/arp";
+const METADATA_ADDRESS: Ipv4Addr = Ipv4Addr::new(169, 254, 169, 254);
+
+/// Prefixes of the MAC addresses of gateways (and ports) of provider virtual networks.
+const MAC_PREFIXES: &[(&str, ProviderId)] = &[
+    ("12:34:56:78:9a:bc", ProviderId::Azure),
+    ("ee:ff:ff:ff:ff:ff", ProviderId::Alibaba),
+    ("42:01:", ProviderId::GCP),
+    ("fa:16:3e:", ProviderId::OpenStack),
+];
+
+/// Tries to identify the provider from the MAC address of the gateway the metadata server is reached through.
+///
+/// This is an auxiliary signal: it only corroborates matches made by providers and never produces a match on its
+/// own, since MAC addresses can be assigned freely.
+#[instrument(skip_all)]
+pub(crate) fn identify() -> Option<ProviderId> {
+    info!("Checking routing table and ARP cache");
+
+    let routes = fs::read_to_string(ROUTE_FILE).ok()?;
+    let Some(next_hop) = next_hop(&routes, METADATA_ADDRESS) else {
+        debug!("No route to the metadata server");
+        return None;
+    };
+    debug!("Metadata server reached through {}", next_hop);
+
+    let arp = fs::read_to_string(ARP_FILE).ok()?;
+    let mac = neighbor_mac(&arp, next_hop)?;
+
+    provider_for_mac(&mac)
+}
+
+/// Returns the address through which an address is reached, following the most specific route covering it.
+///
+/// The address itself is returned if it is on-link.
+fn next_hop(routes: &str, address: Ipv4Addr) -> Option<Ipv4Addr> {
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let destination = parse_hex_address(fields.get(1)?)?;
+            let gateway = parse_hex_address(fields.get(2)?)?;
+            let mask = u32::from(parse_hex_address(fields.get(7)?)?);
+
+            (u32::from(address) & mask == u32::from(destination)).then_some((mask, gateway))
+        })
+        .max_by_key(|&(mask, _)| mask.count_ones())
+        .map(|(_, gateway)| {
+            if gateway.is_unspecified() {
+                address
+            } else {
+                gateway
+            }
+        })
+}
+
+/// Parses an address of the routing table, in little-endian hexadecimal form (e.g. `0100000A` for `10.0.0.1`).
+fn parse_hex_address(hex: &str) -> Option<Ipv4Addr> {
+    u32::from_str_radix(hex, 16)
+        .ok()
+        .map(|value| Ipv4Addr::from(value.to_le_bytes()))
+}
+
+/// Returns the MAC address of a neighbor from the ARP cache, if it has been resolved.
+fn neighbor_mac(arp: &str, address: Ipv4Addr) -> Option<String> {
+    arp.lines()
+        .skip(1)
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.first().and_then(|ip| ip.parse().ok()) == Some(address))
+        .and_then(|fields| normalize_mac(fields.get(3)?))
+        .filter(|mac| mac != "00:00:00:00:00:00")
+}
+
+/// Maps the MAC address of a gateway to a provider.
+fn provider_for_mac(mac: &str) -> Option<ProviderId> {
+    MAC_PREFIXES
+        .iter()
+        .find(|(prefix, _)| mac.starts_with(prefix))
+        .map(|&(_, provider)| provider)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ROUTES: &str = "\
+Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT
+eth0\t00000000\t0100000A\t0003\t0\t0\t0\t00000000\t0\t0\t0
+eth0\t0000000A\t00000000\t0001\t0\t0\t0\t00FFFFFF\t0\t0\t0
+eth0\tFEA9FEA9\t0200000A\t0007\t0\t0\t0\tFFFFFFFF\t0\t0\t0
+";
+
+    const ARP: &str = "\
+IP address       HW type     Flags       HW address            Mask     Device
+10.0.0.1         0x1         0x2         12:34:56:78:9a:bc     *        eth0
+10.0.0.2         0x1         0x0         00:00:00:00:00:00     *        eth0
+";
+
+    #[test]
+    fn test_next_hop() {
+        assert_eq!(
+            next_hop(ROUTES, METADATA_ADDRESS),
+            Some(Ipv4Addr::new(10, 0, 0, 2))
+        );
+        assert_eq!(
+            next_hop(ROUTES, Ipv4Addr::new(10, 0, 0, 5)),
+            Some(Ipv4Addr::new(10, 0, 0, 5))
+        );
+        assert_eq!(
+            next_hop(ROUTES, Ipv4Addr::new(1, 1, 1, 1)),
+            Some(Ipv4Addr::new(10, 0, 0, 1))
+        );
+        assert_eq!(next_hop("", METADATA_ADDRESS), None);
+    }
+
+    #[test]
+    fn test_neighbor_mac() {
+        assert_eq!(
+            neighbor_mac(ARP, Ipv4Addr::new(10, 0, 0, 1)).as_deref(),
+            Some("12:34:56:78:9a:bc")
+        );
+        assert_eq!(neighbor_mac(ARP, Ipv4Addr::new(10, 0, 0, 2)), None);
+        assert_eq!(neighbor_mac(ARP, Ipv4Addr::new(10, 0, 0, 3)), None);
+    }
+
+    #[test]
+    fn test_provider_for_mac() {
+        assert_eq!(
+            provider_for_mac("12:34:56:78:9a:bc"),
+            Some(ProviderId::Azure)
+        );
+        assert_eq!(
+            provider_for_mac("fa:16:3e:12:34:56"),
+            Some(ProviderId::OpenStack)
+        );
+        assert_eq!(provider_for_mac("42:01:0a:80:00:01"), Some(ProviderId::GCP));
+        assert_eq!(provider_for_mac("0e:1f:2a:3b:4c:5d"), None);
+    }
+}
@@ -0,0 +1,127 @@
+//! Prior hints.
+//!
+//! Cheap local signals that point towards a provider without proving it: the DMI attributes providers check anyway,
+//! environment variables set by provider tooling and the DNS search domains handed out by provider DHCP servers (see
+//! [dns]). They only decide the order in which providers are probed, so that the likely provider is
+//! probed first.
+
+use std::path::Path;
+
+use tracing::{debug, info, instrument};
+
+use crate::checks::{Check, Probe};
+use crate::hints::dns;
+use crate::{host_root, smbios, ProviderId};
+
+/// Environment variables set by provider tooling, serverless runtimes and SDK configuration.
+const ENV_VARS: &[(&str, ProviderId)] = &[
+    ("AWS_EXECUTION_ENV", ProviderId::AWS),
+    ("AWS_REGION", ProviderId::AWS),
+    ("AWS_DEFAULT_REGION", ProviderId::AWS),
+    ("ECS_CONTAINER_METADATA_URI_V4", ProviderId::AWS),
+    ("IDENTITY_ENDPOINT", ProviderId::Azure),
+    ("MSI_ENDPOINT", ProviderId::Azure),
+    ("WEBSITE_INSTANCE_ID", ProviderId::Azure),
+    ("GCE_METADATA_HOST", ProviderId::GCP),
+    ("GOOGLE_CLOUD_PROJECT", ProviderId::GCP),
+    ("K_SERVICE", ProviderId::GCP),
+    ("ALIBABA_CLOUD_REGION_ID", ProviderId::Alibaba),
+    ("OCI_RESOURCE_PRINCIPAL_VERSION", ProviderId::OCI),
+    ("OS_AUTH_URL", ProviderId::OpenStack),
+];
+
+/// Returns the providers hinted at by the host mounted at `root`, strongest hint first.
+///
+/// DMI attributes are set by the hypervisor and are the strongest hint, followed by environment variables and DNS
+/// search domains, which can be inherited from elsewhere (e.g. a developer's shell or a VPN).
+#[instrument(skip_all)]
+pub(crate) fn identify(providers: &[crate::P], root: &Path) -> Vec<ProviderId> {
+    info!("Checking prior hints");
+    let mut hinted = Vec::new();
+
+    let dmi = providers
+        .iter()
+        .filter(|provider| {
+            provider
+                .checks()
+                .into_iter()
+                .any(|check| check_dmi(&host_root::rebase(check, root)))
+        })
+        .map(|provider| provider.identifier());
+    let env = check_env(std::env::vars());
+    let dns = dns::identify(root);
+
+    for provider in dmi.chain(env).chain(dns) {
+        if !hinted.contains(&provider) {
+            hinted.push(provider);
+        }
+    }
+
+    debug!("Prior hints: {:?}", hinted);
+    hinted
+}
+
+/// Runs a check if it only inspects DMI attributes, which is cheap enough to do before any probing.
+fn check_dmi(check: &Check) -> bool {
+    match &check.probe {
+        Probe::Dmi { path, matches } => {
+            smbios::read_field(path).is_some_and(|content| matches(&content))
+        }
+        _ => false,
+    }
+}
+
+/// Returns the providers hinted at by environment variables.
+fn check_env<I: IntoIterator<Item = (String, String)>>(vars: I) -> Vec<ProviderId> {
+    let vars: Vec<(String, String)> = vars.into_iter().collect();
+
+    ENV_VARS
+        .iter()
+        .filter(|(name, _)| {
+            vars.iter()
+                .any(|(key, value)| key == name && !value.is_empty())
+        })
+        .map(|&(_, provider)| provider)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::Confidence;
+
+    #[test]
+    fn test_check_dmi() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        vendor_file.write_all(b"Vultr\n")?;
+
+        let check = Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.path(),
+            |c| c.contains("Vultr"),
+        );
+        assert!(check_dmi(&check));
+
+        let check = Check::exists("exists", Confidence::Unverified, vendor_file.path());
+        assert!(!check_dmi(&check));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_env() {
+        let vars = [
+            ("HOME".to_string(), "/root".to_string()),
+            ("K_SERVICE".to_string(), "hello".to_string()),
+            ("AWS_REGION".to_string(), String::new()),
+        ];
+
+        assert_eq!(check_env(vars), [ProviderId::GCP]);
+    }
+}
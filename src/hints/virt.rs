@@ -0,0 +1,62 @@
+//! systemd-detect-virt.
+
+use std::process::Command;
+
+use tracing::{debug, info, instrument};
+
+use crate::ProviderId;
+
+const COMMAND: &str = "systemd-detect-virt";
+
+/// Tries to identify the provider from the hypervisor reported by `systemd-detect-virt`.
+///
+/// This is an auxiliary signal: it only corroborates matches made by providers and never produces a match on its
+/// own, since the same hypervisor can be used outside of the provider's cloud.
+#[instrument(skip_all)]
+pub(crate) fn identify() -> Option<ProviderId> {
+    info!("Checking {}", COMMAND);
+
+    let output = match Command::new(COMMAND).arg("--vm").output() {
+        Ok(output) => output,
+        Err(err) => {
+            debug!("Error running {}: {:?}", COMMAND, err);
+            return None;
+        }
+    };
+
+    // The command exits with a non-zero status and prints "none" when no hypervisor is found
+    let hypervisor = String::from_utf8_lossy(&output.stdout);
+    debug!("{} reported hypervisor: {}", COMMAND, hypervisor.trim());
+
+    provider_for(&hypervisor)
+}
+
+/// Maps a `systemd-detect-virt` hypervisor name to a provider.
+fn provider_for(hypervisor: &str) -> Option<ProviderId> {
+    // Note that "oracle" refers to VirtualBox, not Oracle Cloud Infrastructure
+    match hypervisor.trim() {
+        "amazon" => Some(ProviderId::AWS),
+        "google" => Some(ProviderId::GCP),
+        "microsoft" => Some(ProviderId::Azure),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_provider_for_success() {
+        assert_eq!(provider_for("amazon\n"), Some(ProviderId::AWS));
+        assert_eq!(provider_for("google\n"), Some(ProviderId::GCP));
+        assert_eq!(provider_for("microsoft\n"), Some(ProviderId::Azure));
+    }
+
+    #[test]
+    fn test_provider_for_failure() {
+        assert_eq!(provider_for("none\n"), None);
+        assert_eq!(provider_for("kvm\n"), None);
+        assert_eq!(provider_for("oracle\n"), None);
+    }
+}
@@ -0,0 +1,160 @@
+//! Inspecting the host from a container.
+//!
+//! Agents running in a container often have the host's filesystems bind-mounted under a directory of their own (e.g.
+//! `/host/sys` and `/host/run`), as the container's are isolated from the host's. Detecting with a host root (see
+//! [Detector::host_root](crate::Detector::host_root)) moves every file inspected (DMI attributes, cloud-init's files,
+//! DHCP leases, ...) under that directory, so detection inspects the host rather than the container.
+//!
+//! Node agents (e.g. of a Kubernetes DaemonSet) sharing the PID namespace of the node reach its root filesystem through
+//! that of its init process, at `/proc/1/root`, without mounting anything (see
+//! [Detector::node_agent](crate::Detector::node_agent)). Entering the mount namespace of the node instead isn't
+//! possible from a multithreaded process such as a tokio application, as `setns` rejects threads sharing their
+//! filesystem attributes.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use tracing::{debug, warn};
+
+use crate::checks::{Check, Probe};
+
+/// Root directory of the host when not running in a container.
+pub(crate) const ROOT: &str = "/";
+
+/// Root filesystem of the init process, which is the host's when sharing its PID namespace.
+pub(crate) const INIT_ROOT: &str = "/proc/1/root";
+
+/// Moves a path of the host under a root directory.
+pub(crate) fn host_path(root: &Path, path: impl AsRef<Path>) -> PathBuf {
+    let path = path.as_ref();
+    root.join(path.strip_prefix(ROOT).unwrap_or(path))
+}
+
+/// Returns the root filesystem of the init process, if it can be read (which requires the `CAP_SYS_PTRACE`
+/// capability, e.g. in a privileged container).
+pub(crate) fn init_root() -> Option<PathBuf> {
+    accessible(Path::new(INIT_ROOT))
+}
+
+fn accessible(root: &Path) -> Option<PathBuf> {
+    match fs::read_dir(root) {
+        Ok(_) => {
+            debug!("Inspecting host through {}", root.display());
+            Some(root.to_path_buf())
+        }
+        Err(err) => {
+            warn!(
+                "Cannot read {}, inspecting the container instead: {:?}",
+                root.display(),
+                err
+            );
+            None
+        }
+    }
+}
+
+/// Moves a glob pattern matching paths of the host under a root directory, which is matched literally.
+pub(crate) fn host_pattern(root: &Path, pattern: &str) -> String {
+    if root == Path::new(ROOT) {
+        return pattern.to_string();
+    }
+
+    let root = glob::Pattern::escape(&root.display().to_string());
+    format!(
+        "{}/{}",
+        root.trim_end_matches('/'),
+        pattern.trim_start_matches('/')
+    )
+}
+
+/// Moves the files inspected by a check under a root directory. Checks that don't inspect files are unchanged.
+pub(crate) fn rebase(check: Check, root: &Path) -> Check {
+    let probe = match check.probe {
+        Probe::Dmi { path, matches } => Probe::Dmi {
+            path: host_path(root, path),
+            matches,
+        },
+        Probe::File { path, matches } => Probe::File {
+            path: host_path(root, path),
+            matches,
+        },
+        Probe::Glob { pattern, matches } => Probe::Glob {
+            pattern: host_pattern(root, &pattern),
+            matches,
+        },
+        Probe::Exists { path } => Probe::Exists {
+            path: host_path(root, path),
+        },
+        probe => probe,
+    };
+
+    Check { probe, ..check }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Confidence;
+
+    #[test]
+    fn test_host_path() {
+        let root = Path::new("/host");
+
+        assert_eq!(
+            host_path(root, "/sys/class/dmi/id/sys_vendor"),
+            Path::new("/host/sys/class/dmi/id/sys_vendor")
+        );
+        assert_eq!(
+            host_path(Path::new(ROOT), "/run/cloud-init/cloud-id"),
+            Path::new("/run/cloud-init/cloud-id")
+        );
+    }
+
+    #[test]
+    fn test_accessible() {
+        let root = tempfile::tempdir().unwrap();
+
+        assert_eq!(accessible(root.path()).as_deref(), Some(root.path()));
+        assert_eq!(accessible(&root.path().join("nonexistent")), None);
+    }
+
+    #[test]
+    fn test_host_pattern() {
+        assert_eq!(
+            host_pattern(Path::new("/host"), "/var/lib/dhcp/*.leases"),
+            "/host/var/lib/dhcp/*.leases"
+        );
+        assert_eq!(
+            host_pattern(Path::new("/mnt/[host]/"), "/var/lib/dhcp/*.leases"),
+            "/mnt/[[]host[]]/var/lib/dhcp/*.leases"
+        );
+        assert_eq!(
+            host_pattern(Path::new(ROOT), "/var/lib/dhcp/*.leases"),
+            "/var/lib/dhcp/*.leases"
+        );
+    }
+
+    #[test]
+    fn test_rebase() {
+        let root = Path::new("/host");
+        let dmi = rebase(
+            Check::dmi(
+                "sys_vendor",
+                Confidence::Unverified,
+                "/sys/class/dmi/id/sys_vendor",
+                |_| true,
+            ),
+            root,
+        );
+        let env = rebase(
+            Check::env("env", Confidence::Unverified, "HOME", |_| true),
+            root,
+        );
+
+        assert_eq!(
+            dmi.probe.target(),
+            "/host/sys/class/dmi/id/sys_vendor".to_string()
+        );
+        assert_eq!(env.probe.target(), "$HOME");
+    }
+}
@@ -0,0 +1,213 @@
+//! A ready-made [axum](https://crates.io/crates/axum) endpoint reporting the host's cloud provider as JSON.
+//!
+//! ## Optional
+//!
+//! This requires the `axum` feature to be enabled.
+//!
+//! ## Examples
+//!
+//! Serve the detection report at `/cloud-info`, next to the routes of the service.
+//!
+//! ```
+//! use axum::routing::get;
+//! use axum::Router;
+//! use cloud_detect::integrations::http;
+//! use cloud_detect::Detector;
+//!
+//! let app: Router = Router::new()
+//!     .route("/", get(|| async { "Hello" }))
+//!     .merge(http::router(Detector::new().timeout(1)));
+//!
+//! // Then serve `app` as usual, e.g. with `axum::serve`
+//! ```
+
+use axum::extract::State;
+use axum::routing::get;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+
+use crate::{Confidence, Detection, Detector, Evidence, SkipReason, SkippedProbe};
+
+/// Path the detection report is served at by [router].
+pub const PATH: &str = "/cloud-info";
+
+/// Represents the JSON report of a detection, as served by [handler].
+///
+/// # Examples
+///
+/// ```json
+/// {
+///   "provider": "aws",
+///   "display_name": "Amazon Web Services",
+///   "platform": "aws_ec2",
+///   "reseller": null,
+///   "confidence": "verified",
+///   "evidence": [{ "provider": "aws", "source": "metadata_server", "confidence": "verified" }],
+///   "skipped": [{ "provider": "azure", "check": "metadata_server", "reason": "timeout" }]
+/// }
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct DetectionReport {
+    /// Machine-readable code of the detected provider (see [ProviderId::short_code](crate::ProviderId::short_code)),
+    /// or `unknown`.
+    pub provider: String,
+    /// Human-readable name of the detected provider.
+    pub display_name: String,
+    /// Platform the host runs on within the detected provider, as named by OpenTelemetry (e.g. `aws_lambda`), if any.
+    pub platform: Option<String>,
+    /// Machine-readable code of the white-label cloud the host runs on (see [Detection::reseller](Detection#structfield.reseller)), if any.
+    pub reseller: Option<String>,
+    /// Confidence of the strongest evidence for the detected provider, if any.
+    pub confidence: Option<String>,
+    /// Every match collected during detection, in the order they arrived.
+    pub evidence: Vec<EvidenceReport>,
+    /// Checks skipped because a deadline passed (see [Detection::skipped](Detection#structfield.skipped)).
+    #[serde(default)]
+    pub skipped: Vec<SkippedReport>,
+}
+
+/// Represents a match collected during detection, in a [DetectionReport].
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EvidenceReport {
+    /// Machine-readable code of the provider that matched.
+    pub provider: String,
+    /// What matched (e.g. `metadata_server`).
+    pub source: String,
+    /// How strongly the match identifies the provider.
+    pub confidence: String,
+}
+
+/// Represents a check skipped during detection, in a [DetectionReport].
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct SkippedReport {
+    /// Machine-readable code of the provider the check belongs to.
+    pub provider: String,
+    /// Name of the check (e.g. `metadata_server`).
+    pub check: String,
+    /// Deadline that passed: `local_timeout` or `timeout`.
+    pub reason: String,
+}
+
+impl From<&Detection> for DetectionReport {
+    fn from(detection: &Detection) -> Self {
+        Self {
+            provider: detection.provider.short_code().to_string(),
+            display_name: detection.provider.display_name().to_string(),
+            platform: detection.platform.map(|platform| platform.to_string()),
+            reseller: detection
+                .reseller
+                .map(|reseller| reseller.short_code().to_string()),
+            confidence: detection.confidence().map(confidence),
+            evidence: detection
+                .evidence
+                .iter()
+                .map(EvidenceReport::from)
+                .collect(),
+            skipped: detection.skipped.iter().map(SkippedReport::from).collect(),
+        }
+    }
+}
+
+impl From<&Evidence> for EvidenceReport {
+    fn from(evidence: &Evidence) -> Self {
+        Self {
+            provider: evidence.provider.short_code().to_string(),
+            source: evidence.source.to_string(),
+            confidence: confidence(evidence.confidence),
+        }
+    }
+}
+
+impl From<&SkippedProbe> for SkippedReport {
+    fn from(skipped: &SkippedProbe) -> Self {
+        Self {
+            provider: skipped.provider.short_code().to_string(),
+            check: skipped.check.to_string(),
+            reason: match skipped.reason {
+                SkipReason::LocalTimeout => "local_timeout",
+                SkipReason::Timeout => "timeout",
+            }
+            .to_string(),
+        }
+    }
+}
+
+/// Name of a confidence level in a report.
+fn confidence(confidence: Confidence) -> String {
+    match confidence {
+        Confidence::Unverified => "unverified",
+        Confidence::Verified => "verified",
+        Confidence::Authoritative => "authoritative",
+    }
+    .to_string()
+}
+
+/// Runs detection with the detector of the router, and responds with its report.
+///
+/// Detection runs on every request; clone the detector into a caching layer (e.g. with
+/// [DetectService](crate::DetectService) and the `tower` feature) if the endpoint is polled often.
+pub async fn handler(State(detector): State<Detector>) -> Json<DetectionReport> {
+    let detection = detector.detect_detailed().await;
+    Json(DetectionReport::from(&detection))
+}
+
+/// Returns a router serving the detection report at [PATH], to merge into the router of a service.
+pub fn router(detector: Detector) -> Router {
+    Router::new().route(PATH, get(handler)).with_state(detector)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Match, ProviderId};
+
+    #[tokio::test]
+    async fn test_handler() {
+        let Json(report) = handler(State(Detector::new().providers(&[]))).await;
+
+        assert_eq!(report.provider, "unknown");
+        assert_eq!(report.confidence, None);
+        assert!(report.evidence.is_empty());
+        assert!(report.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_report_json() {
+        let matches = [Match::new(ProviderId::AWS, Confidence::Verified).source("metadata_server")];
+        let report = DetectionReport::from(&Detection::new(ProviderId::AWS, &matches));
+
+        let json = serde_json::to_value(&report).unwrap();
+
+        assert_eq!(json["provider"], "aws");
+        assert_eq!(json["confidence"], "verified");
+        assert_eq!(json["evidence"][0]["source"], "metadata_server");
+        assert_eq!(json["reseller"], serde_json::Value::Null);
+        assert_eq!(json["skipped"], serde_json::json!([]));
+
+        let detection =
+            Detection::new(ProviderId::OpenStack, &[]).reseller(Some(ProviderId::OpenTelekom));
+        let report = DetectionReport::from(&detection);
+        assert_eq!(report.provider, "openstack");
+        assert_eq!(report.reseller.as_deref(), Some("opentelekomcloud"));
+    }
+
+    #[test]
+    fn test_report_skipped() {
+        let skipped = SkippedProbe::new(ProviderId::Azure, "metadata_server", SkipReason::Timeout);
+        let detection = Detection::default().skipped(vec![skipped]);
+
+        let json = serde_json::to_value(DetectionReport::from(&detection)).unwrap();
+
+        assert_eq!(json["skipped"][0]["provider"], "azure");
+        assert_eq!(json["skipped"][0]["check"], "metadata_server");
+        assert_eq!(json["skipped"][0]["reason"], "timeout");
+
+        // Reports without skipped checks (e.g. served by earlier versions) still deserialize
+        let json = r#"{"provider":"aws","display_name":"AWS","platform":null,"confidence":null,"evidence":[]}"#;
+        let report: DetectionReport = serde_json::from_str(json).unwrap();
+        assert!(report.skipped.is_empty());
+    }
+}
@@ -0,0 +1,4 @@
+//! Integrations with web frameworks, for exposing detection results uniformly across services.
+
+#[cfg(feature = "axum")]
+pub mod http;
@@ -43,208 +43,224 @@
 //!     println!("Detected provider: {}", provider);
 //! }
 //! ```
+//!
+//! ## Detection flow
+//!
+//! A [Detector] probes every supported provider concurrently, in three phases:
+//!
+//! 1. Host signals: what cloud-init, the kubelet or the DNS search domains of the host already know about the provider.
+//! 2. Local checks of each provider: DMI attributes, files and environment variables of the host, within a budget of
+//!    their own (see [Detector::local_timeout]). A strong local signal, such as a DMI vendor only the provider sets,
+//!    ends detection right away.
+//! 3. Metadata server checks of each provider, within the detection timeout (see [Detector::timeout]).
+//!
+//! The matches of every phase are then resolved into a [Detection]: the most confident match of a specific provider
+//! wins over generic platforms (e.g. OpenStack) it may be built on, and the evidence of every match is kept along with
+//! what each check found (see [Outcome]). Matches of generic platforms alone don't end detection, as the specific
+//! provider may still be waiting on its metadata server (e.g. bare KVM is identified from CPUID right away on most
+//! clouds).
+//!
+//! ## Stability
+//!
+//! The crate follows semantic versioning, and its public types are designed so that supporting new providers, and
+//! reporting more about them, are minor releases:
+//!
+//! * Public enums (e.g. [ProviderId]) and the structs the crate reports (e.g. [Detection]) are `#[non_exhaustive]`,
+//!   so new variants and fields aren't breaking. Match enums with a wildcard arm, and build the reported structs
+//!   with their constructors (e.g. [Region::new]), e.g. in tests or when receiving them from another process.
+//! * Structs an application describes to the crate (e.g. [CustomProvider]) are built with constructors and builder
+//!   methods, rather than struct expressions.
+//! * The [prelude] gathers the stable surface most applications need.
+//!
+//! Items documented as being for tests (e.g. the `test_support` and `simulation` features) are exempt.
 
-use std::fmt::Debug;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::sync::{Arc, LazyLock};
-use std::time::Duration;
+#![cfg_attr(not(feature = "std"), no_std)]
+// Without a transport (`async` or `blocking-ureq`), `std` only describes providers and their checks, leaving most of
+// what runs them unused.
+#![cfg_attr(
+    all(
+        feature = "std",
+        not(any(feature = "async", feature = "blocking-ureq"))
+    ),
+    allow(dead_code, unused_imports, unused_macros)
+)]
 
-use async_trait::async_trait;
-use strum::Display;
-use tokio::sync::mpsc::Sender;
-use tokio::sync::{mpsc, Mutex, Notify};
-use tracing::{debug, instrument};
+extern crate alloc;
 
-use crate::providers::*;
+#[cfg(feature = "std")]
+pub use crate::budget::DEFAULT_LOCAL_TIMEOUT;
+#[cfg(feature = "config")]
+pub use crate::config::{Config, ProviderConfig, Weights};
+pub use crate::core::{
+    AccountInfo,
+    CredentialsInfo,
+    Naming,
+    NetworkInfo,
+    Platform,
+    ProviderGroup,
+    ProviderId,
+    Region,
+};
+#[cfg(feature = "std")]
+pub use crate::custom::{CustomProvider, EndpointMatch, FileMatch};
+#[cfg(feature = "async")]
+pub use crate::diagnostics::diagnose;
+#[cfg(feature = "std")]
+pub use crate::diagnostics::{Diagnostics, ProbeOutcome};
+#[cfg(feature = "std")]
+pub use crate::engine::*;
+#[cfg(feature = "async")]
+pub use crate::health::metadata_health;
+#[cfg(feature = "std")]
+pub use crate::health::MetadataHealth;
+#[cfg(feature = "std")]
+pub use crate::probe_log::ProbeLogLevel;
+#[cfg(feature = "std")]
+pub use crate::provider_check::{CheckContext, CheckKind, CheckOutcome, ProviderCheck};
+#[cfg(feature = "std")]
+pub use crate::redact::set_redaction;
+#[cfg(feature = "tower")]
+pub use crate::service::{DetectRequest, DetectService};
+#[cfg(feature = "std")]
+pub use crate::snapshot::{capture_snapshot, detect_from_snapshot, CapturedResponse, HostSnapshot};
+#[cfg(feature = "test_support")]
+pub use crate::test_support as testing;
+#[cfg(feature = "async")]
+pub use crate::watch::{watch, Watch};
 
-#[cfg(feature = "blocking")]
+#[cfg(feature = "std")]
+pub(crate) mod background;
+#[cfg(feature = "std")]
+pub(crate) mod bind;
+#[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
 pub mod blocking;
-pub(crate) mod providers;
-
-/// Maximum time allowed for detection.
-pub const DEFAULT_DETECTION_TIMEOUT: u64 = 5; // seconds
-
-/// Represents an identifier for a cloud service provider.
-#[non_exhaustive]
-#[derive(Debug, Default, Display, Eq, PartialEq)]
-pub enum ProviderId {
-    /// Unknown cloud service provider.
-    #[default]
-    #[strum(serialize = "unknown")]
-    Unknown,
-    /// Alibaba Cloud.
-    #[strum(serialize = "alibaba")]
-    Alibaba,
-    /// Amazon Web Services (AWS).
-    #[strum(serialize = "aws")]
-    AWS,
-    /// Microsoft Azure.
-    #[strum(serialize = "azure")]
-    Azure,
-    /// DigitalOcean.
-    #[strum(serialize = "digitalocean")]
-    DigitalOcean,
-    /// Google Cloud Platform (GCP).
-    #[strum(serialize = "gcp")]
-    GCP,
-    /// Oracle Cloud Infrastructure (OCI).
-    #[strum(serialize = "oci")]
-    OCI,
-    /// OpenStack.
-    #[strum(serialize = "openstack")]
-    OpenStack,
-    /// Vultr.
-    #[strum(serialize = "vultr")]
-    Vultr,
-}
-
-/// Represents a cloud service provider.
-#[async_trait]
-pub(crate) trait Provider: Send + Sync {
-    fn identifier(&self) -> ProviderId;
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration);
-}
+#[cfg(feature = "std")]
+pub(crate) mod budget;
+#[cfg(feature = "std")]
+pub(crate) mod checks;
+#[cfg(feature = "std")]
+pub(crate) mod clock;
+#[cfg(feature = "config")]
+mod config;
+pub mod core;
+#[cfg(feature = "std")]
+pub(crate) mod cpuid;
+#[cfg(feature = "std")]
+mod custom;
+#[cfg(feature = "std")]
+mod diagnostics;
+#[cfg(feature = "std")]
+mod engine;
+#[cfg(all(feature = "std", fuzzing))]
+#[doc(hidden)]
+pub mod fuzzing;
+#[cfg(feature = "std")]
+mod health;
+#[cfg(feature = "std")]
+pub(crate) mod hints;
+#[cfg(feature = "std")]
+pub(crate) mod host_root;
+#[cfg(feature = "axum")]
+pub mod integrations;
+#[cfg(feature = "metrics")]
+pub(crate) mod metrics;
+#[cfg(feature = "std")]
+mod opt_out;
+#[cfg(feature = "std")]
+pub(crate) mod platform;
+#[cfg(feature = "std")]
+pub(crate) mod precheck;
+pub mod prelude;
+#[cfg(feature = "std")]
+pub(crate) mod probe_log;
+#[cfg(feature = "std")]
+mod provider_check;
+#[cfg(all(feature = "std", any(test, feature = "simulation")))]
+pub(crate) mod provider_fixtures;
+#[cfg(feature = "std")]
+pub mod providers;
+#[cfg(feature = "std")]
+pub(crate) mod redact;
+#[cfg(feature = "std")]
+pub(crate) mod retry;
+#[cfg(feature = "std")]
+pub(crate) mod serverless;
+#[cfg(feature = "tower")]
+mod service;
+#[cfg(feature = "simulation")]
+pub mod simulation;
+#[cfg(feature = "std")]
+pub(crate) mod smbios;
+#[cfg(feature = "std")]
+mod snapshot;
+#[cfg(feature = "async")]
+pub(crate) mod spawn;
+#[cfg(feature = "test_support")]
+pub mod test_support;
+#[cfg(feature = "std")]
+pub(crate) mod tokens;
+#[cfg(feature = "async")]
+mod watch;
 
-type P = Arc<dyn Provider>;
-
-static PROVIDERS: LazyLock<Mutex<Vec<P>>> = LazyLock::new(|| {
-    Mutex::new(vec![
-        Arc::new(alibaba::Alibaba) as P,
-        Arc::new(aws::Aws) as P,
-        Arc::new(azure::Azure) as P,
-        Arc::new(digitalocean::DigitalOcean) as P,
-        Arc::new(gcp::Gcp) as P,
-        Arc::new(oci::Oci) as P,
-        Arc::new(openstack::OpenStack) as P,
-        Arc::new(vultr::Vultr) as P,
-    ])
-});
-
-/// Returns a list of currently supported providers.
-///
-/// # Examples
-///
-/// Print the list of supported providers.
-///
-/// ```
-/// use cloud_detect::supported_providers;
-///
-/// #[tokio::main]
-/// async fn main() {
-///     let providers = supported_providers().await;
-///     println!("Supported providers: {:?}", providers);
-/// }
-/// ```
-pub async fn supported_providers() -> Vec<String> {
-    let guard = PROVIDERS.lock().await;
-    let providers: Vec<String> = guard.iter().map(|p| p.identifier().to_string()).collect();
-
-    drop(guard);
-
-    providers
-}
-
-/// Detects the host's cloud provider.
-///
-/// Returns [ProviderId::Unknown] if the detection failed or timed out. If the detection was successful, it returns
-/// a value from [ProviderId](enum.ProviderId.html).
-///
-/// # Arguments
-///
-/// * `timeout` - Maximum time (seconds) allowed for detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](constant.DEFAULT_DETECTION_TIMEOUT.html) if `None`.
-///
-/// # Examples
-///
-/// Detect the cloud provider and print the result (with default timeout).
-///
-/// ```
-/// use cloud_detect::detect;
-///
-/// #[tokio::main]
-/// async fn main() {
-///     let provider = detect(None).await;
-///     println!("Detected provider: {}", provider);
-/// }
-/// ```
-///
-/// Detect the cloud provider and print the result (with custom timeout).
-///
-/// ```
-/// use cloud_detect::detect;
-///
-/// #[tokio::main]
-/// async fn main() {
-///     let provider = detect(Some(10)).await;
-///     println!("Detected provider: {}", provider);
-/// }
-/// ```
-#[instrument]
-pub async fn detect(timeout: Option<u64>) -> ProviderId {
-    let timeout = Duration::from_secs(timeout.unwrap_or(DEFAULT_DETECTION_TIMEOUT));
-    let (tx, mut rx) = mpsc::channel::<ProviderId>(1);
-    let guard = PROVIDERS.lock().await;
-    let provider_entries: Vec<P> = guard.iter().cloned().collect();
-    let providers_count = provider_entries.len();
-    let mut handles = Vec::with_capacity(providers_count);
-
-    // Create a counter that will be decremented as tasks complete
-    let counter = Arc::new(AtomicUsize::new(providers_count));
-    let complete = Arc::new(Notify::new());
+/// Compile-time guarantees that public types can be shared across threads (e.g. a detector in a `static`), and that
+/// configuration and result types can be cloned.
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use static_assertions::assert_impl_all;
 
-    for provider in provider_entries {
-        let tx = tx.clone();
-        let counter = counter.clone();
-        let complete = complete.clone();
+    use super::*;
 
-        handles.push(tokio::spawn(async move {
-            debug!("Spawning task for provider: {}", provider.identifier());
-            provider.identify(tx, timeout).await;
+    assert_impl_all!(ProviderId: Send, Sync, Clone, Copy);
+    assert_impl_all!(ProviderGroup: Send, Sync, Clone, Copy);
+    assert_impl_all!(Naming: Send, Sync, Clone, Copy);
+    assert_impl_all!(Platform: Send, Sync, Clone, Copy);
+    assert_impl_all!(Confidence: Send, Sync, Clone, Copy);
+    assert_impl_all!(Region: Send, Sync, Clone);
+    assert_impl_all!(AccountInfo: Send, Sync, Clone);
+    assert_impl_all!(NetworkInfo: Send, Sync, Clone);
+    assert_impl_all!(CredentialsInfo: Send, Sync, Clone);
 
-            // Decrement counter and notify if we're the last task
-            if counter.fetch_sub(1, Ordering::SeqCst) == 1 {
-                complete.notify_one();
-            }
-        }));
-    }
+    assert_impl_all!(Detector: Send, Sync, Clone);
+    assert_impl_all!(Detection: Send, Sync, Clone);
+    assert_impl_all!(Evidence: Send, Sync, Clone);
+    assert_impl_all!(Outcome: Send, Sync, Clone, Copy);
+    assert_impl_all!(ProviderVerdict: Send, Sync, Clone);
+    assert_impl_all!(SkippedProbe: Send, Sync, Clone, Copy);
+    assert_impl_all!(ProbeLogLevel: Send, Sync, Clone, Copy);
+    assert_impl_all!(Diagnostics: Send, Sync, Clone);
+    assert_impl_all!(ProbeOutcome: Send, Sync, Clone);
+    assert_impl_all!(MetadataHealth: Send, Sync, Clone);
+    assert_impl_all!(HostSnapshot: Send, Sync, Clone);
+    assert_impl_all!(CapturedResponse: Send, Sync, Clone);
+    assert_impl_all!(CustomProvider: Send, Sync, Clone);
+    assert_impl_all!(FileMatch: Send, Sync, Clone);
+    assert_impl_all!(EndpointMatch: Send, Sync, Clone);
+    // Watches own their background task, so they are moved rather than cloned
+    assert_impl_all!(Watch: Send, Sync);
 
-    tokio::select! {
-        biased;
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    assert_impl_all!(blocking::Watcher: Send, Sync);
 
-        // Priority 1: If we receive an identifier, return it immediately
-        res = rx.recv() => {
-            debug!("Received result from channel: {:?}", res);
-            res.unwrap_or_default()
-        }
+    #[cfg(feature = "config")]
+    assert_impl_all!(Config: Send, Sync, Clone);
+    #[cfg(feature = "config")]
+    assert_impl_all!(ProviderConfig: Send, Sync, Clone);
+    #[cfg(feature = "config")]
+    assert_impl_all!(Weights: Send, Sync, Clone);
 
-        // Priority 2: If all tasks complete without finding an identifier
-        _ = complete.notified() => {
-            debug!("All providers have finished identifying");
-            Default::default()
-        }
+    #[cfg(feature = "axum")]
+    assert_impl_all!(integrations::http::DetectionReport: Send, Sync, Clone);
 
-        // Priority 3: If we time out
-        _ = tokio::time::sleep(timeout) => {
-            debug!("Detection timed out");
-            Default::default()
-        }
-    }
-}
+    #[cfg(feature = "tower")]
+    assert_impl_all!(DetectService: Send, Sync, Clone);
+    #[cfg(feature = "tower")]
+    assert_impl_all!(DetectRequest: Send, Sync, Clone);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[cfg(feature = "test_support")]
+    assert_impl_all!(test_support::FakeTransport: Send, Sync, Clone);
+    #[cfg(feature = "test_support")]
+    assert_impl_all!(test_support::FakeProvider: Send, Sync, Clone);
 
-    #[tokio::test]
-    async fn test_supported_providers() {
-        let providers = supported_providers().await;
-        assert_eq!(providers.len(), 8);
-        assert!(providers.contains(&alibaba::IDENTIFIER.to_string()));
-        assert!(providers.contains(&aws::IDENTIFIER.to_string()));
-        assert!(providers.contains(&azure::IDENTIFIER.to_string()));
-        assert!(providers.contains(&digitalocean::IDENTIFIER.to_string()));
-        assert!(providers.contains(&gcp::IDENTIFIER.to_string()));
-        assert!(providers.contains(&oci::IDENTIFIER.to_string()));
-        assert!(providers.contains(&openstack::IDENTIFIER.to_string()));
-        assert!(providers.contains(&vultr::IDENTIFIER.to_string()));
-    }
+    #[cfg(feature = "simulation")]
+    assert_impl_all!(simulation::Environment: Send, Sync);
 }
@@ -0,0 +1,150 @@
+//! Metrics of detection, recorded through the [metrics](https://crates.io/crates/metrics) facade.
+//!
+//! Nothing is recorded until the application installs a recorder (e.g. a Prometheus exporter). The metrics are:
+//!
+//! * `detections_total` (counter), labeled by the detected `provider` and the `outcome` of the detection: `detected`,
+//!   `unknown` (no provider matched) or `timeout`.
+//! * `probe_duration_seconds` (histogram), labeled by the probed `provider` and the `method` of the probe: `local`
+//!   for its local checks, or `network` for its metadata server.
+
+use std::time::Duration;
+
+use ::metrics::{counter, histogram};
+
+use crate::{Detection, ProviderId};
+
+/// Represents how a provider is probed.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Method {
+    /// Checks local to the host (DMI attributes, files, environment variables).
+    Local,
+    /// Queries to the metadata server.
+    Network,
+}
+
+impl Method {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Local => "local",
+            Self::Network => "network",
+        }
+    }
+}
+
+/// Records a detection that ran to completion.
+pub(crate) fn record_detection(detection: &Detection) {
+    let outcome = match detection.provider {
+        ProviderId::Unknown => "unknown",
+        _ => "detected",
+    };
+
+    counter!(
+        "detections_total",
+        "provider" => detection.provider.short_code(),
+        "outcome" => outcome,
+    )
+    .increment(1);
+}
+
+/// Records a detection that timed out.
+pub(crate) fn record_timeout() {
+    counter!(
+        "detections_total",
+        "provider" => ProviderId::Unknown.short_code(),
+        "outcome" => "timeout",
+    )
+    .increment(1);
+}
+
+/// Records the time taken to probe a provider with a method.
+pub(crate) fn record_probe(provider: ProviderId, method: Method, elapsed: Duration) {
+    histogram!(
+        "probe_duration_seconds",
+        "provider" => provider.short_code(),
+        "method" => method.label(),
+    )
+    .record(elapsed.as_secs_f64());
+}
+
+#[cfg(test)]
+mod tests {
+    use ::metrics::with_local_recorder;
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder};
+
+    use crate::Detector;
+
+    /// Runs a detection on the current thread, returning the value of every metric recorded with its labels.
+    fn recorded(detector: Detector) -> Vec<(String, Vec<String>, DebugValue)> {
+        let recorder = DebuggingRecorder::new();
+        let snapshotter = recorder.snapshotter();
+
+        with_local_recorder(&recorder, || {
+            tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()
+                .unwrap()
+                .block_on(detector.detect_detailed())
+        });
+
+        snapshotter
+            .snapshot()
+            .into_vec()
+            .into_iter()
+            .map(|(key, _, _, value)| {
+                let key = key.key();
+                let labels = key.labels().map(|l| format!("{}={}", l.key(), l.value()));
+                (key.name().to_string(), labels.collect(), value)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_record_detection() {
+        let metrics = recorded(Detector::new().providers(&[]));
+
+        assert_eq!(
+            metrics,
+            [(
+                "detections_total".to_string(),
+                vec![
+                    "provider=unknown".to_string(),
+                    "outcome=unknown".to_string()
+                ],
+                DebugValue::Counter(1)
+            )]
+        );
+    }
+
+    #[cfg(feature = "test_support")]
+    #[test]
+    fn test_record_probe() {
+        use crate::provider_fixtures as fixtures;
+        use crate::test_support::FakeTransport;
+        use crate::ProviderId;
+
+        let transport =
+            FakeTransport::new().respond("/metadata/instance", 200, fixtures::azure::METADATA);
+        let metrics = recorded(
+            Detector::new()
+                .providers(&[ProviderId::Azure])
+                .transport(transport),
+        );
+
+        let labels = |name: &str| {
+            metrics
+                .iter()
+                .filter(|(n, _, _)| n == name)
+                .map(|(_, labels, _)| labels.join(","))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            labels("detections_total"),
+            ["provider=azure,outcome=detected"]
+        );
+        assert_eq!(
+            labels("probe_duration_seconds"),
+            ["provider=azure,method=network"]
+        );
+    }
+}
@@ -0,0 +1,63 @@
+//! Metadata server opt-outs.
+//!
+//! Provider tooling conventionally honors environment variables disabling the metadata server (e.g.
+//! `AWS_EC2_METADATA_DISABLED=true`), for hosts where querying it is slow, blocked or unwanted. Checks and fetches
+//! querying the metadata server of an opted-out provider are skipped, while local checks still run.
+
+use tracing::debug;
+
+use crate::ProviderId;
+
+/// Environment variables disabling the metadata server of a provider, as honored by the provider's own SDKs.
+const ENV_VARS: &[(&str, ProviderId)] = &[
+    ("ALIBABA_CLOUD_ECS_METADATA_DISABLED", ProviderId::Alibaba),
+    ("AWS_EC2_METADATA_DISABLED", ProviderId::AWS),
+    ("NO_GCE_CHECK", ProviderId::GCP),
+];
+
+/// Whether the metadata server of a provider is disabled through the environment.
+pub(crate) fn metadata_disabled(provider: ProviderId) -> bool {
+    metadata_disabled_by(provider, |name| std::env::var(name).ok())
+}
+
+fn metadata_disabled_by<F: Fn(&str) -> Option<String>>(provider: ProviderId, var: F) -> bool {
+    ENV_VARS
+        .iter()
+        .filter(|&&(_, p)| p == provider)
+        .any(|&(name, _)| {
+            let disabled = var(name).is_some_and(|value| is_true(&value));
+
+            if disabled {
+                debug!("Metadata server of {} disabled by {}", provider, name);
+            }
+
+            disabled
+        })
+}
+
+/// Whether an environment variable is set to a true value.
+fn is_true(value: &str) -> bool {
+    matches!(value.trim().to_lowercase().as_str(), "true" | "1" | "yes")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_metadata_disabled_by() {
+        let var = |name: &str| (name == "AWS_EC2_METADATA_DISABLED").then(|| "True".to_string());
+
+        assert!(metadata_disabled_by(ProviderId::AWS, var));
+        assert!(!metadata_disabled_by(ProviderId::GCP, var));
+    }
+
+    #[test]
+    fn test_is_true() {
+        assert!(is_true("true"));
+        assert!(is_true(" TRUE\n"));
+        assert!(is_true("1"));
+        assert!(!is_true("false"));
+        assert!(!is_true(""));
+    }
+}
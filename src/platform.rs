@@ -0,0 +1,192 @@
+//! Classification of the platform a host runs on within its provider.
+//!
+//! Providers are detected from DMI attributes and metadata servers, which look the same whether the host is a plain
+//! instance or runs a managed service. The platform is then told apart by the environment variables the runtimes of
+//! managed services set (see [`serverless`] for the functions runtimes), or for nodes of managed
+//! Kubernetes clusters, by the names the services give them.
+
+use std::fs;
+use std::path::Path;
+
+use crate::host_root::host_path;
+use crate::providers::{akamai, aws, gcp};
+use crate::serverless;
+use crate::{Platform, ProviderId};
+
+const HOSTNAME_FILE: &str = "/etc/hostname";
+
+/// Classifies the platform of a host running on a provider, from its environment variables.
+///
+/// Returns `None` for providers without platforms in the OpenTelemetry semantic conventions (e.g. DigitalOcean), and
+/// for [ProviderId::Unknown].
+pub(crate) fn classify(provider: ProviderId) -> Option<Platform> {
+    classify_with(provider, |name| {
+        std::env::var(name).ok().filter(|value| !value.is_empty())
+    })
+}
+
+fn classify_with<F: Fn(&str) -> Option<String>>(provider: ProviderId, var: F) -> Option<Platform> {
+    match provider {
+        ProviderId::AWS => {
+            Some(serverless::classify_with(provider, &var).unwrap_or_else(|| classify_aws(var)))
+        }
+        ProviderId::Azure => {
+            Some(serverless::classify_with(provider, var).unwrap_or(Platform::AzureVm))
+        }
+        ProviderId::GCP => Some(match gcp::environment_from(var) {
+            gcp::Environment::CloudRun | gcp::Environment::CloudRunJob => Platform::CloudRun,
+            gcp::Environment::CloudFunctions => Platform::CloudFunctions,
+            gcp::Environment::AppEngineStandard | gcp::Environment::AppEngineFlexible => {
+                Platform::AppEngine
+            }
+            _ => Platform::Gce,
+        }),
+        ProviderId::Alibaba => Some(Platform::AlibabaEcs),
+        ProviderId::OCI => Some(Platform::OciCompute),
+        _ => None,
+    }
+}
+
+/// Classifies an AWS host by the execution environment set by the runtimes of AWS services (e.g. `AWS_ECS_FARGATE` or
+/// `AWS_Lambda_python3.12`).
+fn classify_aws<F: Fn(&str) -> Option<String>>(var: F) -> Platform {
+    match var("AWS_EXECUTION_ENV").as_deref() {
+        Some(env) if env.starts_with("AWS_Lambda_") => Platform::Lambda,
+        Some("AWS_ECS_FARGATE") => Platform::Fargate,
+        Some("AWS_ECS_EC2") => Platform::Ecs,
+        // Set by the ECS agent even for containers not based on an AWS runtime image
+        _ if aws::task_metadata_uri_from(&var).is_some() => Platform::Ecs,
+        _ => Platform::Ec2,
+    }
+}
+
+/// Classifies the platform of a node of a managed Kubernetes cluster running on a provider, from the hostname of the
+/// host mounted at `root`.
+///
+/// Returns `None` if the host isn't a node of a cluster that tells its nodes apart by name (e.g. LKE nodes, named after
+/// their cluster and node pool).
+pub(crate) fn classify_node(provider: ProviderId, root: &Path) -> Option<Platform> {
+    match provider {
+        ProviderId::Akamai => {
+            let hostname = fs::read_to_string(host_path(root, HOSTNAME_FILE)).ok()?;
+            akamai::is_lke_label(hostname.trim()).then_some(Platform::Lke)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        |name| {
+            vars.iter()
+                .find(|(n, _)| *n == name)
+                .map(|(_, value)| value.to_string())
+        }
+    }
+
+    #[test]
+    fn test_classify_aws() {
+        let cases = [
+            (&[][..], Platform::Ec2),
+            (
+                &[("AWS_EXECUTION_ENV", "AWS_Lambda_python3.12")][..],
+                Platform::Lambda,
+            ),
+            (
+                &[("AWS_EXECUTION_ENV", "AWS_ECS_FARGATE")][..],
+                Platform::Fargate,
+            ),
+            (&[("AWS_EXECUTION_ENV", "AWS_ECS_EC2")][..], Platform::Ecs),
+            (
+                &[(
+                    "ECS_CONTAINER_METADATA_URI_V4",
+                    "http://169.254.170.2/v4/abc",
+                )][..],
+                Platform::Ecs,
+            ),
+            (
+                &[("AWS_LAMBDA_FUNCTION_NAME", "hello")][..],
+                Platform::Lambda,
+            ),
+        ];
+
+        for (vars, platform) in cases {
+            assert_eq!(classify_with(ProviderId::AWS, env(vars)), Some(platform));
+        }
+    }
+
+    #[test]
+    fn test_classify_azure() {
+        assert_eq!(
+            classify_with(ProviderId::Azure, env(&[])),
+            Some(Platform::AzureVm)
+        );
+        assert_eq!(
+            classify_with(
+                ProviderId::Azure,
+                env(&[("FUNCTIONS_WORKER_RUNTIME", "dotnet")])
+            ),
+            Some(Platform::AzureFunctions)
+        );
+        assert_eq!(
+            classify_with(ProviderId::Azure, env(&[("WEBSITE_SITE_NAME", "hello")])),
+            Some(Platform::AppService)
+        );
+    }
+
+    #[test]
+    fn test_classify_gcp() {
+        assert_eq!(
+            classify_with(ProviderId::GCP, env(&[])),
+            Some(Platform::Gce)
+        );
+        assert_eq!(
+            classify_with(ProviderId::GCP, env(&[("K_SERVICE", "hello")])),
+            Some(Platform::CloudRun)
+        );
+        assert_eq!(
+            classify_with(ProviderId::GCP, env(&[("GAE_ENV", "standard")])),
+            Some(Platform::AppEngine)
+        );
+    }
+
+    #[test]
+    fn test_classify_providers() {
+        for &provider in crate::supported_providers() {
+            let platform = classify_with(provider, env(&[]));
+            assert!(
+                platform.is_none_or(|p| p.provider() == provider),
+                "{provider}"
+            );
+        }
+
+        assert_eq!(classify_with(ProviderId::Unknown, env(&[])), None);
+        assert_eq!(classify_with(ProviderId::DigitalOcean, env(&[])), None);
+    }
+
+    #[test]
+    fn test_classify_node() -> anyhow::Result<()> {
+        let root = TempDir::new()?;
+        assert_eq!(classify_node(ProviderId::Akamai, root.path()), None);
+
+        let hostname_file = host_path(root.path(), HOSTNAME_FILE);
+        fs::create_dir_all(hostname_file.parent().unwrap())?;
+        fs::write(&hostname_file, format!("{}\n", fixtures::akamai::LKE_LABEL))?;
+        assert_eq!(
+            classify_node(ProviderId::Akamai, root.path()),
+            Some(Platform::Lke)
+        );
+        assert_eq!(classify_node(ProviderId::Vultr, root.path()), None);
+
+        fs::write(&hostname_file, "linode12345678\n")?;
+        assert_eq!(classify_node(ProviderId::Akamai, root.path()), None);
+
+        Ok(())
+    }
+}
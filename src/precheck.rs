@@ -0,0 +1,266 @@
+//! Reachability pre-check of metadata servers.
+//!
+//! Off-cloud, requests to a metadata server often hang until they time out (e.g. when packets to a link-local address
+//! are silently dropped), while connecting to a metadata server that is present takes well under a millisecond. With the
+//! pre-check enabled (see [Detector::precheck_timeout](crate::Detector::precheck_timeout)), each distinct metadata host
+//! is first connected to over TCP, with a deadline independent of the HTTP timeouts, and the checks of unreachable hosts
+//! are skipped. The outcome for each host is shared by the providers of a detection, as several are served from the same
+//! address (e.g. `169.254.169.254`).
+//!
+//! Bound checks (see [crate::bind]) connect from their interface and source address, so the outcome reflects the route
+//! their requests take. The blocking API can't bind its connections, so bound checks are never skipped by it.
+
+use std::collections::HashMap;
+#[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+use std::net::{TcpStream, ToSocketAddrs};
+#[cfg(not(feature = "async"))]
+use std::sync::OnceLock as OnceCell;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use http::uri::{Scheme, Uri};
+#[cfg(feature = "async")]
+use tokio::sync::OnceCell;
+use tracing::debug;
+
+use crate::bind::Binding;
+use crate::checks::{Check, Probe, Request};
+
+/// Represents the pre-check of a detection, caching whether each metadata host is reachable.
+#[derive(Debug)]
+pub(crate) struct Precheck {
+    /// Maximum time allowed for connecting to each host.
+    timeout: Duration,
+    /// Outcome for each host (`host:port`), once known.
+    hosts: Mutex<HashMap<String, Arc<OnceCell<bool>>>>,
+}
+
+impl Precheck {
+    /// Creates a pre-check connecting to each host within the given time.
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            hosts: Default::default(),
+        }
+    }
+
+    /// Whether the metadata server queried by a check is reachable, connecting to it on first use.
+    ///
+    /// Checks that don't query a metadata server, or whose address can't be told, are never skipped.
+    #[cfg(feature = "async")]
+    pub(crate) async fn reachable(&self, check: &Check) -> bool {
+        let Some(address) = address(check) else {
+            return true;
+        };
+
+        let outcome = self.outcome(&address);
+        *outcome
+            .get_or_init(|| connect(&address, binding(check), self.timeout))
+            .await
+    }
+
+    /// Whether the metadata server queried by a check is reachable, connecting to it on first use, for the blocking API.
+    ///
+    /// Threads probing the same host concurrently may each connect to it, until one of them has recorded the outcome.
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    pub(crate) fn reachable_blocking(&self, check: &Check) -> bool {
+        let Some(address) = address(check) else {
+            return true;
+        };
+
+        if binding(check).is_some() {
+            debug!("Not pre-checking {}: connections can't be bound", address);
+            return true;
+        }
+
+        let outcome = self.outcome(&address);
+
+        if let Some(&reachable) = outcome.get() {
+            return reachable;
+        }
+
+        let reachable = connect_blocking(&address, self.timeout);
+        let _ = outcome.set(reachable);
+        reachable
+    }
+
+    fn outcome(&self, address: &str) -> Arc<OnceCell<bool>> {
+        let mut hosts = self.hosts.lock().unwrap_or_else(|err| err.into_inner());
+        hosts.entry(address.to_string()).or_default().clone()
+    }
+}
+
+/// Returns a check of the metadata server queried by the most checks (e.g. `169.254.169.254`, shared by most providers),
+/// if any check queries one whose address can be told.
+pub(crate) fn shared<'a>(checks: impl IntoIterator<Item = &'a Check>) -> Option<&'a Check> {
+    let mut counts: HashMap<String, (usize, &Check)> = HashMap::new();
+    for check in checks {
+        if let Some(address) = address(check) {
+            counts.entry(address).or_insert((0, check)).0 += 1;
+        }
+    }
+
+    counts
+        .into_iter()
+        .max_by(|(a, (a_count, _)), (b, (b_count, _))| a_count.cmp(b_count).then(b.cmp(a)))
+        .map(|(_, (_, check))| check)
+}
+
+/// Returns the address (`host:port`) of the metadata server queried by a check, if any.
+fn address(check: &Check) -> Option<String> {
+    let Probe::Http { request, .. } = &check.probe else {
+        return None;
+    };
+
+    #[cfg(feature = "test_support")]
+    if request.transport.is_some() {
+        return None;
+    }
+
+    address_of(request)
+}
+
+/// Returns the interface and source address the requests of a check are sent from, if bound.
+fn binding(check: &Check) -> Option<&Binding> {
+    match &check.probe {
+        Probe::Http { request, .. } => request.binding.as_deref(),
+        _ => None,
+    }
+}
+
+fn address_of(request: &Request) -> Option<String> {
+    let uri: Uri = request.base_uri.parse().ok()?;
+    let host = uri.host()?;
+    let port = match (uri.port_u16(), uri.scheme()) {
+        (Some(port), _) => port,
+        (None, Some(scheme)) if *scheme == Scheme::HTTP => 80,
+        (None, Some(scheme)) if *scheme == Scheme::HTTPS => 443,
+        _ => return None,
+    };
+
+    Some(format!("{host}:{port}"))
+}
+
+/// Whether a TCP connection to the address can be established within the timeout (including resolving its host), from
+/// the binding if any.
+#[cfg(feature = "async")]
+async fn connect(address: &str, binding: Option<&Binding>, timeout: Duration) -> bool {
+    let reachable = match binding {
+        Some(binding) => matches!(
+            tokio::time::timeout(timeout, binding.connect(address)).await,
+            Ok(Ok(_))
+        ),
+        None => matches!(
+            tokio::time::timeout(timeout, tokio::net::TcpStream::connect(address)).await,
+            Ok(Ok(_))
+        ),
+    };
+    debug!("Pre-checked {}: reachable: {}", address, reachable);
+
+    reachable
+}
+
+/// Whether a TCP connection to the address can be established within the timeout, for the blocking API.
+///
+/// Unlike the connection itself, resolving the host isn't bounded by the timeout.
+#[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+fn connect_blocking(address: &str, timeout: Duration) -> bool {
+    let reachable = address
+        .to_socket_addrs()
+        .map(|mut addrs| addrs.any(|addr| TcpStream::connect_timeout(&addr, timeout).is_ok()))
+        .unwrap_or(false);
+    debug!("Pre-checked {}: reachable: {}", address, reachable);
+
+    reachable
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+
+    use super::*;
+    use crate::Confidence;
+
+    fn check(base_uri: &str) -> Check {
+        Check::http(
+            "metadata_server",
+            Confidence::Verified,
+            Request::get(base_uri, "/"),
+            |_| true,
+        )
+    }
+
+    #[test]
+    fn test_address_of() {
+        let address = |base_uri| address_of(&Request::get(base_uri, "/"));
+
+        assert_eq!(
+            address("http://169.254.169.254").as_deref(),
+            Some("169.254.169.254:80")
+        );
+        assert_eq!(
+            address("http://metadata.google.internal").as_deref(),
+            Some("metadata.google.internal:80")
+        );
+        assert_eq!(
+            address("https://imds.internal:8443").as_deref(),
+            Some("imds.internal:8443")
+        );
+        assert_eq!(address("not a url"), None);
+    }
+
+    #[test]
+    fn test_shared() {
+        let checks = [
+            check("http://169.254.169.254"),
+            check("http://metadata.google.internal"),
+            check("http://169.254.169.254"),
+            Check::env("env", Confidence::Unverified, "HOME", |_| true),
+        ];
+
+        assert_eq!(
+            shared(&checks).and_then(address).as_deref(),
+            Some("169.254.169.254:80")
+        );
+        assert!(shared(&checks[3..]).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_reachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let reachable = format!("http://{}", listener.local_addr().unwrap());
+        // Nothing listens on the port once the listener is dropped
+        let unreachable = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let precheck = Precheck::new(Duration::from_millis(100));
+
+        assert!(precheck.reachable(&check(&reachable)).await);
+        assert!(!precheck.reachable(&check(&unreachable)).await);
+        assert!(
+            precheck
+                .reachable(&Check::env("env", Confidence::Unverified, "HOME", |_| true))
+                .await
+        );
+
+        // The outcome is cached, even once the host has become unreachable
+        drop(listener);
+        assert!(precheck.reachable(&check(&reachable)).await);
+    }
+
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    #[test]
+    fn test_reachable_blocking() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let reachable = format!("http://{}", listener.local_addr().unwrap());
+        let unreachable = {
+            let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+            format!("http://{}", listener.local_addr().unwrap())
+        };
+        let precheck = Precheck::new(Duration::from_millis(100));
+
+        assert!(precheck.reachable_blocking(&check(&reachable)));
+        assert!(!precheck.reachable_blocking(&check(&unreachable)));
+    }
+}
@@ -0,0 +1,43 @@
+//! Prelude of the stable surface of the crate.
+//!
+//! Glob-importing the prelude brings in the detector, the functions detecting the provider of the host and what they
+//! report, without reaching into the modules of the crate:
+//!
+//! ```
+//! use cloud_detect::prelude::*;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let detection = Detector::new().timeout(1).detect_detailed().await;
+//!
+//!     if detection.provider != ProviderId::Unknown {
+//!         println!("Detected provider: {}", detection.provider);
+//!     }
+//! }
+//! ```
+//!
+//! Everything in the prelude follows the semver policy of the crate (see [Stability](crate#stability)).
+
+pub use crate::core::{
+    AccountInfo,
+    CredentialsInfo,
+    NetworkInfo,
+    Platform,
+    ProviderGroup,
+    ProviderId,
+    Region,
+};
+#[cfg(feature = "async")]
+pub use crate::engine::{detect, is_cloud, is_provider};
+#[cfg(feature = "std")]
+pub use crate::engine::{
+    Confidence,
+    Detection,
+    Detector,
+    Evidence,
+    Outcome,
+    ProviderVerdict,
+    SkipReason,
+    SkippedProbe,
+    DEFAULT_DETECTION_TIMEOUT,
+};
@@ -0,0 +1,79 @@
+//! Verbosity of the logs of probes.
+//!
+//! Most probes fail on any given host: a metadata server is only reachable on its own cloud, so requests to those of
+//! other providers are refused or time out. Such expected negatives are logged at the level set with
+//! [Detector::probe_log_level](crate::Detector::probe_log_level) (`DEBUG` by default), or not at all in quiet mode
+//! (see [Detector::quiet](crate::Detector::quiet)), without having to filter the tracing output of the crate. Genuine
+//! faults (e.g. a file that exists but can't be read) are still logged as errors.
+
+/// Represents the level probes log their expected failures at, if any.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Hash)]
+pub enum ProbeLogLevel {
+    /// Expected failures aren't logged.
+    Off,
+    /// Expected failures are logged as errors.
+    Error,
+    Warn,
+    Info,
+    /// Expected failures are logged for debugging, as by default.
+    #[default]
+    Debug,
+    Trace,
+}
+
+/// Logs an expected failure of a probe at the given [ProbeLogLevel], keeping the module of the call site as the target.
+macro_rules! probe_log {
+    ($level:expr, $($arg:tt)+) => {
+        match $level {
+            $crate::ProbeLogLevel::Off => {}
+            $crate::ProbeLogLevel::Error => tracing::error!($($arg)+),
+            $crate::ProbeLogLevel::Warn => tracing::warn!($($arg)+),
+            $crate::ProbeLogLevel::Info => tracing::info!($($arg)+),
+            $crate::ProbeLogLevel::Debug => tracing::debug!($($arg)+),
+            $crate::ProbeLogLevel::Trace => tracing::trace!($($arg)+),
+        }
+    };
+}
+
+pub(crate) use probe_log;
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use tracing::{Event, Level, Subscriber};
+    use tracing_subscriber::layer::{Context, SubscriberExt};
+    use tracing_subscriber::Layer;
+
+    use super::*;
+
+    /// Records the level of every event.
+    struct Levels(Arc<Mutex<Vec<Level>>>);
+
+    impl<S: Subscriber> Layer<S> for Levels {
+        fn on_event(&self, event: &Event<'_>, _: Context<'_, S>) {
+            self.0.lock().unwrap().push(*event.metadata().level());
+        }
+    }
+
+    fn logged(level: ProbeLogLevel) -> Vec<Level> {
+        let levels = Arc::new(Mutex::new(Vec::new()));
+        let subscriber = tracing_subscriber::registry().with(Levels(levels.clone()));
+
+        tracing::subscriber::with_default(subscriber, || {
+            probe_log!(level, "Error making request: {}", "connection refused");
+        });
+
+        let levels = levels.lock().unwrap().clone();
+        levels
+    }
+
+    #[test]
+    fn test_probe_log() {
+        assert!(logged(ProbeLogLevel::Off).is_empty());
+        assert_eq!(logged(ProbeLogLevel::default()), [Level::DEBUG]);
+        assert_eq!(logged(ProbeLogLevel::Error), [Level::ERROR]);
+        assert_eq!(logged(ProbeLogLevel::Trace), [Level::TRACE]);
+    }
+}
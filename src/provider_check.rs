@@ -0,0 +1,489 @@
+//! Individual checks of providers.
+//!
+//! Detection runs every check of every provider, but diagnosing a host often comes down to a single question (e.g.
+//! "does IMDSv2 respond here?"). [Detector::provider_checks] exposes each check of a provider as a [ProviderCheck] that
+//! can be run on its own, and [Detector::check] composes detection with checks of the application's own.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tracing::debug;
+#[cfg(feature = "blocking")]
+use tracing::error;
+
+use crate::budget::Budget;
+use crate::checks::{Check, Probe};
+use crate::clock::Instant;
+use crate::redact::redact;
+use crate::{timeout_or_default, Confidence, Detector, Match, Outcome, ProviderId};
+
+/// Represents what a check inspects.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum CheckKind {
+    /// A DMI attribute of the host (e.g. its system vendor).
+    Dmi,
+    /// A file on the host, other than a DMI attribute.
+    File,
+    /// An environment variable.
+    Env,
+    /// The hypervisor reported by CPUID.
+    Cpuid,
+    /// A metadata server, over the network.
+    MetadataServer,
+}
+
+impl CheckKind {
+    /// Whether checks of this kind only read the host, rather than querying a metadata server.
+    pub fn is_local(self) -> bool {
+        self != Self::MetadataServer
+    }
+}
+
+/// Represents the context a check runs in.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CheckContext {
+    /// Maximum time allowed for requests to a metadata server.
+    pub timeout: Duration,
+}
+
+impl CheckContext {
+    /// Creates a context allowing the given time (seconds) for requests to a metadata server. Defaults to
+    /// [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+    pub fn new(timeout: Option<u64>) -> Self {
+        Self {
+            timeout: timeout_or_default(timeout),
+        }
+    }
+}
+
+impl Default for CheckContext {
+    fn default() -> Self {
+        Self::new(None)
+    }
+}
+
+/// Represents the outcome of running a [ProviderCheck].
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct CheckOutcome {
+    /// Confidence of the match, if the check matched.
+    pub confidence: Option<Confidence>,
+    /// What the check found, e.g. telling a metadata server that can't be reached from one that responded with
+    /// something else.
+    pub outcome: Outcome,
+    /// Status code of the response from the metadata server, if any.
+    pub status: Option<u16>,
+    /// Redacted error that prevented the check from completing, if any.
+    pub error: Option<String>,
+    /// Time taken by the check.
+    pub elapsed: Duration,
+}
+
+impl CheckOutcome {
+    /// Creates the outcome of a check that matched with the given confidence.
+    pub fn matched(confidence: Confidence) -> Self {
+        Self {
+            confidence: Some(confidence),
+            outcome: Outcome::Matched,
+            ..Default::default()
+        }
+    }
+
+    /// Creates the outcome of a check that didn't match what it inspected.
+    pub fn unmatched() -> Self {
+        Self {
+            outcome: Outcome::Mismatched,
+            ..Default::default()
+        }
+    }
+
+    /// Sets what the check found (e.g. [Outcome::Unreachable] for a metadata server that couldn't be reached).
+    pub fn outcome(mut self, outcome: Outcome) -> Self {
+        self.outcome = outcome;
+        self
+    }
+
+    /// Sets the error that prevented the check from completing, as a failure unless its outcome is already one (see
+    /// [Outcome::Unreachable]).
+    pub fn error(mut self, error: &str) -> Self {
+        if self.outcome != Outcome::Unreachable {
+            self.outcome = Outcome::Failed;
+        }
+
+        self.error = Some(redact(error));
+        self
+    }
+
+    /// Whether the check matched.
+    pub fn is_match(&self) -> bool {
+        self.confidence.is_some()
+    }
+}
+
+/// A check identifying a provider, which can be run on its own.
+///
+/// The trait is object-safe, so checks of different types can be stored together as `Box<dyn ProviderCheck>`.
+///
+/// # Examples
+///
+/// Run every check of AWS on its own, e.g. to tell whether IMDSv2 responds.
+///
+/// ```
+/// use cloud_detect::{CheckContext, Detector, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let context = CheckContext::new(Some(1));
+///
+///     for check in Detector::new().provider_checks(ProviderId::AWS) {
+///         let outcome = check.run(&context).await;
+///         println!(
+///             "{} ({:?}): {:?}",
+///             check.name(),
+///             check.kind(),
+///             outcome.confidence
+///         );
+///     }
+/// }
+/// ```
+pub trait ProviderCheck: fmt::Debug + Send + Sync {
+    /// Returns the provider identified by the check.
+    fn provider(&self) -> ProviderId;
+    /// Returns the short name of the check (e.g. `imdsv2`), reported as the source of its matches.
+    fn name(&self) -> &'static str;
+    /// Returns what the check inspects.
+    fn kind(&self) -> CheckKind;
+    /// Runs the check.
+    fn run<'a>(
+        &'a self,
+        context: &'a CheckContext,
+    ) -> Pin<Box<dyn Future<Output = CheckOutcome> + Send + 'a>>;
+}
+
+/// A check of a provider supported by the crate.
+#[cfg(any(feature = "async", feature = "blocking-ureq"))]
+struct BuiltinCheck {
+    provider: ProviderId,
+    check: Check,
+}
+
+#[cfg(any(feature = "async", feature = "blocking-ureq"))]
+impl fmt::Debug for BuiltinCheck {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BuiltinCheck")
+            .field("provider", &self.provider)
+            .field("name", &self.check.name)
+            .field("target", &self.check.probe.target())
+            .finish()
+    }
+}
+
+#[cfg(any(feature = "async", feature = "blocking-ureq"))]
+impl ProviderCheck for BuiltinCheck {
+    fn provider(&self) -> ProviderId {
+        self.provider
+    }
+
+    fn name(&self) -> &'static str {
+        self.check.name
+    }
+
+    fn kind(&self) -> CheckKind {
+        match self.check.probe {
+            Probe::Dmi { .. } => CheckKind::Dmi,
+            Probe::File { .. } | Probe::Glob { .. } | Probe::Exists { .. } => CheckKind::File,
+            Probe::Env { .. } => CheckKind::Env,
+            Probe::Cpuid { .. } => CheckKind::Cpuid,
+            Probe::Http { .. } => CheckKind::MetadataServer,
+        }
+    }
+
+    fn run<'a>(
+        &'a self,
+        context: &'a CheckContext,
+    ) -> Pin<Box<dyn Future<Output = CheckOutcome> + Send + 'a>> {
+        Box::pin(async move {
+            let start = Instant::now();
+            #[cfg(feature = "async")]
+            let inspection = self.check.inspect(context.timeout).await;
+            #[cfg(not(feature = "async"))]
+            let inspection = crate::blocking::checks::inspect(&self.check, context.timeout);
+
+            CheckOutcome {
+                confidence: inspection
+                    .matched()
+                    .then(|| self.check.match_confidence(&inspection)),
+                outcome: inspection.outcome,
+                status: inspection.status,
+                error: inspection.error.as_deref().map(redact),
+                elapsed: start.elapsed(),
+            }
+        })
+    }
+}
+
+impl Detector {
+    /// Returns the checks of a provider probed by the detector, in the order they are tried, each of which can be run
+    /// on its own. Checks querying the metadata server are left out if disabled (see [Detector::metadata]).
+    ///
+    /// Returns no checks for providers the detector doesn't probe.
+    #[cfg(any(feature = "async", feature = "blocking-ureq"))]
+    pub fn provider_checks(&self, provider: ProviderId) -> Vec<Box<dyn ProviderCheck>> {
+        let Some(p) = self
+            .registry()
+            .into_iter()
+            .find(|p| p.identifier() == provider)
+        else {
+            return Vec::new();
+        };
+
+        self.checks(p)
+            .into_iter()
+            .map(|check| Box::new(BuiltinCheck { provider, check }) as Box<dyn ProviderCheck>)
+            .collect()
+    }
+
+    /// Adds a check of the application's own, run alongside the checks of the providers probed by the detector. A
+    /// match is resolved like any other, with the name of the check as its source (see
+    /// [Evidence::source](crate::Evidence::source)).
+    ///
+    /// Checks of providers the detector doesn't probe are ignored, as are checks querying a metadata server when
+    /// detection is local only (see [Detector::local_only]). The blocking API runs each check on its own thread, in a
+    /// current-thread tokio runtime, or without the `async` feature, by polling it on that thread (so checks relying on
+    /// tokio's timers or sockets need the feature).
+    ///
+    /// # Examples
+    ///
+    /// Confirm AWS with IMDSv2 alone, rather than every check of its metadata server.
+    ///
+    /// ```no_run
+    /// use cloud_detect::{Detector, ProviderId};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let imdsv2 = Detector::new()
+    ///         .provider_checks(ProviderId::AWS)
+    ///         .into_iter()
+    ///         .find(|check| check.name() == "imdsv2")
+    ///         .unwrap();
+    ///
+    ///     let provider = Detector::new()
+    ///         .providers(&[ProviderId::AWS])
+    ///         .metadata(ProviderId::AWS, false)
+    ///         .check(imdsv2)
+    ///         .detect()
+    ///         .await;
+    ///     println!("Detected provider: {:?}", provider);
+    /// }
+    /// ```
+    pub fn check(mut self, check: impl ProviderCheck + 'static) -> Self {
+        self.provider_checks.push(Arc::new(check));
+        self
+    }
+
+    /// Returns the checks added to the detector that are run during detection (see [Detector::check]).
+    pub(crate) fn composed_checks(&self) -> Vec<Arc<dyn ProviderCheck>> {
+        self.provider_checks
+            .iter()
+            .filter(|check| self.includes(check.provider()))
+            .filter(|check| !self.local_only || check.kind().is_local())
+            .cloned()
+            .collect()
+    }
+}
+
+impl<T: ProviderCheck + ?Sized> ProviderCheck for Box<T> {
+    fn provider(&self) -> ProviderId {
+        (**self).provider()
+    }
+
+    fn name(&self) -> &'static str {
+        (**self).name()
+    }
+
+    fn kind(&self) -> CheckKind {
+        (**self).kind()
+    }
+
+    fn run<'a>(
+        &'a self,
+        context: &'a CheckContext,
+    ) -> Pin<Box<dyn Future<Output = CheckOutcome> + Send + 'a>> {
+        (**self).run(context)
+    }
+}
+
+/// Returns the context a check runs in during a detection: the budget of the local phase for local checks, or the
+/// network budget for checks querying a metadata server.
+pub(crate) fn context(check: &dyn ProviderCheck, budget: &Budget) -> CheckContext {
+    CheckContext {
+        timeout: match check.kind().is_local() {
+            true => budget.local,
+            false => budget.network,
+        },
+    }
+}
+
+/// Runs a check added to a detector, returning its match, if any.
+#[cfg(any(feature = "async", feature = "blocking-ureq"))]
+pub(crate) async fn identify(check: &dyn ProviderCheck, context: &CheckContext) -> Option<Match> {
+    let outcome = check.run(context).await;
+    debug!(
+        "Check {} of {}: {:?}",
+        check.name(),
+        check.provider(),
+        outcome
+    );
+
+    outcome.confidence.map(|confidence| {
+        Match::new(check.provider(), confidence)
+            .source(check.name())
+            .outcome(outcome.outcome)
+    })
+}
+
+/// Runs a check added to a detector, returning its match, if any, for the blocking API.
+#[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+pub(crate) fn identify_blocking(
+    check: &dyn ProviderCheck,
+    context: &CheckContext,
+) -> Option<Match> {
+    #[cfg(feature = "async")]
+    match tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+    {
+        Ok(runtime) => runtime.block_on(identify(check, context)),
+        Err(err) => {
+            error!("Error creating runtime: {:?}", err);
+            None
+        }
+    }
+
+    #[cfg(not(feature = "async"))]
+    block_on(identify(check, context))
+}
+
+/// Runs a future to completion on the current thread, parking it while the future is pending.
+///
+/// Without the async engine, there is no tokio runtime to run checks added to a detector in. Checks that rely on one
+/// (e.g. using tokio's timers or sockets) need the `async` feature.
+#[cfg(all(feature = "blocking-ureq", not(feature = "async")))]
+fn block_on<F: Future>(future: F) -> F::Output {
+    struct Unpark(std::thread::Thread);
+
+    impl std::task::Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = std::task::Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = std::task::Context::from_waker(&waker);
+    let mut future = std::pin::pin!(future);
+
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        std::thread::park();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug)]
+    struct Marker;
+
+    impl ProviderCheck for Marker {
+        fn provider(&self) -> ProviderId {
+            ProviderId::Azure
+        }
+
+        fn name(&self) -> &'static str {
+            "marker"
+        }
+
+        fn kind(&self) -> CheckKind {
+            CheckKind::File
+        }
+
+        fn run<'a>(
+            &'a self,
+            _context: &'a CheckContext,
+        ) -> Pin<Box<dyn Future<Output = CheckOutcome> + Send + 'a>> {
+            Box::pin(async { CheckOutcome::matched(Confidence::Verified) })
+        }
+    }
+
+    #[test]
+    fn test_provider_checks() {
+        let checks = Detector::new().provider_checks(ProviderId::AWS);
+        let names: Vec<_> = checks.iter().map(|check| check.name()).collect();
+
+        assert!(names.contains(&"bios_vendor_file"));
+        assert!(names.contains(&"imdsv2"));
+        assert!(checks
+            .iter()
+            .all(|check| check.provider() == ProviderId::AWS));
+
+        let checks = Detector::new()
+            .metadata(ProviderId::AWS, false)
+            .provider_checks(ProviderId::AWS);
+
+        assert!(checks.iter().all(|check| check.kind().is_local()));
+        assert!(Detector::new()
+            .providers(&[ProviderId::Azure])
+            .provider_checks(ProviderId::AWS)
+            .is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_run() {
+        let context = CheckContext::new(Some(1));
+        let check = Detector::new()
+            .provider_checks(ProviderId::GCP)
+            .into_iter()
+            .find(|check| check.kind() == CheckKind::Env)
+            .unwrap();
+
+        // The environment variables of serverless runtimes aren't set when testing
+        assert!(!check.run(&context).await.is_match());
+    }
+
+    #[tokio::test]
+    async fn test_detect_with_check() {
+        let provider = Detector::new()
+            .providers(&[ProviderId::Azure])
+            .metadata(ProviderId::Azure, false)
+            .check(Marker)
+            .detect()
+            .await;
+
+        assert_eq!(provider, ProviderId::Azure);
+
+        // Checks of providers the detector doesn't probe are ignored
+        let detector = Detector::new()
+            .providers(&[ProviderId::AWS])
+            .local_only()
+            .check(Marker);
+
+        assert!(detector.composed_checks().is_empty());
+    }
+
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    #[test]
+    fn test_identify_blocking() {
+        let m = identify_blocking(&Marker, &CheckContext::default()).unwrap();
+
+        assert_eq!(m.provider, ProviderId::Azure);
+        assert_eq!(m.source, "marker");
+    }
+}
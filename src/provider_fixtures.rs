@@ -0,0 +1,162 @@
+//! Canned metadata responses and vendor file contents shared by provider tests and simulated environments.
+
+// Simulated environments only use some of them
+#![cfg_attr(not(test), allow(dead_code))]
+
+/// A response body that no provider accepts.
+pub(crate) const MALFORMED: &str = "ABC";
+/// Page served by captive portals (e.g. on hotel networks) in answer to any request.
+pub(crate) const CAPTIVE_PORTAL: &str = "<html><head><title>Welcome</title></head><body>Please \
+                                         log in to access the Internet.</body></html>";
+
+/// Akamai Cloud (formerly Linode).
+pub(crate) mod akamai {
+    pub(crate) const TOKEN: &str = "789ghi";
+    pub(crate) const METADATA: &str = r#"{"id":12345678,"host_uuid":"a1b2c3d4e5f6","label":"linode12345678","region":"us-ord","type":"g6-nanode-1","tags":[]}"#;
+    pub(crate) const METADATA_MISMATCH: &str = r#"{"id":0}"#;
+    pub(crate) const VENDOR: &str = "Akamai Technologies";
+    pub(crate) const VENDOR_LEGACY: &str = "Linode";
+    /// Label (and hostname) of a node of an LKE cluster.
+    pub(crate) const LKE_LABEL: &str = "lke123456-178901-0a1b2c3d4e";
+}
+
+/// Alibaba Cloud.
+pub(crate) mod alibaba {
+    pub(crate) const TOKEN: &str = "456def";
+    pub(crate) const METADATA: &str = "ECS Virt";
+    pub(crate) const IDENTITY_DOCUMENT: &str = r#"{"instance-id":"i-bp1abc123","instance-type":"ecs.g6.large","image-id":"ubuntu_22_04_x64_20G_alibase_20230613.vhd","owner-account-id":"1234567890123456","region-id":"cn-hangzhou","zone-id":"cn-hangzhou-i","private-ipv4":"172.16.0.10","mac":"00:16:3e:00:00:01"}"#;
+    pub(crate) const VENDOR: &str = "Alibaba Cloud ECS";
+}
+
+/// Amazon Web Services (AWS).
+pub(crate) mod aws {
+    pub(crate) const TOKEN: &str = "123abc";
+    pub(crate) const MAC: &str = "0e:1f:2a:3b:4c:5d";
+    pub(crate) const IDENTITY_DOCUMENT: &str = r#"{"accountId":"123456789012","architecture":"x86_64","availabilityZone":"us-east-1a","imageId":"ami-12345678","instanceId":"i-12345678","instanceType":"m5.large","privateIp":"10.0.0.10","region":"us-east-1"}"#;
+    pub(crate) const IDENTITY_DOCUMENT_MISMATCH: &str = r#"{"imageId":"abc","instanceId":"abc"}"#;
+    pub(crate) const VENDOR: &str = "Amazon EC2";
+    pub(crate) const TASK_METADATA: &str = r#"{"Cluster":"arn:aws:ecs:us-east-1:123456789012:cluster/default","TaskARN":"arn:aws:ecs:us-east-1:123456789012:task/default/158d1c8083dd49d6b527399fd6414f5c","Family":"web","Revision":"3","DesiredStatus":"RUNNING","KnownStatus":"RUNNING","AvailabilityZone":"us-east-1a","LaunchType":"FARGATE","Containers":[]}"#;
+    pub(crate) const TASK_METADATA_MISMATCH: &str = r#"{"Cluster":"default","TaskARN":""}"#;
+}
+
+/// Microsoft Azure.
+pub(crate) mod azure {
+    pub(crate) const METADATA: &str = r#"{"compute":{"vmId":"vm-1234","name":"vm","location":"westeurope","vmSize":"Standard_D4s_v5","subscriptionId":"00000000-0000-0000-0000-000000000000","resourceGroupName":"rg","zone":"1","azEnvironment":"AzurePublicCloud","resourceId":"/subscriptions/00000000-0000-0000-0000-000000000000/resourceGroups/rg/providers/Microsoft.Compute/virtualMachines/vm"},"network":{"interface":[{"ipv4":{"ipAddress":[{"privateIpAddress":"10.0.0.4","publicIpAddress":"20.0.0.4"}]},"ipv6":{"ipAddress":[]},"macAddress":"000D3AF806EC"}]}}"#;
+    pub(crate) const METADATA_MISMATCH: &str = r#"{"compute":{"vmId":""}}"#;
+    pub(crate) const VENDOR: &str = "Microsoft Corporation";
+}
+
+/// Civo.
+pub(crate) mod civo {
+    pub(crate) const VENDOR: &str = "Civo";
+}
+
+/// DigitalOcean.
+pub(crate) mod digitalocean {
+    pub(crate) const METADATA: &str = r#"{"droplet_id": 123, "hostname": "droplet", "region": "nyc3", "tags": ["web"], "interfaces": {"public": [{"ipv4": {"ip_address": "203.0.113.10"}, "mac": "d6:4c:8e:00:00:01", "type": "public"}], "private": [{"ipv4": {"ip_address": "10.116.0.2"}, "mac": "d6:4c:8e:00:00:02", "type": "private"}]}, "features": {"dhcp_enabled": false}}"#;
+    pub(crate) const METADATA_MISMATCH: &str = r#"{"droplet_id": 0}"#;
+    pub(crate) const ID: &str = "123";
+    pub(crate) const REGION: &str = "nyc3";
+    pub(crate) const VENDOR: &str = "DigitalOcean";
+}
+
+/// Google Cloud Platform (GCP).
+pub(crate) mod gcp {
+    /// Headers set by the metadata server on every response.
+    pub(crate) const HEADERS: &[(&str, &str)] = &[
+        ("Metadata-Flavor", "Google"),
+        ("Server", "Metadata Server for VM"),
+    ];
+    pub(crate) const INSTANCE_ID: &str = "4520031799277581759";
+    pub(crate) const ZONE: &str = "projects/123456789012/zones/us-central1-a";
+    pub(crate) const MACHINE_TYPE: &str = "projects/123456789012/machineTypes/e2-standard-4";
+    pub(crate) const PROJECT_ID: &str = "my-project";
+    pub(crate) const SERVICE_ACCOUNTS: &str =
+        "123456789012-compute@developer.gserviceaccount.com/\ndefault/\n";
+    pub(crate) const NETWORK_INTERFACES: &str = r#"[{"accessConfigs":[{"externalIp":"34.0.0.2","type":"ONE_TO_ONE_NAT"}],"ip":"10.128.0.2","mac":"42:01:0a:80:00:02","network":"projects/123456789012/networks/default"}]"#;
+    pub(crate) const INSTANCE_ATTRIBUTES: &str =
+        r#"{"enable-oslogin":"TRUE","ssh-keys":"user:ssh-ed25519 AAAA"}"#;
+    pub(crate) const METADATA: &str = r#"{"instance":{"id":4520031799277581759,"name":"instance-1","hostname":"instance-1.us-central1-a.c.my-project.internal","zone":"projects/123456789012/zones/us-central1-a","machineType":"projects/123456789012/machineTypes/e2-standard-4","attributes":{"enable-oslogin":"TRUE"},"serviceAccounts":{"123456789012-compute@developer.gserviceaccount.com":{"aliases":["default"],"email":"123456789012-compute@developer.gserviceaccount.com","scopes":["https://www.googleapis.com/auth/cloud-platform"]},"default":{"aliases":["default"],"email":"123456789012-compute@developer.gserviceaccount.com","scopes":["https://www.googleapis.com/auth/cloud-platform"]}}},"project":{"attributes":{},"numericProjectId":123456789012,"projectId":"my-project"}}"#;
+    pub(crate) const VENDOR: &str = "Google Compute Engine";
+}
+
+/// KVM.
+pub(crate) mod kvm {
+    pub(crate) const HARVESTER_VENDOR: &str = "Harvester";
+    pub(crate) const KUBEVIRT_VENDOR: &str = "KubeVirt";
+    pub(crate) const QEMU_VENDOR: &str = "QEMU";
+    pub(crate) const HYPERVISOR: &str = "KVMKVMKVM";
+}
+
+/// Naver Cloud Platform.
+pub(crate) mod naver {
+    pub(crate) const INSTANCE_ID: &str = "12345678";
+    pub(crate) const VENDOR: &str = "NAVER Cloud";
+}
+
+/// NHN Cloud.
+pub(crate) mod nhn {
+    pub(crate) const METADATA: &str = r#"{"uuid":"0b7e1f2c-6a4d-4c3e-9b8a-7d5f3e2a1c90","name":"vm","hostname":"vm.novalocal","availability_zone":"kr-pub-a","project_id":"9c2e4a6f"}"#;
+}
+
+/// Nutanix AHV.
+pub(crate) mod nutanix {
+    pub(crate) const VENDOR: &str = "Nutanix";
+    pub(crate) const PRODUCT_NAME: &str = "AHV";
+}
+
+/// Oracle Cloud Infrastructure (OCI).
+pub(crate) mod oci {
+    pub(crate) const METADATA_V1: &str = r#"{"oke-tm": "oke"}"#;
+    pub(crate) const METADATA_V1_MISMATCH: &str = r#"{"oke-tm": "abc"}"#;
+    pub(crate) const METADATA_V2: &str = r#"{"id": "ocid1.instance.oc1.iad.abc123", "displayName": "instance", "region": "iad", "canonicalRegionName": "us-ashburn-1", "availabilityDomain": "AD-1", "shape": "VM.Standard.E4.Flex", "compartmentId": "ocid1.compartment.oc1..abc123"}"#;
+    pub(crate) const METADATA_V2_MISMATCH: &str = r#"{"id": "abc"}"#;
+    pub(crate) const VNICS: &str = r#"[{"vnicId": "ocid1.vnic.oc1.iad.abc123", "privateIp": "10.0.0.2", "vlanTag": 0, "macAddr": "02:00:17:00:00:01", "virtualRouterIp": "10.0.0.1", "subnetCidrBlock": "10.0.0.0/24"}]"#;
+    pub(crate) const VENDOR: &str = "OracleCloud.com";
+}
+
+/// OpenStack.
+pub(crate) mod openstack {
+    pub(crate) const CONFIG_DRIVE_METADATA: &str = r#"{"uuid":"83679162-1378-4288-a2d4-70e13ec132aa","name":"vm","hostname":"vm.novalocal","availability_zone":"nova","project_id":"6b4f1d2a"}"#;
+    pub(crate) const PRODUCT_NAME: &str = "OpenStack Compute";
+    pub(crate) const CHASSIS_ASSET_TAG: &str = "HUAWEICLOUD";
+    pub(crate) const OPEN_TELEKOM_CHASSIS_ASSET_TAG: &str = "OpenTelekomCloud";
+    pub(crate) const SAP_CCLOUD_CHASSIS_ASSET_TAG: &str = "SAP CCloud VM";
+}
+
+/// Proxmox Virtual Environment.
+pub(crate) mod proxmox {
+    pub(crate) const BIOS_VENDOR: &str = "Proxmox distribution of EDK II";
+    pub(crate) const VENDOR: &str = "Proxmox";
+}
+
+/// VK Cloud.
+pub(crate) mod vkcloud {
+    pub(crate) const METADATA: &str = r#"{"uuid":"5e1d0c7a-9f3b-4d2e-8a61-2c4b7f9e0d13","name":"vm","hostname":"vm.novalocal","availability_zone":"MS1","project_id":"b3f2d8a1"}"#;
+}
+
+/// VMware.
+pub(crate) mod vmware {
+    pub(crate) const VENDOR: &str = "VMware, Inc.";
+    pub(crate) const PRODUCT_NAME: &str = "VMware Virtual Platform";
+    pub(crate) const HYPERVISOR: &str = "VMwareVMware";
+}
+
+/// Vultr.
+pub(crate) mod vultr {
+    pub(crate) const METADATA: &str = r#"{"instanceid":"i-123abc","hostname":"vultr-guest","region":{"regioncode":"EWR","countrycode":"US"},"plan":"vc2-1c-1gb"}"#;
+    pub(crate) const METADATA_BARE_METAL: &str = r#"{"instanceid":"i-456def","hostname":"vultr-metal","region":{"regioncode":"EWR","countrycode":"US"},"plan":"vbm-4c-32gb"}"#;
+    pub(crate) const METADATA_MISMATCH: &str = r#"{"instanceid":""}"#;
+    pub(crate) const VENDOR: &str = "Vultr";
+    pub(crate) const CHASSIS_ASSET_TAG: &str = "Vultr";
+}
+
+/// Yandex Cloud.
+pub(crate) mod yandex {
+    /// Headers set by the metadata server on every response, which unlike GCP's doesn't identify itself.
+    pub(crate) const HEADERS: &[(&str, &str)] = &[("Metadata-Flavor", "Google")];
+    pub(crate) const IDENTITY_DOCUMENT: &str = r#"{"instanceId":"fhm8e5sljt2qk1ocbh7n","productCodes":null,"availabilityZone":"ru-central1-a","privateIp":"10.128.0.5","version":"2023-03-01","imageId":"fd8kdq6d0p8sij7h5qe3","createdAt":"2024-01-15T09:30:00Z"}"#;
+    pub(crate) const IDENTITY_DOCUMENT_MISMATCH: &str = r#"{"instanceId":""}"#;
+    pub(crate) const INSTANCE_ID: &str = "4520031799277581759";
+    pub(crate) const VENDOR: &str = "Yandex";
+}
@@ -0,0 +1,326 @@
+//! Akamai Cloud (formerly Linode).
+
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::checks::{Check, Fetch, Request, TokenRequest};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{Confidence, Provider, ProviderId, Region};
+
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
+const METADATA_PATH: &str = "/v1/instance";
+const METADATA_TOKEN_PATH: &str = "/v1/token";
+/// Lifetime of a session token of the metadata service, which is also the default.
+const TOKEN_TTL: Duration = Duration::from_secs(3600);
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+/// Vendors named by instances, depending on when they were created.
+const VENDORS: [&str; 2] = ["Akamai", "Linode"];
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Akamai;
+
+/// Represents the metadata of an instance (Linode).
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub id: u64,
+    pub label: Option<String>,
+    pub region: Option<String>,
+    /// Plan (instance type) of the instance, e.g. `g6-nanode-1`.
+    #[serde(rename = "type")]
+    pub instance_type: Option<String>,
+    pub host_uuid: Option<String>,
+}
+
+impl Metadata {
+    /// Whether the instance is a node of a Linode Kubernetes Engine (LKE) cluster, by its label.
+    pub fn is_lke_node(&self) -> bool {
+        self.label.as_deref().is_some_and(is_lke_label)
+    }
+}
+
+pub(crate) struct Akamai;
+
+impl Provider for Akamai {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify Akamai using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_metadata_server(METADATA_URI),
+        ]
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(metadata(METADATA_URI).raw())
+    }
+}
+
+impl Akamai {
+    /// Tries to identify Akamai via the metadata service, which requires a session token.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<Metadata>()
+                .is_some_and(|metadata| metadata.id != 0)
+        })
+    }
+
+    /// Tries to identify Akamai using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| VENDORS.iter().any(|vendor| content.contains(vendor)),
+        )
+        .strong()
+    }
+}
+
+/// Request for the metadata of the instance, as JSON rather than the default plain text.
+fn metadata_request(metadata_uri: &str) -> Request {
+    Request::get(metadata_uri, METADATA_PATH)
+        .header("Accept", "application/json")
+        .token(TokenRequest {
+            path: METADATA_TOKEN_PATH.to_string(),
+            ttl_header: "Metadata-Token-Expiry-Seconds",
+            ttl: TOKEN_TTL,
+            header: "Metadata-Token",
+        })
+}
+
+/// Whether a label (or hostname) is that of an LKE node, which LKE names `lke<cluster>-<pool>-<suffix>` (e.g.
+/// `lke123456-178901-0a1b2c3d4e`).
+pub(crate) fn is_lke_label(label: &str) -> bool {
+    let is_number = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+
+    match label.split('-').collect::<Vec<_>>()[..] {
+        [cluster, pool, suffix] => {
+            cluster.strip_prefix("lke").is_some_and(is_number)
+                && is_number(pool)
+                && !suffix.is_empty()
+                && suffix.bytes().all(|b| b.is_ascii_alphanumeric())
+        }
+        _ => false,
+    }
+}
+
+/// Fetch of the metadata of the instance.
+pub(crate) fn metadata(metadata_uri: &str) -> Fetch<Metadata> {
+    Fetch::json(vec![metadata_request(metadata_uri)])
+}
+
+/// Fetch of the region of the instance, from its metadata.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    metadata(metadata_uri).map(|metadata| {
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: metadata.region.context("Region not available")?,
+            zone: None,
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the plan (instance type) of the instance, from its metadata.
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    metadata(metadata_uri).map(|metadata| metadata.instance_type.context("Plan not available"))
+}
+
+/// Fetches the metadata of the instance from the metadata service.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::akamai::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!("Linode ID: {}", metadata.id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+    use wiremock::matchers::{header, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+    use crate::tokens;
+
+    async fn start_mock_server(body: &str) -> MockServer {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}{}", mock_server.uri(), METADATA_TOKEN_PATH));
+
+        Mock::given(method("PUT"))
+            .and(path(METADATA_TOKEN_PATH))
+            .and(header(
+                "Metadata-Token-Expiry-Seconds",
+                TOKEN_TTL.as_secs().to_string(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::akamai::TOKEN))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(METADATA_PATH))
+            .and(header("Metadata-Token", fixtures::akamai::TOKEN))
+            .and(header("Accept", "application/json"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&mock_server)
+            .await;
+
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_success() {
+        let mock_server = start_mock_server(fixtures::akamai::METADATA).await;
+
+        let provider = Akamai;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_failure() {
+        let mock_server = start_mock_server(fixtures::akamai::METADATA_MISMATCH).await;
+
+        let provider = Akamai;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let provider = Akamai;
+
+        for vendor in [fixtures::akamai::VENDOR, fixtures::akamai::VENDOR_LEGACY] {
+            let mut vendor_file = NamedTempFile::new()?;
+            vendor_file.write_all(vendor.as_bytes())?;
+
+            let result = provider
+                .check_vendor_file(vendor_file.path())
+                .run(Duration::from_secs(1))
+                .await;
+
+            assert!(result, "vendor: {vendor}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let vendor_file = NamedTempFile::new()?;
+
+        let provider = Akamai;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata() -> Result<()> {
+        let mock_server = start_mock_server(fixtures::akamai::METADATA).await;
+
+        let metadata = metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.id, 12345678);
+        assert_eq!(metadata.label.as_deref(), Some("linode12345678"));
+        assert!(!metadata.is_lke_node());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_lke_label() {
+        assert!(is_lke_label(fixtures::akamai::LKE_LABEL));
+        assert!(!is_lke_label("linode12345678"));
+        assert!(!is_lke_label("lke123456"));
+        assert!(!is_lke_label("lke-178901-0a1b2c3d4e"));
+        assert!(!is_lke_label("lkeabc-178901-0a1b2c3d4e"));
+        assert!(!is_lke_label("lke123456-178901-0a1b2c3d4e-extra"));
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = start_mock_server(fixtures::akamai::METADATA).await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "us-ord");
+        assert_eq!(region.zone, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = start_mock_server(fixtures::akamai::METADATA).await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "g6-nanode-1");
+
+        Ok(())
+    }
+}
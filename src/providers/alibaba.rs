@@ -3,111 +3,346 @@
 use std::path::Path;
 use std::time::Duration;
 
-use async_trait::async_trait;
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
+#[cfg(feature = "async")]
+use anyhow::Result;
+use anyhow::{bail, Context};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-use crate::{Provider, ProviderId};
+use crate::checks::{Check, Fetch, Request, TokenRequest};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{
+    normalize_mac,
+    AccountInfo,
+    Confidence,
+    CredentialsInfo,
+    NetworkInfo,
+    Provider,
+    ProviderId,
+    Region,
+};
 
-const METADATA_URI: &str = "http://100.100.100.200";
+pub(crate) const METADATA_URI: &str = "http://100.100.100.200";
 const METADATA_PATH: &str = "/latest/meta-data/latest/meta-data/instance/virtualization-solution";
+const METADATA_TOKEN_PATH: &str = "/latest/api/token";
+const TOKEN_TTL: Duration = Duration::from_secs(21600);
+const IDENTITY_DOCUMENT_PATH: &str = "/latest/dynamic/instance-identity/document";
+const VPC_ID_PATH: &str = "/latest/meta-data/vpc-id";
+const VSWITCH_ID_PATH: &str = "/latest/meta-data/vswitch-id";
+const EIPV4_PATH: &str = "/latest/meta-data/eipv4";
+const RAM_SECURITY_CREDENTIALS_PATH: &str = "/latest/meta-data/ram/security-credentials/";
 const VENDOR_FILE: &str = "/sys/class/dmi/id/product_name";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::Alibaba;
 
+/// Represents the identity document of an ECS instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct IdentityDocument {
+    pub instance_id: String,
+    pub instance_type: Option<String>,
+    pub image_id: Option<String>,
+    pub owner_account_id: Option<String>,
+    pub region_id: Option<String>,
+    pub zone_id: Option<String>,
+    pub private_ipv4: Option<String>,
+    pub mac: Option<String>,
+}
+
 pub(crate) struct Alibaba;
 
-#[async_trait]
 impl Provider for Alibaba {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify Alibaba Cloud using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking Alibaba Cloud");
-        if self.check_vendor_file(VENDOR_FILE).await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified Alibaba Cloud");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_metadata_server_hardened(METADATA_URI),
+            self.check_metadata_server(METADATA_URI),
+        ]
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        Some(network_info(METADATA_URI))
+    }
+
+    fn credentials_info(&self) -> Option<Fetch<CredentialsInfo>> {
+        Some(credentials_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(identity_document(METADATA_URI).raw())
     }
 }
 
 impl Alibaba {
-    /// Tries to identify Alibaba via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-
-        match client.get(url).send().await {
-            Ok(resp) => match resp.text().await {
-                Ok(text) => text.contains("ECS Virt"),
-                Err(err) => {
-                    error!("Error reading response: {:?}", err);
-                    false
-                }
-            },
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
+    /// Tries to identify Alibaba via metadata server (in security-hardened mode, using a session token).
+    pub(crate) fn check_metadata_server_hardened(&self, metadata_uri: &str) -> Check {
+        let request = hardened_request(metadata_uri, METADATA_PATH);
+
+        Check::http(
+            "metadata_server_hardened",
+            Confidence::Verified,
+            request,
+            |resp| resp.is_success() && resp.body.contains("ECS Virt"),
+        )
+    }
+
+    /// Tries to identify Alibaba via metadata server (in normal mode).
+    ///
+    /// In security-hardened mode, requests without a session token are rejected with `403 Forbidden`, which still proves
+    /// the metadata server is present.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = Request::get(metadata_uri, METADATA_PATH);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.is_success() && resp.body.contains("ECS Virt")
+        })
+        .or_unauthorized(Confidence::Unverified)
     }
 
     /// Tries to identify Alibaba using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
-
-        if vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(vendor_file).await {
-                Ok(content) => content.contains("Alibaba Cloud ECS"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Alibaba Cloud ECS"),
+        )
+        .strong()
+    }
+}
+
+/// Request for a metadata path using a session token, as required in security-hardened mode.
+fn hardened_request(metadata_uri: &str, path: &str) -> Request {
+    Request::get(metadata_uri, path).token(TokenRequest {
+        path: METADATA_TOKEN_PATH.to_string(),
+        ttl_header: "X-aliyun-ecs-metadata-token-ttl-seconds",
+        ttl: TOKEN_TTL,
+        header: "X-aliyun-ecs-metadata-token",
+    })
+}
+
+/// Requests for a metadata path, using a session token before falling back to normal mode.
+fn requests(metadata_uri: &str, path: &str) -> Vec<Request> {
+    vec![
+        hardened_request(metadata_uri, path),
+        Request::get(metadata_uri, path),
+    ]
+}
+
+/// Fetch of the identity document of the instance, using a session token before falling back to normal mode.
+pub(crate) fn identity_document(metadata_uri: &str) -> Fetch<IdentityDocument> {
+    Fetch::json(requests(metadata_uri, IDENTITY_DOCUMENT_PATH))
+}
+
+/// Fetch of the region of the instance, from its identity document.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    identity_document(metadata_uri).map(|document| {
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: document.region_id.context("Region not available")?,
+            zone: document.zone_id,
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the instance type of the instance, from its identity document.
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    identity_document(metadata_uri).map(|document| {
+        document
+            .instance_type
+            .context("Instance type not available")
+    })
+}
+
+/// Fetch of the owner account of the instance, from its identity document.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    identity_document(metadata_uri).map(|document| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: document.owner_account_id.context("Account not available")?,
+        })
+    })
+}
+
+/// Fetch of a plaintext metadata value, using a session token before falling back to normal mode.
+fn meta_data(metadata_uri: &str, path: &str) -> Fetch<String> {
+    Fetch::new(requests(metadata_uri, path), |resp| {
+        if !resp.is_success() {
+            bail!("Unexpected status code: {}", resp.status);
         }
 
-        false
-    }
+        match resp.body.trim() {
+            "" => bail!("Empty value"),
+            value => Ok(value.to_string()),
+        }
+    })
+}
+
+/// Fetch of the network configuration of the instance, from its identity document and the metadata of its VPC.
+pub(crate) fn network_info(metadata_uri: &str) -> Fetch<NetworkInfo> {
+    let metadata_uri = metadata_uri.to_string();
+
+    identity_document(&metadata_uri).and_then(move |document| {
+        meta_data(&metadata_uri, EIPV4_PATH)
+            .optional()
+            .zip(meta_data(&metadata_uri, VPC_ID_PATH).optional())
+            .zip(meta_data(&metadata_uri, VSWITCH_ID_PATH).optional())
+            .map(move |((eipv4, vpc_id), vswitch_id)| {
+                Ok(NetworkInfo {
+                    provider: IDENTIFIER,
+                    local_ipv4: document
+                        .private_ipv4
+                        .as_ref()
+                        .and_then(|ip| ip.parse().ok()),
+                    public_ipv4: eipv4.and_then(|ip| ip.parse().ok()),
+                    mac: document.mac.as_deref().and_then(normalize_mac),
+                    network_id: vpc_id,
+                    subnet_id: vswitch_id,
+                    ..Default::default()
+                })
+            })
+    })
+}
+
+/// Fetch of the RAM roles the instance has credentials for.
+///
+/// Only the listing of role names is requested, never the credentials of a role.
+pub(crate) fn credentials_info(metadata_uri: &str) -> Fetch<CredentialsInfo> {
+    Fetch::new(
+        requests(metadata_uri, RAM_SECURITY_CREDENTIALS_PATH),
+        |resp| {
+            let roles = resp.listing()?;
+
+            Ok(CredentialsInfo {
+                provider: IDENTIFIER,
+                exposed: !roles.is_empty(),
+                identities: roles,
+            })
+        },
+    )
+}
+
+/// Fetches the identity document of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::alibaba::fetch_identity_document;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let document = fetch_identity_document(None).await.unwrap();
+///     println!("Instance ID: {}", document.instance_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_identity_document(timeout: Option<u64>) -> Result<IdentityDocument> {
+    identity_document(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
-    use wiremock::matchers::path;
+    use wiremock::matchers::{header, method, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
+    use crate::tokens;
+
+    /// Mounts the token endpoint of the security-hardened mode, and the given metadata path requiring the token.
+    async fn start_hardened_mock_server(metadata_path: &str, body: &str) -> MockServer {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}{}", mock_server.uri(), METADATA_TOKEN_PATH));
+
+        Mock::given(method("PUT"))
+            .and(path(METADATA_TOKEN_PATH))
+            .and(header(
+                "X-aliyun-ecs-metadata-token-ttl-seconds",
+                TOKEN_TTL.as_secs().to_string(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::alibaba::TOKEN))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(metadata_path))
+            .and(header(
+                "X-aliyun-ecs-metadata-token",
+                fixtures::alibaba::TOKEN,
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(body))
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(metadata_path))
+            .respond_with(ResponseTemplate::new(403))
+            .mount(&mock_server)
+            .await;
+
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_hardened() {
+        let mock_server =
+            start_hardened_mock_server(METADATA_PATH, fixtures::alibaba::METADATA).await;
+
+        let provider = Alibaba;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server_hardened(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_forbidden() {
+        let mock_server =
+            start_hardened_mock_server(METADATA_PATH, fixtures::alibaba::METADATA).await;
+
+        let provider = Alibaba;
+        let metadata_uri = mock_server.uri();
+        let check = provider.check_metadata_server(&metadata_uri);
+        let inspection = check.inspect(Duration::from_secs(1)).await;
+
+        assert!(inspection.matched());
+        assert_eq!(check.match_confidence(&inspection), Confidence::Unverified);
+    }
 
     #[tokio::test]
     async fn test_check_metadata_server_success() {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_string("ECS Virt"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::alibaba::METADATA))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -115,7 +350,8 @@ mod tests {
         let provider = Alibaba;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -125,7 +361,7 @@ mod tests {
     async fn test_check_metadata_server_failure() {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_string("ABC"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::MALFORMED))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -133,7 +369,8 @@ mod tests {
         let provider = Alibaba;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -142,10 +379,13 @@ mod tests {
     #[tokio::test]
     async fn test_check_vendor_file_success() -> Result<()> {
         let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"Alibaba Cloud ECS")?;
+        vendor_file.write_all(fixtures::alibaba::VENDOR.as_bytes())?;
 
         let provider = Alibaba;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(result);
 
@@ -157,10 +397,169 @@ mod tests {
         let vendor_file = NamedTempFile::new()?;
 
         let provider = Alibaba;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_identity_document() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::alibaba::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let document = identity_document(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(document.instance_id, "i-bp1abc123");
+        assert_eq!(document.region_id.as_deref(), Some("cn-hangzhou"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_identity_document_hardened() -> Result<()> {
+        let mock_server = start_hardened_mock_server(
+            IDENTITY_DOCUMENT_PATH,
+            fixtures::alibaba::IDENTITY_DOCUMENT,
+        )
+        .await;
+
+        let document = identity_document(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(document.instance_id, "i-bp1abc123");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::alibaba::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "cn-hangzhou");
+        assert_eq!(region.zone.as_deref(), Some("cn-hangzhou-i"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::alibaba::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "ecs.g6.large");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::alibaba::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "1234567890123456");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::alibaba::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(VPC_ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string("vpc-bp1abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(VSWITCH_ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string("vsw-bp1abc"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let network = network_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(network.provider, IDENTIFIER);
+        assert_eq!(network.local_ipv4, Some("172.16.0.10".parse()?));
+        assert_eq!(network.public_ipv4, None);
+        assert_eq!(network.mac.as_deref(), Some("00:16:3e:00:00:01"));
+        assert_eq!(network.network_id.as_deref(), Some("vpc-bp1abc"));
+        assert_eq!(network.subnet_id.as_deref(), Some("vsw-bp1abc"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(RAM_SECURITY_CREDENTIALS_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string("my-role"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials = credentials_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(credentials.provider, IDENTIFIER);
+        assert!(credentials.exposed);
+        assert_eq!(credentials.identities, ["my-role"]);
+
+        Ok(())
+    }
 }
@@ -1,204 +1,517 @@
 //! Amazon Web Services (AWS).
 
 use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::Duration;
 
-use async_trait::async_trait;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use anyhow::{bail, Context};
 use serde::{Deserialize, Serialize};
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
+use serde_json::Value;
+use strum::{Display, EnumString};
 
-use crate::{Provider, ProviderId};
+use crate::checks::{Check, Fetch, Request, Response, TokenRequest};
+use crate::serverless;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{
+    normalize_mac,
+    AccountInfo,
+    Confidence,
+    CredentialsInfo,
+    NetworkInfo,
+    Provider,
+    ProviderId,
+    Region,
+};
 
-const METADATA_URI: &str = "http://169.254.169.254";
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
 const METADATA_PATH: &str = "/latest/dynamic/instance-identity/document";
 const METADATA_TOKEN_PATH: &str = "/latest/api/token";
+const MAC_PATH: &str = "/latest/meta-data/mac";
+const LOCAL_IPV4_PATH: &str = "/latest/meta-data/local-ipv4";
+const PUBLIC_IPV4_PATH: &str = "/latest/meta-data/public-ipv4";
+const INTERFACES_PATH: &str = "/latest/meta-data/network/interfaces/macs/";
+const PARTITION_PATH: &str = "/latest/meta-data/services/partition";
+const SECURITY_CREDENTIALS_PATH: &str = "/latest/meta-data/iam/security-credentials/";
 const PRODUCT_VERSION_FILE: &str = "/sys/class/dmi/id/product_version";
 const BIOS_VENDOR_FILE: &str = "/sys/class/dmi/id/bios_vendor";
+/// Environment variables set by the ECS agent to the task metadata endpoint of the container, by order of preference
+/// (version 4, then version 3).
+const TASK_METADATA_ENV_VARS: [&str; 2] = [
+    "ECS_CONTAINER_METADATA_URI_V4",
+    "ECS_CONTAINER_METADATA_URI",
+];
+const TASK_METADATA_PATH: &str = "/task";
+/// Maximum lifetime (seconds) of an IMDSv2 session token, which is also the default.
+const MAX_TOKEN_TTL: u64 = 21600;
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::AWS;
 
-#[derive(Serialize, Deserialize)]
-struct MetadataResponse {
-    #[serde(rename = "imageId")]
-    image_id: String,
-    #[serde(rename = "instanceId")]
-    instance_id: String,
+static TOKEN_TTL: AtomicU64 = AtomicU64::new(MAX_TOKEN_TTL);
+
+/// Represents the identity document of an EC2 instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityDocument {
+    pub instance_id: String,
+    pub image_id: String,
+    pub instance_type: Option<String>,
+    pub account_id: Option<String>,
+    pub region: Option<String>,
+    pub availability_zone: Option<String>,
+    pub architecture: Option<String>,
+    pub private_ip: Option<String>,
+}
+
+/// Represents the metadata of an ECS task (on EC2 or Fargate), from the task metadata endpoint.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct TaskMetadata {
+    /// ARN (or short name, on EC2 with version 3 of the endpoint) of the cluster the task runs in.
+    pub cluster: String,
+    #[serde(rename = "TaskARN")]
+    pub task_arn: String,
+    pub family: Option<String>,
+    pub revision: Option<String>,
+    pub availability_zone: Option<String>,
+    /// Launch type of the task (`EC2` or `FARGATE`), only served by version 4 of the endpoint.
+    pub launch_type: Option<String>,
+}
+
+/// Represents the partition (isolated group of regions) an EC2 instance runs in.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Display, EnumString)]
+pub enum Partition {
+    /// Standard AWS regions.
+    #[strum(serialize = "aws")]
+    Aws,
+    /// AWS China regions.
+    #[strum(serialize = "aws-cn")]
+    AwsCn,
+    /// AWS GovCloud (US) regions.
+    #[strum(serialize = "aws-us-gov")]
+    AwsUsGov,
+    /// AWS Top Secret-East regions.
+    #[strum(serialize = "aws-iso")]
+    AwsIso,
+    /// AWS Secret regions.
+    #[strum(serialize = "aws-iso-b")]
+    AwsIsoB,
+}
+
+impl Partition {
+    /// Returns the partition a region belongs to, from its name.
+    pub fn from_region(region: &str) -> Self {
+        if region.starts_with("cn-") {
+            Self::AwsCn
+        } else if region.starts_with("us-gov-") {
+            Self::AwsUsGov
+        } else if region.starts_with("us-isob-") {
+            Self::AwsIsoB
+        } else if region.starts_with("us-iso-") {
+            Self::AwsIso
+        } else {
+            Self::Aws
+        }
+    }
+
+    /// Returns the DNS suffix of the service endpoints of the partition (e.g. `amazonaws.com.cn`).
+    pub fn dns_suffix(&self) -> &'static str {
+        match self {
+            Self::Aws | Self::AwsUsGov => "amazonaws.com",
+            Self::AwsCn => "amazonaws.com.cn",
+            Self::AwsIso => "c2s.ic.gov",
+            Self::AwsIsoB => "sc2s.sgov.gov",
+        }
+    }
 }
 
 pub(crate) struct Aws;
 
-#[async_trait]
 impl Provider for Aws {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify AWS using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking Amazon Web Services");
-        if self.check_product_version_file(PRODUCT_VERSION_FILE).await
-            || self.check_bios_vendor_file(BIOS_VENDOR_FILE).await
-            || self
-                .check_metadata_server_imdsv2(METADATA_URI, timeout)
-                .await
-            || self
-                .check_metadata_server_imdsv1(METADATA_URI, timeout)
-                .await
-        {
-            info!("Identified Amazon Web Services");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
+    fn checks(&self) -> Vec<Check> {
+        let mut checks = vec![
+            self.check_product_version_file(PRODUCT_VERSION_FILE),
+            self.check_bios_vendor_file(BIOS_VENDOR_FILE),
+        ];
+        // Lambda exposes neither DMI attributes nor instance metadata
+        checks.extend(serverless::checks(IDENTIFIER));
+        checks.push(self.check_metadata_server_imdsv2(METADATA_URI));
+        checks.push(self.check_metadata_server_imdsv1(METADATA_URI));
+        // Reachable from ECS tasks even if IMDS isn't (e.g. on Fargate, or on EC2 with a hop limit of 1)
+        if let Some(task_metadata_uri) = task_metadata_uri() {
+            checks.push(self.check_task_metadata(&task_metadata_uri));
         }
+        checks
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        Some(network_info(METADATA_URI))
+    }
+
+    fn credentials_info(&self) -> Option<Fetch<CredentialsInfo>> {
+        Some(credentials_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(identity_document(METADATA_URI).raw())
     }
 }
 
 impl Aws {
     /// Tries to identify AWS via metadata server (using IMDSv2).
-    #[instrument(skip_all)]
-    async fn check_metadata_server_imdsv2(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let token_url = format!("{}{}", metadata_uri, METADATA_TOKEN_PATH);
-        debug!("Retrieving {} IMDSv2 token from: {}", IDENTIFIER, token_url);
+    pub(crate) fn check_metadata_server_imdsv2(&self, metadata_uri: &str) -> Check {
+        let request = imdsv2_request(metadata_uri, METADATA_PATH);
 
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
+        Check::http(
+            "imdsv2",
+            Confidence::Verified,
+            request,
+            matches_identity_document,
+        )
+    }
 
-        let token = match client
-            .get(token_url)
-            .header("X-aws-ec2-metadata-token-ttl-seconds", "60")
-            .send()
-            .await
-        {
-            Ok(resp) => resp.text().await.unwrap_or_else(|err| {
-                error!("Error reading token: {:?}", err);
-                String::new()
-            }),
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                return false;
-            }
-        };
+    /// Tries to identify AWS via metadata server (using IMDSv1).
+    ///
+    /// If IMDSv2 is required, IMDSv1 requests are rejected with `401 Unauthorized`, which still proves the metadata
+    /// server is present (e.g. when the IMDSv2 token can't be retrieved because of the hop limit).
+    pub(crate) fn check_metadata_server_imdsv1(&self, metadata_uri: &str) -> Check {
+        let request = Request::get(metadata_uri, METADATA_PATH);
 
-        if token.is_empty() {
-            error!("IMDSv2 token is empty");
-            return false;
-        }
+        Check::http(
+            "imdsv1",
+            Confidence::Verified,
+            request,
+            matches_identity_document,
+        )
+        .or_unauthorized(Confidence::Unverified)
+    }
 
-        // Request to use the token to get metadata
-        let metadata_url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!(
-            "Checking {} metadata using url: {}",
-            IDENTIFIER, metadata_url
-        );
+    /// Tries to identify AWS via the task metadata endpoint of an ECS container.
+    pub(crate) fn check_task_metadata(&self, task_metadata_uri: &str) -> Check {
+        let request = Request::get(task_metadata_uri, TASK_METADATA_PATH);
 
-        let resp = match client
-            .get(metadata_url)
-            .header("X-aws-ec2-metadata-token", token)
-            .send()
-            .await
-        {
-            Ok(resp) => resp.json::<MetadataResponse>().await,
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                return false;
-            }
-        };
+        Check::http(
+            "task_metadata",
+            Confidence::Verified,
+            request,
+            matches_task_metadata,
+        )
+    }
 
-        match resp {
-            Ok(metadata) => {
-                metadata.image_id.starts_with("ami-") && metadata.instance_id.starts_with("i-")
-            }
-            Err(err) => {
-                error!("Error reading response: {:?}", err);
-                false
-            }
-        }
+    /// Tries to identify AWS using the product version file.
+    pub(crate) fn check_product_version_file<P: AsRef<Path>>(
+        &self,
+        product_version_file: P,
+    ) -> Check {
+        Check::dmi(
+            "product_version_file",
+            Confidence::Unverified,
+            product_version_file.as_ref(),
+            |content| content.to_lowercase().contains("amazon"),
+        )
     }
 
-    /// Tries to identify AWS via metadata server (using IMDSv1).
-    #[instrument(skip_all)]
-    async fn check_metadata_server_imdsv1(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
+    /// Tries to identify AWS using the BIOS vendor file.
+    pub(crate) fn check_bios_vendor_file<P: AsRef<Path>>(&self, bios_vendor_file: P) -> Check {
+        Check::dmi(
+            "bios_vendor_file",
+            Confidence::Unverified,
+            bios_vendor_file.as_ref(),
+            |content| content.to_lowercase().contains("amazon"),
+        )
+        .strong()
+    }
+}
 
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
+/// Sets the lifetime (seconds) of IMDSv2 session tokens, clamped between 1 and 21600 (6 hours, the default).
+///
+/// Tokens are reused by all checks and fetches until they expire, and refreshed early if the metadata server rejects
+/// them.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::providers::aws::set_token_ttl;
+///
+/// set_token_ttl(300);
+/// ```
+pub fn set_token_ttl(ttl: u64) {
+    TOKEN_TTL.store(ttl.clamp(1, MAX_TOKEN_TTL), Ordering::Relaxed);
+}
+
+/// Request for a metadata path using an IMDSv2 session token.
+fn imdsv2_request(metadata_uri: &str, path: &str) -> Request {
+    Request::get(metadata_uri, path).token(TokenRequest {
+        path: METADATA_TOKEN_PATH.to_string(),
+        ttl_header: "X-aws-ec2-metadata-token-ttl-seconds",
+        ttl: Duration::from_secs(TOKEN_TTL.load(Ordering::Relaxed)),
+        header: "X-aws-ec2-metadata-token",
+    })
+}
+
+/// Whether the response is an instance identity document.
+fn matches_identity_document(resp: &Response) -> bool {
+    resp.json::<IdentityDocument>().is_some_and(|document| {
+        document.image_id.starts_with("ami-") && document.instance_id.starts_with("i-")
+    })
+}
+
+/// Whether the response is the metadata of an ECS task.
+fn matches_task_metadata(resp: &Response) -> bool {
+    resp.is_success()
+        && resp.json::<TaskMetadata>().is_some_and(|task| {
+            task.task_arn.starts_with("arn:aws") && task.task_arn.contains(":task/")
+        })
+}
+
+/// Returns the task metadata endpoint of the container, if it runs in an ECS task.
+pub(crate) fn task_metadata_uri() -> Option<String> {
+    task_metadata_uri_from(|name| std::env::var(name).ok())
+}
 
-        match client.get(url).send().await {
-            Ok(resp) => match resp.json::<MetadataResponse>().await {
-                Ok(resp) => resp.image_id.starts_with("ami-") && resp.instance_id.starts_with("i-"),
-                Err(err) => {
-                    error!("Error reading response: {:?}", err);
-                    false
-                }
-            },
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
+pub(crate) fn task_metadata_uri_from<F: Fn(&str) -> Option<String>>(var: F) -> Option<String> {
+    TASK_METADATA_ENV_VARS
+        .iter()
+        .filter_map(|name| var(name))
+        .map(|uri| uri.trim().trim_end_matches('/').to_string())
+        .find(|uri| !uri.is_empty())
+}
+
+/// Fetch of the metadata of the ECS task, from the task metadata endpoint of the container.
+pub(crate) fn task_metadata(task_metadata_uri: &str) -> Fetch<TaskMetadata> {
+    Fetch::json(vec![Request::get(task_metadata_uri, TASK_METADATA_PATH)])
+}
+
+/// Fetch of the identity document of the instance, using IMDSv2 before falling back to IMDSv1.
+pub(crate) fn identity_document(metadata_uri: &str) -> Fetch<IdentityDocument> {
+    Fetch::json(vec![
+        imdsv2_request(metadata_uri, METADATA_PATH),
+        Request::get(metadata_uri, METADATA_PATH),
+    ])
+}
+
+/// Fetch of the region of the instance, from its identity document.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    identity_document(metadata_uri).map(|document| {
+        let region = document.region.context("Region not available")?;
+
+        Ok(Region {
+            provider: IDENTIFIER,
+            partition: Some(Partition::from_region(&region).to_string()),
+            region,
+            zone: document.availability_zone,
+        })
+    })
+}
+
+/// Fetch of the instance type of the instance, from its identity document.
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    identity_document(metadata_uri).map(|document| {
+        document
+            .instance_type
+            .context("Instance type not available")
+    })
+}
+
+/// Fetch of the account of the instance, from its identity document.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    identity_document(metadata_uri).map(|document| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: document.account_id.context("Account not available")?,
+        })
+    })
+}
+
+/// Fetch of a plaintext metadata value, using IMDSv2 before falling back to IMDSv1.
+fn meta_data(metadata_uri: &str, path: &str) -> Fetch<String> {
+    Fetch::new(
+        vec![
+            imdsv2_request(metadata_uri, path),
+            Request::get(metadata_uri, path),
+        ],
+        |resp| {
+            if !resp.is_success() {
+                bail!("Unexpected status code: {}", resp.status);
             }
-        }
-    }
 
-    /// Tries to identify AWS using the product version file.
-    #[instrument(skip_all)]
-    async fn check_product_version_file<P: AsRef<Path>>(&self, product_version_file: P) -> bool {
-        debug!(
-            "Checking {} product version file: {}",
-            IDENTIFIER,
-            product_version_file.as_ref().display()
-        );
+            match resp.body.lines().next().map(str::trim) {
+                Some(value) if !value.is_empty() => Ok(value.to_string()),
+                _ => bail!("Empty value"),
+            }
+        },
+    )
+}
 
-        if product_version_file.as_ref().is_file() {
-            return match fs::read_to_string(product_version_file).await {
-                Ok(content) => content.to_lowercase().contains("amazon"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
-        }
+/// Fetch of the partition of the instance, using IMDSv2 before falling back to IMDSv1.
+pub(crate) fn partition(metadata_uri: &str) -> Fetch<Partition> {
+    meta_data(metadata_uri, PARTITION_PATH).map(|partition| {
+        partition
+            .parse()
+            .with_context(|| format!("Unknown partition: {partition}"))
+    })
+}
 
-        false
-    }
+/// Fetch of the network configuration of the primary network interface of the instance.
+///
+/// The interface is identified by its MAC address, which is required to look up its VPC and subnet.
+pub(crate) fn network_info(metadata_uri: &str) -> Fetch<NetworkInfo> {
+    let metadata_uri = metadata_uri.to_string();
 
-    /// Tries to identify AWS using the BIOS vendor file.
-    #[instrument(skip_all)]
-    async fn check_bios_vendor_file<P: AsRef<Path>>(&self, bios_vendor_file: P) -> bool {
-        debug!(
-            "Checking {} BIOS vendor file: {}",
-            IDENTIFIER,
-            bios_vendor_file.as_ref().display()
-        );
+    meta_data(&metadata_uri, MAC_PATH).and_then(move |mac| {
+        let interface_path = format!("{INTERFACES_PATH}{mac}");
 
-        if bios_vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(bios_vendor_file).await {
-                Ok(content) => content.to_lowercase().contains("amazon"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
-        }
+        meta_data(&metadata_uri, LOCAL_IPV4_PATH)
+            .optional()
+            .zip(meta_data(&metadata_uri, PUBLIC_IPV4_PATH).optional())
+            .zip(meta_data(&metadata_uri, &format!("{interface_path}/ipv6s")).optional())
+            .zip(meta_data(&metadata_uri, &format!("{interface_path}/vpc-id")).optional())
+            .zip(meta_data(&metadata_uri, &format!("{interface_path}/subnet-id")).optional())
+            .map(
+                move |((((local_ipv4, public_ipv4), ipv6s), vpc_id), subnet_id)| {
+                    Ok(NetworkInfo {
+                        provider: IDENTIFIER,
+                        local_ipv4: local_ipv4.and_then(|ip| ip.parse().ok()),
+                        local_ipv6: ipv6s.and_then(|ip| ip.parse().ok()),
+                        public_ipv4: public_ipv4.and_then(|ip| ip.parse().ok()),
+                        mac: normalize_mac(&mac),
+                        network_id: vpc_id,
+                        subnet_id,
+                    })
+                },
+            )
+    })
+}
 
-        false
-    }
+/// Fetch of the IAM roles the instance has credentials for, using IMDSv2 before falling back to IMDSv1.
+///
+/// Only the listing of role names is requested, never the credentials of a role.
+pub(crate) fn credentials_info(metadata_uri: &str) -> Fetch<CredentialsInfo> {
+    Fetch::new(
+        vec![
+            imdsv2_request(metadata_uri, SECURITY_CREDENTIALS_PATH),
+            Request::get(metadata_uri, SECURITY_CREDENTIALS_PATH),
+        ],
+        |resp| {
+            let roles = resp.listing()?;
+
+            Ok(CredentialsInfo {
+                provider: IDENTIFIER,
+                exposed: !roles.is_empty(),
+                identities: roles,
+            })
+        },
+    )
+}
+
+/// Fetches the identity document of the instance from the metadata server.
+///
+/// IMDSv2 is used when available, falling back to IMDSv1.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::aws::fetch_identity_document;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let document = fetch_identity_document(None).await.unwrap();
+///     println!("Instance ID: {}", document.instance_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_identity_document(timeout: Option<u64>) -> Result<IdentityDocument> {
+    identity_document(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+/// Fetches the partition (e.g. `aws-us-gov` for GovCloud) of the instance from the metadata server, for selecting
+/// service endpoints.
+///
+/// IMDSv2 is used when available, falling back to IMDSv1.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::aws::fetch_partition;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let partition = fetch_partition(None).await.unwrap();
+///     println!("Partition: {} ({})", partition, partition.dns_suffix());
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_partition(timeout: Option<u64>) -> Result<Partition> {
+    partition(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+/// Fetches the metadata of the ECS task (e.g. the ARNs of the task and its cluster) from the task metadata endpoint of
+/// the container, on EC2 or Fargate.
+///
+/// Version 4 of the endpoint is used when available, falling back to version 3. Fails if the container doesn't run in an
+/// ECS task.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::aws::fetch_task_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let task = fetch_task_metadata(None).await.unwrap();
+///     println!("Task {} in cluster {}", task.task_arn, task.cluster);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_task_metadata(timeout: Option<u64>) -> Result<TaskMetadata> {
+    let task_metadata_uri = task_metadata_uri().context("Task metadata endpoint not available")?;
+
+    task_metadata(&task_metadata_uri)
+        .run(timeout_or_default(timeout))
+        .await
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
@@ -206,23 +519,34 @@ mod tests {
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
+    use crate::tokens;
+
+    /// Starts a mock metadata server, dropping any token cached for a previous server with the same address.
+    async fn start_mock_server() -> MockServer {
+        let mock_server = MockServer::start().await;
+        tokens::invalidate(&format!("{}{}", mock_server.uri(), METADATA_TOKEN_PATH));
+        mock_server
+    }
 
     #[tokio::test]
     async fn test_check_metadata_server_imdsv2_success() {
-        let mock_server = MockServer::start().await;
+        let mock_server = start_mock_server().await;
 
         Mock::given(path(METADATA_TOKEN_PATH))
-            .and(header("X-aws-ec2-metadata-token-ttl-seconds", "60"))
-            .respond_with(ResponseTemplate::new(200).set_body_string("123abc"))
+            .and(header(
+                "X-aws-ec2-metadata-token-ttl-seconds",
+                MAX_TOKEN_TTL.to_string(),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
             .expect(1)
             .mount(&mock_server)
             .await;
 
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                image_id: "ami-123abc".to_string(),
-                instance_id: "i-123abc".to_string(),
-            }))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -230,7 +554,8 @@ mod tests {
         let provider = Aws;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server_imdsv2(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server_imdsv2(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -238,19 +563,19 @@ mod tests {
 
     #[tokio::test]
     async fn test_check_metadata_server_imdsv2_failure() {
-        let mock_server = MockServer::start().await;
+        let mock_server = start_mock_server().await;
 
         Mock::given(path(METADATA_TOKEN_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_string("123abc"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
             .expect(1)
             .mount(&mock_server)
             .await;
 
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                image_id: "abc".to_string(),
-                instance_id: "abc".to_string(),
-            }))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::aws::IDENTITY_DOCUMENT_MISMATCH),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -258,7 +583,8 @@ mod tests {
         let provider = Aws;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server_imdsv2(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server_imdsv2(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -266,12 +592,11 @@ mod tests {
 
     #[tokio::test]
     async fn test_check_metadata_server_imdsv1_success() {
-        let mock_server = MockServer::start().await;
+        let mock_server = start_mock_server().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                image_id: "ami-123abc".to_string(),
-                instance_id: "i-123abc".to_string(),
-            }))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -279,7 +604,8 @@ mod tests {
         let provider = Aws;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server_imdsv1(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server_imdsv1(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -287,12 +613,12 @@ mod tests {
 
     #[tokio::test]
     async fn test_check_metadata_server_imdsv1_failure() {
-        let mock_server = MockServer::start().await;
+        let mock_server = start_mock_server().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                image_id: "abc".to_string(),
-                instance_id: "abc".to_string(),
-            }))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::aws::IDENTITY_DOCUMENT_MISMATCH),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -300,20 +626,138 @@ mod tests {
         let provider = Aws;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server_imdsv1(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server_imdsv1(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_imdsv1_unauthorized() {
+        let mock_server = start_mock_server().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(401))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Aws;
+        let metadata_uri = mock_server.uri();
+        let check = provider.check_metadata_server_imdsv1(&metadata_uri);
+        let inspection = check.inspect(Duration::from_secs(1)).await;
+
+        assert!(inspection.matched());
+        assert_eq!(check.match_confidence(&inspection), Confidence::Unverified);
+    }
+
+    #[tokio::test]
+    async fn test_check_task_metadata_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(TASK_METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TASK_METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Aws;
+        let result = provider
+            .check_task_metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_task_metadata_failure() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(TASK_METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::TASK_METADATA_MISMATCH),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Aws;
+        let result = provider
+            .check_task_metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
     }
 
+    #[test]
+    fn test_task_metadata_uri_from() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(task_metadata_uri_from(env(&[])), None);
+        assert_eq!(
+            task_metadata_uri_from(env(&[(
+                "ECS_CONTAINER_METADATA_URI",
+                "http://169.254.170.2/v3/abc"
+            )])),
+            Some("http://169.254.170.2/v3/abc".to_string())
+        );
+        assert_eq!(
+            task_metadata_uri_from(env(&[
+                ("ECS_CONTAINER_METADATA_URI", "http://169.254.170.2/v3/abc"),
+                (
+                    "ECS_CONTAINER_METADATA_URI_V4",
+                    "http://169.254.170.2/v4/abc/"
+                ),
+            ])),
+            Some("http://169.254.170.2/v4/abc".to_string())
+        );
+        assert_eq!(
+            task_metadata_uri_from(env(&[("ECS_CONTAINER_METADATA_URI_V4", "")])),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn test_task_metadata() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(TASK_METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TASK_METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let task = task_metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(
+            task.cluster,
+            "arn:aws:ecs:us-east-1:123456789012:cluster/default"
+        );
+        assert_eq!(
+            task.task_arn,
+            "arn:aws:ecs:us-east-1:123456789012:task/default/158d1c8083dd49d6b527399fd6414f5c"
+        );
+        assert_eq!(task.launch_type.as_deref(), Some("FARGATE"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn test_check_product_version_file_success() -> Result<()> {
         let mut product_version_file = NamedTempFile::new()?;
-        product_version_file.write_all(b"amazon")?;
+        product_version_file.write_all(fixtures::aws::VENDOR.as_bytes())?;
 
         let provider = Aws;
         let result = provider
             .check_product_version_file(product_version_file.path())
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -328,6 +772,7 @@ mod tests {
         let provider = Aws;
         let result = provider
             .check_product_version_file(product_version_file.path())
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -338,11 +783,12 @@ mod tests {
     #[tokio::test]
     async fn test_check_bios_vendor_file_success() -> Result<()> {
         let mut bios_vendor_file = NamedTempFile::new()?;
-        bios_vendor_file.write_all(b"amazon")?;
+        bios_vendor_file.write_all(fixtures::aws::VENDOR.as_bytes())?;
 
         let provider = Aws;
         let result = provider
             .check_bios_vendor_file(bios_vendor_file.path())
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -357,10 +803,285 @@ mod tests {
         let provider = Aws;
         let result = provider
             .check_bios_vendor_file(bios_vendor_file.path())
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_identity_document() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(METADATA_PATH))
+            .and(header("X-aws-ec2-metadata-token", fixtures::aws::TOKEN))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let document = identity_document(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(document.instance_id, "i-12345678");
+        assert_eq!(document.account_id.as_deref(), Some("123456789012"));
+        assert_eq!(document.region.as_deref(), Some("us-east-1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_identity_document_imdsv1_fallback() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let document = identity_document(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(document.instance_id, "i-12345678");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "us-east-1");
+        assert_eq!(region.zone.as_deref(), Some("us-east-1a"));
+        assert_eq!(region.partition.as_deref(), Some("aws"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_partition() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(PARTITION_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string("aws-us-gov"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let partition = partition(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(partition, Partition::AwsUsGov);
+        assert_eq!(partition.dns_suffix(), "amazonaws.com");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_partition_from_region() {
+        assert_eq!(Partition::from_region("us-east-1"), Partition::Aws);
+        assert_eq!(Partition::from_region("cn-north-1"), Partition::AwsCn);
+        assert_eq!(Partition::from_region("us-gov-west-1"), Partition::AwsUsGov);
+        assert_eq!(Partition::from_region("us-iso-east-1"), Partition::AwsIso);
+        assert_eq!(Partition::from_region("us-isob-east-1"), Partition::AwsIsoB);
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "m5.large");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(METADATA_PATH))
+            .and(header("X-aws-ec2-metadata-token", fixtures::aws::TOKEN))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::aws::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "123456789012");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_info() -> Result<()> {
+        let mock_server = start_mock_server().await;
+        let interface_path = format!("{INTERFACES_PATH}{}", fixtures::aws::MAC);
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .mount(&mock_server)
+            .await;
+
+        for (metadata_path, body) in [
+            (MAC_PATH.to_string(), fixtures::aws::MAC),
+            (LOCAL_IPV4_PATH.to_string(), "10.0.0.10"),
+            (format!("{interface_path}/vpc-id"), "vpc-123abc"),
+            (format!("{interface_path}/subnet-id"), "subnet-123abc"),
+        ] {
+            Mock::given(path(metadata_path))
+                .and(header("X-aws-ec2-metadata-token", fixtures::aws::TOKEN))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let network = network_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(network.provider, IDENTIFIER);
+        assert_eq!(network.local_ipv4, Some("10.0.0.10".parse()?));
+        assert_eq!(network.local_ipv6, None);
+        assert_eq!(network.public_ipv4, None);
+        assert_eq!(network.mac.as_deref(), Some(fixtures::aws::MAC));
+        assert_eq!(network.network_id.as_deref(), Some("vpc-123abc"));
+        assert_eq!(network.subnet_id.as_deref(), Some("subnet-123abc"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_info() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(SECURITY_CREDENTIALS_PATH))
+            .and(header("X-aws-ec2-metadata-token", fixtures::aws::TOKEN))
+            .respond_with(ResponseTemplate::new(200).set_body_string("my-role"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials = credentials_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(credentials.provider, IDENTIFIER);
+        assert!(credentials.exposed);
+        assert_eq!(credentials.identities, ["my-role"]);
+
+        // Only the listing was requested, never the credentials of the role
+        let requests = mock_server.received_requests().await.unwrap_or_default();
+        assert!(requests
+            .iter()
+            .all(|req| !req.url.path().ends_with("my-role")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_info_no_role() -> Result<()> {
+        let mock_server = start_mock_server().await;
+
+        Mock::given(path(METADATA_TOKEN_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::aws::TOKEN))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        Mock::given(path(SECURITY_CREDENTIALS_PATH))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials = credentials_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert!(!credentials.exposed);
+        assert!(credentials.identities.is_empty());
+
+        Ok(())
+    }
 }
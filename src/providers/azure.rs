@@ -1,130 +1,420 @@
 //! Microsoft Azure.
 
 use std::path::Path;
-use std::time::Duration;
+use std::sync::RwLock;
 
-use async_trait::async_trait;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use anyhow::{bail, Context};
 use serde::{Deserialize, Serialize};
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
-
-use crate::{Provider, ProviderId};
-
-const METADATA_URI: &str = "http://169.254.169.254";
-const METADATA_PATH: &str = "/metadata/instance?api-version=2017-12-01";
+use serde_json::Value;
+use strum::Display;
+
+use crate::checks::{Check, Fetch, Request};
+use crate::serverless;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{
+    normalize_mac,
+    AccountInfo,
+    Confidence,
+    CredentialsInfo,
+    NetworkInfo,
+    Provider,
+    ProviderId,
+    Region,
+};
+
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
+const METADATA_PATH: &str = "/metadata/instance";
+/// Default version of the instance metadata API, supported by all Azure clouds (including Azure Stack Hub).
+pub const DEFAULT_API_VERSION: &str = "2021-02-01";
+const IDENTITY_INFO_PATH: &str = "/metadata/identity/info?api-version=2018-02-01";
 const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::Azure;
 
-#[derive(Serialize, Deserialize)]
-struct Compute {
-    #[serde(rename = "vmId")]
-    vm_id: String,
+/// Version of the instance metadata API requested, if not the default.
+static API_VERSION: RwLock<Option<String>> = RwLock::new(None);
+
+/// Represents the compute metadata of a virtual machine.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Compute {
+    pub vm_id: String,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    pub vm_size: Option<String>,
+    pub subscription_id: Option<String>,
+    pub resource_group_name: Option<String>,
+    pub zone: Option<String>,
+    pub az_environment: Option<String>,
+    /// Azure Resource Manager ID of the virtual machine (e.g.
+    /// `/subscriptions/.../resourceGroups/.../providers/Microsoft.Compute/virtualMachines/...`).
+    pub resource_id: Option<String>,
+}
+
+/// Represents the Azure cloud environment a virtual machine runs in.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Display)]
+pub enum CloudEnvironment {
+    /// Azure public cloud.
+    #[strum(serialize = "public")]
+    Public,
+    /// Azure China (operated by 21Vianet).
+    #[strum(serialize = "china")]
+    China,
+    /// Azure Government (US).
+    #[strum(serialize = "us_government")]
+    UsGovernment,
+    /// Azure Germany.
+    #[strum(serialize = "germany")]
+    Germany,
+    /// Azure Stack Hub, running on premises.
+    #[strum(serialize = "azure_stack_hub")]
+    AzureStackHub,
+}
+
+impl CloudEnvironment {
+    /// Parses the `azEnvironment` field of the instance metadata (e.g. `AzurePublicCloud`).
+    fn from_az_environment(az_environment: &str) -> Option<Self> {
+        match az_environment.trim().to_lowercase().as_str() {
+            "azurepubliccloud" => Some(Self::Public),
+            "azurechinacloud" => Some(Self::China),
+            "azureusgovernmentcloud" | "azureusgovernment" => Some(Self::UsGovernment),
+            "azuregermancloud" | "azuregermanycloud" => Some(Self::Germany),
+            name if name.contains("stack") => Some(Self::AzureStackHub),
+            _ => None,
+        }
+    }
+
+    /// Returns the Azure Resource Manager endpoint of the environment, for configuring SDKs.
+    ///
+    /// Azure Stack Hub has no well-known endpoint, as it is specific to each deployment.
+    pub fn resource_manager_endpoint(&self) -> Option<&'static str> {
+        match self {
+            Self::Public => Some("https://management.azure.com/"),
+            Self::China => Some("https://management.chinacloudapi.cn/"),
+            Self::UsGovernment => Some("https://management.usgovcloudapi.net/"),
+            Self::Germany => Some("https://management.microsoftazure.de/"),
+            Self::AzureStackHub => None,
+        }
+    }
 }
 
-#[derive(Serialize, Deserialize)]
-struct MetadataResponse {
-    compute: Compute,
+/// Represents the instance metadata of a virtual machine.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct InstanceMetadata {
+    pub compute: Compute,
+    pub network: Option<Network>,
+}
+
+/// Represents the network metadata of a virtual machine.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Network {
+    #[serde(default)]
+    pub interface: Vec<NetworkInterface>,
+}
+
+/// Represents a network interface of a virtual machine.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct NetworkInterface {
+    pub ipv4: Option<IpConfiguration>,
+    pub ipv6: Option<IpConfiguration>,
+    pub mac_address: Option<String>,
+}
+
+/// Represents the IP configuration of a network interface.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpConfiguration {
+    #[serde(default)]
+    pub ip_address: Vec<IpAddress>,
+}
+
+/// Represents an IP address of a network interface.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IpAddress {
+    pub private_ip_address: Option<String>,
+    pub public_ip_address: Option<String>,
 }
 
 pub(crate) struct Azure;
 
-#[async_trait]
 impl Provider for Azure {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify Azure using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking Microsoft Azure");
-        if self.check_vendor_file(VENDOR_FILE).await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified Microsoft Azure");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        let mut checks = vec![self.check_vendor_file(VENDOR_FILE)];
+        // App Service, Container Apps and Azure Functions expose neither DMI attributes nor instance metadata
+        checks.extend(serverless::checks(IDENTIFIER));
+        checks.push(self.check_metadata_server(METADATA_URI));
+        checks
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        Some(network_info(METADATA_URI))
+    }
+
+    fn credentials_info(&self) -> Option<Fetch<CredentialsInfo>> {
+        Some(credentials_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(instance_metadata(METADATA_URI).raw())
     }
 }
 
 impl Azure {
     /// Tries to identify Azure via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-        let req = client.get(url).header("Metadata", "true");
-
-        match req.send().await {
-            Ok(resp) => match resp.json::<MetadataResponse>().await {
-                Ok(resp) => !resp.compute.vm_id.is_empty(),
-                Err(err) => {
-                    error!("Error reading response: {:?}", err);
-                    false
-                }
-            },
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = instance_metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<InstanceMetadata>()
+                .is_some_and(|metadata| !metadata.compute.vm_id.is_empty())
+        })
     }
 
     /// Tries to identify Azure using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Microsoft Corporation"),
+        )
+    }
+}
+
+/// Sets the version of the instance metadata API requested (e.g. `2023-07-01`), for metadata fields only available
+/// in newer versions, or for Azure Stack Hub deployments only supporting older ones. An empty version restores the
+/// default ([DEFAULT_API_VERSION]).
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::providers::azure::set_api_version;
+///
+/// set_api_version("2023-07-01");
+/// ```
+pub fn set_api_version(version: &str) {
+    let version = Some(version.trim().to_string()).filter(|version| !version.is_empty());
+
+    *API_VERSION.write().unwrap_or_else(|err| err.into_inner()) = version;
+}
+
+/// Returns the version of the instance metadata API requested.
+fn api_version() -> String {
+    API_VERSION
+        .read()
+        .unwrap_or_else(|err| err.into_inner())
+        .clone()
+        .unwrap_or_else(|| DEFAULT_API_VERSION.to_string())
+}
 
-        if vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(vendor_file).await {
-                Ok(content) => content.contains("Microsoft Corporation"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
+/// Request for the instance metadata of the virtual machine.
+fn instance_metadata_request(metadata_uri: &str) -> Request {
+    let path = format!("{METADATA_PATH}?api-version={}", api_version());
+
+    Request::get(metadata_uri, &path).header("Metadata", "true")
+}
+
+/// Fetch of the instance metadata of the virtual machine.
+pub(crate) fn instance_metadata(metadata_uri: &str) -> Fetch<InstanceMetadata> {
+    Fetch::json(vec![instance_metadata_request(metadata_uri)])
+}
+
+/// Fetch of the region of the virtual machine, from its instance metadata.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    instance_metadata(metadata_uri).map(|metadata| {
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: metadata.compute.location.context("Region not available")?,
+            zone: metadata.compute.zone.filter(|zone| !zone.is_empty()),
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the size of the virtual machine, from its instance metadata.
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    instance_metadata(metadata_uri).map(|metadata| {
+        metadata
+            .compute
+            .vm_size
+            .context("Instance type not available")
+    })
+}
+
+/// Fetch of the subscription of the virtual machine, from its instance metadata.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    instance_metadata(metadata_uri).map(|metadata| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: metadata
+                .compute
+                .subscription_id
+                .context("Account not available")?,
+        })
+    })
+}
+
+/// Fetch of the cloud environment of the virtual machine, from its instance metadata.
+pub(crate) fn cloud_environment(metadata_uri: &str) -> Fetch<CloudEnvironment> {
+    instance_metadata(metadata_uri).map(|metadata| {
+        let az_environment = metadata
+            .compute
+            .az_environment
+            .context("Cloud environment not available")?;
+
+        CloudEnvironment::from_az_environment(&az_environment)
+            .with_context(|| format!("Unknown cloud environment: {az_environment}"))
+    })
+}
+
+/// Fetch of the network configuration of the primary network interface of the virtual machine, from its instance
+/// metadata.
+///
+/// The metadata server doesn't expose the virtual network or subnet the interface belongs to.
+pub(crate) fn network_info(metadata_uri: &str) -> Fetch<NetworkInfo> {
+    instance_metadata(metadata_uri).map(|metadata| {
+        let interface = metadata
+            .network
+            .and_then(|network| network.interface.into_iter().next())
+            .context("Network interface not available")?;
+        let ipv4 = interface
+            .ipv4
+            .and_then(|ipv4| ipv4.ip_address.into_iter().next());
+        let ipv6 = interface
+            .ipv6
+            .and_then(|ipv6| ipv6.ip_address.into_iter().next());
+
+        Ok(NetworkInfo {
+            provider: IDENTIFIER,
+            local_ipv4: ipv4
+                .as_ref()
+                .and_then(|ip| ip.private_ip_address.as_ref()?.parse().ok()),
+            local_ipv6: ipv6.and_then(|ip| ip.private_ip_address?.parse().ok()),
+            public_ipv4: ipv4.and_then(|ip| ip.public_ip_address?.parse().ok()),
+            mac: interface.mac_address.as_deref().and_then(normalize_mac),
+            ..Default::default()
+        })
+    })
+}
+
+/// Fetch of whether a managed identity is assigned to the virtual machine.
+///
+/// Only the identity information (the tenant of the identity) is requested, never a token. The metadata server doesn't
+/// list the identities themselves, and answers with `400 Bad Request` when none is assigned.
+pub(crate) fn credentials_info(metadata_uri: &str) -> Fetch<CredentialsInfo> {
+    Fetch::new(
+        vec![Request::get(metadata_uri, IDENTITY_INFO_PATH).header("Metadata", "true")],
+        |resp| {
+            let exposed = match resp.status {
+                400 | 404 => false,
+                _ if resp.is_success() => true,
+                status => bail!("Unexpected status code: {}", status),
             };
-        }
 
-        false
-    }
+            Ok(CredentialsInfo {
+                provider: IDENTIFIER,
+                exposed,
+                identities: Vec::new(),
+            })
+        },
+    )
+}
+
+/// Fetches the instance metadata of the virtual machine from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::azure::fetch_instance_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_instance_metadata(None).await.unwrap();
+///     println!("VM ID: {}", metadata.compute.vm_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_instance_metadata(timeout: Option<u64>) -> Result<InstanceMetadata> {
+    instance_metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+/// Fetches the Azure cloud environment (e.g. public, China or Government) the virtual machine runs in from the metadata
+/// server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::azure::fetch_cloud_environment;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let environment = fetch_cloud_environment(None).await.unwrap();
+///     println!("Environment: {}", environment);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_cloud_environment(timeout: Option<u64>) -> Result<CloudEnvironment> {
+    cloud_environment(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
-    use wiremock::matchers::query_param;
+    use wiremock::matchers::{header, path, query_param};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
 
     #[tokio::test]
     async fn test_check_metadata_server_success() {
         let mock_server = MockServer::start().await;
-        Mock::given(query_param("api-version", "2017-12-01"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                compute: Compute {
-                    vm_id: "vm-123abc".to_string(),
-                },
-            }))
+        Mock::given(query_param("api-version", DEFAULT_API_VERSION))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -132,7 +422,8 @@ mod tests {
         let provider = Azure;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -141,12 +432,10 @@ mod tests {
     #[tokio::test]
     async fn test_check_metadata_server_failure() {
         let mock_server = MockServer::start().await;
-        Mock::given(query_param("api-version", "2017-12-01"))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                compute: Compute {
-                    vm_id: "".to_string(),
-                },
-            }))
+        Mock::given(query_param("api-version", DEFAULT_API_VERSION))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA_MISMATCH),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -154,7 +443,8 @@ mod tests {
         let provider = Azure;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -163,10 +453,13 @@ mod tests {
     #[tokio::test]
     async fn test_check_vendor_file_success() -> Result<()> {
         let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"Microsoft Corporation")?;
+        vendor_file.write_all(fixtures::azure::VENDOR.as_bytes())?;
 
         let provider = Azure;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(result);
 
@@ -178,10 +471,205 @@ mod tests {
         let vendor_file = NamedTempFile::new()?;
 
         let provider = Azure;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_instance_metadata() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/instance"))
+            .and(header("Metadata", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = instance_metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.compute.vm_id, "vm-1234");
+        assert_eq!(metadata.compute.location.as_deref(), Some("westeurope"));
+        assert_eq!(metadata.compute.vm_size.as_deref(), Some("Standard_D4s_v5"));
+        assert_eq!(
+            metadata.compute.resource_id.as_deref(),
+            Some(
+                "/subscriptions/00000000-0000-0000-0000-000000000000/resourceGroups/rg/providers/\
+                 Microsoft.Compute/virtualMachines/vm"
+            )
+        );
+        assert_eq!(
+            metadata.compute.az_environment.as_deref(),
+            Some("AzurePublicCloud")
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cloud_environment() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/instance"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let environment = cloud_environment(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(environment, CloudEnvironment::Public);
+        assert_eq!(
+            environment.resource_manager_endpoint(),
+            Some("https://management.azure.com/")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cloud_environment_from_az_environment() {
+        assert_eq!(
+            CloudEnvironment::from_az_environment("AzureChinaCloud"),
+            Some(CloudEnvironment::China)
+        );
+        assert_eq!(
+            CloudEnvironment::from_az_environment("AzureUSGovernmentCloud"),
+            Some(CloudEnvironment::UsGovernment)
+        );
+        assert_eq!(
+            CloudEnvironment::from_az_environment("AzureStack"),
+            Some(CloudEnvironment::AzureStackHub)
+        );
+        assert_eq!(CloudEnvironment::from_az_environment("abc"), None);
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/instance"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "westeurope");
+        assert_eq!(region.zone.as_deref(), Some("1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/instance"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "Standard_D4s_v5");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/instance"))
+            .and(header("Metadata", "true"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "00000000-0000-0000-0000-000000000000");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/instance"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::azure::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let network = network_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(network.provider, IDENTIFIER);
+        assert_eq!(network.local_ipv4, Some("10.0.0.4".parse()?));
+        assert_eq!(network.local_ipv6, None);
+        assert_eq!(network.public_ipv4, Some("20.0.0.4".parse()?));
+        assert_eq!(network.mac.as_deref(), Some("00:0d:3a:f8:06:ec"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/identity/info"))
+            .and(header("Metadata", "true"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(r#"{"tenantId":"00000000-0000-0000-0000-000000000000"}"#),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials = credentials_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(credentials.provider, IDENTIFIER);
+        assert!(credentials.exposed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_info_no_identity() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/metadata/identity/info"))
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials = credentials_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert!(!credentials.exposed);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,81 @@
+//! Civo.
+//!
+//! Civo has no metadata server to confirm a match with, and its DMI vendor is the only marker of its instances. As any
+//! hypervisor can be configured to report it, it's a weak signal: it doesn't end detection early, so the checks of other
+//! providers still run and are resolved against it.
+
+use std::path::Path;
+
+use crate::checks::Check;
+use crate::{Confidence, Provider, ProviderId};
+
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Civo;
+
+pub(crate) struct Civo;
+
+impl Provider for Civo {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify Civo using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![self.check_vendor_file(VENDOR_FILE)]
+    }
+}
+
+impl Civo {
+    /// Tries to identify Civo using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Civo"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        vendor_file.write_all(fixtures::civo::VENDOR.as_bytes())?;
+
+        let provider = Civo;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let vendor_file = NamedTempFile::new()?;
+
+        let provider = Civo;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+}
@@ -1,106 +1,261 @@
 //! DigitalOcean.
 
 use std::path::Path;
-use std::time::Duration;
 
-use async_trait::async_trait;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
+use serde_json::Value;
 
-use crate::{Provider, ProviderId};
+use crate::checks::{Check, Fetch, Request};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{normalize_mac, Confidence, NetworkInfo, Provider, ProviderId, Region};
 
-const METADATA_URI: &str = "http://169.254.169.254";
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
 const METADATA_PATH: &str = "/metadata/v1.json";
+const ID_PATH: &str = "/metadata/v1/id";
+const HOSTNAME_PATH: &str = "/metadata/v1/hostname";
+const REGION_PATH: &str = "/metadata/v1/region";
+const DHCP_ENABLED_PATH: &str = "/metadata/v1/features/dhcp_enabled";
 const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::DigitalOcean;
 
-pub(crate) struct DigitalOcean;
+/// Represents the metadata of a Droplet.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub droplet_id: u64,
+    pub hostname: Option<String>,
+    pub region: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub interfaces: Interfaces,
+    #[serde(default)]
+    pub features: Features,
+}
+
+/// Represents the features enabled on a Droplet.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Features {
+    pub dhcp_enabled: Option<bool>,
+}
+
+/// Represents the network interfaces of a Droplet.
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Interfaces {
+    #[serde(default)]
+    pub public: Vec<Interface>,
+    #[serde(default)]
+    pub private: Vec<Interface>,
+}
+
+/// Represents a network interface of a Droplet.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Interface {
+    pub ipv4: Option<Address>,
+    pub ipv6: Option<Address>,
+    pub mac: Option<String>,
+}
 
-#[derive(Serialize, Deserialize)]
-struct MetadataResponse {
-    droplet_id: usize,
+/// Represents an IP address of a network interface.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Address {
+    pub ip_address: String,
 }
 
-#[async_trait]
+pub(crate) struct DigitalOcean;
+
 impl Provider for DigitalOcean {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify DigitalOcean using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking DigitalOcean");
-        if self.check_vendor_file(VENDOR_FILE).await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified DigitalOcean");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_metadata_server(METADATA_URI),
+            self.check_metadata_server_plaintext(METADATA_URI),
+        ]
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        Some(network_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(metadata(METADATA_URI).raw())
     }
 }
 
 impl DigitalOcean {
     /// Tries to identify DigitalOcean via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-
-        match client.get(url).send().await {
-            Ok(resp) => match resp.json::<MetadataResponse>().await {
-                Ok(resp) => resp.droplet_id > 0,
-                Err(err) => {
-                    error!("Error reading response: {:?}", err);
-                    false
-                }
-            },
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<Metadata>()
+                .is_some_and(|metadata| metadata.droplet_id > 0)
+        })
+    }
+
+    /// Tries to identify DigitalOcean via the plaintext paths of the metadata server, still served on Droplets blocking
+    /// the JSON document.
+    pub(crate) fn check_metadata_server_plaintext(&self, metadata_uri: &str) -> Check {
+        let request = Request::get(metadata_uri, ID_PATH);
+
+        Check::http(
+            "metadata_server_plaintext",
+            Confidence::Verified,
+            request,
+            |resp| resp.is_success() && parse_droplet_id(&resp.body).is_ok(),
+        )
     }
 
     /// Tries to identify DigitalOcean using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
-
-        if vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(vendor_file).await {
-                Ok(content) => content.contains("DigitalOcean"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("DigitalOcean"),
+        )
+        .strong()
+    }
+}
+
+/// Request for the metadata of the Droplet.
+fn metadata_request(metadata_uri: &str) -> Request {
+    Request::get(metadata_uri, METADATA_PATH)
+}
+
+/// Fetch of the metadata of the Droplet, falling back to its plaintext paths when the JSON document is blocked.
+pub(crate) fn metadata(metadata_uri: &str) -> Fetch<Metadata> {
+    Fetch::json(vec![metadata_request(metadata_uri)]).or_else(plaintext_metadata(metadata_uri))
+}
+
+/// Fetch of the metadata of the Droplet from its plaintext paths.
+///
+/// The tags and network interfaces of the Droplet aren't fetched.
+fn plaintext_metadata(metadata_uri: &str) -> Fetch<Metadata> {
+    meta_data(metadata_uri, ID_PATH)
+        .map(|id| parse_droplet_id(&id))
+        .zip(meta_data(metadata_uri, HOSTNAME_PATH).optional())
+        .zip(meta_data(metadata_uri, REGION_PATH).optional())
+        .zip(meta_data(metadata_uri, DHCP_ENABLED_PATH).optional())
+        .map(|(((droplet_id, hostname), region), dhcp_enabled)| {
+            Ok(Metadata {
+                droplet_id,
+                hostname,
+                region,
+                tags: Vec::new(),
+                interfaces: Interfaces::default(),
+                features: Features {
+                    dhcp_enabled: dhcp_enabled.and_then(|value| value.parse().ok()),
+                },
+            })
+        })
+}
+
+/// Fetch of a plaintext metadata value.
+fn meta_data(metadata_uri: &str, path: &str) -> Fetch<String> {
+    Fetch::new(vec![Request::get(metadata_uri, path)], |resp| {
+        if !resp.is_success() {
+            bail!("Unexpected status code: {}", resp.status);
         }
 
-        false
+        match resp.body.trim() {
+            "" => bail!("Empty value"),
+            value => Ok(value.to_string()),
+        }
+    })
+}
+
+/// Parses the ID of a Droplet, which is always positive.
+fn parse_droplet_id(id: &str) -> Result<u64> {
+    match id.trim().parse().context("Invalid Droplet ID")? {
+        0 => bail!("Invalid Droplet ID: 0"),
+        id => Ok(id),
     }
 }
 
+/// Fetch of the region of the Droplet, from its metadata.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    metadata(metadata_uri).map(|metadata| {
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: metadata.region.context("Region not available")?,
+            zone: None,
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the network configuration of the Droplet, from its metadata.
+///
+/// The public interface is the primary one, with the private (VPC) interface providing the local IPv4 address. The
+/// metadata doesn't expose the VPC the Droplet belongs to.
+pub(crate) fn network_info(metadata_uri: &str) -> Fetch<NetworkInfo> {
+    metadata(metadata_uri).map(|metadata| {
+        let public = metadata.interfaces.public.into_iter().next();
+        let private = metadata.interfaces.private.into_iter().next();
+
+        Ok(NetworkInfo {
+            provider: IDENTIFIER,
+            local_ipv4: private
+                .as_ref()
+                .and_then(|interface| interface.ipv4.as_ref()?.ip_address.parse().ok()),
+            local_ipv6: public
+                .as_ref()
+                .and_then(|interface| interface.ipv6.as_ref()?.ip_address.parse().ok()),
+            public_ipv4: public
+                .as_ref()
+                .and_then(|interface| interface.ipv4.as_ref()?.ip_address.parse().ok()),
+            mac: public
+                .or(private)
+                .and_then(|interface| normalize_mac(interface.mac.as_deref()?)),
+            ..Default::default()
+        })
+    })
+}
+
+/// Fetches the metadata of the Droplet from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::digitalocean::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!("Droplet ID: {}", metadata.droplet_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
@@ -108,13 +263,14 @@ mod tests {
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
 
     #[tokio::test]
     async fn test_check_metadata_server_success() {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
             .respond_with(
-                ResponseTemplate::new(200).set_body_json(MetadataResponse { droplet_id: 123 }),
+                ResponseTemplate::new(200).set_body_string(fixtures::digitalocean::METADATA),
             )
             .expect(1)
             .mount(&mock_server)
@@ -123,7 +279,8 @@ mod tests {
         let provider = DigitalOcean;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -134,7 +291,8 @@ mod tests {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
             .respond_with(
-                ResponseTemplate::new(200).set_body_json(MetadataResponse { droplet_id: 0 }),
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::digitalocean::METADATA_MISMATCH),
             )
             .expect(1)
             .mount(&mock_server)
@@ -143,7 +301,46 @@ mod tests {
         let provider = DigitalOcean;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_plaintext_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::digitalocean::ID))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = DigitalOcean;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server_plaintext(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_plaintext_failure() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::MALFORMED))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = DigitalOcean;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server_plaintext(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -152,10 +349,13 @@ mod tests {
     #[tokio::test]
     async fn test_check_vendor_file_success() -> Result<()> {
         let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"DigitalOcean")?;
+        vendor_file.write_all(fixtures::digitalocean::VENDOR.as_bytes())?;
 
         let provider = DigitalOcean;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(result);
 
@@ -167,10 +367,115 @@ mod tests {
         let vendor_file = NamedTempFile::new()?;
 
         let provider = DigitalOcean;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_metadata() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::digitalocean::METADATA),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.droplet_id, 123);
+        assert_eq!(metadata.region.as_deref(), Some("nyc3"));
+        assert_eq!(metadata.tags, ["web"]);
+        assert_eq!(metadata.features.dhcp_enabled, Some(false));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_plaintext_fallback() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(403))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        for (metadata_path, body) in [
+            (ID_PATH, fixtures::digitalocean::ID),
+            (REGION_PATH, fixtures::digitalocean::REGION),
+            (DHCP_ENABLED_PATH, "true"),
+        ] {
+            Mock::given(path(metadata_path))
+                .respond_with(ResponseTemplate::new(200).set_body_string(body))
+                .expect(1)
+                .mount(&mock_server)
+                .await;
+        }
+
+        let metadata = metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.droplet_id, 123);
+        assert_eq!(metadata.hostname, None);
+        assert_eq!(metadata.region.as_deref(), Some("nyc3"));
+        assert!(metadata.tags.is_empty());
+        assert_eq!(metadata.features.dhcp_enabled, Some(true));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::digitalocean::METADATA),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "nyc3");
+        assert_eq!(region.zone, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::digitalocean::METADATA),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let network = network_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(network.provider, IDENTIFIER);
+        assert_eq!(network.local_ipv4, Some("10.116.0.2".parse()?));
+        assert_eq!(network.local_ipv6, None);
+        assert_eq!(network.public_ipv4, Some("203.0.113.10".parse()?));
+        assert_eq!(network.mac.as_deref(), Some("d6:4c:8e:00:00:01"));
+
+        Ok(())
+    }
 }
@@ -1,92 +1,493 @@
 //! Google Cloud Platform (GCP).
 
+use std::collections::{BTreeMap, HashMap};
 use std::path::Path;
+use std::sync::Mutex;
+#[cfg(feature = "async")]
 use std::time::Duration;
 
-use async_trait::async_trait;
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
-
-use crate::{Provider, ProviderId};
-
-const METADATA_URI: &str = "http://metadata.google.internal";
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use serde_json::Value;
+use strum::Display;
+
+use crate::checks::{Check, Fetch, Request, Response};
+use crate::serverless;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{
+    normalize_mac,
+    AccountInfo,
+    Confidence,
+    CredentialsInfo,
+    NetworkInfo,
+    Provider,
+    ProviderId,
+    Region,
+};
+
+pub(crate) const METADATA_URI: &str = "http://metadata.google.internal";
 const METADATA_PATH: &str = "/computeMetadata/v1/instance/tags";
+const INSTANCE_ID_PATH: &str = "/computeMetadata/v1/instance/id";
+const RAW_METADATA_PATH: &str = "/computeMetadata/v1/?recursive=true";
+const INSTANCE_ATTRIBUTES_PATH: &str = "/computeMetadata/v1/instance/attributes/?recursive=true";
+const ZONE_PATH: &str = "/computeMetadata/v1/instance/zone";
+const MACHINE_TYPE_PATH: &str = "/computeMetadata/v1/instance/machine-type";
+const PROJECT_ID_PATH: &str = "/computeMetadata/v1/project/project-id";
+const SERVICE_ACCOUNTS_PATH: &str = "/computeMetadata/v1/instance/service-accounts/";
+const NETWORK_INTERFACES_PATH: &str =
+    "/computeMetadata/v1/instance/network-interfaces/?recursive=true";
 const VENDOR_FILE: &str = "/sys/class/dmi/id/product_name";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::GCP;
 
+/// Metadata trees fetched so far, by metadata server (see [fetch_metadata]).
+static METADATA: Mutex<BTreeMap<String, Metadata>> = Mutex::new(BTreeMap::new());
+
+/// Represents the metadata tree of an instance and its project, as served recursively by the metadata server.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub instance: Instance,
+    pub project: Project,
+}
+
+/// Represents the metadata of an instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instance {
+    pub id: u64,
+    pub name: Option<String>,
+    pub hostname: Option<String>,
+    /// Zone of the instance (e.g. `us-central1-a`), without the path of its project.
+    #[serde(deserialize_with = "resource")]
+    pub zone: String,
+    /// Machine type of the instance (e.g. `e2-standard-4`), without the path of its project.
+    #[serde(deserialize_with = "resource")]
+    pub machine_type: String,
+    /// Service accounts the instance has credentials for, by email, and the default one again under the `default`
+    /// alias.
+    #[serde(default)]
+    pub service_accounts: BTreeMap<String, ServiceAccount>,
+}
+
+/// Represents a service account an instance has credentials for.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct ServiceAccount {
+    pub email: String,
+    #[serde(default)]
+    pub aliases: Vec<String>,
+    /// OAuth scopes of the credentials of the instance.
+    #[serde(default)]
+    pub scopes: Vec<String>,
+}
+
+/// Represents the metadata of the project of an instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Project {
+    pub project_id: String,
+    pub numeric_project_id: u64,
+}
+
+/// Represents the GCP environment a host runs in.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Display)]
+pub enum Environment {
+    /// Compute Engine (including GKE nodes).
+    #[strum(serialize = "compute_engine")]
+    ComputeEngine,
+    /// Cloud Run service.
+    #[strum(serialize = "cloud_run")]
+    CloudRun,
+    /// Cloud Run job.
+    #[strum(serialize = "cloud_run_job")]
+    CloudRunJob,
+    /// Cloud Functions.
+    #[strum(serialize = "cloud_functions")]
+    CloudFunctions,
+    /// App Engine standard environment.
+    #[strum(serialize = "app_engine_standard")]
+    AppEngineStandard,
+    /// App Engine flexible environment.
+    #[strum(serialize = "app_engine_flexible")]
+    AppEngineFlexible,
+}
+
+/// Represents a network interface of an instance.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct NetworkInterface {
+    ip: Option<String>,
+    mac: Option<String>,
+    network: Option<String>,
+    #[serde(default)]
+    ipv6s: Vec<String>,
+    #[serde(default)]
+    access_configs: Vec<AccessConfig>,
+}
+
+/// Represents an external access configuration of a network interface.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AccessConfig {
+    external_ip: Option<String>,
+}
+
 pub(crate) struct Gcp;
 
-#[async_trait]
 impl Provider for Gcp {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify GCP using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking Google Cloud Platform");
-        if self.check_vendor_file(VENDOR_FILE).await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified Google Cloud Platform");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        let mut checks = vec![self.check_vendor_file(VENDOR_FILE)];
+        // The serverless runtimes expose neither DMI attributes nor instance metadata
+        checks.extend(serverless::checks(IDENTIFIER));
+        checks.push(self.check_metadata_server(METADATA_URI));
+        checks.push(self.check_instance_id(METADATA_URI));
+        checks.push(self.check_project_metadata(METADATA_URI));
+        checks
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        Some(network_info(METADATA_URI))
+    }
+
+    fn credentials_info(&self) -> Option<Fetch<CredentialsInfo>> {
+        Some(credentials_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(raw_metadata(METADATA_URI))
     }
 }
 
 impl Gcp {
     /// Tries to identify GCP via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri, METADATA_PATH);
 
-        let req = client.get(url).header("Metadata-Flavor", "Google");
-        let resp = req.send().await;
+        Check::http("metadata_server", Confidence::Unverified, request, |resp| {
+            resp.is_success() && is_metadata_server(resp)
+        })
+    }
 
-        match resp {
-            Ok(resp) => resp.status().is_success(),
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
+    /// Tries to identify GCP via the ID of the instance, which every instance has (unlike tags in some serverless
+    /// contexts).
+    pub(crate) fn check_instance_id(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri, INSTANCE_ID_PATH);
+
+        Check::http("instance_id", Confidence::Unverified, request, |resp| {
+            resp.is_success() && is_metadata_server(resp) && resp.body.trim().parse::<u64>().is_ok()
+        })
+    }
+
+    /// Tries to identify GCP via the project metadata, which the serverless runtimes serve even though the instance
+    /// metadata (e.g. tags) is not available.
+    pub(crate) fn check_project_metadata(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri, PROJECT_ID_PATH);
+
+        Check::http(
+            "project_metadata",
+            Confidence::Unverified,
+            request,
+            |resp| resp.is_success() && is_metadata_server(resp) && !resp.body.trim().is_empty(),
+        )
     }
 
     /// Tries to identify GCP using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Google"),
+        )
+        .strong()
+    }
+}
 
-        if vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(vendor_file).await {
-                Ok(content) => content.contains("Google"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
-        }
+/// Classifies the GCP environment the host runs in, assuming it runs on GCP.
+///
+/// The serverless runtimes are told apart by the environment variables they set, and anything else is assumed to be
+/// Compute Engine.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::providers::gcp::environment;
+///
+/// println!("Environment: {}", environment());
+/// ```
+pub fn environment() -> Environment {
+    environment_from(|name| std::env::var(name).ok().filter(|value| !value.is_empty()))
+}
+
+pub(crate) fn environment_from<F: Fn(&str) -> Option<String>>(var: F) -> Environment {
+    if var("FUNCTION_TARGET").is_some() || var("FUNCTION_NAME").is_some() {
+        Environment::CloudFunctions
+    } else if var("GAE_ENV").as_deref() == Some("standard") {
+        Environment::AppEngineStandard
+    } else if var("GAE_INSTANCE").is_some() {
+        Environment::AppEngineFlexible
+    } else if var("CLOUD_RUN_JOB").is_some() {
+        Environment::CloudRunJob
+    } else if var("K_SERVICE").is_some() {
+        Environment::CloudRun
+    } else {
+        Environment::ComputeEngine
+    }
+}
+
+/// Request for a metadata path, with the header required by the metadata server.
+fn metadata_request(metadata_uri: &str, path: &str) -> Request {
+    Request::get(metadata_uri, path).header("Metadata-Flavor", "Google")
+}
+
+/// Whether a response was served by the metadata server, which echoes the `Metadata-Flavor` header of the request and
+/// identifies itself in the `Server` header (e.g. `Metadata Server for VM`, or `Metadata Server for Serverless` in the
+/// serverless runtimes).
+fn is_metadata_server(resp: &Response) -> bool {
+    resp.header("Metadata-Flavor") == Some("Google")
+        && resp
+            .header("Server")
+            .is_some_and(|server| server.starts_with("Metadata Server"))
+}
+
+/// Deserializes the name of a resource from its full path (e.g. `projects/<project-number>/zones/<zone>`), as with
+/// [resource_name].
+fn resource<'de, D: Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    let path = String::deserialize(deserializer)?;
+
+    match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() => Ok(name.to_string()),
+        _ => Err(serde::de::Error::custom(format!(
+            "Unexpected resource path: {path}"
+        ))),
+    }
+}
+
+/// Fetch of the whole metadata tree of the instance and its project.
+pub(crate) fn metadata(metadata_uri: &str) -> Fetch<Metadata> {
+    Fetch::json(vec![metadata_request(metadata_uri, RAW_METADATA_PATH)])
+}
+
+/// Fetch of the whole metadata tree of the instance and its project, without deserializing it.
+pub(crate) fn raw_metadata(metadata_uri: &str) -> Fetch<Value> {
+    metadata(metadata_uri).raw()
+}
+
+/// Returns the metadata tree fetched from a metadata server, if it has been already.
+pub(crate) fn cached_metadata(metadata_uri: &str) -> Option<Metadata> {
+    let cache = METADATA.lock().unwrap_or_else(|err| err.into_inner());
+    cache.get(metadata_uri).cloned()
+}
+
+/// Caches the metadata tree fetched from a metadata server, for the lifetime of the process.
+pub(crate) fn cache_metadata(metadata_uri: &str, metadata: &Metadata) {
+    let mut cache = METADATA.lock().unwrap_or_else(|err| err.into_inner());
+    cache.insert(metadata_uri.to_string(), metadata.clone());
+}
 
-        false
+/// Fetches the metadata tree from a metadata server, unless it has been already.
+#[cfg(feature = "async")]
+pub(crate) async fn fetch_metadata_from(metadata_uri: &str, timeout: Duration) -> Result<Metadata> {
+    if let Some(metadata) = cached_metadata(metadata_uri) {
+        return Ok(metadata);
     }
+
+    let metadata = metadata(metadata_uri).run(timeout).await?;
+    cache_metadata(metadata_uri, &metadata);
+
+    Ok(metadata)
+}
+
+/// Fetch of the custom metadata attributes of the instance.
+pub(crate) fn instance_attributes(metadata_uri: &str) -> Fetch<HashMap<String, String>> {
+    Fetch::json(vec![metadata_request(
+        metadata_uri,
+        INSTANCE_ATTRIBUTES_PATH,
+    )])
+}
+
+/// Fetch of a plaintext metadata value.
+fn value(metadata_uri: &str, path: &str) -> Fetch<String> {
+    Fetch::new(vec![metadata_request(metadata_uri, path)], |resp| {
+        if !resp.is_success() {
+            bail!("Unexpected status code: {}", resp.status);
+        }
+
+        match resp.body.trim() {
+            "" => bail!("Empty value"),
+            value => Ok(value.to_string()),
+        }
+    })
+}
+
+/// Fetch of the name of a resource the instance belongs to, such as its zone or machine type.
+///
+/// The metadata server returns these as full resource paths (e.g. `projects/<project-number>/zones/<zone>`), of
+/// which only the last segment is kept.
+fn resource_name(metadata_uri: &str, path: &str) -> Fetch<String> {
+    value(metadata_uri, path).map(|path| match path.rsplit('/').next() {
+        Some(name) if !name.is_empty() => Ok(name.to_string()),
+        _ => bail!("Unexpected resource path: {}", path),
+    })
+}
+
+/// Fetch of the region of the instance, derived from its zone.
+///
+/// Zones are named after their region (e.g. `us-central1-a` in `us-central1`).
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    resource_name(metadata_uri, ZONE_PATH).map(|zone| {
+        let (region, _) = zone.rsplit_once('-').context("Unexpected zone format")?;
+
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: region.to_string(),
+            zone: Some(zone),
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the machine type of the instance (e.g. `e2-standard-4`).
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    resource_name(metadata_uri, MACHINE_TYPE_PATH)
+}
+
+/// Fetch of the project of the instance.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    value(metadata_uri, PROJECT_ID_PATH).map(|project_id| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: project_id,
+        })
+    })
+}
+
+/// Fetch of the network configuration of the primary network interface of the instance.
+///
+/// The metadata server doesn't expose the subnetwork the interface belongs to.
+pub(crate) fn network_info(metadata_uri: &str) -> Fetch<NetworkInfo> {
+    Fetch::<Vec<NetworkInterface>>::json(vec![metadata_request(
+        metadata_uri,
+        NETWORK_INTERFACES_PATH,
+    )])
+    .map(|interfaces| {
+        let interface = interfaces
+            .into_iter()
+            .next()
+            .context("Network interface not available")?;
+
+        Ok(NetworkInfo {
+            provider: IDENTIFIER,
+            local_ipv4: interface.ip.and_then(|ip| ip.parse().ok()),
+            local_ipv6: interface.ipv6s.first().and_then(|ip| ip.parse().ok()),
+            public_ipv4: interface
+                .access_configs
+                .into_iter()
+                .find_map(|config| config.external_ip?.parse().ok()),
+            mac: interface.mac.as_deref().and_then(normalize_mac),
+            network_id: interface
+                .network
+                .and_then(|network| network.rsplit('/').next().map(str::to_string)),
+            ..Default::default()
+        })
+    })
+}
+
+/// Fetch of the service accounts the instance has credentials for.
+///
+/// Only the listing of service accounts is requested, never their tokens. The listing includes each service account
+/// by email, and the default one again under the `default` alias, which is left out.
+pub(crate) fn credentials_info(metadata_uri: &str) -> Fetch<CredentialsInfo> {
+    Fetch::new(
+        vec![metadata_request(metadata_uri, SERVICE_ACCOUNTS_PATH)],
+        |resp| {
+            let service_accounts: Vec<String> = resp
+                .listing()?
+                .into_iter()
+                .filter(|service_account| service_account != "default")
+                .collect();
+
+            Ok(CredentialsInfo {
+                provider: IDENTIFIER,
+                exposed: !service_accounts.is_empty(),
+                identities: service_accounts,
+            })
+        },
+    )
+}
+
+/// Fetches the custom metadata attributes of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::gcp::fetch_instance_attributes;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let attributes = fetch_instance_attributes(None).await.unwrap();
+///     println!("Attributes: {:?}", attributes);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_instance_attributes(timeout: Option<u64>) -> Result<HashMap<String, String>> {
+    instance_attributes(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+/// Fetches the metadata tree of the instance and its project (e.g. the ID, zone, machine type and service accounts of
+/// the instance) from the metadata server, in a single request.
+///
+/// The tree is cached once fetched, so later calls (including from the blocking API) don't query the metadata server
+/// again. Custom metadata attributes aren't kept (see [fetch_instance_attributes]).
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::gcp::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!(
+///         "Instance {} in {}",
+///         metadata.instance.id, metadata.instance.zone
+///     );
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    fetch_metadata_from(METADATA_URI, timeout_or_default(timeout)).await
 }
 
 #[cfg(test)]
@@ -95,16 +496,27 @@ mod tests {
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
-    use wiremock::matchers::path;
+    use wiremock::matchers::{header, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    /// Response of the metadata server, with the headers it sets.
+    fn metadata_response(body: &str) -> ResponseTemplate {
+        fixtures::gcp::HEADERS
+            .iter()
+            .fold(ResponseTemplate::new(200), |template, &(name, value)| {
+                template.insert_header(name, value)
+            })
+            .set_body_string(body)
+    }
 
     #[tokio::test]
     async fn test_check_metadata_server_success() {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200))
+            .respond_with(metadata_response("[]"))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -112,7 +524,8 @@ mod tests {
         let provider = Gcp;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -123,6 +536,25 @@ mod tests {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
             .respond_with(ResponseTemplate::new(500))
+            .expect(u64::from(crate::retry::MAX_ATTEMPTS))
+            .mount(&mock_server)
+            .await;
+
+        let provider = Gcp;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_missing_headers() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string("[]"))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -130,19 +562,96 @@ mod tests {
         let provider = Gcp;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
     }
 
+    #[tokio::test]
+    async fn test_check_instance_id() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(INSTANCE_ID_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(metadata_response(fixtures::gcp::INSTANCE_ID))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Gcp;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_instance_id(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_project_metadata() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(PROJECT_ID_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(metadata_response(fixtures::gcp::PROJECT_ID))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Gcp;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_project_metadata(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[test]
+    fn test_environment_from() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        assert_eq!(environment_from(env(&[])), Environment::ComputeEngine);
+        assert_eq!(
+            environment_from(env(&[("K_SERVICE", "hello")])),
+            Environment::CloudRun
+        );
+        assert_eq!(
+            environment_from(env(&[("CLOUD_RUN_JOB", "hello")])),
+            Environment::CloudRunJob
+        );
+        assert_eq!(
+            environment_from(env(&[("K_SERVICE", "hello"), ("FUNCTION_TARGET", "main")])),
+            Environment::CloudFunctions
+        );
+        assert_eq!(
+            environment_from(env(&[("GAE_ENV", "standard"), ("GAE_INSTANCE", "abc")])),
+            Environment::AppEngineStandard
+        );
+        assert_eq!(
+            environment_from(env(&[("GAE_INSTANCE", "abc")])),
+            Environment::AppEngineFlexible
+        );
+    }
+
     #[tokio::test]
     async fn test_check_vendor_file_success() -> Result<()> {
         let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"Google")?;
+        vendor_file.write_all(fixtures::gcp::VENDOR.as_bytes())?;
 
         let provider = Gcp;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(result);
 
@@ -154,10 +663,216 @@ mod tests {
         let vendor_file = NamedTempFile::new()?;
 
         let provider = Gcp;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_instance_attributes() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/computeMetadata/v1/instance/attributes/"))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::gcp::INSTANCE_ATTRIBUTES),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let attributes = instance_attributes(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(
+            attributes.get("enable-oslogin").map(String::as_str),
+            Some("TRUE")
+        );
+
+        Ok(())
+    }
+
+    /// Starts a mock metadata server, dropping any metadata tree cached for a previous server with the same address.
+    async fn start_mock_server() -> MockServer {
+        let mock_server = MockServer::start().await;
+        METADATA
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .remove(&mock_server.uri());
+        mock_server
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata() -> Result<()> {
+        let mock_server = start_mock_server().await;
+        Mock::given(path("/computeMetadata/v1/"))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(metadata_response(fixtures::gcp::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = fetch_metadata_from(&mock_server.uri(), Duration::from_secs(1)).await?;
+
+        assert_eq!(metadata.instance.id, 4520031799277581759);
+        assert_eq!(metadata.instance.zone, "us-central1-a");
+        assert_eq!(metadata.instance.machine_type, "e2-standard-4");
+        assert_eq!(
+            metadata.instance.service_accounts["default"].email,
+            "123456789012-compute@developer.gserviceaccount.com"
+        );
+        assert_eq!(metadata.project.project_id, "my-project");
+        assert_eq!(metadata.project.numeric_project_id, 123456789012);
+
+        // The tree is cached, so the metadata server is queried once
+        let cached = fetch_metadata_from(&mock_server.uri(), Duration::from_secs(1)).await?;
+        assert_eq!(cached, metadata);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_malformed() {
+        let mock_server = start_mock_server().await;
+        Mock::given(path("/computeMetadata/v1/"))
+            .respond_with(metadata_response(fixtures::MALFORMED))
+            .mount(&mock_server)
+            .await;
+
+        let result = fetch_metadata_from(&mock_server.uri(), Duration::from_secs(1)).await;
+
+        assert!(result.is_err());
+        assert_eq!(cached_metadata(&mock_server.uri()), None);
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(ZONE_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::gcp::ZONE))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "us-central1");
+        assert_eq!(region.zone.as_deref(), Some("us-central1-a"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region_malformed() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(ZONE_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::MALFORMED))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let result = region(&mock_server.uri()).run(Duration::from_secs(1)).await;
+
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(MACHINE_TYPE_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::gcp::MACHINE_TYPE))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "e2-standard-4");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(PROJECT_ID_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::gcp::PROJECT_ID))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "my-project");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path("/computeMetadata/v1/instance/network-interfaces/"))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::gcp::NETWORK_INTERFACES),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let network = network_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(network.provider, IDENTIFIER);
+        assert_eq!(network.local_ipv4, Some("10.128.0.2".parse()?));
+        assert_eq!(network.public_ipv4, Some("34.0.0.2".parse()?));
+        assert_eq!(network.mac.as_deref(), Some("42:01:0a:80:00:02"));
+        assert_eq!(network.network_id.as_deref(), Some("default"));
+        assert_eq!(network.subnet_id, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_credentials_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(SERVICE_ACCOUNTS_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::gcp::SERVICE_ACCOUNTS),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let credentials = credentials_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(credentials.provider, IDENTIFIER);
+        assert!(credentials.exposed);
+        assert_eq!(
+            credentials.identities,
+            ["123456789012-compute@developer.gserviceaccount.com"]
+        );
+
+        Ok(())
+    }
 }
@@ -0,0 +1,147 @@
+//! KVM guests not identified as any other provider, including KubeVirt and Harvester virtual machines.
+//!
+//! The flavour of KVM the host runs on is named by the check that matched (e.g. `harvester`), which is reported as
+//! evidence by [diagnose](crate::diagnose).
+
+use std::path::Path;
+
+use crate::checks::Check;
+use crate::{Confidence, Provider, ProviderId};
+
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+/// Vendor signature reported by KVM through CPUID.
+const HYPERVISOR_VENDOR: &str = "KVMKVMKVM";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::KVM;
+
+pub(crate) struct Kvm;
+
+impl Provider for Kvm {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify KVM using all the implemented options, most specific flavour first.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_harvester(VENDOR_FILE),
+            self.check_kubevirt(VENDOR_FILE),
+            self.check_qemu(VENDOR_FILE),
+            self.check_hypervisor(),
+        ]
+    }
+}
+
+impl Kvm {
+    /// Tries to identify a Harvester virtual machine using vendor file(s).
+    ///
+    /// Harvester runs its virtual machines with KubeVirt, but names itself in their DMI attributes instead.
+    pub(crate) fn check_harvester<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "harvester",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Harvester"),
+        )
+    }
+
+    /// Tries to identify a KubeVirt virtual machine using vendor file(s).
+    pub(crate) fn check_kubevirt<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "kubevirt",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("KubeVirt"),
+        )
+    }
+
+    /// Tries to identify a plain QEMU guest using vendor file(s).
+    pub(crate) fn check_qemu<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "qemu",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("QEMU"),
+        )
+    }
+
+    /// Tries to identify KVM using the hypervisor vendor signature reported by CPUID, for guests whose DMI attributes
+    /// are not available (e.g. in containers) or name no vendor.
+    pub(crate) fn check_hypervisor(&self) -> Check {
+        Check::cpuid("kvm", Confidence::Unverified, |vendor| {
+            vendor == HYPERVISOR_VENDOR
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::checks::Probe;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut harvester_file = NamedTempFile::new()?;
+        let mut kubevirt_file = NamedTempFile::new()?;
+        let mut qemu_file = NamedTempFile::new()?;
+
+        harvester_file.write_all(fixtures::kvm::HARVESTER_VENDOR.as_bytes())?;
+        kubevirt_file.write_all(fixtures::kvm::KUBEVIRT_VENDOR.as_bytes())?;
+        qemu_file.write_all(fixtures::kvm::QEMU_VENDOR.as_bytes())?;
+
+        let provider = Kvm;
+        let timeout = Duration::from_secs(1);
+
+        assert!(
+            provider
+                .check_harvester(harvester_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(
+            provider
+                .check_kubevirt(kubevirt_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(provider.check_qemu(qemu_file.path()).run(timeout).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let mut qemu_file = NamedTempFile::new()?;
+        qemu_file.write_all(fixtures::kvm::QEMU_VENDOR.as_bytes())?;
+
+        let provider = Kvm;
+        let timeout = Duration::from_secs(1);
+
+        assert!(
+            !provider
+                .check_harvester(qemu_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(!provider.check_kubevirt(qemu_file.path()).run(timeout).await);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_hypervisor() {
+        let check = Kvm.check_hypervisor();
+        let Probe::Cpuid { matches } = &check.probe else {
+            unreachable!();
+        };
+
+        assert!(matches(fixtures::kvm::HYPERVISOR));
+        assert!(!matches(fixtures::vmware::HYPERVISOR));
+    }
+}
@@ -1,10 +1,23 @@
 //! Provider modules.
+//!
+//! Besides detection, each module exposes typed access to the metadata server of its provider, for when the provider
+//! is already known.
 
-pub(crate) mod alibaba;
-pub(crate) mod aws;
-pub(crate) mod azure;
-pub(crate) mod digitalocean;
-pub(crate) mod gcp;
-pub(crate) mod oci;
-pub(crate) mod openstack;
-pub(crate) mod vultr;
+pub mod akamai;
+pub mod alibaba;
+pub mod aws;
+pub mod azure;
+pub(crate) mod civo;
+pub mod digitalocean;
+pub mod gcp;
+pub(crate) mod kvm;
+pub mod naver;
+pub mod nhn;
+pub(crate) mod nutanix;
+pub mod oci;
+pub mod openstack;
+pub(crate) mod proxmox;
+pub mod vkcloud;
+pub(crate) mod vmware;
+pub mod vultr;
+pub mod yandex;
@@ -0,0 +1,206 @@
+//! Naver Cloud Platform.
+//!
+//! The metadata server of Naver Cloud Platform serves EC2-style paths (e.g. `/latest/meta-data/instance-id`), but not
+//! the instance identity document AWS is identified by, and identifies servers by numeric server instance numbers
+//! rather than AWS's `i-` prefixed instance IDs.
+
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::checks::{Check, Fetch, Request};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{Confidence, Provider, ProviderId};
+
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
+const INSTANCE_ID_PATH: &str = "/latest/meta-data/instance-id";
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Naver;
+
+pub(crate) struct Naver;
+
+impl Provider for Naver {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify Naver Cloud Platform using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_metadata_server(METADATA_URI),
+        ]
+    }
+}
+
+impl Naver {
+    /// Tries to identify Naver Cloud Platform via the server instance number served by the metadata server.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = Request::get(metadata_uri, INSTANCE_ID_PATH);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.is_success() && parse_instance_id(&resp.body).is_ok()
+        })
+    }
+
+    /// Tries to identify Naver Cloud Platform using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("NAVER"),
+        )
+        .strong()
+    }
+}
+
+/// Parses a server instance number, which is always positive.
+fn parse_instance_id(id: &str) -> Result<u64> {
+    match id
+        .trim()
+        .parse()
+        .context("Invalid server instance number")?
+    {
+        0 => bail!("Invalid server instance number: 0"),
+        id => Ok(id),
+    }
+}
+
+/// Fetch of the server instance number of the instance.
+pub(crate) fn instance_id(metadata_uri: &str) -> Fetch<u64> {
+    Fetch::new(vec![Request::get(metadata_uri, INSTANCE_ID_PATH)], |resp| {
+        if !resp.is_success() {
+            bail!("Unexpected status code: {}", resp.status);
+        }
+
+        parse_instance_id(&resp.body)
+    })
+}
+
+/// Fetches the server instance number of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::naver::fetch_instance_id;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let instance_id = fetch_instance_id(None).await.unwrap();
+///     println!("Server instance number: {}", instance_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_instance_id(timeout: Option<u64>) -> Result<u64> {
+    instance_id(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_metadata_server_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(INSTANCE_ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::naver::INSTANCE_ID))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Naver;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_failure() {
+        // AWS serves the same path, with instance IDs of its own
+        let mock_server = MockServer::start().await;
+        Mock::given(path(INSTANCE_ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string("i-0123456789abcdef0"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Naver;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        vendor_file.write_all(fixtures::naver::VENDOR.as_bytes())?;
+
+        let provider = Naver;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let vendor_file = NamedTempFile::new()?;
+
+        let provider = Naver;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_id() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(INSTANCE_ID_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::naver::INSTANCE_ID))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_id = instance_id(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_id, 12345678);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,254 @@
+//! NHN Cloud, formerly TOAST.
+//!
+//! NHN Cloud is built on OpenStack, and its instances are told apart from those of other OpenStack clouds by the
+//! availability zones of NHN Cloud (e.g. `kr-pub-a`), named in the OpenStack metadata (served by the metadata server,
+//! and by config drives). OpenStack matches too on NHN Cloud, but loses to it as a generic platform.
+
+use std::path::Path;
+
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::checks::{Check, Fetch};
+use crate::providers::openstack;
+pub use crate::providers::openstack::Metadata;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{AccountInfo, Confidence, Provider, ProviderId, Region};
+
+pub(crate) const METADATA_URI: &str = openstack::METADATA_URI;
+/// Regions of NHN Cloud, by the prefix of the names of their availability zones (e.g. `kr2` for `kr2-pub-a`).
+const REGIONS: [(&str, &str); 3] = [("kr", "KR1"), ("kr2", "KR2"), ("jp", "JP1")];
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Nhn;
+
+pub(crate) struct Nhn;
+
+impl Provider for Nhn {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify NHN Cloud using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        openstack::CONFIG_DRIVE_MOUNT_DIRS
+            .iter()
+            .map(|mount_dir| self.check_config_drive_metadata(mount_dir))
+            .chain([self.check_metadata_server(METADATA_URI)])
+            .collect()
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(openstack::metadata(METADATA_URI).raw())
+    }
+}
+
+impl Nhn {
+    /// Tries to identify NHN Cloud via the availability zone in the OpenStack metadata served by the metadata server.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = openstack::metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<Metadata>()
+                .is_some_and(|metadata| is_nhn_cloud(&metadata))
+        })
+    }
+
+    /// Tries to identify NHN Cloud via the availability zone in the `meta_data.json` file of a mounted config drive.
+    pub(crate) fn check_config_drive_metadata<P: AsRef<Path>>(&self, mount_dir: P) -> Check {
+        Check::file(
+            "config_drive_metadata",
+            Confidence::Verified,
+            mount_dir
+                .as_ref()
+                .join(openstack::CONFIG_DRIVE_METADATA_PATH),
+            |content| {
+                serde_json::from_str::<Metadata>(content)
+                    .is_ok_and(|metadata| is_nhn_cloud(&metadata))
+            },
+        )
+    }
+}
+
+/// Whether OpenStack metadata is that of an NHN Cloud instance, by its availability zone.
+fn is_nhn_cloud(metadata: &Metadata) -> bool {
+    !metadata.uuid.is_empty()
+        && metadata
+            .availability_zone
+            .as_deref()
+            .and_then(region_of)
+            .is_some()
+}
+
+/// Returns the region of an availability zone of NHN Cloud (e.g. `KR1` for `kr-pub-a`).
+fn region_of(zone: &str) -> Option<&'static str> {
+    let (prefix, _) = zone.split_once("-pub-")?;
+
+    REGIONS
+        .iter()
+        .find(|&&(zone_prefix, _)| zone_prefix == prefix)
+        .map(|&(_, region)| region)
+}
+
+/// Fetch of the region and availability zone of the instance, from its metadata.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    openstack::metadata(metadata_uri).map(|metadata| {
+        let zone = metadata
+            .availability_zone
+            .context("Availability zone not available")?;
+        let region =
+            region_of(&zone).with_context(|| format!("Unknown availability zone {zone}"))?;
+
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: region.to_string(),
+            zone: Some(zone),
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the project of the instance, from its metadata.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    openstack::metadata(metadata_uri).map(|metadata| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: metadata.project_id.context("Account not available")?,
+        })
+    })
+}
+
+/// Fetches the OpenStack metadata of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::nhn::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!("Instance UUID: {}", metadata.uuid);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    openstack::metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::TempDir;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    const META_DATA_PATH: &str = "/openstack/latest/meta_data.json";
+
+    #[tokio::test]
+    async fn test_check_metadata_server_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::nhn::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Nhn;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_failure() {
+        // Other OpenStack clouds name availability zones of their own
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vkcloud::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Nhn;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_config_drive_metadata() -> Result<()> {
+        let mount_dir = TempDir::new()?;
+        let metadata_file = mount_dir.path().join(openstack::CONFIG_DRIVE_METADATA_PATH);
+        fs::create_dir_all(metadata_file.parent().unwrap())?;
+
+        let provider = Nhn;
+        let check = || provider.check_config_drive_metadata(mount_dir.path());
+
+        fs::write(&metadata_file, fixtures::nhn::METADATA)?;
+        assert!(check().run(Duration::from_secs(1)).await);
+
+        fs::write(&metadata_file, fixtures::openstack::CONFIG_DRIVE_METADATA)?;
+        assert!(!check().run(Duration::from_secs(1)).await);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_region_of() {
+        assert_eq!(region_of("kr-pub-a"), Some("KR1"));
+        assert_eq!(region_of("kr2-pub-b"), Some("KR2"));
+        assert_eq!(region_of("jp-pub-a"), Some("JP1"));
+        assert_eq!(region_of("nova"), None);
+        assert_eq!(region_of("us-pub-a"), None);
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::nhn::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "KR1");
+        assert_eq!(region.zone.as_deref(), Some("kr-pub-a"));
+
+        Ok(())
+    }
+}
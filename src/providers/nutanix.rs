@@ -0,0 +1,111 @@
+//! Nutanix AHV.
+
+use std::path::Path;
+
+use crate::checks::Check;
+use crate::{Confidence, Provider, ProviderId};
+
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+const PRODUCT_NAME_FILE: &str = "/sys/class/dmi/id/product_name";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Nutanix;
+
+pub(crate) struct Nutanix;
+
+impl Provider for Nutanix {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify Nutanix using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_product_name_file(PRODUCT_NAME_FILE),
+        ]
+    }
+}
+
+impl Nutanix {
+    /// Tries to identify Nutanix using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Nutanix"),
+        )
+    }
+
+    /// Tries to identify Nutanix using the product name file.
+    pub(crate) fn check_product_name_file<P: AsRef<Path>>(&self, product_name_file: P) -> Check {
+        Check::dmi(
+            "product_name_file",
+            Confidence::Unverified,
+            product_name_file.as_ref(),
+            |content| content.trim() == "AHV",
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        let mut product_name_file = NamedTempFile::new()?;
+
+        vendor_file.write_all(fixtures::nutanix::VENDOR.as_bytes())?;
+        product_name_file.write_all(fixtures::nutanix::PRODUCT_NAME.as_bytes())?;
+
+        let provider = Nutanix;
+        let timeout = Duration::from_secs(1);
+
+        assert!(
+            provider
+                .check_vendor_file(vendor_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(
+            provider
+                .check_product_name_file(product_name_file.path())
+                .run(timeout)
+                .await
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let vendor_file = NamedTempFile::new()?;
+        let product_name_file = NamedTempFile::new()?;
+
+        let provider = Nutanix;
+        let timeout = Duration::from_secs(1);
+
+        assert!(
+            !provider
+                .check_vendor_file(vendor_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(
+            !provider
+                .check_product_name_file(product_name_file.path())
+                .run(timeout)
+                .await
+        );
+
+        Ok(())
+    }
+}
@@ -1,18 +1,24 @@
 //! Oracle Cloud Infrastructure (OCI).
 
 use std::path::Path;
-use std::time::Duration;
 
-use async_trait::async_trait;
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
-
-use crate::{Provider, ProviderId};
-
-const METADATA_URI: &str = "http://169.254.169.254";
-const METADATA_PATH: &str = "/opc/v1/instance/metadata/";
+use serde_json::Value;
+
+use crate::checks::{Check, Fetch, Request};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{normalize_mac, AccountInfo, Confidence, NetworkInfo, Provider, ProviderId, Region};
+
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
+const METADATA_V1_PATH: &str = "/opc/v1/instance/metadata/";
+const METADATA_V2_PATH: &str = "/opc/v2/instance/";
+const INSTANCE_V1_PATH: &str = "/opc/v1/instance/";
+const VNICS_V2_PATH: &str = "/opc/v2/vnics/";
+const VNICS_V1_PATH: &str = "/opc/v1/vnics/";
 const VENDOR_FILE: &str = "/sys/class/dmi/id/chassis_asset_tag";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::OCI;
 
@@ -22,101 +28,223 @@ struct MetadataResponse {
     oke_tm: String,
 }
 
+/// Represents the metadata of a compute instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Instance {
+    pub id: String,
+    pub display_name: Option<String>,
+    pub region: Option<String>,
+    pub canonical_region_name: Option<String>,
+    pub availability_domain: Option<String>,
+    pub shape: Option<String>,
+    pub compartment_id: Option<String>,
+}
+
+/// Represents a VNIC attached to an instance.
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Vnic {
+    private_ip: Option<String>,
+    mac_addr: Option<String>,
+}
+
 pub(crate) struct Oci;
 
-#[async_trait]
 impl Provider for Oci {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify OCI using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking Oracle Cloud Infrastructure");
-        if self.check_vendor_file(VENDOR_FILE).await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified Oracle Cloud Infrastructure");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_metadata_server_v2(METADATA_URI),
+            self.check_metadata_server_v1(METADATA_URI),
+        ]
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn network_info(&self) -> Option<Fetch<NetworkInfo>> {
+        Some(network_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(instance(METADATA_URI).raw())
     }
 }
 
 impl Oci {
-    /// Tries to identify OCI via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-
-        match client.get(url).send().await {
-            Ok(resp) => match resp.json::<MetadataResponse>().await {
-                Ok(resp) => resp.oke_tm.contains("oke"),
-                Err(err) => {
-                    error!("Error reading response: {:?}", err);
-                    false
-                }
+    /// Tries to identify OCI via metadata server (using IMDSv2).
+    pub(crate) fn check_metadata_server_v2(&self, metadata_uri: &str) -> Check {
+        let request = v2_request(metadata_uri, METADATA_V2_PATH);
+
+        Check::http(
+            "metadata_server_v2",
+            Confidence::Verified,
+            request,
+            |resp| {
+                resp.json::<Instance>()
+                    .is_some_and(|instance| instance.id.starts_with("ocid1.instance."))
+            },
+        )
+        .or_unauthorized(Confidence::Unverified)
+    }
+
+    /// Tries to identify OCI via metadata server (using IMDSv1).
+    ///
+    /// If only IMDSv2 is allowed, IMDSv1 requests are rejected, which still proves the metadata server is present.
+    pub(crate) fn check_metadata_server_v1(&self, metadata_uri: &str) -> Check {
+        let request = Request::get(metadata_uri, METADATA_V1_PATH);
+
+        Check::http(
+            "metadata_server_v1",
+            Confidence::Verified,
+            request,
+            |resp| {
+                resp.json::<MetadataResponse>()
+                    .is_some_and(|metadata| metadata.oke_tm.contains("oke"))
             },
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
+        )
+        .or_unauthorized(Confidence::Unverified)
     }
 
     /// Tries to identify OCI using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
-
-        if vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(vendor_file).await {
-                Ok(content) => content.contains("OracleCloud"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
-        }
-
-        false
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("OracleCloud"),
+        )
+        .strong()
     }
 }
 
+/// Request for a metadata path using IMDSv2.
+fn v2_request(metadata_uri: &str, path: &str) -> Request {
+    Request::get(metadata_uri, path).header("Authorization", "Bearer Oracle")
+}
+
+/// Fetch of the metadata of the instance, using IMDSv2 before falling back to IMDSv1.
+pub(crate) fn instance(metadata_uri: &str) -> Fetch<Instance> {
+    Fetch::json(vec![
+        v2_request(metadata_uri, METADATA_V2_PATH),
+        Request::get(metadata_uri, INSTANCE_V1_PATH),
+    ])
+}
+
+/// Fetch of the region of the instance, from its metadata.
+///
+/// The canonical region name (e.g. `us-ashburn-1`) is preferred over the short region key (e.g. `iad`).
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    instance(metadata_uri).map(|instance| {
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: instance
+                .canonical_region_name
+                .or(instance.region)
+                .context("Region not available")?,
+            zone: instance.availability_domain,
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the shape of the instance, from its metadata.
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    instance(metadata_uri).map(|instance| instance.shape.context("Instance type not available"))
+}
+
+/// Fetch of the compartment of the instance, from its metadata.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    instance(metadata_uri).map(|instance| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: instance.compartment_id.context("Account not available")?,
+        })
+    })
+}
+
+/// Fetch of the network configuration of the primary VNIC of the instance, using IMDSv2 before falling back to
+/// IMDSv1.
+///
+/// The metadata server exposes neither public IP addresses nor the VCN or subnet the VNIC belongs to.
+pub(crate) fn network_info(metadata_uri: &str) -> Fetch<NetworkInfo> {
+    Fetch::<Vec<Vnic>>::json(vec![
+        v2_request(metadata_uri, VNICS_V2_PATH),
+        Request::get(metadata_uri, VNICS_V1_PATH),
+    ])
+    .map(|vnics| {
+        let vnic = vnics.into_iter().next().context("VNIC not available")?;
+
+        Ok(NetworkInfo {
+            provider: IDENTIFIER,
+            local_ipv4: vnic.private_ip.and_then(|ip| ip.parse().ok()),
+            mac: vnic.mac_addr.as_deref().and_then(normalize_mac),
+            ..Default::default()
+        })
+    })
+}
+
+/// Fetches the metadata of the instance from the metadata server.
+///
+/// IMDSv2 is used when available, falling back to IMDSv1.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::oci::fetch_instance;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let instance = fetch_instance(None).await.unwrap();
+///     println!("Instance OCID: {}", instance.id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_instance(timeout: Option<u64>) -> Result<Instance> {
+    instance(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
-    use wiremock::matchers::path;
+    use wiremock::matchers::{header, path};
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
 
     #[tokio::test]
-    async fn test_check_metadata_server_success() {
+    async fn test_check_metadata_server_v2_success() {
         let mock_server = MockServer::start().await;
-        Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                oke_tm: "oke".to_string(),
-            }))
+        Mock::given(path(METADATA_V2_PATH))
+            .and(header("Authorization", "Bearer Oracle"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -124,19 +252,20 @@ mod tests {
         let provider = Oci;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server_v2(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
     }
 
     #[tokio::test]
-    async fn test_check_metadata_server_failure() {
+    async fn test_check_metadata_server_v2_failure() {
         let mock_server = MockServer::start().await;
-        Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                oke_tm: "abc".to_string(),
-            }))
+        Mock::given(path(METADATA_V2_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2_MISMATCH),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -144,7 +273,48 @@ mod tests {
         let provider = Oci;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server_v2(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_v1_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V1_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V1))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Oci;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server_v1(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_v1_failure() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V1_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V1_MISMATCH),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Oci;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server_v1(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -153,10 +323,13 @@ mod tests {
     #[tokio::test]
     async fn test_check_vendor_file_success() -> Result<()> {
         let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"OracleCloud")?;
+        vendor_file.write_all(fixtures::oci::VENDOR.as_bytes())?;
 
         let provider = Oci;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(result);
 
@@ -168,10 +341,138 @@ mod tests {
         let vendor_file = NamedTempFile::new()?;
 
         let provider = Oci;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_instance() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V2_PATH))
+            .and(header("Authorization", "Bearer Oracle"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance = instance(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance.id, "ocid1.instance.oc1.iad.abc123");
+        assert_eq!(instance.shape.as_deref(), Some("VM.Standard.E4.Flex"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_instance_v1_fallback() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V2_PATH))
+            .respond_with(ResponseTemplate::new(404))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(path(INSTANCE_V1_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance = instance(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance.id, "ocid1.instance.oc1.iad.abc123");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V2_PATH))
+            .and(header("Authorization", "Bearer Oracle"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "us-ashburn-1");
+        assert_eq!(region.zone.as_deref(), Some("AD-1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V2_PATH))
+            .and(header("Authorization", "Bearer Oracle"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "VM.Standard.E4.Flex");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_V2_PATH))
+            .and(header("Authorization", "Bearer Oracle"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::METADATA_V2))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "ocid1.compartment.oc1..abc123");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_network_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(VNICS_V2_PATH))
+            .and(header("Authorization", "Bearer Oracle"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::oci::VNICS))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let network = network_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(network.provider, IDENTIFIER);
+        assert_eq!(network.local_ipv4, Some("10.0.0.2".parse()?));
+        assert_eq!(network.public_ipv4, None);
+        assert_eq!(network.mac.as_deref(), Some("02:00:17:00:00:01"));
+
+        Ok(())
+    }
 }
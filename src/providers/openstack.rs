@@ -1,17 +1,23 @@
 //! OpenStack.
 
 use std::path::Path;
-use std::time::Duration;
 
-use async_trait::async_trait;
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
-
-use crate::{Provider, ProviderId};
-
-const METADATA_URI: &str = "http://169.254.169.254";
-const METADATA_PATH: &str = "/openstack/";
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tracing::debug;
+
+use crate::checks::{Check, Fetch, Probe, Request};
+use crate::redact::Redacted;
+use crate::smbios::SmbiosData;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{AccountInfo, Confidence, Provider, ProviderId};
+
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
+const META_DATA_PATH: &str = "/openstack/latest/meta_data.json";
 const PRODUCT_NAME_FILE: &str = "/sys/class/dmi/id/product_name";
 const PRODUCT_NAMES: [&str; 2] = ["Openstack Nova", "OpenStack Compute"];
 const CHASSIS_ASSET_TAG_FILE: &str = "/sys/class/dmi/id/chassis_asset_tag";
@@ -22,126 +28,240 @@ const CHASSIS_ASSET_TAGS: [&str; 5] = [
     "OpenStack Nova",
     "OpenStack Compute",
 ];
+/// White-label clouds built on OpenStack, by the chassis asset tag they set.
+const RESELLERS: [(&str, ProviderId); 3] = [
+    ("HUAWEICLOUD", ProviderId::HuaweiCloud),
+    ("OpenTelekomCloud", ProviderId::OpenTelekom),
+    ("SAP CCloud VM", ProviderId::SapCCloud),
+];
+const CONFIG_DRIVE_LABEL_FILES: [&str; 2] =
+    ["/dev/disk/by-label/config-2", "/dev/disk/by-label/CONFIG-2"];
+pub(crate) const CONFIG_DRIVE_MOUNT_DIRS: [&str; 3] =
+    ["/mnt/config", "/media/configdrive", "/config-2"];
+pub(crate) const CONFIG_DRIVE_METADATA_PATH: &str = "openstack/latest/meta_data.json";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::OpenStack;
 
+/// Represents the metadata of an instance, as served by the metadata server and config drives.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
+    pub uuid: String,
+    pub name: Option<String>,
+    pub hostname: Option<String>,
+    pub availability_zone: Option<String>,
+    pub project_id: Option<String>,
+}
+
 pub(crate) struct OpenStack;
 
-#[async_trait]
 impl Provider for OpenStack {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify OpenStack using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking OpenStack");
-        if self
-            .check_vendor_files(PRODUCT_NAME_FILE, CHASSIS_ASSET_TAG_FILE)
-            .await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified OpenStack");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        let config_drive_metadata = CONFIG_DRIVE_MOUNT_DIRS
+            .iter()
+            .map(|mount_dir| self.check_config_drive_metadata(mount_dir));
+        let config_drive_label = CONFIG_DRIVE_LABEL_FILES
+            .iter()
+            .map(|label_file| self.check_config_drive_label(label_file));
+
+        config_drive_metadata
+            .chain([
+                self.check_product_name_file(PRODUCT_NAME_FILE),
+                self.check_chassis_asset_tag_file(CHASSIS_ASSET_TAG_FILE),
+            ])
+            .chain(config_drive_label)
+            .chain([self.check_metadata_server(METADATA_URI)])
+            .collect()
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(metadata(METADATA_URI).raw())
     }
 }
 
 impl OpenStack {
     /// Tries to identify OpenStack via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-
-        match client.get(url).send().await {
-            Ok(resp) => resp.status().is_success(),
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
-    }
-
-    /// Tries to identify OpenStack using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_files<P: AsRef<Path>>(
-        &self,
-        product_name_file: P,
-        chassis_asset_tag_file: P,
-    ) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            product_name_file.as_ref().display()
-        );
+    ///
+    /// The metadata of the instance must parse, so that other servers answering any request (e.g. captive portals)
+    /// aren't mistaken for the metadata server.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<Metadata>()
+                .is_some_and(|metadata| !metadata.uuid.is_empty())
+        })
+    }
 
-        if product_name_file.as_ref().is_file() {
-            match fs::read_to_string(product_name_file).await {
-                Ok(content) => {
-                    if PRODUCT_NAMES.iter().any(|&name| content.contains(name)) {
-                        return true;
-                    }
-                }
+    /// Tries to identify OpenStack via the `meta_data.json` file of a mounted config drive.
+    pub(crate) fn check_config_drive_metadata<P: AsRef<Path>>(&self, mount_dir: P) -> Check {
+        Check::file(
+            "config_drive_metadata",
+            Confidence::Verified,
+            mount_dir.as_ref().join(CONFIG_DRIVE_METADATA_PATH),
+            |content| match serde_json::from_str::<Metadata>(content) {
+                Ok(metadata) => !metadata.uuid.is_empty(),
                 Err(err) => {
-                    error!("Error reading file: {:?}", err);
+                    debug!("Error parsing file: {:?}", Redacted(&err));
+                    false
                 }
-            }
-        }
+            },
+        )
+    }
 
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            chassis_asset_tag_file.as_ref().display(),
-        );
+    /// Tries to identify OpenStack via the disk label of an attached config drive.
+    pub(crate) fn check_config_drive_label<P: AsRef<Path>>(&self, label_file: P) -> Check {
+        Check::exists(
+            "config_drive_label",
+            Confidence::Unverified,
+            label_file.as_ref(),
+        )
+    }
 
-        if chassis_asset_tag_file.as_ref().is_file() {
-            match fs::read_to_string(chassis_asset_tag_file).await {
-                Ok(content) => {
-                    if CHASSIS_ASSET_TAGS
-                        .iter()
-                        .any(|&name| content.contains(name))
-                    {
-                        return true;
-                    }
-                }
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                }
-            }
-        }
+    /// Tries to identify OpenStack using the product name file.
+    pub(crate) fn check_product_name_file<P: AsRef<Path>>(&self, product_name_file: P) -> Check {
+        Check::dmi(
+            "product_name_file",
+            Confidence::Unverified,
+            product_name_file.as_ref(),
+            |content| PRODUCT_NAMES.iter().any(|&name| content.contains(name)),
+        )
+    }
 
-        false
+    /// Tries to identify OpenStack using the chassis asset tag file.
+    pub(crate) fn check_chassis_asset_tag_file<P: AsRef<Path>>(
+        &self,
+        chassis_asset_tag_file: P,
+    ) -> Check {
+        Check::dmi(
+            "chassis_asset_tag_file",
+            Confidence::Unverified,
+            chassis_asset_tag_file.as_ref(),
+            |content| {
+                CHASSIS_ASSET_TAGS
+                    .iter()
+                    .any(|&name| content.contains(name))
+            },
+        )
     }
 }
 
+/// Classifies the white-label cloud an OpenStack host runs on from the chassis asset tag inspected by its checks, if
+/// the cloud sets one of its own.
+pub(crate) fn reseller(checks: &[Check], smbios: &SmbiosData) -> Option<ProviderId> {
+    checks
+        .iter()
+        .find_map(|check| match &check.probe {
+            Probe::Dmi { path, .. } if check.name == "chassis_asset_tag_file" => smbios.field(path),
+            _ => None,
+        })
+        .and_then(|tag| reseller_from(&tag))
+}
+
+fn reseller_from(chassis_asset_tag: &str) -> Option<ProviderId> {
+    RESELLERS
+        .iter()
+        .find(|(tag, _)| chassis_asset_tag.contains(tag))
+        .map(|&(_, reseller)| reseller)
+}
+
+/// Request for the metadata of the instance.
+pub(crate) fn metadata_request(metadata_uri: &str) -> Request {
+    Request::get(metadata_uri, META_DATA_PATH)
+}
+
+/// Fetch of the metadata of the instance.
+pub(crate) fn metadata(metadata_uri: &str) -> Fetch<Metadata> {
+    Fetch::json(vec![metadata_request(metadata_uri)])
+}
+
+/// Fetch of the project of the instance, from its metadata.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    metadata(metadata_uri).map(|metadata| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: metadata.project_id.context("Account not available")?,
+        })
+    })
+}
+
+/// Fetches the metadata of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::openstack::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!("Instance UUID: {}", metadata.uuid);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
-    use tempfile::NamedTempFile;
+    use tempfile::{NamedTempFile, TempDir};
     use wiremock::matchers::path;
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[test]
+    fn test_reseller() -> Result<()> {
+        let mut chassis_asset_tag_file = NamedTempFile::new()?;
+        chassis_asset_tag_file.write_all(fixtures::openstack::CHASSIS_ASSET_TAG.as_bytes())?;
+        let checks = [OpenStack.check_chassis_asset_tag_file(chassis_asset_tag_file.path())];
+
+        assert_eq!(
+            reseller(&checks, &SmbiosData::default()),
+            Some(ProviderId::HuaweiCloud)
+        );
+        assert_eq!(
+            reseller_from(fixtures::openstack::OPEN_TELEKOM_CHASSIS_ASSET_TAG),
+            Some(ProviderId::OpenTelekom)
+        );
+        assert_eq!(
+            reseller_from(fixtures::openstack::SAP_CCLOUD_CHASSIS_ASSET_TAG),
+            Some(ProviderId::SapCCloud)
+        );
+        // Plain OpenStack clouds are not resold
+        assert_eq!(reseller_from("OpenStack Nova"), None);
+
+        Ok(())
+    }
 
     #[tokio::test]
     async fn test_check_metadata_server_success() {
         let mock_server = MockServer::start().await;
-        Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200))
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::openstack::CONFIG_DRIVE_METADATA),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -149,7 +269,8 @@ mod tests {
         let provider = OpenStack;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -158,8 +279,27 @@ mod tests {
     #[tokio::test]
     async fn test_check_metadata_server_failure() {
         let mock_server = MockServer::start().await;
-        Mock::given(path(METADATA_PATH))
+        Mock::given(path(META_DATA_PATH))
             .respond_with(ResponseTemplate::new(500))
+            .expect(u64::from(crate::retry::MAX_ATTEMPTS))
+            .mount(&mock_server)
+            .await;
+
+        let provider = OpenStack;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_captive_portal() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::CAPTIVE_PORTAL))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -167,7 +307,8 @@ mod tests {
         let provider = OpenStack;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
@@ -178,15 +319,24 @@ mod tests {
         let mut product_name_file = NamedTempFile::new()?;
         let mut chassis_asset_tag_file = NamedTempFile::new()?;
 
-        product_name_file.write_all(PRODUCT_NAMES[0].as_bytes())?;
-        chassis_asset_tag_file.write_all(CHASSIS_ASSET_TAGS[0].as_bytes())?;
+        product_name_file.write_all(fixtures::openstack::PRODUCT_NAME.as_bytes())?;
+        chassis_asset_tag_file.write_all(fixtures::openstack::CHASSIS_ASSET_TAG.as_bytes())?;
 
         let provider = OpenStack;
-        let result = provider
-            .check_vendor_files(product_name_file.path(), chassis_asset_tag_file.path())
-            .await;
+        let timeout = Duration::from_secs(1);
 
-        assert!(result);
+        assert!(
+            provider
+                .check_product_name_file(product_name_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(
+            provider
+                .check_chassis_asset_tag_file(chassis_asset_tag_file.path())
+                .run(timeout)
+                .await
+        );
 
         Ok(())
     }
@@ -196,13 +346,132 @@ mod tests {
         let product_name_file = NamedTempFile::new()?;
         let chassis_asset_tag_file = NamedTempFile::new()?;
 
+        let provider = OpenStack;
+        let timeout = Duration::from_secs(1);
+
+        assert!(
+            !provider
+                .check_product_name_file(product_name_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(
+            !provider
+                .check_chassis_asset_tag_file(chassis_asset_tag_file.path())
+                .run(timeout)
+                .await
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_config_drive_metadata_success() -> Result<()> {
+        let mount_dir = TempDir::new()?;
+        let metadata_file = mount_dir.path().join(CONFIG_DRIVE_METADATA_PATH);
+        std::fs::create_dir_all(metadata_file.parent().unwrap())?;
+        std::fs::write(&metadata_file, fixtures::openstack::CONFIG_DRIVE_METADATA)?;
+
+        let provider = OpenStack;
+        let result = provider
+            .check_config_drive_metadata(mount_dir.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_config_drive_metadata_failure() -> Result<()> {
+        let mount_dir = TempDir::new()?;
+        let metadata_file = mount_dir.path().join(CONFIG_DRIVE_METADATA_PATH);
+        std::fs::create_dir_all(metadata_file.parent().unwrap())?;
+        std::fs::write(&metadata_file, fixtures::MALFORMED)?;
+
         let provider = OpenStack;
         let result = provider
-            .check_vendor_files(product_name_file.path(), chassis_asset_tag_file.path())
+            .check_config_drive_metadata(mount_dir.path())
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_check_config_drive_label_success() -> Result<()> {
+        let label_file = NamedTempFile::new()?;
+
+        let provider = OpenStack;
+        let result = provider
+            .check_config_drive_label(label_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_config_drive_label_failure() -> Result<()> {
+        let label_dir = TempDir::new()?;
+
+        let provider = OpenStack;
+        let result = provider
+            .check_config_drive_label(label_dir.path().join("config-2"))
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::openstack::CONFIG_DRIVE_METADATA),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.uuid, "83679162-1378-4288-a2d4-70e13ec132aa");
+        assert_eq!(metadata.availability_zone.as_deref(), Some("nova"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::openstack::CONFIG_DRIVE_METADATA),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "6b4f1d2a");
+
+        Ok(())
+    }
 }
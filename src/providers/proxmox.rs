@@ -0,0 +1,112 @@
+//! Proxmox Virtual Environment.
+//!
+//! Proxmox VE guests are QEMU/KVM guests, which only identify Proxmox in their DMI attributes when booted with its
+//! UEFI firmware, or when the SMBIOS settings of the virtual machine name it.
+
+use std::path::Path;
+
+use crate::checks::Check;
+use crate::{Confidence, Provider, ProviderId};
+
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+const BIOS_VENDOR_FILE: &str = "/sys/class/dmi/id/bios_vendor";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Proxmox;
+
+pub(crate) struct Proxmox;
+
+impl Provider for Proxmox {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify Proxmox using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_bios_vendor_file(BIOS_VENDOR_FILE),
+            self.check_vendor_file(VENDOR_FILE),
+        ]
+    }
+}
+
+impl Proxmox {
+    /// Tries to identify Proxmox using the BIOS vendor file, naming its UEFI firmware (`Proxmox distribution of EDK
+    /// II`).
+    pub(crate) fn check_bios_vendor_file<P: AsRef<Path>>(&self, bios_vendor_file: P) -> Check {
+        Check::dmi(
+            "bios_vendor_file",
+            Confidence::Unverified,
+            bios_vendor_file.as_ref(),
+            |content| content.contains("Proxmox"),
+        )
+    }
+
+    /// Tries to identify Proxmox using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Proxmox"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_bios_vendor_file_success() -> Result<()> {
+        let mut bios_vendor_file = NamedTempFile::new()?;
+        bios_vendor_file.write_all(fixtures::proxmox::BIOS_VENDOR.as_bytes())?;
+
+        let provider = Proxmox;
+        let result = provider
+            .check_bios_vendor_file(bios_vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_bios_vendor_file_failure() -> Result<()> {
+        let mut bios_vendor_file = NamedTempFile::new()?;
+        bios_vendor_file.write_all(b"SeaBIOS")?;
+
+        let provider = Proxmox;
+        let result = provider
+            .check_bios_vendor_file(bios_vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        vendor_file.write_all(fixtures::proxmox::VENDOR.as_bytes())?;
+
+        let provider = Proxmox;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,215 @@
+//! VK Cloud, formerly Mail.ru Cloud Solutions.
+//!
+//! VK Cloud is built on OpenStack, and its instances look like those of any other OpenStack cloud, down to their DMI
+//! attributes. They are told apart by the availability zones of VK Cloud, named in the OpenStack metadata (served by
+//! the metadata server, and by config drives). OpenStack
+//! matches too on VK Cloud, but loses to it as a generic platform.
+
+use std::path::Path;
+
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use serde_json::Value;
+
+use crate::checks::{Check, Fetch};
+use crate::providers::openstack;
+pub use crate::providers::openstack::Metadata;
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{AccountInfo, Confidence, Provider, ProviderId};
+
+pub(crate) const METADATA_URI: &str = openstack::METADATA_URI;
+/// Availability zones of VK Cloud, named after its data centers.
+const AVAILABILITY_ZONES: [&str; 3] = ["GZ1", "MS1", "ME1"];
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::VkCloud;
+
+pub(crate) struct VkCloud;
+
+impl Provider for VkCloud {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify VK Cloud using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        openstack::CONFIG_DRIVE_MOUNT_DIRS
+            .iter()
+            .map(|mount_dir| self.check_config_drive_metadata(mount_dir))
+            .chain([self.check_metadata_server(METADATA_URI)])
+            .collect()
+    }
+
+    fn account_info(&self) -> Option<Fetch<AccountInfo>> {
+        Some(account_info(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(openstack::metadata(METADATA_URI).raw())
+    }
+}
+
+impl VkCloud {
+    /// Tries to identify VK Cloud via the availability zone in the OpenStack metadata served by the metadata server.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = openstack::metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<Metadata>()
+                .is_some_and(|metadata| is_vk_cloud(&metadata))
+        })
+    }
+
+    /// Tries to identify VK Cloud via the availability zone in the `meta_data.json` file of a mounted config drive.
+    pub(crate) fn check_config_drive_metadata<P: AsRef<Path>>(&self, mount_dir: P) -> Check {
+        Check::file(
+            "config_drive_metadata",
+            Confidence::Verified,
+            mount_dir
+                .as_ref()
+                .join(openstack::CONFIG_DRIVE_METADATA_PATH),
+            |content| {
+                serde_json::from_str::<Metadata>(content)
+                    .is_ok_and(|metadata| is_vk_cloud(&metadata))
+            },
+        )
+    }
+}
+
+/// Whether OpenStack metadata is that of a VK Cloud instance, by its availability zone.
+fn is_vk_cloud(metadata: &Metadata) -> bool {
+    !metadata.uuid.is_empty()
+        && metadata
+            .availability_zone
+            .as_deref()
+            .is_some_and(|zone| AVAILABILITY_ZONES.contains(&zone))
+}
+
+/// Fetch of the project of the instance, from its metadata.
+pub(crate) fn account_info(metadata_uri: &str) -> Fetch<AccountInfo> {
+    openstack::metadata(metadata_uri).map(|metadata| {
+        Ok(AccountInfo {
+            provider: IDENTIFIER,
+            account_id: metadata.project_id.context("Account not available")?,
+        })
+    })
+}
+
+/// Fetches the OpenStack metadata of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::vkcloud::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!("Instance UUID: {}", metadata.uuid);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    openstack::metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::TempDir;
+    use wiremock::matchers::path;
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    const META_DATA_PATH: &str = "/openstack/latest/meta_data.json";
+
+    #[tokio::test]
+    async fn test_check_metadata_server_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vkcloud::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = VkCloud;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_failure() {
+        // Other OpenStack clouds name availability zones of their own
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::openstack::CONFIG_DRIVE_METADATA),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = VkCloud;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_config_drive_metadata() -> Result<()> {
+        let mount_dir = TempDir::new()?;
+        let metadata_file = mount_dir.path().join(openstack::CONFIG_DRIVE_METADATA_PATH);
+        fs::create_dir_all(metadata_file.parent().unwrap())?;
+
+        let provider = VkCloud;
+        let check = || provider.check_config_drive_metadata(mount_dir.path());
+
+        fs::write(&metadata_file, fixtures::vkcloud::METADATA)?;
+        assert!(check().run(Duration::from_secs(1)).await);
+
+        fs::write(&metadata_file, fixtures::openstack::CONFIG_DRIVE_METADATA)?;
+        assert!(!check().run(Duration::from_secs(1)).await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_account_info() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(META_DATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vkcloud::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let account = account_info(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(account.provider, IDENTIFIER);
+        assert_eq!(account.account_id, "b3f2d8a1");
+
+        Ok(())
+    }
+}
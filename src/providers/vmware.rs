@@ -0,0 +1,124 @@
+//! VMware.
+
+use std::path::Path;
+
+use crate::checks::Check;
+use crate::{Confidence, Provider, ProviderId};
+
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+const PRODUCT_NAME_FILE: &str = "/sys/class/dmi/id/product_name";
+/// Vendor signature reported by VMware hypervisors through CPUID.
+const HYPERVISOR_VENDOR: &str = "VMwareVMware";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::VMware;
+
+pub(crate) struct VMware;
+
+impl Provider for VMware {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify VMware using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_product_name_file(PRODUCT_NAME_FILE),
+            self.check_hypervisor(),
+        ]
+    }
+}
+
+impl VMware {
+    /// Tries to identify VMware using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("VMware"),
+        )
+    }
+
+    /// Tries to identify VMware using the product name file (e.g. `VMware Virtual Platform` or `VMware7,1`).
+    pub(crate) fn check_product_name_file<P: AsRef<Path>>(&self, product_name_file: P) -> Check {
+        Check::dmi(
+            "product_name_file",
+            Confidence::Unverified,
+            product_name_file.as_ref(),
+            |content| content.contains("VMware"),
+        )
+    }
+
+    /// Tries to identify VMware using the hypervisor vendor signature reported by CPUID.
+    pub(crate) fn check_hypervisor(&self) -> Check {
+        Check::cpuid("hypervisor", Confidence::Unverified, |vendor| {
+            vendor == HYPERVISOR_VENDOR
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+
+    use super::*;
+    use crate::checks::Probe;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        let mut product_name_file = NamedTempFile::new()?;
+
+        vendor_file.write_all(fixtures::vmware::VENDOR.as_bytes())?;
+        product_name_file.write_all(fixtures::vmware::PRODUCT_NAME.as_bytes())?;
+
+        let provider = VMware;
+        let timeout = Duration::from_secs(1);
+
+        assert!(
+            provider
+                .check_vendor_file(vendor_file.path())
+                .run(timeout)
+                .await
+        );
+        assert!(
+            provider
+                .check_product_name_file(product_name_file.path())
+                .run(timeout)
+                .await
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let vendor_file = NamedTempFile::new()?;
+
+        let provider = VMware;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_check_hypervisor() {
+        let check = VMware.check_hypervisor();
+        let Probe::Cpuid { matches } = &check.probe else {
+            unreachable!();
+        };
+
+        assert!(matches(fixtures::vmware::HYPERVISOR));
+        assert!(!matches("KVMKVMKVM"));
+    }
+}
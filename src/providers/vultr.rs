@@ -1,107 +1,184 @@
 //! Vultr.
 
 use std::path::Path;
-use std::time::Duration;
 
-use async_trait::async_trait;
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
 use serde::{Deserialize, Serialize};
-use tokio::fs;
-use tokio::sync::mpsc::Sender;
-use tracing::{debug, error, info, instrument};
+use serde_json::Value;
 
-use crate::{Provider, ProviderId};
+use crate::checks::{Check, Fetch, Request};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{Confidence, Provider, ProviderId, Region};
 
-const METADATA_URI: &str = "http://169.254.169.254";
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
 const METADATA_PATH: &str = "/v1.json";
 const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+const CHASSIS_ASSET_TAG_FILE: &str = "/sys/class/dmi/id/chassis_asset_tag";
+/// Prefix of the plans of bare metal instances (e.g. `vbm-4c-32gb`).
+const BARE_METAL_PLAN_PREFIX: &str = "vbm-";
 pub(crate) const IDENTIFIER: ProviderId = ProviderId::Vultr;
 
-pub(crate) struct Vultr;
-
-#[derive(Serialize, Deserialize)]
-struct MetadataResponse {
+/// Represents the metadata of an instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Metadata {
     #[serde(rename = "instanceid")]
-    instance_id: String,
+    pub instance_id: String,
+    pub hostname: Option<String>,
+    pub region: Option<MetadataRegion>,
+    /// Plan (instance type) of the instance, e.g. `vc2-1c-1gb`.
+    pub plan: Option<String>,
+}
+
+impl Metadata {
+    /// Whether the instance is a bare metal server rather than a virtual machine, according to its plan.
+    pub fn is_bare_metal(&self) -> bool {
+        self.plan
+            .as_deref()
+            .is_some_and(|plan| plan.starts_with(BARE_METAL_PLAN_PREFIX))
+    }
+}
+
+/// Represents the region of an instance, as reported in its metadata.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+pub struct MetadataRegion {
+    #[serde(rename = "regioncode")]
+    pub region_code: String,
+    #[serde(rename = "countrycode")]
+    pub country_code: Option<String>,
 }
 
-#[async_trait]
+pub(crate) struct Vultr;
+
 impl Provider for Vultr {
     fn identifier(&self) -> ProviderId {
         IDENTIFIER
     }
 
     /// Tries to identify Vultr using all the implemented options.
-    #[instrument(skip_all)]
-    async fn identify(&self, tx: Sender<ProviderId>, timeout: Duration) {
-        info!("Checking Vultr");
-        if self.check_vendor_file(VENDOR_FILE).await
-            || self.check_metadata_server(METADATA_URI, timeout).await
-        {
-            info!("Identified Vultr");
-            let res = tx.send(IDENTIFIER).await;
-
-            if let Err(err) = res {
-                error!("Error sending message: {:?}", err);
-            }
-        }
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_chassis_asset_tag_file(CHASSIS_ASSET_TAG_FILE),
+            self.check_metadata_server(METADATA_URI),
+        ]
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn instance_type(&self) -> Option<Fetch<String>> {
+        Some(instance_type(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(metadata(METADATA_URI).raw())
     }
 }
 
 impl Vultr {
     /// Tries to identify Vultr via metadata server.
-    #[instrument(skip_all)]
-    async fn check_metadata_server(&self, metadata_uri: &str, timeout: Duration) -> bool {
-        let url = format!("{}{}", metadata_uri, METADATA_PATH);
-        debug!("Checking {} metadata using url: {}", IDENTIFIER, url);
-
-        let client = if let Ok(client) = reqwest::Client::builder().timeout(timeout).build() {
-            client
-        } else {
-            error!("Error creating client");
-            return false;
-        };
-
-        match client.get(url).send().await {
-            Ok(resp) => match resp.json::<MetadataResponse>().await {
-                Ok(resp) => !resp.instance_id.is_empty(),
-                Err(err) => {
-                    error!("Error reading response: {:?}", err);
-                    false
-                }
-            },
-            Err(err) => {
-                error!("Error making request: {:?}", err);
-                false
-            }
-        }
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = metadata_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<Metadata>()
+                .is_some_and(|metadata| !metadata.instance_id.is_empty())
+        })
     }
 
     /// Tries to identify Vultr using vendor file(s).
-    #[instrument(skip_all)]
-    async fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> bool {
-        debug!(
-            "Checking {} vendor file: {}",
-            IDENTIFIER,
-            vendor_file.as_ref().display()
-        );
-
-        if vendor_file.as_ref().is_file() {
-            return match fs::read_to_string(vendor_file).await {
-                Ok(content) => content.contains("Vultr"),
-                Err(err) => {
-                    error!("Error reading file: {:?}", err);
-                    false
-                }
-            };
-        }
-
-        false
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Vultr"),
+        )
+        .strong()
     }
+
+    /// Tries to identify Vultr using the chassis asset tag file, set on bare metal servers whose vendor file names the
+    /// hardware manufacturer instead.
+    pub(crate) fn check_chassis_asset_tag_file<P: AsRef<Path>>(
+        &self,
+        chassis_asset_tag_file: P,
+    ) -> Check {
+        Check::dmi(
+            "chassis_asset_tag_file",
+            Confidence::Unverified,
+            chassis_asset_tag_file.as_ref(),
+            |content| content.contains("Vultr"),
+        )
+        .strong()
+    }
+}
+
+/// Request for the metadata of the instance.
+fn metadata_request(metadata_uri: &str) -> Request {
+    Request::get(metadata_uri, METADATA_PATH)
+}
+
+/// Fetch of the metadata of the instance.
+pub(crate) fn metadata(metadata_uri: &str) -> Fetch<Metadata> {
+    Fetch::json(vec![metadata_request(metadata_uri)])
+}
+
+/// Fetch of the region of the instance, from its metadata.
+///
+/// The metadata reports region codes in upper case (e.g. `EWR`), while the Vultr API identifies regions in lower case
+/// (e.g. `ewr`), which is what is returned.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    metadata(metadata_uri).map(|metadata| {
+        let region = metadata.region.context("Region not available")?;
+
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: region.region_code.to_lowercase(),
+            zone: None,
+            partition: None,
+        })
+    })
+}
+
+/// Fetch of the plan (instance type) of the instance, from its metadata.
+pub(crate) fn instance_type(metadata_uri: &str) -> Fetch<String> {
+    metadata(metadata_uri).map(|metadata| metadata.plan.context("Plan not available"))
+}
+
+/// Fetches the metadata of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::vultr::fetch_metadata;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let metadata = fetch_metadata(None).await.unwrap();
+///     println!("Instance ID: {}", metadata.instance_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_metadata(timeout: Option<u64>) -> Result<Metadata> {
+    metadata(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
 }
 
 #[cfg(test)]
 mod tests {
     use std::io::Write;
+    use std::time::Duration;
 
     use anyhow::Result;
     use tempfile::NamedTempFile;
@@ -109,14 +186,13 @@ mod tests {
     use wiremock::{Mock, MockServer, ResponseTemplate};
 
     use super::*;
+    use crate::provider_fixtures as fixtures;
 
     #[tokio::test]
     async fn test_check_metadata_server_success() {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                instance_id: "i-123abc".to_string(),
-            }))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA))
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -124,7 +200,8 @@ mod tests {
         let provider = Vultr;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
             .await;
 
         assert!(result);
@@ -134,9 +211,9 @@ mod tests {
     async fn test_check_metadata_server_failure() {
         let mock_server = MockServer::start().await;
         Mock::given(path(METADATA_PATH))
-            .respond_with(ResponseTemplate::new(200).set_body_json(MetadataResponse {
-                instance_id: "".to_string(),
-            }))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA_MISMATCH),
+            )
             .expect(1)
             .mount(&mock_server)
             .await;
@@ -144,19 +221,47 @@ mod tests {
         let provider = Vultr;
         let metadata_uri = mock_server.uri();
         let result = provider
-            .check_metadata_server(&metadata_uri, Duration::from_secs(1))
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_timeout() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::vultr::METADATA)
+                    .set_delay(Duration::from_secs(5)),
+            )
+            .mount(&mock_server)
+            .await;
+
+        let provider = Vultr;
+        let metadata_uri = mock_server.uri();
+        let start = std::time::Instant::now();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_millis(200))
             .await;
 
         assert!(!result);
+        assert!(start.elapsed() < Duration::from_secs(5));
     }
 
     #[tokio::test]
     async fn test_check_vendor_file_success() -> Result<()> {
         let mut vendor_file = NamedTempFile::new()?;
-        vendor_file.write_all(b"Vultr")?;
+        vendor_file.write_all(fixtures::vultr::VENDOR.as_bytes())?;
 
         let provider = Vultr;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(result);
 
@@ -168,10 +273,144 @@ mod tests {
         let vendor_file = NamedTempFile::new()?;
 
         let provider = Vultr;
-        let result = provider.check_vendor_file(vendor_file.path()).await;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_chassis_asset_tag_file_success() -> Result<()> {
+        let mut chassis_asset_tag_file = NamedTempFile::new()?;
+        chassis_asset_tag_file.write_all(fixtures::vultr::CHASSIS_ASSET_TAG.as_bytes())?;
+
+        let provider = Vultr;
+        let result = provider
+            .check_chassis_asset_tag_file(chassis_asset_tag_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_chassis_asset_tag_file_failure() -> Result<()> {
+        let chassis_asset_tag_file = NamedTempFile::new()?;
+
+        let provider = Vultr;
+        let result = provider
+            .check_chassis_asset_tag_file(chassis_asset_tag_file.path())
+            .run(Duration::from_secs(1))
+            .await;
 
         assert!(!result);
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_fetch_metadata() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.instance_id, "i-123abc");
+        assert_eq!(metadata.hostname.as_deref(), Some("vultr-guest"));
+        assert_eq!(metadata.plan.as_deref(), Some("vc2-1c-1gb"));
+        assert!(!metadata.is_bare_metal());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fetch_metadata_bare_metal() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA_BARE_METAL),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = metadata(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata.plan.as_deref(), Some("vbm-4c-32gb"));
+        assert!(metadata.is_bare_metal());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_metadata() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let metadata = metadata(&mock_server.uri())
+            .raw()
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(metadata["instanceid"], "i-123abc");
+        assert_eq!(metadata["region"]["regioncode"], "EWR");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "ewr");
+        assert_eq!(region.zone, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_instance_type() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(METADATA_PATH))
+            .respond_with(ResponseTemplate::new(200).set_body_string(fixtures::vultr::METADATA))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let instance_type = instance_type(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(instance_type, "vc2-1c-1gb");
+
+        Ok(())
+    }
 }
@@ -0,0 +1,271 @@
+//! Yandex Cloud.
+//!
+//! The metadata server of Yandex Cloud is compatible with that of GCP: it serves the same paths, and requires and echoes
+//! the same `Metadata-Flavor: Google` header. It doesn't identify itself as GCP's does, so GCP's checks don't match it,
+//! and matches of Yandex Cloud's own checks overrule GCP's anyway.
+
+use std::path::Path;
+
+use anyhow::Context;
+#[cfg(feature = "async")]
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::checks::{Check, Fetch, Request};
+#[cfg(feature = "async")]
+use crate::timeout_or_default;
+use crate::{Confidence, Provider, ProviderId, Region};
+
+pub(crate) const METADATA_URI: &str = "http://169.254.169.254";
+const IDENTITY_DOCUMENT_PATH: &str = "/computeMetadata/v1/instance/vendor/identity/document";
+const VENDOR_FILE: &str = "/sys/class/dmi/id/sys_vendor";
+pub(crate) const IDENTIFIER: ProviderId = ProviderId::Yandex;
+
+/// Represents the identity document of an instance.
+#[non_exhaustive]
+#[derive(Debug, Clone, Eq, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct IdentityDocument {
+    pub instance_id: String,
+    /// Availability zone of the instance (e.g. `ru-central1-a`).
+    pub availability_zone: Option<String>,
+    pub private_ip: Option<String>,
+    pub image_id: Option<String>,
+}
+
+pub(crate) struct Yandex;
+
+impl Provider for Yandex {
+    fn identifier(&self) -> ProviderId {
+        IDENTIFIER
+    }
+
+    /// Tries to identify Yandex Cloud using all the implemented options.
+    fn checks(&self) -> Vec<Check> {
+        vec![
+            self.check_vendor_file(VENDOR_FILE),
+            self.check_metadata_server(METADATA_URI),
+        ]
+    }
+
+    fn region(&self) -> Option<Fetch<Region>> {
+        Some(region(METADATA_URI))
+    }
+
+    fn raw_metadata(&self) -> Option<Fetch<Value>> {
+        Some(identity_document(METADATA_URI).raw())
+    }
+}
+
+impl Yandex {
+    /// Tries to identify Yandex Cloud via the identity document served by the metadata server, which GCP doesn't
+    /// serve.
+    pub(crate) fn check_metadata_server(&self, metadata_uri: &str) -> Check {
+        let request = identity_document_request(metadata_uri);
+
+        Check::http("metadata_server", Confidence::Verified, request, |resp| {
+            resp.json::<IdentityDocument>()
+                .is_some_and(|document| !document.instance_id.is_empty())
+        })
+    }
+
+    /// Tries to identify Yandex Cloud using vendor file(s).
+    pub(crate) fn check_vendor_file<P: AsRef<Path>>(&self, vendor_file: P) -> Check {
+        Check::dmi(
+            "vendor_file",
+            Confidence::Unverified,
+            vendor_file.as_ref(),
+            |content| content.contains("Yandex"),
+        )
+        .strong()
+    }
+}
+
+/// Request for the identity document of the instance, with the header required by the metadata server.
+fn identity_document_request(metadata_uri: &str) -> Request {
+    Request::get(metadata_uri, IDENTITY_DOCUMENT_PATH).header("Metadata-Flavor", "Google")
+}
+
+/// Fetch of the identity document of the instance.
+pub(crate) fn identity_document(metadata_uri: &str) -> Fetch<IdentityDocument> {
+    Fetch::json(vec![identity_document_request(metadata_uri)])
+}
+
+/// Fetch of the region and availability zone of the instance, from its identity document.
+///
+/// Only the availability zone is reported (e.g. `ru-central1-a`), and the region is the zone without its suffix.
+pub(crate) fn region(metadata_uri: &str) -> Fetch<Region> {
+    identity_document(metadata_uri).map(|document| {
+        let zone = document
+            .availability_zone
+            .context("Availability zone not available")?;
+        let (region, _) = zone
+            .rsplit_once('-')
+            .with_context(|| format!("Invalid availability zone {zone}"))?;
+
+        Ok(Region {
+            provider: IDENTIFIER,
+            region: region.to_string(),
+            zone: Some(zone.clone()),
+            partition: None,
+        })
+    })
+}
+
+/// Fetches the identity document of the instance from the metadata server.
+///
+/// # Arguments
+///
+/// * `timeout` - Maximum time (seconds) allowed for each request. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// ```no_run
+/// use cloud_detect::providers::yandex::fetch_identity_document;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let document = fetch_identity_document(None).await.unwrap();
+///     println!("Instance ID: {}", document.instance_id);
+/// }
+/// ```
+#[cfg(feature = "async")]
+pub async fn fetch_identity_document(timeout: Option<u64>) -> Result<IdentityDocument> {
+    identity_document(METADATA_URI)
+        .run(timeout_or_default(timeout))
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::time::Duration;
+
+    use anyhow::Result;
+    use tempfile::NamedTempFile;
+    use wiremock::matchers::{header, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[tokio::test]
+    async fn test_check_metadata_server_success() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .and(header("Metadata-Flavor", "Google"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::yandex::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Yandex;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+    }
+
+    #[tokio::test]
+    async fn test_check_metadata_server_failure() {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(fixtures::yandex::IDENTITY_DOCUMENT_MISMATCH),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let provider = Yandex;
+        let metadata_uri = mock_server.uri();
+        let result = provider
+            .check_metadata_server(&metadata_uri)
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_gcp_checks_mismatch() {
+        // The metadata server answers GCP's requests too, without identifying itself as GCP's
+        let mock_server = MockServer::start().await;
+        let template = fixtures::yandex::HEADERS
+            .iter()
+            .fold(ResponseTemplate::new(200), |template, &(name, value)| {
+                template.insert_header(name, value)
+            });
+        Mock::given(path("/computeMetadata/v1/instance/id"))
+            .respond_with(template.set_body_string(fixtures::yandex::INSTANCE_ID))
+            .mount(&mock_server)
+            .await;
+
+        let result = crate::providers::gcp::Gcp
+            .check_instance_id(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_success() -> Result<()> {
+        let mut vendor_file = NamedTempFile::new()?;
+        vendor_file.write_all(fixtures::yandex::VENDOR.as_bytes())?;
+
+        let provider = Yandex;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_check_vendor_file_failure() -> Result<()> {
+        let vendor_file = NamedTempFile::new()?;
+
+        let provider = Yandex;
+        let result = provider
+            .check_vendor_file(vendor_file.path())
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_region() -> Result<()> {
+        let mock_server = MockServer::start().await;
+        Mock::given(path(IDENTITY_DOCUMENT_PATH))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_string(fixtures::yandex::IDENTITY_DOCUMENT),
+            )
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        let region = region(&mock_server.uri())
+            .run(Duration::from_secs(1))
+            .await?;
+
+        assert_eq!(region.provider, IDENTIFIER);
+        assert_eq!(region.region, "ru-central1");
+        assert_eq!(region.zone.as_deref(), Some("ru-central1-a"));
+
+        Ok(())
+    }
+}
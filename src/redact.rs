@@ -0,0 +1,145 @@
+//! Redaction of metadata.
+//!
+//! Metadata servers return instance identifiers, addresses and session tokens, which end up in error messages, logs and
+//! diagnostics. They are masked by default, and redaction can be disabled with [set_redaction] for local debugging.
+
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// Minimum length of a word containing digits for it to be treated as an identifier or token.
+const IDENTIFIER_LEN: usize = 16;
+
+/// Well-known addresses of metadata servers, which identify nothing about the host.
+const METADATA_ADDRESSES: &[IpAddr] = &[
+    IpAddr::V4(Ipv4Addr::new(169, 254, 169, 254)),
+    IpAddr::V4(Ipv4Addr::new(100, 100, 100, 200)),
+];
+
+const REDACTED: &str = "[redacted]";
+
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+/// Enables or disables redaction of metadata in logs and diagnostics.
+///
+/// Redaction is enabled by default. Disabling it exposes instance identifiers, addresses and tokens, so only do so for
+/// local debugging.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::set_redaction;
+///
+/// set_redaction(false);
+/// ```
+pub fn set_redaction(enabled: bool) {
+    ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+/// Redacts metadata from text, unless redaction is disabled.
+pub(crate) fn redact(content: &str) -> String {
+    if ENABLED.load(Ordering::Relaxed) {
+        mask(content)
+    } else {
+        content.to_string()
+    }
+}
+
+/// Wraps a value so that it is redacted when formatted, e.g. in tracing output.
+pub(crate) struct Redacted<T>(pub(crate) T);
+
+impl<T: fmt::Display> fmt::Display for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&redact(&self.0.to_string()))
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Redacted<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&redact(&format!("{:?}", self.0)))
+    }
+}
+
+/// Masks IP addresses and identifier-like words (long words containing digits, such as instance IDs, MAC addresses
+/// and tokens).
+fn mask(content: &str) -> String {
+    let is_word =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':' | '+' | '=');
+    let mut masked = String::with_capacity(content.len());
+    let mut rest = content;
+
+    while let Some(start) = rest.find(is_word) {
+        masked.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let end = rest.find(|c: char| !is_word(c)).unwrap_or(rest.len());
+        // Punctuation ending a sentence or a key isn't part of the word
+        let word = rest[..end].trim_end_matches(['.', ':']);
+
+        if is_sensitive(word) {
+            masked.push_str(REDACTED);
+        } else {
+            masked.push_str(word);
+        }
+
+        masked.push_str(&rest[word.len()..end]);
+        rest = &rest[end..];
+    }
+
+    masked.push_str(rest);
+    masked
+}
+
+/// Whether a word looks like an address of the host or an identifier.
+fn is_sensitive(word: &str) -> bool {
+    let ip = word
+        .parse::<IpAddr>()
+        .or_else(|_| word.parse::<SocketAddr>().map(|addr| addr.ip()))
+        .ok();
+
+    match ip {
+        Some(ip) => !ip.is_loopback() && !METADATA_ADDRESSES.contains(&ip),
+        None => word.len() >= IDENTIFIER_LEN && word.chars().any(|c| c.is_ascii_digit()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mask() {
+        assert_eq!(
+            mask(r#"{"instanceId": "i-0123456789abcdef0", "privateIp": "10.0.0.4"}"#),
+            r#"{"instanceId": "[redacted]", "privateIp": "[redacted]"}"#
+        );
+        assert_eq!(mask("Amazon EC2\n"), "Amazon EC2\n");
+        assert_eq!(mask("2001:db8::1 ok"), "[redacted] ok");
+        assert_eq!(mask("Address: 10.0.0.4."), "Address: [redacted].");
+    }
+
+    #[test]
+    fn test_mask_urls() {
+        assert_eq!(
+            mask("http://169.254.169.254/latest/meta-data/network/interfaces/macs/0e:1f:2a:3b:4c:5d/vpc-id"),
+            "http://169.254.169.254/latest/meta-data/network/interfaces/macs/[redacted]/vpc-id"
+        );
+        assert_eq!(
+            mask("http://127.0.0.1:8080/metadata"),
+            "http://127.0.0.1:8080/metadata"
+        );
+        assert_eq!(
+            mask("http://10.0.0.1:8080/metadata"),
+            "http://[redacted]/metadata"
+        );
+    }
+
+    #[test]
+    fn test_redacted() {
+        assert_eq!(
+            Redacted("token AQAEAFTNrA4eEGx0AQgJ1arIq_Cc").to_string(),
+            "token [redacted]"
+        );
+        assert_eq!(format!("{:?}", Redacted("10.0.0.4")), r#""[redacted]""#);
+    }
+}
@@ -0,0 +1,153 @@
+//! Retries of transient failures.
+//!
+//! Busy metadata servers throttle requests (e.g. AWS IMDS responds with `429 Too Many Requests`) or briefly fail with a
+//! 5xx status code. Both executors retry such responses a bounded number of times, backing off exponentially with full
+//! jitter so that concurrent probes don't retry in lockstep. A delay requested by the metadata server with the
+//! `Retry-After` header (in seconds) is honored instead. Retries never outlast the timeout of the request: if the next
+//! attempt couldn't start before the timeout elapses, the last response is returned instead.
+//!
+//! Errors (e.g. an unreachable metadata server or a timed out request) are not retried, as they are how detection
+//! learns that a provider is absent.
+
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::Duration;
+
+use http::header::{HeaderMap, RETRY_AFTER};
+
+use crate::checks::Response;
+
+/// Maximum number of attempts of a request, including the first one.
+pub(crate) const MAX_ATTEMPTS: u32 = 3;
+
+/// Upper bound of the delay before the first retry.
+const BASE_DELAY: Duration = Duration::from_millis(50);
+
+/// Upper bound of the delay before any retry.
+const MAX_DELAY: Duration = Duration::from_millis(500);
+
+/// Whether a status code denotes a transient failure, worth retrying.
+pub(crate) fn is_transient(status: u16) -> bool {
+    matches!(status, 429 | 500 | 502 | 503 | 504)
+}
+
+/// Returns the delay before retrying a request, or `None` if it shouldn't be retried.
+///
+/// # Arguments
+///
+/// * `resp` - The response to the last attempt.
+/// * `attempt` - The number of attempts made so far.
+/// * `elapsed` - The time elapsed since the first attempt.
+/// * `timeout` - Maximum time allowed for the request.
+pub(crate) fn delay(
+    resp: &Response,
+    attempt: u32,
+    elapsed: Duration,
+    timeout: Duration,
+) -> Option<Duration> {
+    if !is_transient(resp.status) || attempt >= MAX_ATTEMPTS {
+        return None;
+    }
+
+    let delay = resp.retry_after.unwrap_or_else(|| backoff(attempt));
+    (elapsed + delay < timeout).then_some(delay)
+}
+
+/// Returns the delay requested by the `Retry-After` header of a response, if any.
+///
+/// Only delays in seconds are supported, as metadata servers don't send dates.
+pub(crate) fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(RETRY_AFTER)?.to_str().ok()?;
+
+    value.trim().parse().ok().map(Duration::from_secs)
+}
+
+/// Returns a random delay, up to a bound doubling with every attempt (full jitter).
+fn backoff(attempt: u32) -> Duration {
+    let bound = BASE_DELAY
+        .saturating_mul(1 << attempt.saturating_sub(1).min(16))
+        .min(MAX_DELAY);
+
+    bound.mul_f64(jitter())
+}
+
+/// Returns a random number in `[0, 1]`.
+///
+/// Every [RandomState] is randomly seeded, which is good enough for jitter without depending on a random number
+/// generator.
+fn jitter() -> f64 {
+    RandomState::new().build_hasher().finish() as f64 / u64::MAX as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16) -> Response {
+        Response {
+            status,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(is_transient(429));
+        assert!(is_transient(503));
+        assert!(!is_transient(200));
+        assert!(!is_transient(401));
+        assert!(!is_transient(404));
+        assert!(!is_transient(501));
+    }
+
+    #[test]
+    fn test_backoff() {
+        for attempt in 1..=20 {
+            assert!(backoff(attempt) <= MAX_DELAY);
+        }
+        assert!(backoff(1) <= BASE_DELAY);
+    }
+
+    #[test]
+    fn test_delay() {
+        let timeout = Duration::from_secs(1);
+
+        assert!(delay(&response(429), 1, Duration::ZERO, timeout).is_some());
+        assert!(delay(&response(503), 2, Duration::ZERO, timeout).is_some());
+        assert!(delay(&response(200), 1, Duration::ZERO, timeout).is_none());
+        assert!(delay(&response(404), 1, Duration::ZERO, timeout).is_none());
+        assert!(delay(&response(429), MAX_ATTEMPTS, Duration::ZERO, timeout).is_none());
+        assert!(delay(&response(429), 1, timeout, timeout).is_none());
+    }
+
+    #[test]
+    fn test_delay_retry_after() {
+        let timeout = Duration::from_secs(5);
+        let resp = Response {
+            status: 503,
+            retry_after: Some(Duration::from_secs(2)),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            delay(&resp, 1, Duration::ZERO, timeout),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(delay(&resp, 1, Duration::from_secs(3), timeout), None);
+    }
+
+    #[test]
+    fn test_retry_after() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(retry_after(&headers), None);
+
+        headers.insert(RETRY_AFTER, "3".parse().unwrap());
+        assert_eq!(retry_after(&headers), Some(Duration::from_secs(3)));
+
+        headers.insert(
+            RETRY_AFTER,
+            "Wed, 21 Oct 2015 07:28:00 GMT".parse().unwrap(),
+        );
+        assert_eq!(retry_after(&headers), None);
+    }
+}
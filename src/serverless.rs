@@ -0,0 +1,154 @@
+//! Identification of serverless runtimes.
+//!
+//! Serverless functions and the sandboxes of managed application platforms (e.g. Azure App Service) expose neither DMI
+//! attributes nor (for most providers) a metadata server, but their runtimes reliably set environment variables naming
+//! the function or application. These identify both the provider and the platform.
+
+use crate::checks::Check;
+use crate::{Confidence, Platform, ProviderId};
+
+/// Environment variables set by the serverless runtimes, with the platform each identifies.
+///
+/// Ordered by precedence, as some runtimes are built on others (e.g. Cloud Functions also sets `K_SERVICE`, as it runs
+/// on Cloud Run, and Azure Functions sets `WEBSITE_SITE_NAME`, as it runs on App Service).
+const RUNTIMES: [(&str, Platform); 9] = [
+    ("AWS_LAMBDA_FUNCTION_NAME", Platform::Lambda),
+    ("FUNCTIONS_WORKER_RUNTIME", Platform::AzureFunctions),
+    ("CONTAINER_APP_NAME", Platform::ContainerApps),
+    ("WEBSITE_INSTANCE_ID", Platform::AppService),
+    ("WEBSITE_SITE_NAME", Platform::AppService),
+    ("FUNCTION_TARGET", Platform::CloudFunctions),
+    ("K_SERVICE", Platform::CloudRun),
+    ("CLOUD_RUN_JOB", Platform::CloudRun),
+    ("GAE_ENV", Platform::AppEngine),
+];
+
+/// Returns the checks identifying the serverless runtimes of a provider, one for each environment variable.
+pub(crate) fn checks(provider: ProviderId) -> Vec<Check> {
+    RUNTIMES
+        .iter()
+        .filter(|(_, platform)| platform.provider() == provider)
+        .map(|(var, _)| check(var))
+        .collect()
+}
+
+/// Tries to identify a serverless runtime using an environment variable it sets.
+pub(crate) fn check(var: &'static str) -> Check {
+    Check::env("serverless_env", Confidence::Unverified, var, |value| {
+        !value.is_empty()
+    })
+}
+
+/// Classifies the serverless runtime of a provider a host runs in, from its environment variables.
+pub(crate) fn classify_with<F: Fn(&str) -> Option<String>>(
+    provider: ProviderId,
+    var: F,
+) -> Option<Platform> {
+    RUNTIMES
+        .iter()
+        .filter(|(_, platform)| platform.provider() == provider)
+        .find(|(name, _)| var(name).is_some_and(|value| !value.is_empty()))
+        .map(|&(_, platform)| platform)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_check() {
+        std::env::set_var("CLOUD_DETECT_TEST_SERVERLESS_ENV", "hello");
+
+        let result = check("CLOUD_DETECT_TEST_SERVERLESS_ENV")
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(result);
+
+        let result = check("CLOUD_DETECT_TEST_SERVERLESS_ENV_UNSET")
+            .run(Duration::from_secs(1))
+            .await;
+
+        assert!(!result);
+    }
+
+    #[test]
+    fn test_checks() {
+        for &provider in crate::supported_providers() {
+            for check in checks(provider) {
+                assert!(check.is_local(), "{provider}");
+            }
+        }
+
+        assert_eq!(checks(ProviderId::AWS).len(), 1);
+        assert_eq!(checks(ProviderId::Azure).len(), 4);
+        assert_eq!(checks(ProviderId::GCP).len(), 4);
+        assert!(checks(ProviderId::DigitalOcean).is_empty());
+    }
+
+    #[test]
+    fn test_classify_with() {
+        let env = |vars: &'static [(&str, &str)]| {
+            move |name: &str| {
+                vars.iter()
+                    .find(|(n, _)| *n == name)
+                    .map(|(_, value)| value.to_string())
+            }
+        };
+
+        let cases = [
+            (
+                ProviderId::AWS,
+                &[("AWS_LAMBDA_FUNCTION_NAME", "hello")][..],
+                Some(Platform::Lambda),
+            ),
+            (
+                ProviderId::Azure,
+                &[("FUNCTIONS_WORKER_RUNTIME", "node")][..],
+                Some(Platform::AzureFunctions),
+            ),
+            (
+                ProviderId::Azure,
+                &[("WEBSITE_SITE_NAME", "hello")][..],
+                Some(Platform::AppService),
+            ),
+            (
+                ProviderId::Azure,
+                &[("CONTAINER_APP_NAME", "hello")][..],
+                Some(Platform::ContainerApps),
+            ),
+            (
+                ProviderId::Azure,
+                &[
+                    ("FUNCTIONS_WORKER_RUNTIME", "node"),
+                    ("WEBSITE_SITE_NAME", "hello"),
+                ][..],
+                Some(Platform::AzureFunctions),
+            ),
+            (
+                ProviderId::GCP,
+                &[("FUNCTION_TARGET", "hello"), ("K_SERVICE", "hello")][..],
+                Some(Platform::CloudFunctions),
+            ),
+            // Variables of other providers' runtimes
+            (
+                ProviderId::Azure,
+                &[("AWS_LAMBDA_FUNCTION_NAME", "hello")][..],
+                None,
+            ),
+            // Unset or empty variables
+            (
+                ProviderId::AWS,
+                &[("AWS_LAMBDA_FUNCTION_NAME", "")][..],
+                None,
+            ),
+            (ProviderId::AWS, &[][..], None),
+        ];
+
+        for (provider, vars, platform) in cases {
+            assert_eq!(classify_with(provider, env(vars)), platform, "{vars:?}");
+        }
+    }
+}
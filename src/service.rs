@@ -0,0 +1,115 @@
+//! Detection as a [tower](https://crates.io/crates/tower) service.
+//!
+//! Services exposing the host's cloud provider (e.g. on an admin endpoint) can compose detection with tower layers,
+//! such as caching, rate limiting or timeouts. Dropping the response future (e.g. once a timeout layer gives up on it)
+//! aborts the detection.
+
+use std::convert::Infallible;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use tower_service::Service;
+
+use crate::{Detection, Detector, ProviderId};
+
+/// Represents a request for detection, answered by a [DetectService].
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct DetectRequest {
+    /// Providers to probe instead of those of the detector, if any (see [Detector::providers]).
+    pub providers: Option<Vec<ProviderId>>,
+}
+
+impl DetectRequest {
+    /// Creates a request probing the providers of the detector.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Restricts the detection to the given providers, instead of those of the detector.
+    pub fn providers(mut self, providers: &[ProviderId]) -> Self {
+        self.providers = Some(providers.to_vec());
+        self
+    }
+}
+
+/// A [Service] detecting the host's cloud provider with a [Detector], responding with the [Detection].
+///
+/// Detection never fails (it resolves to [ProviderId::Unknown] instead), and the service is always ready.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::{DetectRequest, DetectService, Detector};
+/// use tower_service::Service;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut service = DetectService::new(Detector::new().timeout(1));
+///
+///     let detection = service.call(DetectRequest::new()).await.unwrap();
+///     println!("Detected provider: {}", detection.provider);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct DetectService {
+    detector: Detector,
+}
+
+impl DetectService {
+    /// Creates a service detecting with the given detector.
+    pub fn new(detector: Detector) -> Self {
+        Self { detector }
+    }
+}
+
+impl Service<DetectRequest> for DetectService {
+    type Error = Infallible;
+    type Future = Pin<Box<dyn Future<Output = Result<Detection, Infallible>> + Send>>;
+    type Response = Detection;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: DetectRequest) -> Self::Future {
+        let detector = match &request.providers {
+            Some(providers) => self.detector.clone().providers(providers),
+            None => self.detector.clone(),
+        };
+
+        Box::pin(async move { Ok(detector.detect_detailed().await) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_call() {
+        let mut service = DetectService::new(Detector::new().providers(&[]));
+
+        let detection = service.call(DetectRequest::new()).await.unwrap();
+
+        assert_eq!(detection.provider, ProviderId::Unknown);
+        assert!(detection.evidence.is_empty());
+    }
+
+    #[cfg(feature = "test_support")]
+    #[tokio::test]
+    async fn test_call_providers() {
+        use crate::test_support::FakeProvider;
+
+        let detector = Detector::new()
+            .fake_provider(FakeProvider::matching(ProviderId::AWS))
+            .fake_provider(FakeProvider::matching(ProviderId::Azure));
+        let mut service = DetectService::new(detector);
+
+        let request = DetectRequest::new().providers(&[ProviderId::Azure]);
+        let detection = service.call(request).await.unwrap();
+
+        assert_eq!(detection.provider, ProviderId::Azure);
+    }
+}
@@ -0,0 +1,451 @@
+//! Simulated cloud environments.
+//!
+//! An [Environment] stands up everything a provider exposes to its hosts: a temporary root directory with the
+//! provider's DMI attributes, and a local metadata server (backed by [wiremock]) serving the provider's metadata. A
+//! [Detector] probing the environment runs the real checks of every provider end to end, over real files and HTTP,
+//! only with paths and metadata server addresses moved into the environment. Signals read from elsewhere on the host,
+//! such as cloud-init and environment variables, are ignored.
+//!
+//! # Examples
+//!
+//! ```
+//! use cloud_detect::simulation::Environment;
+//! use cloud_detect::ProviderId;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let environment = Environment::start(ProviderId::Azure).await.unwrap();
+//!     let provider = environment.detector().timeout(1).detect().await;
+//!
+//!     assert_eq!(provider, ProviderId::Azure);
+//! }
+//! ```
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use tempfile::TempDir;
+use wiremock::matchers::path;
+use wiremock::{Mock, MockServer, ResponseTemplate};
+
+use crate::checks::{Check, Probe};
+use crate::provider_fixtures as fixtures;
+use crate::{host_root, Detector, ProviderId, P};
+
+/// Represents a simulated cloud environment of a provider.
+///
+/// The environment is torn down (its metadata server stopped and its root directory removed) when dropped.
+pub struct Environment {
+    provider: ProviderId,
+    root: TempDir,
+    server: MockServer,
+}
+
+impl Environment {
+    /// Stands up a simulated environment of a provider.
+    ///
+    /// Simulating [ProviderId::Unknown] (or a custom provider) stands up an empty environment, in which no provider
+    /// should be detected. Simulating a white-label cloud built on OpenStack (e.g. [ProviderId::HuaweiCloud]) stands up
+    /// an OpenStack environment tagged with the cloud's chassis asset tag, detected as OpenStack resold by that cloud
+    /// (see [Detection::reseller](crate::Detection#structfield.reseller)).
+    pub async fn start(provider: ProviderId) -> Result<Self> {
+        let root = TempDir::new().context("Error creating root directory")?;
+        let server = MockServer::start().await;
+
+        for &(file, content) in files(provider) {
+            let file = host_root::host_path(root.path(), file);
+
+            fs::create_dir_all(file.parent().context("File has no parent directory")?)?;
+            fs::write(&file, content)
+                .with_context(|| format!("Error writing {}", file.display()))?;
+        }
+
+        // Requests are answered whatever their method (session tokens are retrieved with PUT requests) and query
+        for &(endpoint, body) in responses(provider) {
+            let template = headers(provider)
+                .iter()
+                .fold(ResponseTemplate::new(200), |template, &(name, value)| {
+                    template.insert_header(name, value)
+                });
+
+            Mock::given(path(endpoint))
+                .respond_with(template.set_body_string(body))
+                .mount(&server)
+                .await;
+        }
+
+        Ok(Self {
+            provider,
+            root,
+            server,
+        })
+    }
+
+    /// Returns the simulated provider.
+    pub fn provider(&self) -> ProviderId {
+        self.provider
+    }
+
+    /// Returns the root directory the DMI attributes of the environment are written under.
+    pub fn root(&self) -> &Path {
+        self.root.path()
+    }
+
+    /// Returns the address of the metadata server of the environment.
+    pub fn metadata_uri(&self) -> String {
+        self.server.uri()
+    }
+
+    /// Returns a detector probing the environment instead of the host.
+    pub fn detector(&self) -> Detector {
+        Detector {
+            host: Some(SimulatedHost {
+                root: self.root().to_path_buf(),
+                metadata_uri: self.metadata_uri(),
+            }),
+            ..Detector::new()
+        }
+    }
+}
+
+/// Represents where the checks of a detector probing a simulated environment are moved to.
+#[derive(Debug, Clone)]
+pub(crate) struct SimulatedHost {
+    root: PathBuf,
+    metadata_uri: String,
+}
+
+impl SimulatedHost {
+    /// Returns the checks of a provider, with files and metadata server requests moved into the environment.
+    pub(crate) fn checks(&self, provider: P) -> Vec<Check> {
+        provider
+            .checks()
+            .into_iter()
+            .filter_map(|check| match check.probe {
+                Probe::Env { .. } | Probe::Cpuid { .. } => None,
+                Probe::Http {
+                    mut request,
+                    matches,
+                } => {
+                    request.base_uri = self.metadata_uri.clone();
+                    Some(Check {
+                        probe: Probe::Http { request, matches },
+                        ..check
+                    })
+                }
+                _ => Some(host_root::rebase(check, &self.root)),
+            })
+            .collect()
+    }
+}
+
+/// DMI attributes set by the hypervisor of each provider.
+fn files(provider: ProviderId) -> &'static [(&'static str, &'static str)] {
+    match provider {
+        ProviderId::Akamai => &[("/sys/class/dmi/id/sys_vendor", fixtures::akamai::VENDOR)],
+        ProviderId::Alibaba => &[("/sys/class/dmi/id/product_name", fixtures::alibaba::VENDOR)],
+        ProviderId::AWS => &[("/sys/class/dmi/id/bios_vendor", fixtures::aws::VENDOR)],
+        ProviderId::Azure => &[("/sys/class/dmi/id/sys_vendor", fixtures::azure::VENDOR)],
+        ProviderId::Civo => &[("/sys/class/dmi/id/sys_vendor", fixtures::civo::VENDOR)],
+        ProviderId::DigitalOcean => &[(
+            "/sys/class/dmi/id/sys_vendor",
+            fixtures::digitalocean::VENDOR,
+        )],
+        ProviderId::GCP => &[("/sys/class/dmi/id/product_name", fixtures::gcp::VENDOR)],
+        ProviderId::Naver => &[("/sys/class/dmi/id/sys_vendor", fixtures::naver::VENDOR)],
+        ProviderId::Nhn => &[(
+            "/sys/class/dmi/id/product_name",
+            fixtures::openstack::PRODUCT_NAME,
+        )],
+        ProviderId::OCI => &[("/sys/class/dmi/id/chassis_asset_tag", fixtures::oci::VENDOR)],
+        ProviderId::OpenStack => &[(
+            "/sys/class/dmi/id/product_name",
+            fixtures::openstack::PRODUCT_NAME,
+        )],
+        ProviderId::HuaweiCloud => &[
+            (
+                "/sys/class/dmi/id/product_name",
+                fixtures::openstack::PRODUCT_NAME,
+            ),
+            (
+                "/sys/class/dmi/id/chassis_asset_tag",
+                fixtures::openstack::CHASSIS_ASSET_TAG,
+            ),
+        ],
+        ProviderId::OpenTelekom => &[
+            (
+                "/sys/class/dmi/id/product_name",
+                fixtures::openstack::PRODUCT_NAME,
+            ),
+            (
+                "/sys/class/dmi/id/chassis_asset_tag",
+                fixtures::openstack::OPEN_TELEKOM_CHASSIS_ASSET_TAG,
+            ),
+        ],
+        ProviderId::SapCCloud => &[
+            (
+                "/sys/class/dmi/id/product_name",
+                fixtures::openstack::PRODUCT_NAME,
+            ),
+            (
+                "/sys/class/dmi/id/chassis_asset_tag",
+                fixtures::openstack::SAP_CCLOUD_CHASSIS_ASSET_TAG,
+            ),
+        ],
+        ProviderId::Vultr => &[("/sys/class/dmi/id/sys_vendor", fixtures::vultr::VENDOR)],
+        ProviderId::VkCloud => &[(
+            "/sys/class/dmi/id/product_name",
+            fixtures::openstack::PRODUCT_NAME,
+        )],
+        ProviderId::Yandex => &[("/sys/class/dmi/id/sys_vendor", fixtures::yandex::VENDOR)],
+        ProviderId::VMware => &[("/sys/class/dmi/id/sys_vendor", fixtures::vmware::VENDOR)],
+        ProviderId::Proxmox => &[(
+            "/sys/class/dmi/id/bios_vendor",
+            fixtures::proxmox::BIOS_VENDOR,
+        )],
+        ProviderId::Nutanix => &[("/sys/class/dmi/id/sys_vendor", fixtures::nutanix::VENDOR)],
+        ProviderId::KVM => &[("/sys/class/dmi/id/sys_vendor", fixtures::kvm::QEMU_VENDOR)],
+        ProviderId::Unknown | ProviderId::Custom(_) => &[],
+    }
+}
+
+/// Headers set by the metadata server of each provider on every response.
+fn headers(provider: ProviderId) -> &'static [(&'static str, &'static str)] {
+    match provider {
+        ProviderId::GCP => fixtures::gcp::HEADERS,
+        ProviderId::Yandex => fixtures::yandex::HEADERS,
+        _ => &[],
+    }
+}
+
+/// Metadata served by the metadata server of each provider, by path.
+fn responses(provider: ProviderId) -> &'static [(&'static str, &'static str)] {
+    match provider {
+        ProviderId::Akamai => &[
+            ("/v1/token", fixtures::akamai::TOKEN),
+            ("/v1/instance", fixtures::akamai::METADATA),
+        ],
+        ProviderId::Alibaba => &[
+            ("/latest/api/token", fixtures::alibaba::TOKEN),
+            (
+                "/latest/meta-data/latest/meta-data/instance/virtualization-solution",
+                fixtures::alibaba::METADATA,
+            ),
+            (
+                "/latest/dynamic/instance-identity/document",
+                fixtures::alibaba::IDENTITY_DOCUMENT,
+            ),
+        ],
+        ProviderId::AWS => &[
+            ("/latest/api/token", fixtures::aws::TOKEN),
+            (
+                "/latest/dynamic/instance-identity/document",
+                fixtures::aws::IDENTITY_DOCUMENT,
+            ),
+            ("/latest/meta-data/mac", fixtures::aws::MAC),
+        ],
+        ProviderId::Azure => &[("/metadata/instance", fixtures::azure::METADATA)],
+        ProviderId::DigitalOcean => &[
+            ("/metadata/v1.json", fixtures::digitalocean::METADATA),
+            ("/metadata/v1/id", fixtures::digitalocean::ID),
+        ],
+        ProviderId::GCP => &[
+            ("/computeMetadata/v1/instance/tags", "[]"),
+            (
+                "/computeMetadata/v1/instance/id",
+                fixtures::gcp::INSTANCE_ID,
+            ),
+            ("/computeMetadata/v1/instance/zone", fixtures::gcp::ZONE),
+            (
+                "/computeMetadata/v1/instance/machine-type",
+                fixtures::gcp::MACHINE_TYPE,
+            ),
+            (
+                "/computeMetadata/v1/project/project-id",
+                fixtures::gcp::PROJECT_ID,
+            ),
+        ],
+        ProviderId::Naver => &[(
+            "/latest/meta-data/instance-id",
+            fixtures::naver::INSTANCE_ID,
+        )],
+        ProviderId::Nhn => &[("/openstack/latest/meta_data.json", fixtures::nhn::METADATA)],
+        ProviderId::OCI => &[
+            ("/opc/v2/instance/", fixtures::oci::METADATA_V2),
+            ("/opc/v1/instance/metadata/", fixtures::oci::METADATA_V1),
+        ],
+        ProviderId::OpenStack
+        | ProviderId::HuaweiCloud
+        | ProviderId::OpenTelekom
+        | ProviderId::SapCCloud => &[(
+            "/openstack/latest/meta_data.json",
+            fixtures::openstack::CONFIG_DRIVE_METADATA,
+        )],
+        ProviderId::Vultr => &[("/v1.json", fixtures::vultr::METADATA)],
+        ProviderId::VkCloud => &[(
+            "/openstack/latest/meta_data.json",
+            fixtures::vkcloud::METADATA,
+        )],
+        // The metadata server is compatible with GCP's
+        ProviderId::Yandex => &[
+            (
+                "/computeMetadata/v1/instance/vendor/identity/document",
+                fixtures::yandex::IDENTITY_DOCUMENT,
+            ),
+            ("/computeMetadata/v1/instance/tags", "[]"),
+            (
+                "/computeMetadata/v1/instance/id",
+                fixtures::yandex::INSTANCE_ID,
+            ),
+        ],
+        ProviderId::Civo
+        | ProviderId::VMware
+        | ProviderId::Proxmox
+        | ProviderId::Nutanix
+        | ProviderId::KVM
+        | ProviderId::Unknown
+        | ProviderId::Custom(_) => &[],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{supported_providers, ProviderGroup};
+
+    #[tokio::test]
+    async fn test_detect() -> Result<()> {
+        for &provider in supported_providers() {
+            let environment = Environment::start(provider).await?;
+            let detected = environment.detector().timeout(1).detect().await;
+
+            assert_eq!(detected, provider);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_unknown() -> Result<()> {
+        let environment = Environment::start(ProviderId::Unknown).await?;
+        let detected = environment.detector().timeout(1).detect().await;
+
+        assert_eq!(detected, ProviderId::Unknown);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_gcp_compatible() -> Result<()> {
+        // Yandex Cloud's metadata server answers GCP's requests, but doesn't identify itself as GCP's
+        let environment = Environment::start(ProviderId::Yandex).await?;
+        let detection = environment
+            .detector()
+            .timeout(1)
+            .thorough()
+            .detect_detailed()
+            .await;
+
+        assert_eq!(detection.provider, ProviderId::Yandex);
+        assert!(detection
+            .evidence
+            .iter()
+            .all(|evidence| evidence.provider != ProviderId::GCP));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_reseller() -> Result<()> {
+        for reseller in [
+            ProviderId::HuaweiCloud,
+            ProviderId::OpenTelekom,
+            ProviderId::SapCCloud,
+        ] {
+            let environment = Environment::start(reseller).await?;
+            let detection = environment.detector().timeout(1).detect_detailed().await;
+
+            assert_eq!(detection.provider, ProviderId::OpenStack);
+            assert_eq!(detection.reseller, Some(reseller));
+        }
+
+        let environment = Environment::start(ProviderId::OpenStack).await?;
+        let detection = environment.detector().timeout(1).detect_detailed().await;
+        assert_eq!(detection.reseller, None);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_detect_fast_exit() -> Result<()> {
+        let environment = Environment::start(ProviderId::DigitalOcean).await?;
+
+        // The DMI vendor alone identifies the provider, without querying any metadata server
+        let detection = environment.detector().timeout(1).detect_detailed().await;
+        assert_eq!(detection.provider, ProviderId::DigitalOcean);
+        assert_eq!(detection.evidence[0].source, "vendor_file");
+        let requests = environment
+            .server
+            .received_requests()
+            .await
+            .unwrap_or_default();
+        assert!(requests.is_empty());
+
+        let detected = environment.detector().timeout(1).thorough().detect().await;
+        assert_eq!(detected, ProviderId::DigitalOcean);
+        let requests = environment
+            .server
+            .received_requests()
+            .await
+            .unwrap_or_default();
+        assert!(!requests.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_cloud() -> Result<()> {
+        for &provider in supported_providers() {
+            let environment = Environment::start(provider).await?;
+            let on_cloud = environment.detector().timeout(1).is_cloud().await;
+
+            assert_eq!(
+                on_cloud,
+                provider.group() == Some(ProviderGroup::Cloud),
+                "{provider}"
+            );
+        }
+
+        let environment = Environment::start(ProviderId::Unknown).await?;
+        assert!(!environment.detector().timeout(1).is_cloud().await);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_is_provider() -> Result<()> {
+        for &provider in supported_providers() {
+            let environment = Environment::start(provider).await?;
+            let verdict = environment
+                .detector()
+                .timeout(1)
+                .is_provider(provider)
+                .await;
+
+            assert!(verdict.matched, "{provider}");
+        }
+
+        // Yandex Cloud's metadata server answers GCP's requests, which its own checks prove wrong
+        let environment = Environment::start(ProviderId::Yandex).await?;
+        let verdict = environment
+            .detector()
+            .timeout(1)
+            .thorough()
+            .is_provider(ProviderId::GCP)
+            .await;
+        assert!(!verdict.matched);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,438 @@
+//! SMBIOS/DMI data.
+//!
+//! Providers read vendor information from the DMI attributes exposed under `/sys/class/dmi/id`. Minimal containers may
+//! lack those attributes yet still expose the raw SMBIOS tables they are decoded from (`/sys/firmware/dmi/tables/DMI`),
+//! which are parsed directly as a first fallback. arm64 hosts booted from a device tree rather than ACPI/SMBIOS have no
+//! DMI attributes, but carry equivalent vendor information in the device tree's `model` and `compatible` properties,
+//! which are used as a last fallback.
+//!
+//! The fallbacks are read from the same root directory as the attributes, so that a host mounted under a directory of
+//! a container (see [Detector::host_root](crate::Detector::host_root)) is inspected as a whole.
+//!
+//! Several providers match the same attributes (e.g. `sys_vendor`), so a detection reads them all once into
+//! [SmbiosData], which the checks of every provider are matched against.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use tracing::{debug, error};
+
+const ROOT: &str = "/";
+const DMI_DIR: &str = "sys/class/dmi/id";
+const TABLES_FILE: &str = "/sys/firmware/dmi/tables/DMI";
+const DEVICE_TREE_DIR: &str = "/proc/device-tree";
+const DEVICE_TREE_FILES: [&str; 3] = ["model", "compatible", "hypervisor/compatible"];
+
+/// Location of each DMI attribute in the raw SMBIOS tables: the type of the structure holding it, and the offset of its
+/// string number in the structure (see the SMBIOS reference specification).
+const TABLE_FIELDS: [(&str, u8, usize); 9] = [
+    ("bios_vendor", 0, 0x04),
+    ("bios_version", 0, 0x05),
+    ("sys_vendor", 1, 0x04),
+    ("product_name", 1, 0x05),
+    ("product_version", 1, 0x06),
+    ("board_vendor", 2, 0x04),
+    ("board_name", 2, 0x05),
+    ("chassis_vendor", 3, 0x04),
+    ("chassis_asset_tag", 3, 0x08),
+];
+
+/// Type of the structure marking the end of the SMBIOS tables.
+const END_OF_TABLE: u8 = 127;
+
+/// Reads a DMI attribute file, falling back to the raw SMBIOS tables, then to the device tree, if the file does not
+/// exist.
+pub(crate) fn read_field<P: AsRef<Path>>(dmi_file: P) -> Option<String> {
+    let root = root_of(dmi_file.as_ref());
+    read_field_from(
+        &dmi_file,
+        under(root, TABLES_FILE),
+        under(root, DEVICE_TREE_DIR),
+    )
+}
+
+/// Returns the root directory a DMI attribute file is read from (e.g. `/host` for `/host/sys/class/dmi/id/sys_vendor`).
+fn root_of(dmi_file: &Path) -> &Path {
+    dmi_file
+        .parent()
+        .filter(|dir| dir.ends_with(DMI_DIR))
+        .and_then(|dir| dir.ancestors().nth(4))
+        .unwrap_or(Path::new(ROOT))
+}
+
+/// Moves a path of the host under a root directory.
+fn under(root: &Path, path: &str) -> PathBuf {
+    root.join(path.trim_start_matches('/'))
+}
+
+fn read_field_from<P: AsRef<Path>, Q: AsRef<Path>, R: AsRef<Path>>(
+    dmi_file: P,
+    tables_file: Q,
+    device_tree_dir: R,
+) -> Option<String> {
+    if dmi_file.as_ref().is_file() {
+        return read_dmi_file(dmi_file);
+    }
+
+    read_tables(tables_file)
+        .and_then(|tables| table_field(&tables, dmi_file.as_ref()))
+        .or_else(|| read_device_tree(device_tree_dir))
+}
+
+fn read_dmi_file<P: AsRef<Path>>(dmi_file: P) -> Option<String> {
+    match fs::read_to_string(dmi_file) {
+        Ok(content) => Some(content),
+        Err(err) => {
+            error!("Error reading file: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Represents the DMI attributes of the host, each read once and shared by the checks of every provider.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct SmbiosData {
+    fields: Arc<HashMap<PathBuf, Option<String>>>,
+}
+
+impl SmbiosData {
+    /// Reads the given DMI attribute files, each once, falling back to the raw SMBIOS tables and the device tree (also
+    /// read once, from the root directory of the first file) for those that do not exist.
+    pub(crate) fn read<I, P>(dmi_files: I) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        let dmi_files: Vec<P> = dmi_files.into_iter().collect();
+        let root = dmi_files
+            .first()
+            .map_or(Path::new(ROOT), |dmi_file| root_of(dmi_file.as_ref()));
+
+        Self::read_from(
+            &dmi_files,
+            under(root, TABLES_FILE),
+            under(root, DEVICE_TREE_DIR),
+        )
+    }
+
+    fn read_from<I, P, Q, R>(dmi_files: I, tables_file: Q, device_tree_dir: R) -> Self
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+        R: AsRef<Path>,
+    {
+        let mut fields = HashMap::new();
+        let mut tables = None;
+        let mut device_tree = None;
+
+        for dmi_file in dmi_files {
+            let dmi_file = dmi_file.as_ref();
+
+            if fields.contains_key(dmi_file) {
+                continue;
+            }
+
+            let content = if dmi_file.is_file() {
+                read_dmi_file(dmi_file)
+            } else {
+                tables
+                    .get_or_insert_with(|| read_tables(&tables_file))
+                    .as_deref()
+                    .and_then(|tables| table_field(tables, dmi_file))
+                    .or_else(|| {
+                        device_tree
+                            .get_or_insert_with(|| read_device_tree(&device_tree_dir))
+                            .clone()
+                    })
+            };
+
+            fields.insert(dmi_file.to_path_buf(), content);
+        }
+
+        Self {
+            fields: Arc::new(fields),
+        }
+    }
+
+    /// Returns the content of a DMI attribute file, read from the host if it wasn't read already.
+    pub(crate) fn field(&self, dmi_file: &Path) -> Option<String> {
+        match self.fields.get(dmi_file) {
+            Some(content) => content.clone(),
+            None => read_field(dmi_file),
+        }
+    }
+
+    /// Number of DMI attribute files read.
+    pub(crate) fn len(&self) -> usize {
+        self.fields.len()
+    }
+}
+
+/// Reads the raw SMBIOS tables, if present.
+fn read_tables<P: AsRef<Path>>(tables_file: P) -> Option<Vec<u8>> {
+    if !tables_file.as_ref().is_file() {
+        return None;
+    }
+
+    debug!("Checking SMBIOS tables: {}", tables_file.as_ref().display());
+
+    match fs::read(tables_file) {
+        Ok(tables) => Some(tables),
+        Err(err) => {
+            error!("Error reading file: {:?}", err);
+            None
+        }
+    }
+}
+
+/// Returns the value of a DMI attribute, named by its file, from the raw SMBIOS tables.
+fn table_field(tables: &[u8], dmi_file: &Path) -> Option<String> {
+    let name = dmi_file.file_name()?.to_str()?;
+    let &(_, kind, offset) = TABLE_FIELDS.iter().find(|(field, _, _)| *field == name)?;
+
+    let mut pos = 0;
+
+    // Each structure is a formatted area, starting with its type and length, followed by a set of NUL-terminated
+    // strings ending with an extra NUL
+    while let Some(&[kind_at, length, ..]) = tables.get(pos..pos + 4) {
+        let length = usize::from(length);
+        let formatted = tables.get(pos..pos + length).filter(|_| length >= 4)?;
+        let strings_start = pos + length;
+        let strings_end = strings_start
+            + tables
+                .get(strings_start..)?
+                .windows(2)
+                .position(|window| window == [0, 0])?;
+
+        if kind_at == kind {
+            // String numbers start at 1, and 0 means the string is not set
+            let number = usize::from(*formatted.get(offset)?).checked_sub(1)?;
+            let value = tables[strings_start..strings_end]
+                .split(|&byte| byte == 0)
+                .nth(number)?;
+
+            return Some(String::from_utf8_lossy(value).trim().to_string());
+        }
+
+        if kind_at == END_OF_TABLE {
+            break;
+        }
+
+        pos = strings_end + 2;
+    }
+
+    None
+}
+
+/// Reads the vendor-identifying properties of the device tree, if present.
+fn read_device_tree<P: AsRef<Path>>(device_tree_dir: P) -> Option<String> {
+    if !device_tree_dir.as_ref().is_dir() {
+        return None;
+    }
+
+    debug!(
+        "Checking device tree: {}",
+        device_tree_dir.as_ref().display()
+    );
+
+    let properties: Vec<String> = DEVICE_TREE_FILES
+        .iter()
+        .map(|file| device_tree_dir.as_ref().join(file))
+        .filter(|path| path.is_file())
+        .filter_map(|path| match fs::read(path) {
+            Ok(bytes) => Some(bytes),
+            Err(err) => {
+                error!("Error reading file: {:?}", err);
+                None
+            }
+        })
+        // Device tree string lists are NUL-separated
+        .map(|bytes| String::from_utf8_lossy(&bytes).replace('\0', "\n"))
+        .collect();
+
+    if properties.is_empty() {
+        None
+    } else {
+        Some(properties.join("\n"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use anyhow::Result;
+    use tempfile::{NamedTempFile, TempDir};
+
+    use super::*;
+
+    /// Builds an SMBIOS structure of the given type, with the given formatted area (after the header) and strings.
+    fn structure(kind: u8, formatted: &[u8], strings: &[&str]) -> Vec<u8> {
+        let mut bytes = vec![kind, 4 + formatted.len() as u8, 0, 0];
+        bytes.extend_from_slice(formatted);
+
+        for string in strings {
+            bytes.extend_from_slice(string.as_bytes());
+            bytes.push(0);
+        }
+
+        if strings.is_empty() {
+            bytes.push(0);
+        }
+
+        bytes.push(0);
+        bytes
+    }
+
+    /// Raw SMBIOS tables of an EC2 instance: BIOS, system and end-of-table structures.
+    fn tables() -> Vec<u8> {
+        [
+            structure(0, &[1, 2, 0, 0], &["Amazon EC2", "1.0"]),
+            // No product version (string number 0)
+            structure(1, &[1, 2, 0, 3], &["Amazon EC2", "m5.large", "ec2abc"]),
+            structure(END_OF_TABLE, &[], &[]),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn test_root_of() {
+        assert_eq!(
+            root_of(Path::new("/host/sys/class/dmi/id/sys_vendor")),
+            Path::new("/host")
+        );
+        assert_eq!(
+            root_of(Path::new("/sys/class/dmi/id/sys_vendor")),
+            Path::new("/")
+        );
+        assert_eq!(root_of(Path::new("/opt/acme/vendor")), Path::new("/"));
+    }
+
+    #[test]
+    fn test_table_field() {
+        let field = |name: &str| table_field(&tables(), &Path::new("/sys/class/dmi/id").join(name));
+
+        assert_eq!(field("bios_vendor").as_deref(), Some("Amazon EC2"));
+        assert_eq!(field("bios_version").as_deref(), Some("1.0"));
+        assert_eq!(field("product_name").as_deref(), Some("m5.large"));
+        assert_eq!(field("product_version"), None);
+        // No chassis structure
+        assert_eq!(field("chassis_asset_tag"), None);
+        // Not an attribute of the tables
+        assert_eq!(field("modalias"), None);
+    }
+
+    #[test]
+    fn test_table_field_truncated() {
+        let tables = tables();
+
+        assert_eq!(table_field(&tables[..20], Path::new("product_name")), None);
+        assert_eq!(table_field(&[], Path::new("product_name")), None);
+    }
+
+    #[test]
+    fn test_read_field_falls_back_to_tables() -> Result<()> {
+        let dmi_dir = TempDir::new()?;
+        let tables_file = dmi_dir.path().join("DMI");
+        fs::write(&tables_file, tables())?;
+
+        let data = SmbiosData::read_from(
+            [dmi_dir.path().join("sys_vendor")],
+            &tables_file,
+            dmi_dir.path().join("device-tree"),
+        );
+
+        assert_eq!(
+            data.field(&dmi_dir.path().join("sys_vendor")).as_deref(),
+            Some("Amazon EC2")
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_field_prefers_dmi() -> Result<()> {
+        let mut dmi_file = NamedTempFile::new()?;
+        dmi_file.write_all(b"Amazon EC2")?;
+        let device_tree_dir = TempDir::new()?;
+        fs::write(device_tree_dir.path().join("model"), b"Other\0")?;
+
+        let result = read_field_from(
+            dmi_file.path(),
+            device_tree_dir.path().join("DMI"),
+            device_tree_dir.path(),
+        );
+
+        assert_eq!(result.as_deref(), Some("Amazon EC2"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_field_falls_back_to_device_tree() -> Result<()> {
+        let dmi_dir = TempDir::new()?;
+        let device_tree_dir = TempDir::new()?;
+        fs::write(
+            device_tree_dir.path().join("model"),
+            b"Google Compute Engine\0",
+        )?;
+        fs::create_dir(device_tree_dir.path().join("hypervisor"))?;
+        fs::write(
+            device_tree_dir.path().join("hypervisor/compatible"),
+            b"linux,kvm\0",
+        )?;
+
+        let result = read_field_from(
+            dmi_dir.path().join("sys_vendor"),
+            dmi_dir.path().join("DMI"),
+            device_tree_dir.path(),
+        );
+
+        assert!(result.is_some_and(|content| content.contains("Google") && content.contains("kvm")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_smbios_data_reads_once() -> Result<()> {
+        let dmi_dir = TempDir::new()?;
+        let sys_vendor = dmi_dir.path().join("sys_vendor");
+        fs::write(&sys_vendor, b"DigitalOcean")?;
+        let device_tree_dir = TempDir::new()?;
+        fs::write(device_tree_dir.path().join("model"), b"Other\0")?;
+
+        let product_name = dmi_dir.path().join("product_name");
+        let data = SmbiosData::read_from(
+            [&sys_vendor, &product_name, &sys_vendor],
+            dmi_dir.path().join("DMI"),
+            device_tree_dir.path(),
+        );
+        fs::write(&sys_vendor, b"Vultr")?;
+
+        assert_eq!(data.len(), 2);
+        assert_eq!(data.field(&sys_vendor).as_deref(), Some("DigitalOcean"));
+        assert!(data
+            .field(&product_name)
+            .is_some_and(|content| content.contains("Other")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_field_missing() -> Result<()> {
+        let dmi_dir = TempDir::new()?;
+
+        let result = read_field_from(
+            dmi_dir.path().join("sys_vendor"),
+            dmi_dir.path().join("DMI"),
+            dmi_dir.path().join("device-tree"),
+        );
+
+        assert!(result.is_none());
+
+        Ok(())
+    }
+}
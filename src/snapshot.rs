@@ -0,0 +1,453 @@
+//! Offline detection.
+//!
+//! A [HostSnapshot] holds the signals of a host that detection relies on, such as the contents of its DMI attribute
+//! files and the responses of its metadata server, captured beforehand (e.g. for forensics). Detection then runs the
+//! checks of every provider against the snapshot instead of the live host, without performing any I/O.
+//!
+//! The local signals of a host are captured with [capture_snapshot], e.g. to attach to a bug report. Responses from
+//! metadata servers are not captured, and can be added to the snapshot separately.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::{env, fs};
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+use tracing::{debug, info, instrument};
+
+use crate::checks::{glob_files, Check, Inspection, Probe, Request, Response};
+use crate::hints::{cloudinit, dns};
+use crate::{cpuid, smbios, Confidence, Detector, Match, ProviderId, PROVIDERS};
+
+/// Represents a response captured from a metadata server.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CapturedResponse {
+    /// Status code of the response.
+    pub status: u16,
+    /// Body of the response.
+    pub body: String,
+    /// Headers of the response, by name (e.g. `Metadata-Flavor`, which GCP requires).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+}
+
+/// Represents the signals of a host, captured for offline detection.
+///
+/// Files are keyed by their absolute path on the host (e.g. `/sys/class/dmi/id/sys_vendor`), and responses by the path
+/// of the request to the metadata server (e.g. `/latest/dynamic/instance-identity/document`). The query of a request
+/// (e.g. an API version) is ignored, unless a response was captured for it. Missing files and environment variables
+/// are treated as absent from the host, and missing responses as if the metadata server was unreachable.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::{detect_from_snapshot, HostSnapshot, ProviderId};
+///
+/// let snapshot = HostSnapshot::new()
+///     .file("/sys/class/dmi/id/sys_vendor", "Microsoft Corporation\n")
+///     .response(
+///         "/metadata/instance",
+///         200,
+///         r#"{"compute": {"vmId": "vm-1234"}}"#,
+///     );
+///
+/// assert_eq!(detect_from_snapshot(&snapshot), ProviderId::Azure);
+/// ```
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct HostSnapshot {
+    /// Contents of the files of the host, by path.
+    pub files: BTreeMap<PathBuf, String>,
+    /// Environment variables of the host.
+    pub env: BTreeMap<String, String>,
+    /// Vendor signature of the hypervisor reported by CPUID (e.g. `VMwareVMware`), if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hypervisor: Option<String>,
+    /// Responses from the metadata server of the host, by request path.
+    pub responses: BTreeMap<String, CapturedResponse>,
+}
+
+impl CapturedResponse {
+    /// Creates a response without headers.
+    pub fn new(status: u16, body: &str) -> Self {
+        Self {
+            status,
+            body: body.to_string(),
+            headers: BTreeMap::new(),
+        }
+    }
+
+    /// Adds a header to the response.
+    pub fn header(mut self, name: &str, value: &str) -> Self {
+        self.headers.insert(name.to_string(), value.to_string());
+        self
+    }
+}
+
+impl HostSnapshot {
+    /// Creates an empty snapshot.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the contents of a file.
+    pub fn file<P: AsRef<Path>>(mut self, path: P, content: &str) -> Self {
+        self.files
+            .insert(path.as_ref().to_path_buf(), content.to_string());
+        self
+    }
+
+    /// Sets the vendor signature of the hypervisor reported by CPUID.
+    pub fn hypervisor(mut self, vendor: &str) -> Self {
+        self.hypervisor = Some(vendor.to_string());
+        self
+    }
+
+    /// Adds an environment variable.
+    pub fn env(mut self, name: &str, value: &str) -> Self {
+        self.env.insert(name.to_string(), value.to_string());
+        self
+    }
+
+    /// Adds a response from the metadata server.
+    pub fn response(mut self, path: &str, status: u16, body: &str) -> Self {
+        self.responses
+            .insert(path.to_string(), CapturedResponse::new(status, body));
+        self
+    }
+
+    /// Adds a header to the response from the metadata server to a request path, which must already be added.
+    pub fn response_header(mut self, path: &str, name: &str, value: &str) -> Self {
+        if let Some(resp) = self.responses.get_mut(path) {
+            resp.headers.insert(name.to_string(), value.to_string());
+        }
+        self
+    }
+
+    /// Returns the captured response to a request, if any.
+    fn respond(&self, request: &Request) -> Option<Response> {
+        let path = request.path.split('?').next().unwrap_or_default();
+
+        self.responses
+            .get(&request.path)
+            .or_else(|| self.responses.get(path))
+            .map(|resp| Response {
+                status: resp.status,
+                body: resp.body.clone(),
+                // Headers that aren't valid HTTP headers couldn't have been served, so are dropped
+                headers: resp
+                    .headers
+                    .iter()
+                    .filter_map(|(name, value)| Some((name.parse().ok()?, value.parse().ok()?)))
+                    .collect(),
+                ..Default::default()
+            })
+    }
+
+    /// Runs a check against the snapshot.
+    fn inspect(&self, check: &Check) -> Inspection {
+        match &check.probe {
+            Probe::Dmi { path, matches } | Probe::File { path, matches } => {
+                Inspection::content(self.files.get(path).cloned(), matches)
+            }
+            Probe::Glob { pattern, matches } => {
+                let contents = match glob::Pattern::new(pattern) {
+                    Ok(pattern) => self
+                        .files
+                        .iter()
+                        .filter(|(path, _)| pattern.matches_path(path))
+                        .map(|(_, content)| content.clone())
+                        .collect(),
+                    Err(err) => return Inspection::error(err.into()),
+                };
+
+                Inspection::contents(contents, matches)
+            }
+            Probe::Exists { path } => Inspection::presence(self.files.contains_key(path)),
+            Probe::Env { name, matches } => {
+                Inspection::content(self.env.get(*name).cloned(), matches)
+            }
+            Probe::Cpuid { matches } => Inspection::content(self.hypervisor.clone(), matches),
+            Probe::Http { request, matches } => match self.respond(request) {
+                Some(resp) => {
+                    Inspection::response(resp, matches).or_unauthorized(check.unauthorized)
+                }
+                None => {
+                    Inspection::unreachable(anyhow!("No response captured for {}", request.path))
+                }
+            },
+        }
+    }
+}
+
+/// Captures the local signals of the host: the files inspected by the checks of every provider (with DMI attributes
+/// read as detection reads them), the environment variables they inspect, the hypervisor reported by CPUID, and the
+/// files of cloud-init and of the DNS resolver. Files and environment variables absent from the host are left out.
+///
+/// Metadata servers are not queried.
+///
+/// # Examples
+///
+/// Capture the signals of the host, and serialize them for a bug report.
+///
+/// ```
+/// use cloud_detect::capture_snapshot;
+///
+/// let snapshot = capture_snapshot();
+/// println!("{}", serde_json::to_string_pretty(&snapshot).unwrap());
+/// ```
+#[instrument]
+pub fn capture_snapshot() -> HostSnapshot {
+    let mut snapshot = HostSnapshot::new();
+
+    for check in PROVIDERS.iter().flat_map(|provider| provider.checks()) {
+        match check.probe {
+            Probe::Dmi { path, .. } => {
+                if let Some(content) = smbios::read_field(&path) {
+                    snapshot.files.insert(path, content);
+                }
+            }
+            Probe::File { path, .. } => {
+                if let Ok(content) = fs::read_to_string(&path) {
+                    snapshot.files.insert(path, content);
+                }
+            }
+            Probe::Glob { pattern, .. } => {
+                for path in glob_files(&pattern) {
+                    if let Ok(content) = fs::read_to_string(&path) {
+                        snapshot.files.insert(path, content);
+                    }
+                }
+            }
+            Probe::Exists { path } => {
+                if path.exists() {
+                    snapshot.files.insert(path, String::new());
+                }
+            }
+            Probe::Env { name, .. } => {
+                if let Ok(value) = env::var(name) {
+                    snapshot.env.insert(name.to_string(), value);
+                }
+            }
+            Probe::Cpuid { .. } => snapshot.hypervisor = cpuid::hypervisor_vendor(),
+            Probe::Http { .. } => {}
+        }
+    }
+
+    for file in cloudinit::FILES.iter().chain([&dns::RESOLV_CONF_FILE]) {
+        if let Ok(content) = fs::read_to_string(file) {
+            snapshot.files.insert(PathBuf::from(file), content);
+        }
+    }
+
+    debug!(
+        "Captured {} files and {} environment variables",
+        snapshot.files.len(),
+        snapshot.env.len()
+    );
+    snapshot
+}
+
+/// Detects the cloud provider of a host from a snapshot of its signals, instead of the live host.
+///
+/// Returns [ProviderId::Unknown] if no provider matched.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::{detect_from_snapshot, HostSnapshot, ProviderId};
+///
+/// let snapshot =
+///     HostSnapshot::new().file("/sys/class/dmi/id/product_name", "Google Compute Engine");
+///
+/// assert_eq!(detect_from_snapshot(&snapshot), ProviderId::GCP);
+/// ```
+pub fn detect_from_snapshot(snapshot: &HostSnapshot) -> ProviderId {
+    Detector::new().detect_snapshot(snapshot)
+}
+
+impl Detector {
+    /// Detects the cloud provider of a host from a snapshot of its signals, instead of the live host (see
+    /// [detect_from_snapshot]).
+    ///
+    /// Only the providers probed by the detector are matched. In local-only mode, responses from metadata servers are
+    /// ignored, and providers are also matched by the DNS search domains in the resolver configuration file of the
+    /// snapshot. The timeout and concurrency settings don't apply, as no I/O is performed.
+    #[instrument(skip_all)]
+    pub fn detect_snapshot(&self, snapshot: &HostSnapshot) -> ProviderId {
+        let mut matches = Vec::new();
+        let read = |path: &Path| snapshot.files.get(path).cloned();
+
+        if let Some(provider) = cloudinit::identify_with(read).filter(|&p| self.includes(p)) {
+            info!("cloud-init identified {}", provider);
+            matches.push(Match::new(provider, Confidence::Authoritative).source("cloud-init"));
+        }
+
+        if self.local_only {
+            matches.extend(
+                dns::identify_with(read)
+                    .into_iter()
+                    .filter(|&p| self.includes(p))
+                    .map(|p| Match::new(p, Confidence::Unverified).source("dns")),
+            );
+        }
+
+        for provider in self.registry() {
+            let found = self
+                .tuned_checks(provider)
+                .into_iter()
+                .filter(|check| !(self.local_only && matches!(check.probe, Probe::Http { .. })))
+                .find_map(|check| {
+                    let inspection = snapshot.inspect(&check);
+                    inspection
+                        .matched()
+                        .then(|| (check.name, check.match_confidence(&inspection)))
+                });
+
+            if let Some((name, confidence)) = found {
+                info!("Identified {} using {}", provider.identifier(), name);
+                matches.push(Match::new(provider.identifier(), confidence).source(name));
+            }
+        }
+
+        debug!("Matches: {:?}", matches);
+        self.resolve(&matches)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::provider_fixtures as fixtures;
+
+    #[test]
+    fn test_detect_from_snapshot() {
+        let snapshot = HostSnapshot::new()
+            .file("/sys/class/dmi/id/bios_vendor", fixtures::aws::VENDOR)
+            .response(
+                "/latest/dynamic/instance-identity/document",
+                200,
+                fixtures::aws::IDENTITY_DOCUMENT,
+            );
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::AWS);
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_query() {
+        let snapshot =
+            HostSnapshot::new().response("/metadata/instance", 200, fixtures::azure::METADATA);
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::Azure);
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_headers() {
+        let snapshot = HostSnapshot::new().response(
+            "/computeMetadata/v1/instance/id",
+            200,
+            fixtures::gcp::INSTANCE_ID,
+        );
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::Unknown);
+
+        let snapshot = fixtures::gcp::HEADERS
+            .iter()
+            .fold(snapshot, |snapshot, &(name, value)| {
+                snapshot.response_header("/computeMetadata/v1/instance/id", name, value)
+            });
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::GCP);
+
+        let expected = fixtures::gcp::HEADERS.iter().fold(
+            CapturedResponse::new(200, fixtures::gcp::INSTANCE_ID),
+            |resp, &(name, value)| resp.header(name, value),
+        );
+        assert_eq!(
+            snapshot.responses["/computeMetadata/v1/instance/id"],
+            expected
+        );
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_hypervisor() {
+        let snapshot = HostSnapshot::new().hypervisor(fixtures::vmware::HYPERVISOR);
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::VMware);
+
+        let snapshot = snapshot.response("/v1.json", 200, fixtures::vultr::METADATA);
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::Vultr);
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_cloud_init() {
+        let snapshot = HostSnapshot::new()
+            .file(
+                "/sys/class/dmi/id/product_name",
+                fixtures::openstack::PRODUCT_NAME,
+            )
+            .file("/run/cloud-init/cloud-id", "aws\n");
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::AWS);
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_local_only() {
+        let snapshot =
+            HostSnapshot::new().response("/metadata/instance", 200, fixtures::azure::METADATA);
+
+        assert_eq!(
+            Detector::new().local_only().detect_snapshot(&snapshot),
+            ProviderId::Unknown
+        );
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_dns() {
+        let snapshot = HostSnapshot::new().file("/etc/resolv.conf", "search ec2.internal\n");
+
+        assert_eq!(detect_from_snapshot(&snapshot), ProviderId::Unknown);
+        assert_eq!(
+            Detector::new().local_only().detect_snapshot(&snapshot),
+            ProviderId::AWS
+        );
+    }
+
+    #[test]
+    fn test_capture_snapshot() {
+        let snapshot = capture_snapshot();
+
+        assert!(snapshot.responses.is_empty());
+        assert!(snapshot.files.keys().all(|path| path.is_absolute()));
+    }
+
+    #[test]
+    fn test_detect_from_snapshot_empty() {
+        assert_eq!(
+            detect_from_snapshot(&HostSnapshot::new()),
+            ProviderId::Unknown
+        );
+    }
+
+    #[test]
+    fn test_snapshot_serde() -> anyhow::Result<()> {
+        let snapshot = HostSnapshot::new()
+            .file("/sys/class/dmi/id/sys_vendor", fixtures::vultr::VENDOR)
+            .env("K_SERVICE", "service")
+            .hypervisor(fixtures::vmware::HYPERVISOR)
+            .response("/v1.json", 200, fixtures::vultr::METADATA);
+
+        let json = serde_json::to_string(&snapshot)?;
+
+        assert_eq!(serde_json::from_str::<HostSnapshot>(&json)?, snapshot);
+        assert_eq!(
+            serde_json::from_str::<HostSnapshot>("{}")?,
+            HostSnapshot::new()
+        );
+
+        Ok(())
+    }
+}
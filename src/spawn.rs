@@ -0,0 +1,87 @@
+//! Spawning of named tasks.
+//!
+//! Embedded in a large application, the tasks spawned by a detection are hard to tell apart from the application's own.
+//! Each task is named after what it probes (e.g. `cloud-detect/provider/aws`) and runs within a span of the same name,
+//! nested under the span of the detection, so tracing attributes its events, including those of the blocking hints it
+//! awaits. With the `tokio-console` feature, names are also given to the tasks themselves, as shown by
+//! [tokio-console](https://github.com/tokio-rs/console). Task builders are unstable, so this also requires building with
+//! `RUSTFLAGS="--cfg tokio_unstable"`, as tokio-console itself does.
+
+use std::future::Future;
+
+use tokio::task::{JoinHandle, JoinSet};
+use tracing::{info_span, Instrument, Span};
+
+/// Prefix of the names of the spawned tasks.
+const PREFIX: &str = "cloud-detect";
+
+/// Returns the name of a task probing something (e.g. `provider`, `aws`).
+pub(crate) fn name(kind: &str, target: impl std::fmt::Display) -> String {
+    format!("{PREFIX}/{kind}/{target}")
+}
+
+/// Spawns a named task on a set, within a span of the same name.
+pub(crate) fn named<T, F>(tasks: &mut JoinSet<T>, name: &str, future: F)
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let future = future.instrument(info_span!("task", name));
+
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    tasks
+        .build_task()
+        .name(name)
+        .spawn(future)
+        .expect("Error spawning task");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    tasks.spawn(future);
+}
+
+/// Spawns a named task on the current runtime, within a span of the same name.
+pub(crate) fn detached<T, F>(name: &str, future: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: Future<Output = T> + Send + 'static,
+{
+    let future = future.instrument(info_span!("task", name));
+
+    #[cfg(all(tokio_unstable, feature = "tokio-console"))]
+    return tokio::task::Builder::new()
+        .name(name)
+        .spawn(future)
+        .expect("Error spawning task");
+    #[cfg(not(all(tokio_unstable, feature = "tokio-console")))]
+    tokio::spawn(future)
+}
+
+/// Runs a blocking function on the runtime's blocking threads, within the current span.
+pub(crate) fn blocking<T, F>(f: F) -> JoinHandle<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    let span = Span::current();
+    tokio::task::spawn_blocking(move || span.in_scope(f))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_name() {
+        assert_eq!(name("provider", "aws"), "cloud-detect/provider/aws");
+        assert_eq!(name("hint", "cloud-init"), "cloud-detect/hint/cloud-init");
+    }
+
+    #[tokio::test]
+    async fn test_spawn() {
+        let mut tasks = JoinSet::new();
+        named(&mut tasks, "cloud-detect/test", async { 1 });
+
+        assert_eq!(tasks.join_next().await.unwrap().unwrap(), 1);
+        assert_eq!(detached("cloud-detect/test", async { 2 }).await.unwrap(), 2);
+        assert_eq!(blocking(|| 3).await.unwrap(), 3);
+    }
+}
@@ -0,0 +1,659 @@
+//! Deterministic test harness.
+//!
+//! Detection normally depends on the host it runs on and on real time. With the `test_support` feature, a [Detector]
+//! can instead probe a [FakeTransport] serving canned metadata server responses, and [FakeProvider]s with scripted
+//! outcomes, ignoring the host entirely. Delays are measured with tokio's clock, so tests pausing time (see
+//! [tokio::time::pause]) run instantly.
+//!
+//! # Examples
+//!
+//! Test that a slow provider is still detected within the timeout, without sleeping.
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use cloud_detect::test_support::FakeProvider;
+//! use cloud_detect::{Detector, ProviderId};
+//!
+//! #[tokio::main(flavor = "current_thread", start_paused = true)]
+//! async fn main() {
+//!     let provider = Detector::new()
+//!         .timeout(5)
+//!         .fake_provider(FakeProvider::matching(ProviderId::AWS).after(Duration::from_secs(4)))
+//!         .detect()
+//!         .await;
+//!
+//!     assert_eq!(provider, ProviderId::AWS);
+//! }
+//! ```
+//!
+//! Applications can also force the result of every detection with [set_forced_result], to test the code paths of
+//! each provider without a [Detector] in reach. The module is also available as `cloud_detect::testing`.
+//!
+//! [Detector]: crate::Detector
+
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+
+use crate::checks::{Check, Probe, Request, Response};
+use crate::{clock, Confidence, Detection, ProviderId, P};
+
+/// Base URI of the requests probing fake providers.
+const FAKE_URI: &str = "http://fake.test";
+
+/// Provider every detection results in, if forced (see [set_forced_result]).
+static FORCED_RESULT: RwLock<Option<ProviderId>> = RwLock::new(None);
+
+/// Forces every detection, from both the async and blocking APIs, to result in the given provider, without probing the
+/// host (neither its files nor the network), until cleared with [clear_forced_result].
+///
+/// The result is forced for the whole process, and leaks into every test running in parallel with the one forcing
+/// it, including tests that never force a result. Tests forcing results should run in a test binary of their own (e.g.
+/// a file under `tests/`), serialized with a lock if there are several.
+///
+/// Only detection is forced ([detect](crate::detect()), [Detector::detect_detailed](crate::Detector::detect_detailed),
+/// [is_cloud](crate::is_cloud()), [is_provider](crate::is_provider()), their blocking counterparts, and the functions
+/// detecting the provider before fetching its metadata). [diagnose](crate::diagnose()) and
+/// [detect_from_snapshot](crate::detect_from_snapshot()) ignore the forced result, as they report on the host or the
+/// snapshot itself, and fetching metadata still queries the metadata server of the provider.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::testing::{clear_forced_result, set_forced_result};
+/// use cloud_detect::{detect, is_cloud, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     set_forced_result(ProviderId::GCP);
+///
+///     assert_eq!(detect(Some(1)).await, ProviderId::GCP);
+///     assert!(is_cloud(Some(1)).await);
+///
+///     clear_forced_result();
+/// }
+/// ```
+pub fn set_forced_result(provider: ProviderId) {
+    *FORCED_RESULT.write().unwrap_or_else(|err| err.into_inner()) = Some(provider);
+}
+
+/// Stops forcing the result of detection (see [set_forced_result]).
+pub fn clear_forced_result() {
+    *FORCED_RESULT.write().unwrap_or_else(|err| err.into_inner()) = None;
+}
+
+/// Returns the detection forced with [set_forced_result], if any.
+pub(crate) fn forced_detection() -> Option<Detection> {
+    let provider = (*FORCED_RESULT.read().unwrap_or_else(|err| err.into_inner()))?;
+
+    Some(Detection::new(provider, &[]))
+}
+
+/// Represents a canned response from a metadata server.
+#[derive(Debug, Clone)]
+struct Route {
+    path: String,
+    status: u16,
+    body: String,
+    delay: Duration,
+}
+
+/// Serves canned responses to requests to metadata servers, in place of the network.
+///
+/// Responses are matched by path (e.g. `/latest/dynamic/instance-identity/document`), whatever the metadata server.
+/// Requests to any other path fail as if the metadata server was unreachable. Session tokens are not requested.
+///
+/// # Examples
+///
+/// ```
+/// use cloud_detect::test_support::FakeTransport;
+/// use cloud_detect::{Detector, ProviderId};
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let transport = FakeTransport::new().respond(
+///         "/latest/dynamic/instance-identity/document",
+///         200,
+///         r#"{"imageId": "ami-12345678", "instanceId": "i-12345678"}"#,
+///     );
+///
+///     let provider = Detector::new().transport(transport).detect().await;
+///     assert_eq!(provider, ProviderId::AWS);
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FakeTransport {
+    routes: Vec<Route>,
+}
+
+impl FakeTransport {
+    /// Creates a transport without any responses.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Responds to requests for a path immediately.
+    pub fn respond(self, path: &str, status: u16, body: &str) -> Self {
+        self.respond_after(path, status, body, Duration::ZERO)
+    }
+
+    /// Responds to requests for a path after a delay. Requests time out if the delay exceeds their timeout.
+    pub fn respond_after(mut self, path: &str, status: u16, body: &str, delay: Duration) -> Self {
+        self.routes.push(Route {
+            path: path.to_string(),
+            status,
+            body: body.to_string(),
+            delay,
+        });
+        self
+    }
+
+    /// Returns the route for a request, and the time to wait before responding (or timing out).
+    fn route(&self, request: &Request, timeout: Duration) -> Result<(&Route, Duration)> {
+        // Unless the route has a query, the query of the request (e.g. an API version) is ignored
+        let path = request.path.split('?').next().unwrap_or_default();
+        let route = self
+            .routes
+            .iter()
+            .find(|route| route.path == request.path || route.path == path)
+            .ok_or_else(|| anyhow!("No response for {}", request.path))?;
+
+        Ok((route, route.delay.min(timeout)))
+    }
+
+    /// Responds to a request, waiting on tokio's clock.
+    pub(crate) async fn send(&self, request: &Request, timeout: Duration) -> Result<Response> {
+        let (route, delay) = self.route(request, timeout)?;
+        clock::sleep(delay).await;
+
+        respond(route, timeout)
+    }
+
+    /// Responds to a request, blocking the current thread.
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    pub(crate) fn send_blocking(&self, request: &Request, timeout: Duration) -> Result<Response> {
+        let (route, delay) = self.route(request, timeout)?;
+        std::thread::sleep(delay);
+
+        respond(route, timeout)
+    }
+}
+
+/// Returns the response of a route, unless it took longer than the timeout.
+fn respond(route: &Route, timeout: Duration) -> Result<Response> {
+    if route.delay > timeout {
+        bail!("Request timed out");
+    }
+
+    Ok(Response {
+        status: route.status,
+        body: route.body.clone(),
+        ..Default::default()
+    })
+}
+
+/// Represents a provider with a scripted outcome, replacing the checks of the real provider.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use cloud_detect::test_support::FakeProvider;
+/// use cloud_detect::ProviderId;
+///
+/// let provider = FakeProvider::failing(ProviderId::GCP).after(Duration::from_secs(2));
+/// ```
+#[derive(Debug, Clone)]
+pub struct FakeProvider {
+    pub(crate) identifier: ProviderId,
+    matches: bool,
+    delay: Duration,
+}
+
+impl FakeProvider {
+    /// Creates a provider that matches immediately.
+    pub fn matching(identifier: ProviderId) -> Self {
+        Self {
+            identifier,
+            matches: true,
+            delay: Duration::ZERO,
+        }
+    }
+
+    /// Creates a provider that fails to match immediately.
+    pub fn failing(identifier: ProviderId) -> Self {
+        Self {
+            matches: false,
+            ..Self::matching(identifier)
+        }
+    }
+
+    /// Delays the outcome of the provider.
+    pub fn after(mut self, delay: Duration) -> Self {
+        self.delay = delay;
+        self
+    }
+
+    /// Returns the single check of the provider, answered by a transport of its own.
+    fn checks(&self) -> Vec<Check> {
+        let path = format!("/{}", self.identifier);
+        let status = if self.matches { 200 } else { 404 };
+        let transport = FakeTransport::new().respond_after(&path, status, "", self.delay);
+
+        vec![Check::http(
+            "fake",
+            Confidence::Verified,
+            Request::get(FAKE_URI, &path).transport(Arc::new(transport)),
+            Response::is_success,
+        )]
+    }
+}
+
+/// Returns the checks of a provider when the host is simulated: the scripted checks of a fake provider, or the checks
+/// of the real provider querying a metadata server, answered by the transport.
+pub(crate) fn checks(
+    provider: P,
+    transport: &Arc<FakeTransport>,
+    fakes: &[FakeProvider],
+) -> Vec<Check> {
+    if let Some(fake) = fakes
+        .iter()
+        .find(|fake| fake.identifier == provider.identifier())
+    {
+        return fake.checks();
+    }
+
+    provider
+        .checks()
+        .into_iter()
+        .filter_map(|check| match check.probe {
+            Probe::Http { request, matches } => Some(Check {
+                probe: Probe::Http {
+                    request: request.transport(transport.clone()),
+                    matches,
+                },
+                ..check
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+
+    use super::*;
+    use crate::engine::{resolve, Match, RESOLUTION_WINDOW};
+    use crate::provider_fixtures as fixtures;
+    use crate::{Detector, Outcome, SkipReason, SkippedProbe};
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fake_provider() {
+        let provider = Detector::new()
+            .timeout(5)
+            .fake_provider(FakeProvider::failing(ProviderId::Azure))
+            .fake_provider(FakeProvider::matching(ProviderId::AWS).after(Duration::from_secs(4)))
+            .detect()
+            .await;
+
+        assert_eq!(provider, ProviderId::AWS);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fake_provider_timeout() {
+        let start = clock::Instant::now();
+        let provider = Detector::new()
+            .timeout(5)
+            .fake_provider(FakeProvider::matching(ProviderId::AWS).after(Duration::from_secs(10)))
+            .detect()
+            .await;
+
+        assert_eq!(provider, ProviderId::Unknown);
+        assert_eq!(start.elapsed(), Duration::from_secs(5));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fake_provider_clock_jump() {
+        let detect = |delay| {
+            let detector = Detector::new()
+                .timeout(5)
+                .fake_provider(FakeProvider::matching(ProviderId::AWS).after(delay));
+            tokio::spawn(async move { detector.detect().await })
+        };
+
+        // A jump within the timeout (e.g. a brief suspend) doesn't cut detection short
+        let start = clock::Instant::now();
+        let detection = detect(Duration::from_secs(4));
+        // Let detection start before the jump
+        clock::sleep(Duration::from_millis(1)).await;
+        tokio::time::advance(Duration::from_secs(3)).await;
+        assert_eq!(detection.await.unwrap(), ProviderId::AWS);
+        assert_eq!(start.elapsed(), Duration::from_secs(4));
+
+        // A jump past the timeout times detection out at once, rather than never
+        let start = clock::Instant::now();
+        let detection = detect(Duration::from_secs(7200));
+        clock::sleep(Duration::from_millis(1)).await;
+        tokio::time::advance(Duration::from_secs(3600)).await;
+        assert_eq!(detection.await.unwrap(), ProviderId::Unknown);
+        assert!(start.elapsed() < Duration::from_secs(3601));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_fake_provider_timeout_skipped() {
+        let detection = Detector::new()
+            .timeout(5)
+            .providers(&[ProviderId::AWS, ProviderId::Azure])
+            .fake_provider(FakeProvider::failing(ProviderId::Azure))
+            .fake_provider(FakeProvider::matching(ProviderId::AWS).after(Duration::from_secs(10)))
+            .detect_detailed()
+            .await;
+
+        assert_eq!(detection.provider, ProviderId::Unknown);
+        assert_eq!(
+            detection.skipped,
+            [SkippedProbe::new(
+                ProviderId::AWS,
+                "fake",
+                SkipReason::Timeout
+            )]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_detect_aborts_remaining_tasks() {
+        let provider = Detector::new()
+            .timeout(5)
+            .fake_provider(FakeProvider::matching(ProviderId::AWS))
+            .fake_provider(FakeProvider::matching(ProviderId::Azure).after(Duration::from_secs(4)))
+            .detect()
+            .await;
+
+        assert_eq!(provider, ProviderId::AWS);
+
+        // The aborted tasks are reaped once the runtime gets to them
+        tokio::task::yield_now().await;
+        let metrics = tokio::runtime::Handle::current().metrics();
+        assert_eq!(metrics.num_alive_tasks(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_detect_detailed() {
+        let transport =
+            FakeTransport::new().respond("/metadata/instance", 200, fixtures::azure::METADATA);
+
+        let detection = Detector::new().transport(transport).detect_detailed().await;
+
+        assert_eq!(detection.provider, ProviderId::Azure);
+        assert_eq!(detection.confidence(), Some(Confidence::Verified));
+        assert_eq!(detection.evidence.len(), 1);
+        assert_eq!(detection.evidence[0].source, "metadata_server");
+    }
+
+    #[tokio::test]
+    async fn test_detect_detailed_auth_required() {
+        // IMDSv1 is disabled, and the IMDSv2 token can't be retrieved
+        let transport =
+            FakeTransport::new().respond("/latest/dynamic/instance-identity/document", 401, "");
+
+        let detection = Detector::new().transport(transport).detect_detailed().await;
+
+        assert_eq!(detection.provider, ProviderId::AWS);
+        assert_eq!(detection.evidence[0].source, "imdsv1");
+        assert_eq!(detection.evidence[0].outcome, Outcome::AuthRequired);
+    }
+
+    #[tokio::test]
+    async fn test_detect_openstack_ec2_compatible() {
+        // OpenStack serves its own metadata alongside an EC2-compatible API
+        let transport = FakeTransport::new()
+            .respond(
+                "/latest/dynamic/instance-identity/document",
+                200,
+                fixtures::aws::IDENTITY_DOCUMENT,
+            )
+            .respond(
+                "/openstack/latest/meta_data.json",
+                200,
+                fixtures::openstack::CONFIG_DRIVE_METADATA,
+            );
+
+        let detection = Detector::new().transport(transport).detect_detailed().await;
+
+        assert_eq!(detection.provider, ProviderId::OpenStack);
+        assert!(detection
+            .evidence
+            .iter()
+            .any(|evidence| evidence.provider == ProviderId::AWS));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_detect_generic_waits_for_specific() {
+        // KVM matches right away (as from CPUID on most clouds), long before the metadata server of AWS answers
+        let transport = FakeTransport::new().respond_after(
+            "/latest/dynamic/instance-identity/document",
+            200,
+            fixtures::aws::IDENTITY_DOCUMENT,
+            Duration::from_secs(1),
+        );
+        let start = clock::Instant::now();
+
+        let detection = Detector::new()
+            .timeout(5)
+            .transport(transport)
+            .fake_provider(FakeProvider::matching(ProviderId::KVM))
+            .detect_detailed()
+            .await;
+
+        assert_eq!(detection.provider, ProviderId::AWS);
+        assert!(detection
+            .evidence
+            .iter()
+            .any(|evidence| evidence.provider == ProviderId::KVM));
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_detect_generic_only() {
+        // Without any specific provider matching, bare KVM is detected once the other providers have failed
+        let provider = Detector::new()
+            .timeout(5)
+            .providers(&[ProviderId::AWS, ProviderId::KVM])
+            .fake_provider(FakeProvider::matching(ProviderId::KVM))
+            .fake_provider(FakeProvider::failing(ProviderId::AWS).after(Duration::from_secs(2)))
+            .detect()
+            .await;
+
+        assert_eq!(provider, ProviderId::KVM);
+    }
+
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    #[test]
+    fn test_detect_generic_waits_for_specific_blocking() {
+        let transport = FakeTransport::new().respond_after(
+            "/latest/dynamic/instance-identity/document",
+            200,
+            fixtures::aws::IDENTITY_DOCUMENT,
+            RESOLUTION_WINDOW * 3,
+        );
+
+        let detector = Detector::new()
+            .timeout(5)
+            .transport(transport)
+            .fake_provider(FakeProvider::matching(ProviderId::KVM));
+
+        assert_eq!(detector.detect_blocking().ok(), Some(ProviderId::AWS));
+    }
+
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    #[test]
+    fn test_detect_detailed_blocking() {
+        let transport =
+            FakeTransport::new().respond("/metadata/instance", 200, fixtures::azure::METADATA);
+
+        let detector = Detector::new().transport(transport);
+        let detection = detector.detect_detailed_blocking();
+
+        assert_eq!(detection.provider, ProviderId::Azure);
+        assert_eq!(detection.confidence(), Some(Confidence::Verified));
+        assert_eq!(detection.evidence[0].source, "metadata_server");
+        assert_eq!(detector.detect_blocking().ok(), Some(detection.provider));
+    }
+
+    #[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+    #[test]
+    fn test_shutdown_blocking() {
+        let detector = Detector::new()
+            .fake_provider(FakeProvider::matching(ProviderId::AWS))
+            .fake_provider(
+                FakeProvider::matching(ProviderId::Azure).after(Duration::from_millis(500)),
+            );
+        let start = std::time::Instant::now();
+
+        assert_eq!(detector.detect_blocking().ok(), Some(ProviderId::AWS));
+
+        // The thread still probing Azure is reaped
+        detector.shutdown();
+        assert_eq!(detector.background.len(), 0);
+        assert!(start.elapsed() >= Duration::from_millis(500));
+    }
+
+    #[tokio::test]
+    async fn test_fake_transport() {
+        let transport =
+            FakeTransport::new().respond("/metadata/instance", 200, fixtures::azure::METADATA);
+
+        let provider = Detector::new().transport(transport).detect().await;
+
+        assert_eq!(provider, ProviderId::Azure);
+    }
+
+    /// Providers faked by the property tests, specific and generic ones alike.
+    const FAKED: [ProviderId; 8] = [
+        ProviderId::AWS,
+        ProviderId::Azure,
+        ProviderId::GCP,
+        ProviderId::DigitalOcean,
+        ProviderId::Alibaba,
+        ProviderId::OpenStack,
+        ProviderId::VMware,
+        ProviderId::KVM,
+    ];
+
+    /// Granularity of the delays of fake providers, which never lands a delay exactly on the timeout or on the end of
+    /// the resolution window of another provider (both multiples of 100ms), where the outcome would be a race.
+    const DELAY_STEP: u64 = 70;
+
+    /// Slack allowed past the timeout for detection to return.
+    const EPSILON: Duration = Duration::from_millis(1);
+
+    /// Outcomes of the faked providers: whether each one is faked at all, whether it matches, and after how long.
+    fn outcomes() -> impl Strategy<Value = Vec<Option<(bool, Duration)>>> {
+        proptest::collection::vec(
+            proptest::option::of((
+                any::<bool>(),
+                (0..80u64).prop_map(|step| Duration::from_millis(step * DELAY_STEP)),
+            )),
+            FAKED.len(),
+        )
+    }
+
+    /// Runs a detection against the given fake providers in a runtime of its own, with time paused, returning the
+    /// detected provider and the time detection took.
+    fn detect(timeout: u64, outcomes: &[Option<(bool, Duration)>]) -> (ProviderId, Duration) {
+        let detector = FAKED
+            .iter()
+            .zip(outcomes)
+            .filter_map(|(&provider, outcome)| outcome.map(|outcome| (provider, outcome)))
+            .fold(
+                // The host is simulated even if no provider is faked
+                Detector::new()
+                    .timeout(timeout)
+                    .transport(FakeTransport::new()),
+                |detector, (provider, (matches, delay))| {
+                    let fake = if matches {
+                        FakeProvider::matching(provider)
+                    } else {
+                        FakeProvider::failing(provider)
+                    };
+                    detector.fake_provider(fake.after(delay))
+                },
+            );
+
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .start_paused(true)
+            .build()
+            .unwrap()
+            .block_on(async {
+                let start = clock::Instant::now();
+                let provider = detector.detect().await;
+                (provider, start.elapsed())
+            })
+    }
+
+    /// Returns the provider expected to win: among the providers matching before the timeout, those matching within
+    /// the resolution window of the first specific provider (or all of them, if only generic platforms matched),
+    /// ranked by the resolution rules.
+    fn expected(timeout: u64, outcomes: &[Option<(bool, Duration)>]) -> ProviderId {
+        let timeout = Duration::from_secs(timeout);
+        let matching: Vec<_> = FAKED
+            .iter()
+            .zip(outcomes)
+            .filter_map(|(&provider, outcome)| match outcome {
+                Some((true, delay)) if *delay < timeout => Some((provider, *delay)),
+                _ => None,
+            })
+            .collect();
+
+        let window = matching
+            .iter()
+            .filter(|(provider, _)| provider.genericity() == 0)
+            .map(|&(_, delay)| (delay + RESOLUTION_WINDOW).min(timeout))
+            .min()
+            .unwrap_or(timeout);
+
+        let matches: Vec<_> = matching
+            .iter()
+            .filter(|&&(_, delay)| delay < window)
+            .map(|&(provider, _)| Match::new(provider, Confidence::Verified).source("fake"))
+            .collect();
+
+        resolve(&matches)
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(64))]
+
+        #[test]
+        fn test_detect_within_timeout(timeout in 1..=5u64, outcomes in outcomes()) {
+            let (_, elapsed) = detect(timeout, &outcomes);
+
+            prop_assert!(elapsed <= Duration::from_secs(timeout) + EPSILON);
+        }
+
+        #[test]
+        fn test_detect_resolution(timeout in 1..=5u64, outcomes in outcomes()) {
+            let (provider, _) = detect(timeout, &outcomes);
+
+            prop_assert_eq!(provider, expected(timeout, &outcomes));
+            // Another run against the same providers picks the same winner
+            prop_assert_eq!(detect(timeout, &outcomes).0, provider);
+        }
+
+        #[test]
+        fn test_detect_any_match(timeout in 1..=5u64, outcomes in outcomes()) {
+            let deadline = Duration::from_secs(timeout);
+            let matched = outcomes
+                .iter()
+                .any(|outcome| matches!(outcome, Some((true, delay)) if *delay < deadline));
+            let (provider, _) = detect(timeout, &outcomes);
+
+            prop_assert_eq!(provider == ProviderId::Unknown, !matched);
+        }
+    }
+}
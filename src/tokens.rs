@@ -0,0 +1,88 @@
+//! Session tokens.
+//!
+//! Some metadata servers (e.g. AWS IMDSv2) require a session token, retrieved with a PUT request, for every request.
+//! Tokens are cached for most of their lifetime and shared by every check and fetch using the same token endpoint, by
+//! both the async and the blocking executors. A token rejected by the metadata server is dropped, so that it is
+//! refreshed.
+
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::clock::Instant;
+use crate::redact::Redacted;
+
+/// Represents a cached session token.
+struct Token {
+    value: String,
+    expires_at: Instant,
+}
+
+/// Cached tokens, keyed by the URL of their token endpoint.
+fn tokens() -> &'static Mutex<HashMap<String, Token>> {
+    static TOKENS: OnceLock<Mutex<HashMap<String, Token>>> = OnceLock::new();
+    TOKENS.get_or_init(Default::default)
+}
+
+/// Returns the cached token for a token endpoint, if it hasn't expired.
+pub(crate) fn get(token_url: &str) -> Option<String> {
+    let tokens = tokens().lock().ok()?;
+
+    tokens
+        .get(token_url)
+        .filter(|token| token.expires_at > Instant::now())
+        .map(|token| token.value.clone())
+}
+
+/// Caches a token retrieved from a token endpoint.
+///
+/// The token is reused for 90% of its lifetime, so that it doesn't expire while requests using it are in flight.
+pub(crate) fn insert(token_url: &str, value: &str, ttl: Duration) {
+    debug!("Caching token from {} for {:?}", Redacted(token_url), ttl);
+
+    if let Ok(mut tokens) = tokens().lock() {
+        tokens.insert(
+            token_url.to_string(),
+            Token {
+                value: value.to_string(),
+                expires_at: Instant::now() + ttl - ttl / 10,
+            },
+        );
+    }
+}
+
+/// Drops the cached token for a token endpoint, so that it is refreshed.
+pub(crate) fn invalidate(token_url: &str) {
+    debug!("Invalidating token from {}", Redacted(token_url));
+
+    if let Ok(mut tokens) = tokens().lock() {
+        tokens.remove(token_url);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokens() {
+        let url = "http://tokens.test/test_tokens";
+        assert_eq!(get(url), None);
+
+        insert(url, "abc", Duration::from_secs(60));
+        assert_eq!(get(url).as_deref(), Some("abc"));
+
+        invalidate(url);
+        assert_eq!(get(url), None);
+    }
+
+    #[test]
+    fn test_tokens_expire() {
+        let url = "http://tokens.test/test_tokens_expire";
+
+        insert(url, "abc", Duration::ZERO);
+        assert_eq!(get(url), None);
+    }
+}
@@ -0,0 +1,196 @@
+//! Watching for changes of the host's cloud provider.
+//!
+//! Hosts may change environment over their lifetime (e.g. an image reused both on-premises and in the cloud, or a
+//! live-migrated VM). Watching re-runs detection periodically and only reports the provider when it changes. Each run
+//! first re-checks the previously detected provider alone, falling back to a full detection only once it no longer
+//! matches, so watching a host that stays put is cheap.
+
+use std::future::Future;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_core::Stream;
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio::time::MissedTickBehavior;
+use tracing::{debug, instrument};
+
+use crate::{detect, provider, spawn, timeout_or_default, Detector, ProviderId, P};
+
+/// A stream of the host's cloud provider, yielding the detected provider and then every change of it.
+///
+/// Watching stops when the stream is dropped.
+#[derive(Debug)]
+pub struct Watch {
+    rx: mpsc::Receiver<ProviderId>,
+    task: JoinHandle<()>,
+}
+
+impl Watch {
+    /// Starts watching, running `redetect` (with the last detected provider, if any) every `interval`.
+    fn spawn<F, Fut>(interval: Duration, mut redetect: F) -> Self
+    where
+        F: FnMut(Option<ProviderId>) -> Fut + Send + 'static,
+        Fut: Future<Output = ProviderId> + Send,
+    {
+        let (tx, rx) = mpsc::channel(1);
+
+        let task = spawn::detached(&spawn::name("watch", "provider"), async move {
+            let mut interval = tokio::time::interval(interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+            let mut last = None;
+
+            loop {
+                interval.tick().await;
+
+                let provider = redetect(last).await;
+                if last == Some(provider) {
+                    continue;
+                }
+
+                debug!("Provider changed from {:?} to {}", last, provider);
+                last = Some(provider);
+
+                if tx.send(provider).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        Self { rx, task }
+    }
+
+    /// Waits for the next change of provider.
+    ///
+    /// The first call returns the provider detected when watching started.
+    pub async fn recv(&mut self) -> Option<ProviderId> {
+        self.rx.recv().await
+    }
+}
+
+impl Stream for Watch {
+    type Item = ProviderId;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+/// Whether the provider is still identified by any of its checks.
+pub(crate) async fn verify(provider: P, timeout: Duration) -> bool {
+    for check in Detector::new().checks(provider) {
+        if check.run(timeout).await {
+            return true;
+        }
+    }
+
+    false
+}
+
+/// Re-checks the last detected provider, running a full detection only if it no longer matches.
+#[instrument]
+async fn redetect(last: Option<ProviderId>, timeout: Option<u64>) -> ProviderId {
+    if let Some(last) = last.and_then(provider) {
+        if verify(last, timeout_or_default(timeout)).await {
+            debug!("Still running on {}", last.identifier());
+            return last.identifier();
+        }
+    }
+
+    detect(timeout).await
+}
+
+/// Watches the host's cloud provider for changes.
+///
+/// Detection runs every `interval`, and the returned stream yields the detected provider and then every change of
+/// it. Must be called from within a Tokio runtime.
+///
+/// # Arguments
+///
+/// * `interval` - Time between detections.
+/// * `timeout` - Maximum time (seconds) allowed for each detection. Defaults to [DEFAULT_DETECTION_TIMEOUT](crate::DEFAULT_DETECTION_TIMEOUT) if `None`.
+///
+/// # Examples
+///
+/// Print the provider whenever it changes.
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// use cloud_detect::watch;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     let mut changes = watch(Duration::from_secs(60), None);
+///
+///     while let Some(provider) = changes.recv().await {
+///         println!("Running on: {}", provider);
+///     }
+/// }
+/// ```
+pub fn watch(interval: Duration, timeout: Option<u64>) -> Watch {
+    Watch::spawn(interval, move |last| redetect(last, timeout))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn test_watch_emits_changes() {
+        let results = Arc::new(Mutex::new(
+            vec![
+                ProviderId::AWS,
+                ProviderId::AWS,
+                ProviderId::Unknown,
+                ProviderId::Unknown,
+                ProviderId::GCP,
+            ]
+            .into_iter(),
+        ));
+
+        let mut watch = Watch::spawn(Duration::from_millis(10), move |_| {
+            let next = results.lock().unwrap().next();
+            async move { next.unwrap_or(ProviderId::GCP) }
+        });
+
+        assert_eq!(watch.recv().await, Some(ProviderId::AWS));
+        assert_eq!(watch.recv().await, Some(ProviderId::Unknown));
+        assert_eq!(watch.recv().await, Some(ProviderId::GCP));
+    }
+
+    #[tokio::test]
+    async fn test_watch_passes_last_provider() {
+        let mut watch = Watch::spawn(Duration::from_millis(10), |last| async move {
+            match last {
+                None => ProviderId::AWS,
+                Some(ProviderId::AWS) => ProviderId::Azure,
+                Some(provider) => provider,
+            }
+        });
+
+        assert_eq!(watch.recv().await, Some(ProviderId::AWS));
+        assert_eq!(watch.recv().await, Some(ProviderId::Azure));
+    }
+
+    #[tokio::test]
+    async fn test_watch_stops_on_drop() {
+        let watch = Watch::spawn(Duration::from_millis(10), |_| async { ProviderId::AWS });
+        let task = watch.task.abort_handle();
+
+        drop(watch);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        assert!(task.is_finished());
+    }
+}
@@ -0,0 +1,71 @@
+//! Forcing the result of detection (see `testing::set_forced_result`).
+//!
+//! The forced result is global to the process, so these tests run in a binary of their own, one at a time.
+
+use cloud_detect::testing::{clear_forced_result, set_forced_result, FakeProvider};
+use cloud_detect::{Detector, ProviderId};
+use tokio::sync::Mutex;
+
+/// Serializes the tests forcing results.
+static FORCED: Mutex<()> = Mutex::const_new(());
+
+/// Returns a detector finding Azure, unless the result is forced.
+fn detector() -> Detector {
+    Detector::new()
+        .timeout(1)
+        .fake_provider(FakeProvider::matching(ProviderId::Azure))
+}
+
+#[tokio::test]
+async fn test_forced_result() {
+    let _lock = FORCED.lock().await;
+    let detector = detector();
+
+    set_forced_result(ProviderId::GCP);
+
+    assert_eq!(cloud_detect::detect(Some(1)).await, ProviderId::GCP);
+    assert_eq!(detector.detect().await, ProviderId::GCP);
+
+    let detection = detector.detect_detailed().await;
+    assert_eq!(detection.provider, ProviderId::GCP);
+    assert!(detection.evidence.is_empty());
+
+    assert!(
+        cloud_detect::is_provider(ProviderId::GCP, Some(1))
+            .await
+            .matched
+    );
+    assert!(detector.is_provider(ProviderId::GCP).await.matched);
+    assert!(!detector.is_provider(ProviderId::Azure).await.matched);
+
+    clear_forced_result();
+
+    assert_eq!(detector.detect().await, ProviderId::Azure);
+    assert!(detector.is_provider(ProviderId::Azure).await.matched);
+}
+
+#[cfg(any(feature = "blocking", feature = "blocking-ureq"))]
+#[test]
+fn test_forced_result_blocking() {
+    let _lock = FORCED.blocking_lock();
+    let detector = detector();
+
+    set_forced_result(ProviderId::AWS);
+
+    assert_eq!(
+        cloud_detect::blocking::detect(Some(1)).ok(),
+        Some(ProviderId::AWS)
+    );
+    assert_eq!(detector.detect_blocking().ok(), Some(ProviderId::AWS));
+    assert_eq!(
+        detector.detect_detailed_blocking().provider,
+        ProviderId::AWS
+    );
+    assert!(cloud_detect::blocking::is_provider(ProviderId::AWS, Some(1)).matched);
+    assert!(!detector.is_provider_blocking(ProviderId::Azure).matched);
+
+    clear_forced_result();
+
+    assert_eq!(detector.detect_blocking().ok(), Some(ProviderId::Azure));
+    assert!(detector.is_provider_blocking(ProviderId::Azure).matched);
+}
@@ -0,0 +1,51 @@
+//! Detecting the provider from an executor other than tokio, with the blocking API of the `blocking-ureq` feature.
+//!
+//! With the default features disabled (`cargo test --no-default-features --features blocking-ureq --test smol`), the
+//! crate is built without tokio and reqwest, so detection can only go through the path that doesn't need them.
+
+use anyhow::Result;
+use cloud_detect::{CustomProvider, Detector, ProviderId};
+use mockito::{Mock, Server};
+
+/// Returns a detector only probing a custom provider, identified by the endpoint of a mocked metadata server.
+fn detector(server: &mut Server) -> Result<(Detector, Mock)> {
+    let mock = server
+        .mock("GET", "/acme/v1/instance")
+        .with_body(r#"{"cloud": {"name": "acme"}}"#)
+        .create();
+    let url = format!("{}/acme/v1/instance", server.url());
+
+    let detector = Detector::new()
+        .timeout(1)
+        .providers(&[])
+        .custom_provider(CustomProvider::new("acme").endpoint(&url, "/cloud/name", "acme"))?;
+
+    Ok((detector, mock))
+}
+
+#[test]
+fn test_detect_on_blocking_pool() -> Result<()> {
+    let mut server = Server::new();
+    let (detector, mock) = detector(&mut server)?;
+
+    let provider = smol::block_on(smol::unblock(move || detector.detect_blocking()))?;
+
+    assert_eq!(provider, ProviderId::Custom("acme"));
+    mock.assert();
+
+    Ok(())
+}
+
+#[test]
+fn test_detect_detailed_within_executor() -> Result<()> {
+    let mut server = Server::new();
+    let (detector, mock) = detector(&mut server)?;
+
+    let detection = smol::block_on(async { detector.detect_detailed_blocking() });
+
+    assert_eq!(detection.provider, ProviderId::Custom("acme"));
+    assert_eq!(detection.evidence.len(), 1);
+    mock.assert();
+
+    Ok(())
+}